@@ -0,0 +1,111 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Keypair},
+};
+use std::{path::PathBuf, str::FromStr};
+
+// GorbChain defaults, used when no Solana CLI config is present
+pub const DEFAULT_RPC_URL: &str = "https://rpc.gorbchain.xyz";
+pub const DEFAULT_ATA_PROGRAM: &str = "GoATGVNeSXerFerPqTJ8hcED1msPWHHLxao2vwBYqowm";
+
+// Subset of the Solana CLI config file (~/.config/solana/cli/config.yml)
+#[derive(Debug, Default, Deserialize)]
+struct SolanaCliConfig {
+    json_rpc_url: Option<String>,
+    keypair_path: Option<String>,
+    commitment: Option<String>,
+}
+
+pub struct Config {
+    pub rpc_url: String,
+    pub keypair: Keypair,
+    pub commitment: CommitmentConfig,
+    pub program_id: Pubkey,
+    pub token_program_id: Pubkey,
+    pub ata_program_id: Pubkey,
+    // Multisig admin instructions go through, and the members signing next
+    // to the keypair
+    pub multisig: Option<Pubkey>,
+    pub multisig_signers: Vec<Keypair>,
+}
+
+pub struct Overrides {
+    pub config_file: Option<PathBuf>,
+    pub url: Option<String>,
+    pub keypair: Option<PathBuf>,
+    pub program_id: Option<Pubkey>,
+    pub token_program_id: Option<Pubkey>,
+    pub ata_program_id: Option<Pubkey>,
+    pub multisig: Option<Pubkey>,
+    pub multisig_signers: Vec<PathBuf>,
+}
+
+fn solana_config_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config/solana"))
+}
+
+impl Config {
+    pub fn load(overrides: Overrides) -> Result<Self> {
+        let file = match overrides.config_file {
+            Some(path) => Some(path),
+            None => solana_config_dir()
+                .map(|dir| dir.join("cli/config.yml"))
+                .filter(|path| path.exists()),
+        };
+
+        let cli_config = match &file {
+            Some(path) => {
+                let text = std::fs::read_to_string(path)
+                    .with_context(|| format!("reading config {}", path.display()))?;
+                serde_yaml::from_str(&text)
+                    .with_context(|| format!("parsing config {}", path.display()))?
+            }
+            None => SolanaCliConfig::default(),
+        };
+
+        let rpc_url = overrides
+            .url
+            .or(cli_config.json_rpc_url)
+            .unwrap_or_else(|| DEFAULT_RPC_URL.to_string());
+
+        let keypair_path = overrides
+            .keypair
+            .or(cli_config.keypair_path.map(PathBuf::from))
+            .or_else(|| solana_config_dir().map(|dir| dir.join("id.json")))
+            .context("no keypair configured; pass --keypair")?;
+        let keypair = read_keypair_file(&keypair_path)
+            .map_err(|e| anyhow::anyhow!("reading keypair {}: {e}", keypair_path.display()))?;
+
+        let multisig_signers = overrides
+            .multisig_signers
+            .iter()
+            .map(|path| {
+                read_keypair_file(path).map_err(|e| anyhow::anyhow!("reading keypair {}: {e}", path.display()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let commitment = match cli_config.commitment.as_deref() {
+            Some(level) => CommitmentConfig::from_str(level)
+                .map_err(|_| anyhow::anyhow!("unknown commitment level {level:?}"))?,
+            None => CommitmentConfig::confirmed(),
+        };
+
+        Ok(Config {
+            rpc_url,
+            keypair,
+            commitment,
+            program_id: overrides.program_id.unwrap_or(cargo_swap::id()),
+            token_program_id: overrides
+                .token_program_id
+                .unwrap_or(Pubkey::from_str(cargo_swap::GORBCHAIN_SPL_TOKEN_PROGRAM)?),
+            ata_program_id: overrides
+                .ata_program_id
+                .unwrap_or(Pubkey::from_str(DEFAULT_ATA_PROGRAM)?),
+            multisig: overrides.multisig,
+            multisig_signers,
+        })
+    }
+}
@@ -0,0 +1,2228 @@
+// cargo-swap: command-line client for the GorbChain AMM program
+
+mod config;
+mod output;
+mod route;
+mod rpc;
+
+use anyhow::{bail, Result};
+use cargo_swap::{
+    instruction::{self, OrderKeys, PoolKeys, RewardKeys, TokenFee, UserKeys},
+    is_native_mint, quote, CreationPolicy, CreationRule, CreationRules, DcaParams, DcaVault, Farm, FarmStake,
+    GovernanceChange, LimitOrder, LiquidityLock, LongTermOrder, LpMetadataField, MintStatus, Multisig, OrderSchedule, PendingChange,
+    Pool, PoolType, RewardSchedule, PAUSE_ALL, PAUSE_DEPOSITS, PAUSE_SWAPS, PAUSE_WITHDRAWALS, POOL_VERSION,
+};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use config::{Config, Overrides};
+use output::OutputFormat;
+use rpc::Client;
+use serde_json::{json, Value};
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey};
+use std::path::PathBuf;
+
+#[derive(Parser)]
+#[command(name = "cargo-swap", version, about = "Manage and trade against GorbChain AMM pools")]
+struct Cli {
+    /// Solana CLI config file [default: ~/.config/solana/cli/config.yml]
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// RPC URL, overriding the config file
+    #[arg(long, short = 'u', global = true)]
+    url: Option<String>,
+
+    /// Fee payer and signer keypair, overriding the config file
+    #[arg(long, short = 'k', global = true)]
+    keypair: Option<PathBuf>,
+
+    /// AMM program id
+    #[arg(long, global = true)]
+    program_id: Option<Pubkey>,
+
+    /// SPL token program id [default: GorbChain token program]
+    #[arg(long, global = true)]
+    token_program_id: Option<Pubkey>,
+
+    /// Associated token account program id [default: GorbChain ATA program]
+    #[arg(long, global = true)]
+    ata_program_id: Option<Pubkey>,
+
+    #[arg(long, value_enum, default_value = "human", global = true)]
+    output: OutputFormat,
+
+    /// Simulate transactions instead of sending them
+    #[arg(long, global = true)]
+    simulate: bool,
+
+    /// Admin multisig to act through; the keypair signs as one of its members
+    #[arg(long, global = true)]
+    multisig: Option<Pubkey>,
+
+    /// Keypair of another multisig member approving the transaction; repeatable
+    #[arg(long = "multisig-signer", global = true)]
+    multisig_signers: Vec<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Pool registry management
+    #[command(subcommand)]
+    Registry(RegistryCommand),
+    /// Program config and admin key
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// Timelocked fee and guardian changes (admin only)
+    #[command(subcommand)]
+    Governance(GovernanceCommand),
+    /// M-of-N multisigs that can hold the admin role
+    #[command(subcommand)]
+    Multisig(MultisigCommand),
+    /// Pool creation fee, pool type rules and mint allow/deny list (admin only)
+    #[command(subcommand)]
+    Policy(PolicyCommand),
+    /// Pool creation and initialization
+    #[command(subcommand)]
+    Pool(PoolCommand),
+    /// Limit orders resting against a pool
+    #[command(subcommand)]
+    Order(OrderCommand),
+    /// Time-weighted long-term orders sold over many intervals
+    #[command(subcommand)]
+    Twamm(TwammCommand),
+    /// Dollar-cost-averaging vaults that buy a fixed amount every period
+    #[command(subcommand)]
+    Dca(DcaCommand),
+    /// LP staking farms paying out reward tokens
+    #[command(subcommand)]
+    Farm(FarmCommand),
+    /// LP tokens locked until a time, boosted in the pool's farm
+    #[command(subcommand)]
+    Lock(LockCommand),
+    /// Deposit tokens and receive LP tokens
+    AddLiquidity {
+        #[command(flatten)]
+        pool: PoolArgs,
+        /// LP mint, the pool's PDA mint if omitted
+        #[arg(long)]
+        lp_mint: Option<Pubkey>,
+        #[arg(long)]
+        amount_a: u64,
+        #[arg(long)]
+        amount_b: u64,
+    },
+    /// Burn LP tokens and withdraw the underlying tokens
+    RemoveLiquidity {
+        #[command(flatten)]
+        pool: PoolArgs,
+        /// LP mint, the pool's PDA mint if omitted
+        #[arg(long)]
+        lp_mint: Option<Pubkey>,
+        #[arg(long)]
+        lp_amount: u64,
+    },
+    /// Swap through a single pool
+    Swap {
+        #[command(flatten)]
+        pool: PoolArgs,
+        #[arg(long)]
+        amount_in: u64,
+        /// Swap token B for token A instead of A for B
+        #[arg(long)]
+        b_to_a: bool,
+        /// Abort if the quoted output is below this amount
+        #[arg(long)]
+        min_out: Option<u64>,
+    },
+    /// Find the best multi-hop route between two mints
+    Route {
+        #[arg(long)]
+        from: Pubkey,
+        #[arg(long)]
+        to: Pubkey,
+        #[arg(long)]
+        amount_in: u64,
+        #[arg(long, default_value_t = 3)]
+        max_hops: usize,
+        /// Abort if the quoted output is below this amount
+        #[arg(long)]
+        min_out: Option<u64>,
+        /// Send the swaps instead of only quoting
+        #[arg(long)]
+        execute: bool,
+    },
+    /// List all pools owned by the program
+    List,
+    /// Show a pool's state and vault balances
+    Inspect {
+        /// Pool address; alternatively pass --token-a and --token-b
+        #[arg(long, conflicts_with_all = ["token_a", "token_b"])]
+        pool: Option<Pubkey>,
+        #[arg(long, requires = "token_b")]
+        token_a: Option<Pubkey>,
+        #[arg(long, requires = "token_a")]
+        token_b: Option<Pubkey>,
+    },
+}
+
+#[derive(Subcommand)]
+enum RegistryCommand {
+    /// Create the pool registry account
+    Init,
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Create the program config; the keypair must be the program's upgrade authority
+    Init {
+        /// Admin key [default: the keypair]
+        #[arg(long)]
+        admin: Option<Pubkey>,
+        /// Key allowed to pause the program [default: the keypair]
+        #[arg(long)]
+        guardian: Option<Pubkey>,
+    },
+    /// Pause swaps, deposits or withdrawals (guardian only); with no flags, pauses everything
+    Pause {
+        #[arg(long)]
+        swaps: bool,
+        #[arg(long)]
+        deposits: bool,
+        #[arg(long)]
+        withdrawals: bool,
+    },
+    /// Lift every pause (guardian only)
+    Unpause,
+    /// Hand the admin role to another key or a multisig (admin only)
+    SetAdmin {
+        #[arg(long)]
+        admin: Pubkey,
+    },
+}
+
+#[derive(Subcommand)]
+enum MultisigCommand {
+    /// Create a multisig
+    Create {
+        /// Member key; repeat for each member
+        #[arg(long = "signer", required = true)]
+        signers: Vec<Pubkey>,
+        /// Signatures needed to act
+        #[arg(long)]
+        threshold: u8,
+        /// Seed of the multisig address [default: a new random key]
+        #[arg(long)]
+        create_key: Option<Pubkey>,
+    },
+    /// Show the --multisig's members and threshold
+    Show,
+    /// Add a member to the --multisig (approved by its members)
+    AddSigner {
+        #[arg(long)]
+        signer: Pubkey,
+    },
+    /// Remove a member from the --multisig (approved by its members)
+    RemoveSigner {
+        #[arg(long)]
+        signer: Pubkey,
+    },
+    /// Change the --multisig's threshold (approved by its members)
+    SetThreshold {
+        #[arg(long)]
+        threshold: u8,
+    },
+}
+
+#[derive(Subcommand)]
+enum GovernanceCommand {
+    /// Propose a new swap fee for a pool
+    ProposeFee {
+        #[arg(long)]
+        token_a: Pubkey,
+        #[arg(long)]
+        token_b: Pubkey,
+        /// Fee in basis points
+        #[arg(long)]
+        fee_rate: u16,
+    },
+    /// Propose dynamic fees for a pool, charged between the two bounds as volatility rises
+    ProposeDynamicFee {
+        #[arg(long)]
+        token_a: Pubkey,
+        #[arg(long)]
+        token_b: Pubkey,
+        /// Fee in basis points when the pool is calm
+        #[arg(long)]
+        min_fee_rate: u16,
+        /// Fee in basis points at full volatility
+        #[arg(long)]
+        max_fee_rate: u16,
+    },
+    /// Propose a limit on how far swaps may move a pool's price within one slot
+    ProposePriceBand {
+        #[arg(long)]
+        token_a: Pubkey,
+        #[arg(long)]
+        token_b: Pubkey,
+        /// Largest price move per slot in basis points, 0 to disable
+        #[arg(long)]
+        max_move_bps: u16,
+    },
+    /// Propose a new guardian
+    ProposeGuardian {
+        #[arg(long)]
+        guardian: Pubkey,
+        /// Keep withdrawals open whatever the guardian pauses
+        #[arg(long)]
+        withdrawals_open: bool,
+    },
+    /// Show the change pending for a pool, or for the program config if --pool is omitted
+    Show {
+        #[arg(long)]
+        pool: Option<Pubkey>,
+    },
+    /// Apply a pending change once its delay has passed
+    Execute {
+        #[arg(long)]
+        pool: Option<Pubkey>,
+    },
+    /// Drop a pending change
+    Cancel {
+        #[arg(long)]
+        pool: Option<Pubkey>,
+    },
+}
+
+#[derive(Subcommand)]
+enum PolicyCommand {
+    /// Set the pool creation fee and who may create each pool type; creates the policy on first use
+    Set {
+        /// Fee charged per pool, 0 for none
+        #[arg(long, default_value_t = 0)]
+        fee_amount: u64,
+        /// Token the fee is paid in [default: lamports]
+        #[arg(long)]
+        fee_mint: Option<Pubkey>,
+        /// Fee recipient: a wallet for lamport fees, a --fee-mint token account otherwise
+        #[arg(long)]
+        treasury: Option<Pubkey>,
+        #[arg(long, value_enum, default_value = "open")]
+        standard: CreationRuleArg,
+        #[arg(long, value_enum, default_value = "open")]
+        stable: CreationRuleArg,
+        #[arg(long, value_enum, default_value = "open")]
+        concentrated: CreationRuleArg,
+    },
+    /// Show the creation policy and listed mints
+    Show,
+    /// Allowlist a mint
+    Allow {
+        #[arg(long)]
+        mint: Pubkey,
+    },
+    /// Deny pools with a mint
+    Deny {
+        #[arg(long)]
+        mint: Pubkey,
+    },
+    /// Take a mint off the list
+    Unlist {
+        #[arg(long)]
+        mint: Pubkey,
+    },
+}
+
+#[derive(Subcommand)]
+enum OrderCommand {
+    /// Escrow tokens until the pool pays at least --min-out for them
+    Place {
+        #[arg(long)]
+        token_a: Pubkey,
+        #[arg(long)]
+        token_b: Pubkey,
+        /// Any number not used by another of your open orders on the pool
+        #[arg(long)]
+        order_id: u64,
+        #[arg(long)]
+        amount_in: u64,
+        /// Least output to accept, which sets the limit price
+        #[arg(long)]
+        min_out: u64,
+        /// Sell token B for token A instead of A for B
+        #[arg(long)]
+        b_to_a: bool,
+    },
+    /// Cancel one of your orders and get its tokens back
+    Cancel {
+        #[arg(long)]
+        token_a: Pubkey,
+        #[arg(long)]
+        token_b: Pubkey,
+        #[arg(long)]
+        order_id: u64,
+    },
+    /// List a pool's open orders
+    List {
+        #[arg(long)]
+        token_a: Pubkey,
+        #[arg(long)]
+        token_b: Pubkey,
+        /// Only this owner's orders
+        #[arg(long)]
+        owner: Option<Pubkey>,
+    },
+    /// Fill the orders the pool's price reaches and collect their keeper rewards
+    Fill {
+        #[arg(long)]
+        token_a: Pubkey,
+        #[arg(long)]
+        token_b: Pubkey,
+        /// Most orders to fill in one transaction
+        #[arg(long, default_value_t = 4)]
+        max_orders: usize,
+    },
+}
+
+#[derive(Subcommand)]
+enum FarmCommand {
+    /// Create the pool's farm (admin)
+    Create {
+        #[arg(long)]
+        token_a: Pubkey,
+        #[arg(long)]
+        token_b: Pubkey,
+    },
+    /// Add a reward stream paying out a mint (admin); fund it by transferring to its vault
+    AddReward {
+        #[arg(long)]
+        token_a: Pubkey,
+        #[arg(long)]
+        token_b: Pubkey,
+        #[arg(long)]
+        mint: Pubkey,
+        #[command(flatten)]
+        schedule: ScheduleArgs,
+    },
+    /// Reschedule a reward stream from now on (admin)
+    SetSchedule {
+        #[arg(long)]
+        token_a: Pubkey,
+        #[arg(long)]
+        token_b: Pubkey,
+        /// Stream position, in the order they were added
+        #[arg(long)]
+        reward_index: u8,
+        #[command(flatten)]
+        schedule: ScheduleArgs,
+    },
+    /// Stake LP tokens
+    Stake {
+        #[arg(long)]
+        token_a: Pubkey,
+        #[arg(long)]
+        token_b: Pubkey,
+        #[arg(long)]
+        amount: u64,
+    },
+    /// Unstake LP tokens; earned rewards stay claimable
+    Unstake {
+        #[arg(long)]
+        token_a: Pubkey,
+        #[arg(long)]
+        token_b: Pubkey,
+        #[arg(long)]
+        amount: u64,
+    },
+    /// Claim every stream's pending rewards to your ATAs
+    Claim {
+        #[arg(long)]
+        token_a: Pubkey,
+        #[arg(long)]
+        token_b: Pubkey,
+    },
+    /// Show the farm's streams and a stake with its pending rewards
+    Show {
+        #[arg(long)]
+        token_a: Pubkey,
+        #[arg(long)]
+        token_b: Pubkey,
+        /// Whose stake to show [default: the keypair's]
+        #[arg(long)]
+        owner: Option<Pubkey>,
+    },
+}
+
+#[derive(Subcommand)]
+enum LockCommand {
+    /// Lock LP tokens until a time, or add to and extend your lock
+    Add {
+        #[arg(long)]
+        token_a: Pubkey,
+        #[arg(long)]
+        token_b: Pubkey,
+        /// LP tokens to add; zero only extends
+        #[arg(long, default_value_t = 0)]
+        amount: u64,
+        /// Unix time the lock opens; never earlier than the current one
+        #[arg(long)]
+        unlock_time: i64,
+    },
+    /// Withdraw your LP tokens once the lock has opened
+    Unlock {
+        #[arg(long)]
+        token_a: Pubkey,
+        #[arg(long)]
+        token_b: Pubkey,
+    },
+    /// Drop an expired lock back to its LP amount in the pool's farm
+    Expire {
+        #[arg(long)]
+        token_a: Pubkey,
+        #[arg(long)]
+        token_b: Pubkey,
+        /// The lock's owner
+        #[arg(long)]
+        owner: Pubkey,
+    },
+    /// List a pool's locks
+    List {
+        #[arg(long)]
+        token_a: Pubkey,
+        #[arg(long)]
+        token_b: Pubkey,
+        /// Only this owner's lock
+        #[arg(long)]
+        owner: Option<Pubkey>,
+    },
+}
+
+#[derive(Args)]
+struct ScheduleArgs {
+    /// Reward tokens emitted per second, shared among stakers
+    #[arg(long)]
+    emission_per_second: u64,
+    /// Unix time emissions start [default: now]
+    #[arg(long)]
+    start_time: Option<i64>,
+    /// How long emissions run
+    #[arg(long)]
+    duration_seconds: i64,
+}
+
+#[derive(Subcommand)]
+enum DcaCommand {
+    /// Deposit tokens to be swapped a fixed amount at a time, once per period
+    Create {
+        #[arg(long)]
+        token_a: Pubkey,
+        #[arg(long)]
+        token_b: Pubkey,
+        /// Any number not used by another of your DCA vaults on the pool
+        #[arg(long)]
+        dca_id: u64,
+        #[arg(long)]
+        deposit: u64,
+        /// Input swapped each period
+        #[arg(long)]
+        amount_per_period: u64,
+        #[arg(long, default_value_t = 86400)]
+        interval_seconds: i64,
+        /// Most a swap may fall short of the pool's average price since the last one
+        #[arg(long, default_value_t = 300)]
+        max_slippage_bps: u16,
+        /// Sell token B for token A instead of A for B
+        #[arg(long)]
+        b_to_a: bool,
+    },
+    /// Run the swaps that have come due on a pool's vaults, one transaction each
+    Execute {
+        #[arg(long)]
+        token_a: Pubkey,
+        #[arg(long)]
+        token_b: Pubkey,
+        /// Most vaults to execute
+        #[arg(long, default_value_t = 8)]
+        max_vaults: usize,
+    },
+    /// Close one of your vaults and get its unswapped deposit back
+    Close {
+        #[arg(long)]
+        token_a: Pubkey,
+        #[arg(long)]
+        token_b: Pubkey,
+        #[arg(long)]
+        dca_id: u64,
+    },
+    /// List a pool's DCA vaults
+    List {
+        #[arg(long)]
+        token_a: Pubkey,
+        #[arg(long)]
+        token_b: Pubkey,
+        /// Only this owner's vaults
+        #[arg(long)]
+        owner: Option<Pubkey>,
+    },
+}
+
+#[derive(Subcommand)]
+enum TwammCommand {
+    /// Deposit tokens to be sold in equal slices, one per interval
+    Place {
+        #[arg(long)]
+        token_a: Pubkey,
+        #[arg(long)]
+        token_b: Pubkey,
+        /// Any number not used by another of your long-term orders on the pool
+        #[arg(long)]
+        order_id: u64,
+        #[arg(long)]
+        amount_in: u64,
+        /// Number of slices to split the trade into
+        #[arg(long)]
+        intervals: u32,
+        /// Length of each interval
+        #[arg(long, default_value_t = 3600)]
+        interval_seconds: i64,
+        /// Most a slice may fall short of the pool's average price since the last one
+        #[arg(long, default_value_t = 300)]
+        max_slippage_bps: u16,
+        /// Sell token B for token A instead of A for B
+        #[arg(long)]
+        b_to_a: bool,
+    },
+    /// Sell the intervals that have come due on a pool's orders
+    Execute {
+        #[arg(long)]
+        token_a: Pubkey,
+        #[arg(long)]
+        token_b: Pubkey,
+        /// Most orders to execute in one transaction
+        #[arg(long, default_value_t = 8)]
+        max_orders: usize,
+    },
+    /// Withdraw an order's proceeds so far and leave it running
+    Withdraw {
+        #[arg(long)]
+        token_a: Pubkey,
+        #[arg(long)]
+        token_b: Pubkey,
+        #[arg(long)]
+        order_id: u64,
+    },
+    /// Stop an order, or close a finished one, and get its unsold input and proceeds
+    Cancel {
+        #[arg(long)]
+        token_a: Pubkey,
+        #[arg(long)]
+        token_b: Pubkey,
+        #[arg(long)]
+        order_id: u64,
+    },
+    /// List a pool's long-term orders
+    List {
+        #[arg(long)]
+        token_a: Pubkey,
+        #[arg(long)]
+        token_b: Pubkey,
+        /// Only this owner's orders
+        #[arg(long)]
+        owner: Option<Pubkey>,
+    },
+}
+
+#[derive(Subcommand)]
+enum PoolCommand {
+    /// Register a new pool and create its vaults and LP mint
+    Create {
+        #[arg(long)]
+        token_a: Pubkey,
+        #[arg(long)]
+        token_b: Pubkey,
+        #[arg(long, value_enum, default_value = "standard")]
+        pool_type: PoolTypeArg,
+    },
+    /// Create the pool account and deposit initial liquidity
+    Init {
+        #[arg(long)]
+        token_a: Pubkey,
+        #[arg(long)]
+        token_b: Pubkey,
+        #[arg(long)]
+        amount_a: u64,
+        #[arg(long)]
+        amount_b: u64,
+        /// Registry pool id, 0 for pools outside the registry
+        #[arg(long, default_value_t = 0)]
+        pool_id: u64,
+    },
+    /// Convert a pool created by an older program version to the current layout
+    Migrate {
+        #[arg(long)]
+        token_a: Pubkey,
+        #[arg(long)]
+        token_b: Pubkey,
+    },
+    /// Rewrite a field of a Token-2022 LP mint's metadata (admin only)
+    SetLpMetadata {
+        #[arg(long)]
+        token_a: Pubkey,
+        #[arg(long)]
+        token_b: Pubkey,
+        #[arg(long, value_enum)]
+        field: LpMetadataFieldArg,
+        #[arg(long)]
+        value: String,
+    },
+}
+
+#[derive(Args)]
+struct PoolArgs {
+    #[arg(long)]
+    token_a: Pubkey,
+    #[arg(long)]
+    token_b: Pubkey,
+    /// Vault for token A, looked up on-chain if omitted
+    #[arg(long)]
+    vault_a: Option<Pubkey>,
+    /// Vault for token B, looked up on-chain if omitted
+    #[arg(long)]
+    vault_b: Option<Pubkey>,
+    /// Pay and receive native SOL for the pool's wrapped-SOL side
+    #[arg(long)]
+    native: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum PoolTypeArg {
+    Standard,
+    Stable,
+    Concentrated,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum CreationRuleArg {
+    Open,
+    AllowlistOnly,
+    Disabled,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum LpMetadataFieldArg {
+    Name,
+    Symbol,
+    Uri,
+}
+
+impl From<LpMetadataFieldArg> for LpMetadataField {
+    fn from(arg: LpMetadataFieldArg) -> Self {
+        match arg {
+            LpMetadataFieldArg::Name => LpMetadataField::Name,
+            LpMetadataFieldArg::Symbol => LpMetadataField::Symbol,
+            LpMetadataFieldArg::Uri => LpMetadataField::Uri,
+        }
+    }
+}
+
+impl From<CreationRuleArg> for CreationRule {
+    fn from(arg: CreationRuleArg) -> Self {
+        match arg {
+            CreationRuleArg::Open => CreationRule::Open,
+            CreationRuleArg::AllowlistOnly => CreationRule::AllowlistOnly,
+            CreationRuleArg::Disabled => CreationRule::Disabled,
+        }
+    }
+}
+
+impl From<PoolTypeArg> for PoolType {
+    fn from(arg: PoolTypeArg) -> Self {
+        match arg {
+            PoolTypeArg::Standard => PoolType::Standard,
+            PoolTypeArg::Stable => PoolType::Stable,
+            PoolTypeArg::Concentrated => PoolType::Concentrated,
+        }
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let format = cli.output;
+
+    match run(cli) {
+        Ok(value) => output::print(format, &value),
+        Err(err) => {
+            match format {
+                OutputFormat::Json => println!("{}", json!({ "error": format!("{err:#}") })),
+                OutputFormat::Human => eprintln!("error: {err:#}"),
+            }
+            std::process::exit(1);
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<Value> {
+    let config = Config::load(Overrides {
+        config_file: cli.config,
+        url: cli.url,
+        keypair: cli.keypair,
+        program_id: cli.program_id,
+        token_program_id: cli.token_program_id,
+        ata_program_id: cli.ata_program_id,
+        multisig: cli.multisig,
+        multisig_signers: cli.multisig_signers,
+    })?;
+    let client = Client::new(config, cli.simulate);
+
+    match cli.command {
+        Command::Registry(RegistryCommand::Init) => registry_init(&client),
+        Command::Config(ConfigCommand::Init { admin, guardian }) => config_init(&client, admin, guardian),
+        Command::Config(ConfigCommand::Pause { swaps, deposits, withdrawals }) => {
+            let paused = [(swaps, PAUSE_SWAPS), (deposits, PAUSE_DEPOSITS), (withdrawals, PAUSE_WITHDRAWALS)]
+                .into_iter()
+                .filter(|(set, _)| *set)
+                .fold(0, |paused, (_, flag)| paused | flag);
+            config_set_pause(&client, if paused == 0 { PAUSE_ALL } else { paused })
+        }
+        Command::Config(ConfigCommand::Unpause) => config_set_pause(&client, 0),
+        Command::Config(ConfigCommand::SetAdmin { admin }) => config_set_admin(&client, &admin),
+        Command::Multisig(MultisigCommand::Create { signers, threshold, create_key }) => {
+            multisig_create(&client, &signers, threshold, create_key)
+        }
+        Command::Multisig(MultisigCommand::Show) => multisig_show(&client),
+        Command::Multisig(MultisigCommand::AddSigner { signer }) => {
+            multisig_update(&client, |program_id, multisig, members| {
+                instruction::add_multisig_signer(program_id, multisig, members, &signer)
+            })
+        }
+        Command::Multisig(MultisigCommand::RemoveSigner { signer }) => {
+            multisig_update(&client, |program_id, multisig, members| {
+                instruction::remove_multisig_signer(program_id, multisig, members, &signer)
+            })
+        }
+        Command::Multisig(MultisigCommand::SetThreshold { threshold }) => {
+            multisig_update(&client, |program_id, multisig, members| {
+                instruction::set_multisig_threshold(program_id, multisig, members, threshold)
+            })
+        }
+        Command::Governance(GovernanceCommand::ProposeFee { token_a, token_b, fee_rate }) => {
+            let pool = instruction::find_pool_address(&client.config.program_id, &token_a, &token_b).0;
+            governance_propose(&client, &pool, GovernanceChange::FeeRate { fee_rate })
+        }
+        Command::Governance(GovernanceCommand::ProposeDynamicFee { token_a, token_b, min_fee_rate, max_fee_rate }) => {
+            let pool = instruction::find_pool_address(&client.config.program_id, &token_a, &token_b).0;
+            governance_propose(&client, &pool, GovernanceChange::DynamicFee { min_fee_rate, max_fee_rate })
+        }
+        Command::Governance(GovernanceCommand::ProposePriceBand { token_a, token_b, max_move_bps }) => {
+            let pool = instruction::find_pool_address(&client.config.program_id, &token_a, &token_b).0;
+            governance_propose(&client, &pool, GovernanceChange::PriceBand { max_price_move_bps: max_move_bps })
+        }
+        Command::Governance(GovernanceCommand::ProposeGuardian { guardian, withdrawals_open }) => {
+            let config = instruction::find_config_address(&client.config.program_id).0;
+            governance_propose(&client, &config, GovernanceChange::Guardian { guardian, withdrawals_open })
+        }
+        Command::Governance(GovernanceCommand::Show { pool }) => governance_show(&client, pool),
+        Command::Governance(GovernanceCommand::Execute { pool }) => governance_execute(&client, pool),
+        Command::Governance(GovernanceCommand::Cancel { pool }) => governance_cancel(&client, pool),
+        Command::Policy(PolicyCommand::Set { fee_amount, fee_mint, treasury, standard, stable, concentrated }) => {
+            let rules =
+                CreationRules { standard: standard.into(), stable: stable.into(), concentrated: concentrated.into() };
+            policy_set(&client, fee_amount, fee_mint, treasury, rules)
+        }
+        Command::Policy(PolicyCommand::Show) => policy_show(&client),
+        Command::Policy(PolicyCommand::Allow { mint }) => policy_set_mint(&client, &mint, MintStatus::Allowed),
+        Command::Policy(PolicyCommand::Deny { mint }) => policy_set_mint(&client, &mint, MintStatus::Denied),
+        Command::Policy(PolicyCommand::Unlist { mint }) => policy_set_mint(&client, &mint, MintStatus::Unlisted),
+        Command::Pool(PoolCommand::Create { token_a, token_b, pool_type }) => {
+            pool_create(&client, &token_a, &token_b, pool_type.into())
+        }
+        Command::Pool(PoolCommand::Init { token_a, token_b, amount_a, amount_b, pool_id }) => {
+            pool_init(&client, &token_a, &token_b, amount_a, amount_b, pool_id)
+        }
+        Command::Pool(PoolCommand::Migrate { token_a, token_b }) => pool_migrate(&client, &token_a, &token_b),
+        Command::Pool(PoolCommand::SetLpMetadata { token_a, token_b, field, value }) => {
+            pool_set_lp_metadata(&client, &token_a, &token_b, field.into(), value)
+        }
+        Command::Order(OrderCommand::Place { token_a, token_b, order_id, amount_in, min_out, b_to_a }) => {
+            order_place(&client, &token_a, &token_b, order_id, amount_in, min_out, !b_to_a)
+        }
+        Command::Order(OrderCommand::Cancel { token_a, token_b, order_id }) => {
+            order_cancel(&client, &token_a, &token_b, order_id)
+        }
+        Command::Order(OrderCommand::List { token_a, token_b, owner }) => {
+            order_list(&client, &token_a, &token_b, owner.as_ref())
+        }
+        Command::Order(OrderCommand::Fill { token_a, token_b, max_orders }) => {
+            order_fill(&client, &token_a, &token_b, max_orders)
+        }
+        Command::Twamm(TwammCommand::Place {
+            token_a,
+            token_b,
+            order_id,
+            amount_in,
+            intervals,
+            interval_seconds,
+            max_slippage_bps,
+            b_to_a,
+        }) => {
+            let schedule = OrderSchedule { intervals, interval_seconds, max_slippage_bps };
+            twamm_place(&client, &token_a, &token_b, order_id, amount_in, schedule, !b_to_a)
+        }
+        Command::Twamm(TwammCommand::Execute { token_a, token_b, max_orders }) => {
+            twamm_execute(&client, &token_a, &token_b, max_orders)
+        }
+        Command::Twamm(TwammCommand::Withdraw { token_a, token_b, order_id }) => {
+            twamm_withdraw(&client, &token_a, &token_b, order_id)
+        }
+        Command::Twamm(TwammCommand::Cancel { token_a, token_b, order_id }) => {
+            twamm_cancel(&client, &token_a, &token_b, order_id)
+        }
+        Command::Twamm(TwammCommand::List { token_a, token_b, owner }) => {
+            twamm_list(&client, &token_a, &token_b, owner.as_ref())
+        }
+        Command::Dca(DcaCommand::Create {
+            token_a,
+            token_b,
+            dca_id,
+            deposit,
+            amount_per_period,
+            interval_seconds,
+            max_slippage_bps,
+            b_to_a,
+        }) => {
+            let params = DcaParams { amount_per_period, interval_seconds, max_slippage_bps };
+            dca_create(&client, &token_a, &token_b, dca_id, deposit, params, !b_to_a)
+        }
+        Command::Dca(DcaCommand::Execute { token_a, token_b, max_vaults }) => {
+            dca_execute(&client, &token_a, &token_b, max_vaults)
+        }
+        Command::Dca(DcaCommand::Close { token_a, token_b, dca_id }) => dca_close(&client, &token_a, &token_b, dca_id),
+        Command::Dca(DcaCommand::List { token_a, token_b, owner }) => {
+            dca_list(&client, &token_a, &token_b, owner.as_ref())
+        }
+        Command::Farm(FarmCommand::Create { token_a, token_b }) => farm_create(&client, &token_a, &token_b),
+        Command::Farm(FarmCommand::AddReward { token_a, token_b, mint, schedule }) => {
+            farm_add_reward(&client, &token_a, &token_b, &mint, &schedule)
+        }
+        Command::Farm(FarmCommand::SetSchedule { token_a, token_b, reward_index, schedule }) => {
+            farm_set_schedule(&client, &token_a, &token_b, reward_index, &schedule)
+        }
+        Command::Farm(FarmCommand::Stake { token_a, token_b, amount }) => {
+            farm_stake(&client, &token_a, &token_b, amount, true)
+        }
+        Command::Farm(FarmCommand::Unstake { token_a, token_b, amount }) => {
+            farm_stake(&client, &token_a, &token_b, amount, false)
+        }
+        Command::Farm(FarmCommand::Claim { token_a, token_b }) => farm_claim(&client, &token_a, &token_b),
+        Command::Farm(FarmCommand::Show { token_a, token_b, owner }) => {
+            farm_show(&client, &token_a, &token_b, owner.as_ref())
+        }
+        Command::Lock(LockCommand::Add { token_a, token_b, amount, unlock_time }) => {
+            lock_add(&client, &token_a, &token_b, amount, unlock_time)
+        }
+        Command::Lock(LockCommand::Unlock { token_a, token_b }) => lock_unlock(&client, &token_a, &token_b),
+        Command::Lock(LockCommand::Expire { token_a, token_b, owner }) => {
+            lock_expire(&client, &token_a, &token_b, &owner)
+        }
+        Command::Lock(LockCommand::List { token_a, token_b, owner }) => {
+            lock_list(&client, &token_a, &token_b, owner.as_ref())
+        }
+        Command::AddLiquidity { pool, lp_mint, amount_a, amount_b } => {
+            add_liquidity(&client, &pool, lp_mint, amount_a, amount_b)
+        }
+        Command::RemoveLiquidity { pool, lp_mint, lp_amount } => {
+            remove_liquidity(&client, &pool, lp_mint, lp_amount)
+        }
+        Command::Swap { pool, amount_in, b_to_a, min_out } => {
+            swap(&client, &pool, amount_in, !b_to_a, min_out)
+        }
+        Command::Route { from, to, amount_in, max_hops, min_out, execute } => {
+            route(&client, &from, &to, amount_in, max_hops, min_out, execute)
+        }
+        Command::List => list(&client),
+        Command::Inspect { pool, token_a, token_b } => {
+            let address = match (pool, token_a, token_b) {
+                (Some(pool), _, _) => pool,
+                (None, Some(a), Some(b)) => {
+                    instruction::find_pool_address(&client.config.program_id, &a, &b).0
+                }
+                _ => bail!("pass --pool or both --token-a and --token-b"),
+            };
+            inspect(&client, &address)
+        }
+    }
+}
+
+fn pool_json(address: &Pubkey, pool: &Pool) -> Value {
+    json!({
+        "address": address.to_string(),
+        "pool_id": pool.pool_id,
+        "pool_type": format!("{:?}", pool.pool_type),
+        "token_a": pool.token_a.to_string(),
+        "token_b": pool.token_b.to_string(),
+        "token_program_a": pool.token_program_a.to_string(),
+        "token_program_b": pool.token_program_b.to_string(),
+        "fee_rate_bps": pool.fee_rate,
+        "dynamic_fee": pool.dynamic_fee,
+        "min_fee_rate_bps": pool.min_fee_rate,
+        "max_fee_rate_bps": pool.max_fee_rate,
+        "volatility_bps": pool.volatility_bps,
+        "max_price_move_bps": pool.max_price_move_bps,
+        "reserve_a": pool.reserve_a,
+        "reserve_b": pool.reserve_b,
+        "total_lp_supply": pool.total_lp_supply,
+        "is_active": pool.is_active,
+        "created_at": pool.created_at,
+        "price_a_cumulative": u128::from_le_bytes(pool.price_a_cumulative).to_string(),
+        "price_b_cumulative": u128::from_le_bytes(pool.price_b_cumulative).to_string(),
+        "price_cumulative_updated_at": pool.price_cumulative_updated_at,
+    })
+}
+
+fn with_transaction(mut value: Value, transaction: Value) -> Value {
+    value["transaction"] = transaction;
+    value
+}
+
+fn check_min_out(amount_out: u64, min_out: Option<u64>) -> Result<()> {
+    match min_out {
+        Some(min_out) if amount_out < min_out => {
+            bail!("quoted output {amount_out} is below --min-out {min_out}")
+        }
+        _ => Ok(()),
+    }
+}
+
+fn user_keys(client: &Client, pool: &PoolKeys) -> UserKeys {
+    let user = client.payer();
+    UserKeys {
+        user,
+        token_a: client.associated_token_address(&user, &pool.token_a, &pool.token_program_a),
+        token_b: client.associated_token_address(&user, &pool.token_b, &pool.token_program_b),
+        lp: client.associated_token_address(&user, &pool.lp_mint, &client.config.token_program_id),
+    }
+}
+
+fn registry_init(client: &Client) -> Result<Value> {
+    let config = &client.config;
+    let (registry, _) = instruction::find_registry_address(&config.program_id);
+    let ix = instruction::initialize_registry(&config.program_id, &client.payer(), &config.token_program_id);
+
+    Ok(with_transaction(
+        json!({ "registry": registry.to_string() }),
+        client.execute(&[ix], &[])?,
+    ))
+}
+
+fn config_init(client: &Client, admin: Option<Pubkey>, guardian: Option<Pubkey>) -> Result<Value> {
+    let config = &client.config;
+    let (program_config, _) = instruction::find_config_address(&config.program_id);
+    let admin = admin.unwrap_or_else(|| client.payer());
+    let guardian = guardian.unwrap_or_else(|| client.payer());
+    let ix = instruction::initialize_config(&config.program_id, &client.payer(), &admin, &guardian);
+
+    Ok(with_transaction(
+        json!({
+            "config": program_config.to_string(),
+            "admin": admin.to_string(),
+            "guardian": guardian.to_string(),
+        }),
+        client.execute(&[ix], &[])?,
+    ))
+}
+
+fn config_set_admin(client: &Client, admin: &Pubkey) -> Result<Value> {
+    let config = &client.config;
+    let ix = instruction::set_admin(&config.program_id, &client.admin(), admin);
+
+    Ok(with_transaction(json!({ "admin": admin.to_string() }), client.execute_admin(ix)?))
+}
+
+fn multisig_create(client: &Client, signers: &[Pubkey], threshold: u8, create_key: Option<Pubkey>) -> Result<Value> {
+    let config = &client.config;
+    let create_key = create_key.unwrap_or_else(Pubkey::new_unique);
+    let (multisig, _) = instruction::find_multisig_address(&config.program_id, &create_key);
+    let ix = instruction::create_multisig(&config.program_id, &client.payer(), &create_key, threshold, signers);
+
+    Ok(with_transaction(
+        json!({
+            "multisig": multisig.to_string(),
+            "create_key": create_key.to_string(),
+            "threshold": threshold,
+            "signers": signers.iter().map(Pubkey::to_string).collect::<Vec<_>>(),
+        }),
+        client.execute(&[ix], &[])?,
+    ))
+}
+
+fn multisig_show(client: &Client) -> Result<Value> {
+    let Some(address) = client.config.multisig else {
+        bail!("pass --multisig");
+    };
+    Ok(multisig_json(&address, &client.fetch_multisig(&address)?))
+}
+
+// Sends a membership change to the --multisig, approved by the keypair and
+// each --multisig-signer
+fn multisig_update(client: &Client, build: impl FnOnce(&Pubkey, &Pubkey, &[Pubkey]) -> Instruction) -> Result<Value> {
+    let Some(address) = client.config.multisig else {
+        bail!("pass --multisig");
+    };
+    let ix = build(&client.config.program_id, &address, &client.multisig_members());
+    let transaction = client.execute_as_members(&[ix])?;
+    Ok(with_transaction(multisig_json(&address, &client.fetch_multisig(&address)?), transaction))
+}
+
+fn multisig_json(address: &Pubkey, multisig: &Multisig) -> Value {
+    json!({
+        "multisig": address.to_string(),
+        "threshold": multisig.threshold,
+        "signers": multisig.members().iter().map(Pubkey::to_string).collect::<Vec<_>>(),
+    })
+}
+
+fn governance_propose(client: &Client, target: &Pubkey, change: GovernanceChange) -> Result<Value> {
+    let config = &client.config;
+    let ix = instruction::propose_change(&config.program_id, &client.admin(), &client.payer(), target, change);
+    let transaction = client.execute_admin(ix)?;
+
+    // The eta is only known once the proposal has landed
+    let value = match client.fetch_pending_change(target) {
+        Ok((address, pending)) => pending_change_json(&address, &pending),
+        Err(_) => json!({ "target": target.to_string(), "change": format!("{change:?}") }),
+    };
+    Ok(with_transaction(value, transaction))
+}
+
+fn governance_show(client: &Client, pool: Option<Pubkey>) -> Result<Value> {
+    let target = change_target(client, pool);
+    let (address, pending) = client.fetch_pending_change(&target)?;
+    Ok(pending_change_json(&address, &pending))
+}
+
+fn governance_execute(client: &Client, pool: Option<Pubkey>) -> Result<Value> {
+    let config = &client.config;
+    let target = change_target(client, pool);
+    let (address, pending) = client.fetch_pending_change(&target)?;
+    // Only fee-rate changes update the pool's registry entry
+    let pool_id = match (pool, pending.change()) {
+        (Some(pool), GovernanceChange::FeeRate { .. }) => client.fetch_pool(&pool)?.pool_id,
+        _ => 0,
+    };
+    let ix = instruction::execute_change(&config.program_id, &client.admin(), &client.payer(), &target, pool_id);
+
+    Ok(with_transaction(pending_change_json(&address, &pending), client.execute_admin(ix)?))
+}
+
+fn governance_cancel(client: &Client, pool: Option<Pubkey>) -> Result<Value> {
+    let config = &client.config;
+    let target = change_target(client, pool);
+    let (address, pending) = client.fetch_pending_change(&target)?;
+    let ix = instruction::cancel_change(&config.program_id, &client.admin(), &client.payer(), &target);
+
+    Ok(with_transaction(pending_change_json(&address, &pending), client.execute_admin(ix)?))
+}
+
+// Pool changes target their pool, guardian changes the program config
+fn change_target(client: &Client, pool: Option<Pubkey>) -> Pubkey {
+    pool.unwrap_or_else(|| instruction::find_config_address(&client.config.program_id).0)
+}
+
+fn pending_change_json(address: &Pubkey, pending: &PendingChange) -> Value {
+    let change = match pending.change() {
+        GovernanceChange::FeeRate { fee_rate } => json!({ "fee_rate_bps": fee_rate }),
+        GovernanceChange::DynamicFee { min_fee_rate, max_fee_rate } => {
+            json!({ "min_fee_rate_bps": min_fee_rate, "max_fee_rate_bps": max_fee_rate })
+        }
+        GovernanceChange::Guardian { guardian, withdrawals_open } => {
+            json!({ "guardian": guardian.to_string(), "withdrawals_open": withdrawals_open })
+        }
+        GovernanceChange::PriceBand { max_price_move_bps } => json!({ "max_price_move_bps": max_price_move_bps }),
+    };
+    json!({
+        "pending_change": address.to_string(),
+        "target": pending.target.to_string(),
+        "change": change,
+        "proposed_at": pending.proposed_at,
+        "eta": pending.eta,
+    })
+}
+
+fn config_set_pause(client: &Client, paused: u8) -> Result<Value> {
+    let config = &client.config;
+    let ix = instruction::set_pause(&config.program_id, &client.payer(), paused);
+
+    Ok(with_transaction(
+        json!({
+            "swaps_paused": paused & PAUSE_SWAPS != 0,
+            "deposits_paused": paused & PAUSE_DEPOSITS != 0,
+            "withdrawals_paused": paused & PAUSE_WITHDRAWALS != 0,
+        }),
+        client.execute(&[ix], &[])?,
+    ))
+}
+
+fn policy_set(
+    client: &Client,
+    fee_amount: u64,
+    fee_mint: Option<Pubkey>,
+    treasury: Option<Pubkey>,
+    rules: CreationRules,
+) -> Result<Value> {
+    let config = &client.config;
+    let fee_mint = fee_mint.unwrap_or_default();
+    let treasury = match treasury {
+        Some(treasury) => treasury,
+        None if fee_amount == 0 => Pubkey::default(),
+        None => bail!("pass --treasury to charge a fee"),
+    };
+    let ix = instruction::set_creation_policy(
+        &config.program_id,
+        &client.admin(),
+        &client.payer(),
+        &fee_mint,
+        fee_amount,
+        &treasury,
+        rules,
+    );
+    let transaction = client.execute_admin(ix)?;
+    Ok(with_transaction(policy_show(client)?, transaction))
+}
+
+fn policy_show(client: &Client) -> Result<Value> {
+    let Some(policy) = client.fetch_creation_policy()? else {
+        bail!("no creation policy; run `cargo-swap policy set` first");
+    };
+    Ok(policy_json(&policy))
+}
+
+fn policy_set_mint(client: &Client, mint: &Pubkey, status: MintStatus) -> Result<Value> {
+    let config = &client.config;
+    let ix = instruction::set_mint_status(&config.program_id, &client.admin(), mint, status);
+
+    Ok(with_transaction(
+        json!({ "mint": mint.to_string(), "status": format!("{status:?}") }),
+        client.execute_admin(ix)?,
+    ))
+}
+
+fn policy_json(policy: &CreationPolicy) -> Value {
+    let listed = |status: MintStatus| {
+        policy.mints[..policy.mint_count as usize]
+            .iter()
+            .filter(|mint| policy.mint_status(mint) == status)
+            .map(Pubkey::to_string)
+            .collect::<Vec<_>>()
+    };
+    json!({
+        "fee_amount": policy.fee_amount,
+        "fee_mint": if policy.is_lamport_fee() { "lamports".to_string() } else { policy.fee_mint.to_string() },
+        "treasury": policy.treasury.to_string(),
+        "standard": format!("{:?}", policy.rules.standard),
+        "stable": format!("{:?}", policy.rules.stable),
+        "concentrated": format!("{:?}", policy.rules.concentrated),
+        "allowed_mints": listed(MintStatus::Allowed),
+        "denied_mints": listed(MintStatus::Denied),
+    })
+}
+
+// Vaults and the LP mint live at PDAs of the pool
+fn derive_pool_keys(client: &Client, token_a: &Pubkey, token_b: &Pubkey) -> Result<PoolKeys> {
+    Ok(PoolKeys::new(
+        &client.config.program_id,
+        token_a,
+        token_b,
+        &client.mint_token_program(token_a)?,
+        &client.mint_token_program(token_b)?,
+    ))
+}
+
+fn pool_create(client: &Client, token_a: &Pubkey, token_b: &Pubkey, pool_type: PoolType) -> Result<Value> {
+    let config = &client.config;
+    let keys = derive_pool_keys(client, token_a, token_b)?;
+    let pool_id = client.fetch_registry()?.next_pool_id;
+    let mut ix = instruction::create_pool(
+        &config.program_id,
+        &client.payer(),
+        &keys,
+        pool_type,
+        &config.token_program_id,
+        pool_id,
+    );
+    // Token fees come out of the keypair's ATA for the fee mint
+    if let Some(policy) = client.fetch_creation_policy()?.filter(|policy| policy.fee_amount > 0) {
+        let token_fee = if policy.is_lamport_fee() {
+            None
+        } else {
+            let token_program = client.mint_token_program(&policy.fee_mint)?;
+            let source = client.associated_token_address(&client.payer(), &policy.fee_mint, &token_program);
+            Some(TokenFee { mint: policy.fee_mint, source, token_program })
+        };
+        ix = instruction::pay_creation_fee(ix, &policy.treasury, token_fee.as_ref());
+    }
+
+    Ok(with_transaction(
+        json!({
+            "pool": keys.pool.to_string(),
+            "pool_id": pool_id,
+            "pool_type": format!("{pool_type:?}"),
+            "fee_rate_bps": pool_type.default_fee_rate(),
+            "lp_mint": keys.lp_mint.to_string(),
+            "vault_a": keys.vault_a.to_string(),
+            "vault_b": keys.vault_b.to_string(),
+        }),
+        client.execute(&[ix], &[])?,
+    ))
+}
+
+fn pool_migrate(client: &Client, token_a: &Pubkey, token_b: &Pubkey) -> Result<Value> {
+    let config = &client.config;
+    let (pool, _) = instruction::find_pool_address(&config.program_id, token_a, token_b);
+    // Legacy pools only ever held tokens of the configured token program
+    let token_program = config.token_program_id;
+    let keys = PoolKeys::new(&config.program_id, token_a, token_b, &token_program, &token_program);
+    let legacy_vault_a = client.find_vault(&pool, token_a, &token_program)?;
+    let legacy_vault_b = client.find_vault(&pool, token_b, &token_program)?;
+    let ix = instruction::migrate_pool(&config.program_id, &keys, &legacy_vault_a, &legacy_vault_b, &client.payer());
+
+    Ok(with_transaction(
+        json!({ "pool": pool.to_string(), "version": POOL_VERSION }),
+        client.execute(&[ix], &[])?,
+    ))
+}
+
+fn pool_set_lp_metadata(
+    client: &Client,
+    token_a: &Pubkey,
+    token_b: &Pubkey,
+    field: LpMetadataField,
+    value: String,
+) -> Result<Value> {
+    let config = &client.config;
+    let keys = derive_pool_keys(client, token_a, token_b)?;
+    let ix = instruction::update_lp_metadata(
+        &config.program_id,
+        &client.admin(),
+        &client.payer(),
+        &keys,
+        field,
+        value.clone(),
+    );
+
+    Ok(with_transaction(
+        json!({ "lp_mint": keys.lp_mint.to_string(), "field": format!("{field:?}"), "value": value }),
+        client.execute_admin(ix)?,
+    ))
+}
+
+fn pool_init(
+    client: &Client,
+    token_a: &Pubkey,
+    token_b: &Pubkey,
+    amount_a: u64,
+    amount_b: u64,
+    pool_id: u64,
+) -> Result<Value> {
+    let config = &client.config;
+    let keys = derive_pool_keys(client, token_a, token_b)?;
+    let user = user_keys(client, &keys);
+
+    let mut ixs = Vec::new();
+    client.ensure_associated_token_account(&user.user, &keys.lp_mint, &config.token_program_id, &mut ixs)?;
+    ixs.push(instruction::init_pool(
+        &config.program_id,
+        pool_id,
+        &keys,
+        &user,
+        &config.token_program_id,
+        amount_a,
+        amount_b,
+    ));
+
+    Ok(with_transaction(
+        json!({
+            "pool": keys.pool.to_string(),
+            "lp_mint": keys.lp_mint.to_string(),
+            "vault_a": keys.vault_a.to_string(),
+            "vault_b": keys.vault_b.to_string(),
+            "user_lp": user.lp.to_string(),
+        }),
+        client.execute(&ixs, &[])?,
+    ))
+}
+
+fn add_liquidity(client: &Client, args: &PoolArgs, lp_mint: Option<Pubkey>, amount_a: u64, amount_b: u64) -> Result<Value> {
+    let config = &client.config;
+    let (keys, state) = client.pool_keys(&args.token_a, &args.token_b, lp_mint, args.vault_a, args.vault_b)?;
+    let user = user_keys(client, &keys);
+    let quote = quote::quote_add_liquidity(&state, amount_a, amount_b)?;
+
+    let mut ixs = Vec::new();
+    let ix = if args.native {
+        client.ensure_associated_token_account(&user.user, &keys.lp_mint, &config.token_program_id, &mut ixs)?;
+        instruction::add_liquidity_native(
+            &config.program_id,
+            state.pool_id,
+            &keys,
+            &user,
+            &config.token_program_id,
+            amount_a,
+            amount_b,
+        )
+    } else {
+        let ix = instruction::add_liquidity(
+            &config.program_id,
+            state.pool_id,
+            &keys,
+            &user,
+            &config.token_program_id,
+            amount_a,
+            amount_b,
+        );
+        instruction::create_missing_accounts(ix, &user.user, &config.ata_program_id)
+    };
+    ixs.push(ix);
+
+    Ok(with_transaction(
+        json!({
+            "pool": keys.pool.to_string(),
+            "deposit_a": quote.amount_a,
+            "deposit_b": quote.amount_b,
+            "lp_minted": quote.lp_amount,
+            "reserve_a_after": quote.reserve_a_after,
+            "reserve_b_after": quote.reserve_b_after,
+        }),
+        client.execute(&ixs, &[])?,
+    ))
+}
+
+fn remove_liquidity(client: &Client, args: &PoolArgs, lp_mint: Option<Pubkey>, lp_amount: u64) -> Result<Value> {
+    let config = &client.config;
+    let (keys, state) = client.pool_keys(&args.token_a, &args.token_b, lp_mint, args.vault_a, args.vault_b)?;
+    let user = user_keys(client, &keys);
+    let quote = quote::quote_remove_liquidity(&state, lp_amount)?;
+
+    let mut ixs = Vec::new();
+    let ix = if args.native {
+        for (mint, token_program) in [(&keys.token_a, &keys.token_program_a), (&keys.token_b, &keys.token_program_b)] {
+            // Native withdrawals pay wrapped SOL out as lamports
+            if !is_native_mint(mint) {
+                client.ensure_associated_token_account(&user.user, mint, token_program, &mut ixs)?;
+            }
+        }
+        instruction::remove_liquidity_native(
+            &config.program_id,
+            state.pool_id,
+            &keys,
+            &user,
+            &config.token_program_id,
+            lp_amount,
+        )
+    } else {
+        let ix = instruction::remove_liquidity(
+            &config.program_id,
+            state.pool_id,
+            &keys,
+            &user,
+            &config.token_program_id,
+            lp_amount,
+        );
+        instruction::create_missing_accounts(ix, &user.user, &config.ata_program_id)
+    };
+    ixs.push(ix);
+
+    Ok(with_transaction(
+        json!({
+            "pool": keys.pool.to_string(),
+            "lp_burned": lp_amount,
+            "withdraw_a": quote.amount_a,
+            "withdraw_b": quote.amount_b,
+            "reserve_a_after": quote.reserve_a_after,
+            "reserve_b_after": quote.reserve_b_after,
+        }),
+        client.execute(&ixs, &[])?,
+    ))
+}
+
+fn swap(client: &Client, args: &PoolArgs, amount_in: u64, direction_a_to_b: bool, min_out: Option<u64>) -> Result<Value> {
+    let config = &client.config;
+    let (keys, state) = client.pool_keys(&args.token_a, &args.token_b, None, args.vault_a, args.vault_b)?;
+    let user = user_keys(client, &keys);
+    let state = if state.dynamic_fee { quote::with_current_fee(&state, client.unix_timestamp()?) } else { state };
+    let quote = quote::quote_swap_exact_in(&state, amount_in, direction_a_to_b)?;
+
+    check_min_out(quote.amount_out, min_out)?;
+
+    let mut ixs = Vec::new();
+    let ix = if args.native {
+        let (mint_out, program_out) = if direction_a_to_b {
+            (&keys.token_b, &keys.token_program_b)
+        } else {
+            (&keys.token_a, &keys.token_program_a)
+        };
+        if !is_native_mint(mint_out) {
+            client.ensure_associated_token_account(&user.user, mint_out, program_out, &mut ixs)?;
+        }
+        instruction::swap_native(&config.program_id, state.pool_id, &keys, &user, amount_in, direction_a_to_b)
+    } else {
+        let ix = instruction::swap(&config.program_id, state.pool_id, &keys, &user, amount_in, direction_a_to_b);
+        instruction::create_missing_accounts(ix, &user.user, &config.ata_program_id)
+    };
+    ixs.push(ix);
+
+    Ok(with_transaction(
+        json!({
+            "pool": keys.pool.to_string(),
+            "direction": if direction_a_to_b { "A->B" } else { "B->A" },
+            "amount_in": quote.amount_in,
+            "amount_out": quote.amount_out,
+            "fee": quote.fee,
+            "price_impact_bps": quote.price_impact_bps,
+            "reserve_a_after": quote.reserve_a_after,
+            "reserve_b_after": quote.reserve_b_after,
+        }),
+        client.execute(&ixs, &[])?,
+    ))
+}
+
+fn route(
+    client: &Client,
+    from: &Pubkey,
+    to: &Pubkey,
+    amount_in: u64,
+    max_hops: usize,
+    min_out: Option<u64>,
+    execute: bool,
+) -> Result<Value> {
+    let config = &client.config;
+    let now = client.unix_timestamp()?;
+    let pools: Vec<_> =
+        client.fetch_pools()?.into_iter().map(|(address, pool)| (address, quote::with_current_fee(&pool, now))).collect();
+    let Some(hops) = route::best_route(&pools, from, to, amount_in, max_hops) else {
+        bail!("no route from {from} to {to} within {max_hops} hops");
+    };
+    let amount_out = hops.last().map(|hop| hop.amount_out).unwrap_or_default();
+
+    check_min_out(amount_out, min_out)?;
+
+    let mut result = json!({
+        "amount_in": amount_in,
+        "amount_out": amount_out,
+        "hops": hops
+            .iter()
+            .map(|hop| json!({
+                "pool": hop.pool_address.to_string(),
+                "mint_in": hop.mint_in().to_string(),
+                "mint_out": hop.mint_out().to_string(),
+                "amount_in": hop.amount_in,
+                "amount_out": hop.amount_out,
+            }))
+            .collect::<Vec<_>>(),
+    });
+
+    if execute {
+        let user = client.payer();
+        let mut ixs = Vec::new();
+        for hop in &hops {
+            let pool = hop.pool;
+            let keys = PoolKeys {
+                pool: hop.pool_address,
+                token_a: pool.token_a,
+                token_b: pool.token_b,
+                vault_a: client.find_vault(&hop.pool_address, &pool.token_a, &pool.token_program_a)?,
+                vault_b: client.find_vault(&hop.pool_address, &pool.token_b, &pool.token_program_b)?,
+                lp_mint: Pubkey::default(),
+                token_program_a: pool.token_program_a,
+                token_program_b: pool.token_program_b,
+            };
+            let user_keys = UserKeys {
+                user,
+                token_a: client.associated_token_address(&user, &pool.token_a, &pool.token_program_a),
+                token_b: client.associated_token_address(&user, &pool.token_b, &pool.token_program_b),
+                lp: Pubkey::default(),
+            };
+            let ix = instruction::swap(
+                &config.program_id,
+                pool.pool_id,
+                &keys,
+                &user_keys,
+                hop.amount_in,
+                hop.direction_a_to_b,
+            );
+            ixs.push(instruction::create_missing_accounts(ix, &user, &config.ata_program_id));
+        }
+        result = with_transaction(result, client.execute(&ixs, &[])?);
+    }
+
+    Ok(result)
+}
+
+fn order_place(
+    client: &Client,
+    token_a: &Pubkey,
+    token_b: &Pubkey,
+    order_id: u64,
+    amount_in: u64,
+    min_out: u64,
+    direction_a_to_b: bool,
+) -> Result<Value> {
+    let config = &client.config;
+    let (keys, _) = client.pool_keys(token_a, token_b, None, None, None)?;
+    let user = user_keys(client, &keys);
+    let (order, _) = instruction::find_limit_order_address(&config.program_id, &keys.pool, &user.user, order_id);
+
+    // Fills pay into the owner's ATA for the output side
+    let mut ixs = Vec::new();
+    let (mint_out, program_out) = if direction_a_to_b {
+        (&keys.token_b, &keys.token_program_b)
+    } else {
+        (&keys.token_a, &keys.token_program_a)
+    };
+    client.ensure_associated_token_account(&user.user, mint_out, program_out, &mut ixs)?;
+    ixs.push(instruction::place_limit_order(
+        &config.program_id,
+        &keys,
+        &user,
+        order_id,
+        amount_in,
+        min_out,
+        direction_a_to_b,
+    ));
+
+    Ok(with_transaction(
+        json!({
+            "order": order.to_string(),
+            "pool": keys.pool.to_string(),
+            "direction": if direction_a_to_b { "A->B" } else { "B->A" },
+            "amount_in": amount_in,
+            "min_amount_out": min_out,
+        }),
+        client.execute(&ixs, &[])?,
+    ))
+}
+
+fn order_cancel(client: &Client, token_a: &Pubkey, token_b: &Pubkey, order_id: u64) -> Result<Value> {
+    let config = &client.config;
+    let (keys, _) = client.pool_keys(token_a, token_b, None, None, None)?;
+    let user = user_keys(client, &keys);
+    let (address, _) = instruction::find_limit_order_address(&config.program_id, &keys.pool, &user.user, order_id);
+    let order = client.fetch_limit_order(&address)?;
+    let ix = instruction::cancel_limit_order(&config.program_id, &keys, &user, order_id, order.direction_a_to_b);
+
+    Ok(with_transaction(order_json(&address, &order), client.execute(&[ix], &[])?))
+}
+
+fn order_list(client: &Client, token_a: &Pubkey, token_b: &Pubkey, owner: Option<&Pubkey>) -> Result<Value> {
+    let (pool, _) = instruction::find_pool_address(&client.config.program_id, token_a, token_b);
+    let orders = client.fetch_limit_orders(&pool, owner)?;
+    Ok(json!({
+        "pool": pool.to_string(),
+        "orders": orders.iter().map(|(address, order)| order_json(address, order)).collect::<Vec<_>>(),
+    }))
+}
+
+// Picks the orders a fill would execute by quoting them in turn the way the
+// program does, so the crank doesn't pay for orders it can't fill
+fn order_fill(client: &Client, token_a: &Pubkey, token_b: &Pubkey, max_orders: usize) -> Result<Value> {
+    let config = &client.config;
+    let (keys, state) = client.pool_keys(token_a, token_b, None, None, None)?;
+    let now = client.unix_timestamp()?;
+    let mut pool = state;
+    let mut fills = Vec::new();
+    for (address, order) in client.fetch_limit_orders(&keys.pool, None)? {
+        if fills.len() == max_orders {
+            break;
+        }
+        let pricing = quote::with_current_fee(&pool, now);
+        let Ok(quote) = quote::quote_swap_exact_in(&pricing, order.amount_in, order.direction_a_to_b) else {
+            continue;
+        };
+        if quote.amount_out < order.min_amount_out {
+            continue;
+        }
+        pool.reserve_a = quote.reserve_a_after;
+        pool.reserve_b = quote.reserve_b_after;
+        let (mint_in, program_in) = if order.direction_a_to_b {
+            (keys.token_a, keys.token_program_a)
+        } else {
+            (keys.token_b, keys.token_program_b)
+        };
+        let refund = client.associated_token_address(&order.owner, &mint_in, &program_in);
+        let order_keys = OrderKeys { order: address, owner: order.owner, destination: order.destination, refund };
+        fills.push((order_keys, quote.amount_out));
+    }
+    if fills.is_empty() {
+        bail!("no open order on {} is fillable at the current price", keys.pool);
+    }
+
+    let orders: Vec<_> = fills.iter().map(|(order, _)| *order).collect();
+    let ix = instruction::fill_limit_orders(&config.program_id, &keys, &client.payer(), &orders);
+    Ok(with_transaction(
+        json!({
+            "pool": keys.pool.to_string(),
+            "filled": fills
+                .iter()
+                .map(|(order, amount_out)| json!({ "order": order.order.to_string(), "amount_out": amount_out }))
+                .collect::<Vec<_>>(),
+        }),
+        client.execute(&[ix], &[])?,
+    ))
+}
+
+fn order_json(address: &Pubkey, order: &LimitOrder) -> Value {
+    json!({
+        "order": address.to_string(),
+        "order_id": order.order_id,
+        "owner": order.owner.to_string(),
+        "direction": if order.direction_a_to_b { "A->B" } else { "B->A" },
+        "amount_in": order.amount_in,
+        "min_amount_out": order.min_amount_out,
+        "destination": order.destination.to_string(),
+        "created_at": order.created_at,
+    })
+}
+
+fn twamm_place(
+    client: &Client,
+    token_a: &Pubkey,
+    token_b: &Pubkey,
+    order_id: u64,
+    amount_in: u64,
+    schedule: OrderSchedule,
+    direction_a_to_b: bool,
+) -> Result<Value> {
+    let config = &client.config;
+    let (keys, _) = client.pool_keys(token_a, token_b, None, None, None)?;
+    let user = user_keys(client, &keys);
+    let (order, _) = instruction::find_long_term_order_address(&config.program_id, &keys.pool, &user.user, order_id);
+    let ix = instruction::place_long_term_order(
+        &config.program_id,
+        &keys,
+        &user,
+        order_id,
+        amount_in,
+        schedule,
+        direction_a_to_b,
+    );
+
+    Ok(with_transaction(
+        json!({
+            "order": order.to_string(),
+            "pool": keys.pool.to_string(),
+            "direction": if direction_a_to_b { "A->B" } else { "B->A" },
+            "amount_in": amount_in,
+            "intervals": schedule.intervals,
+            "interval_seconds": schedule.interval_seconds,
+            "max_slippage_bps": schedule.max_slippage_bps,
+        }),
+        client.execute(&[ix], &[])?,
+    ))
+}
+
+// Executes the next due interval of each order with one due by the cluster
+// clock
+fn twamm_execute(client: &Client, token_a: &Pubkey, token_b: &Pubkey, max_orders: usize) -> Result<Value> {
+    let (pool, _) = instruction::find_pool_address(&client.config.program_id, token_a, token_b);
+    let now = client.unix_timestamp()?;
+    let due: Vec<_> = client
+        .fetch_long_term_orders(&pool, None)?
+        .into_iter()
+        .filter(|(_, order)| order.intervals_due(now) > order.intervals_executed)
+        .take(max_orders)
+        .collect();
+    if due.is_empty() {
+        bail!("no long-term order on {pool} has an interval due");
+    }
+
+    let orders: Vec<_> = due.iter().map(|(address, _)| *address).collect();
+    let ix = instruction::execute_long_term_orders(&client.config.program_id, &pool, &orders);
+    Ok(with_transaction(
+        json!({
+            "pool": pool.to_string(),
+            "executed": due
+                .iter()
+                .map(|(address, order)| json!({
+                    "order": address.to_string(),
+                    "intervals_due": order.intervals_due(now) - order.intervals_executed,
+                }))
+                .collect::<Vec<_>>(),
+        }),
+        client.execute(&[ix], &[])?,
+    ))
+}
+
+fn twamm_withdraw(client: &Client, token_a: &Pubkey, token_b: &Pubkey, order_id: u64) -> Result<Value> {
+    let config = &client.config;
+    let (keys, _) = client.pool_keys(token_a, token_b, None, None, None)?;
+    let user = user_keys(client, &keys);
+    let (address, _) = instruction::find_long_term_order_address(&config.program_id, &keys.pool, &user.user, order_id);
+    let order = client.fetch_long_term_order(&address)?;
+
+    // Proceeds pay into the owner's ATA for the output side
+    let mut ixs = Vec::new();
+    let (mint_out, program_out) = if order.direction_a_to_b {
+        (&keys.token_b, &keys.token_program_b)
+    } else {
+        (&keys.token_a, &keys.token_program_a)
+    };
+    client.ensure_associated_token_account(&user.user, mint_out, program_out, &mut ixs)?;
+    ixs.push(instruction::withdraw_long_term_proceeds(
+        &config.program_id,
+        &keys,
+        &user,
+        order_id,
+        order.direction_a_to_b,
+    ));
+
+    Ok(with_transaction(long_term_order_json(&address, &order), client.execute(&ixs, &[])?))
+}
+
+fn twamm_cancel(client: &Client, token_a: &Pubkey, token_b: &Pubkey, order_id: u64) -> Result<Value> {
+    let config = &client.config;
+    let (keys, _) = client.pool_keys(token_a, token_b, None, None, None)?;
+    let user = user_keys(client, &keys);
+    let (address, _) = instruction::find_long_term_order_address(&config.program_id, &keys.pool, &user.user, order_id);
+    let order = client.fetch_long_term_order(&address)?;
+
+    let mut ixs = Vec::new();
+    client.ensure_associated_token_account(&user.user, &keys.token_a, &keys.token_program_a, &mut ixs)?;
+    client.ensure_associated_token_account(&user.user, &keys.token_b, &keys.token_program_b, &mut ixs)?;
+    ixs.push(instruction::cancel_long_term_order(&config.program_id, &keys, &user, order_id));
+
+    Ok(with_transaction(long_term_order_json(&address, &order), client.execute(&ixs, &[])?))
+}
+
+fn twamm_list(client: &Client, token_a: &Pubkey, token_b: &Pubkey, owner: Option<&Pubkey>) -> Result<Value> {
+    let (pool, _) = instruction::find_pool_address(&client.config.program_id, token_a, token_b);
+    let orders = client.fetch_long_term_orders(&pool, owner)?;
+    Ok(json!({
+        "pool": pool.to_string(),
+        "orders": orders.iter().map(|(address, order)| long_term_order_json(address, order)).collect::<Vec<_>>(),
+    }))
+}
+
+fn long_term_order_json(address: &Pubkey, order: &LongTermOrder) -> Value {
+    json!({
+        "order": address.to_string(),
+        "order_id": order.order_id,
+        "owner": order.owner.to_string(),
+        "direction": if order.direction_a_to_b { "A->B" } else { "B->A" },
+        "amount_in": order.amount_in,
+        "sold": order.sold,
+        "proceeds": order.proceeds,
+        "intervals": order.intervals,
+        "intervals_executed": order.intervals_executed,
+        "interval_seconds": order.interval_seconds,
+        "max_slippage_bps": order.max_slippage_bps,
+        "start_time": order.start_time,
+    })
+}
+
+fn dca_create(
+    client: &Client,
+    token_a: &Pubkey,
+    token_b: &Pubkey,
+    dca_id: u64,
+    deposit: u64,
+    params: DcaParams,
+    direction_a_to_b: bool,
+) -> Result<Value> {
+    let config = &client.config;
+    let (keys, _) = client.pool_keys(token_a, token_b, None, None, None)?;
+    let user = user_keys(client, &keys);
+    let (dca, _) = instruction::find_dca_address(&config.program_id, &keys.pool, &user.user, dca_id);
+
+    // Each period's output pays into the owner's ATA for the output side
+    let mut ixs = Vec::new();
+    let (mint_out, program_out) = if direction_a_to_b {
+        (&keys.token_b, &keys.token_program_b)
+    } else {
+        (&keys.token_a, &keys.token_program_a)
+    };
+    client.ensure_associated_token_account(&user.user, mint_out, program_out, &mut ixs)?;
+    ixs.push(instruction::create_dca(&config.program_id, &keys, &user, dca_id, deposit, params, direction_a_to_b));
+
+    Ok(with_transaction(
+        json!({
+            "dca": dca.to_string(),
+            "pool": keys.pool.to_string(),
+            "direction": if direction_a_to_b { "A->B" } else { "B->A" },
+            "deposit": deposit,
+            "amount_per_period": params.amount_per_period,
+            "interval_seconds": params.interval_seconds,
+            "max_slippage_bps": params.max_slippage_bps,
+        }),
+        client.execute(&ixs, &[])?,
+    ))
+}
+
+// Executes the vaults due by the cluster clock separately, so one vault
+// refusing a manipulated price doesn't hold back the rest
+fn dca_execute(client: &Client, token_a: &Pubkey, token_b: &Pubkey, max_vaults: usize) -> Result<Value> {
+    let config = &client.config;
+    let (keys, _) = client.pool_keys(token_a, token_b, None, None, None)?;
+    let now = client.unix_timestamp()?;
+    let due: Vec<_> = client
+        .fetch_dca_vaults(&keys.pool, None)?
+        .into_iter()
+        .filter(|(_, vault)| vault.next_execution <= now)
+        .take(max_vaults)
+        .collect();
+    if due.is_empty() {
+        bail!("no DCA vault on {} is due", keys.pool);
+    }
+
+    let executed = due
+        .iter()
+        .map(|(address, vault)| {
+            let ix = instruction::execute_dca(
+                &config.program_id,
+                &keys,
+                address,
+                &vault.destination,
+                vault.direction_a_to_b,
+            );
+            match client.execute(&[ix], &[]) {
+                Ok(transaction) => json!({ "dca": address.to_string(), "transaction": transaction }),
+                Err(err) => json!({ "dca": address.to_string(), "error": err.to_string() }),
+            }
+        })
+        .collect::<Vec<_>>();
+    Ok(json!({ "pool": keys.pool.to_string(), "executed": executed }))
+}
+
+fn dca_close(client: &Client, token_a: &Pubkey, token_b: &Pubkey, dca_id: u64) -> Result<Value> {
+    let config = &client.config;
+    let (keys, _) = client.pool_keys(token_a, token_b, None, None, None)?;
+    let user = user_keys(client, &keys);
+    let (address, _) = instruction::find_dca_address(&config.program_id, &keys.pool, &user.user, dca_id);
+    let vault = client.fetch_dca_vault(&address)?;
+    let ix = instruction::close_dca(&config.program_id, &keys, &user, dca_id, vault.direction_a_to_b);
+
+    Ok(with_transaction(dca_json(&address, &vault), client.execute(&[ix], &[])?))
+}
+
+fn dca_list(client: &Client, token_a: &Pubkey, token_b: &Pubkey, owner: Option<&Pubkey>) -> Result<Value> {
+    let (pool, _) = instruction::find_pool_address(&client.config.program_id, token_a, token_b);
+    let vaults = client.fetch_dca_vaults(&pool, owner)?;
+    Ok(json!({
+        "pool": pool.to_string(),
+        "vaults": vaults.iter().map(|(address, vault)| dca_json(address, vault)).collect::<Vec<_>>(),
+    }))
+}
+
+fn dca_json(address: &Pubkey, vault: &DcaVault) -> Value {
+    json!({
+        "dca": address.to_string(),
+        "dca_id": vault.dca_id,
+        "owner": vault.owner.to_string(),
+        "direction": if vault.direction_a_to_b { "A->B" } else { "B->A" },
+        "amount_per_period": vault.amount_per_period,
+        "interval_seconds": vault.interval_seconds,
+        "max_slippage_bps": vault.max_slippage_bps,
+        "destination": vault.destination.to_string(),
+        "next_execution": vault.next_execution,
+        "periods_executed": vault.periods_executed,
+    })
+}
+
+fn farm_create(client: &Client, token_a: &Pubkey, token_b: &Pubkey) -> Result<Value> {
+    let config = &client.config;
+    let (keys, _) = client.pool_keys(token_a, token_b, None, None, None)?;
+    let (farm, _) = instruction::find_farm_address(&config.program_id, &keys.pool);
+    let (admin, payer) = (client.admin(), client.payer());
+    let ix = instruction::create_farm(&config.program_id, &admin, &payer, &keys, &config.token_program_id);
+
+    Ok(with_transaction(
+        json!({ "farm": farm.to_string(), "pool": keys.pool.to_string() }),
+        client.execute_admin(ix)?,
+    ))
+}
+
+fn reward_schedule(client: &Client, args: &ScheduleArgs) -> Result<RewardSchedule> {
+    let start_time = match args.start_time {
+        Some(start_time) => start_time,
+        None => client.unix_timestamp()?,
+    };
+    Ok(RewardSchedule {
+        emission_per_second: args.emission_per_second,
+        start_time,
+        end_time: start_time.saturating_add(args.duration_seconds),
+    })
+}
+
+fn farm_add_reward(
+    client: &Client,
+    token_a: &Pubkey,
+    token_b: &Pubkey,
+    mint: &Pubkey,
+    args: &ScheduleArgs,
+) -> Result<Value> {
+    let config = &client.config;
+    let (pool, _) = instruction::find_pool_address(&config.program_id, token_a, token_b);
+    let (farm, _) = instruction::find_farm_address(&config.program_id, &pool);
+    let schedule = reward_schedule(client, args)?;
+    let token_program = client.mint_token_program(mint)?;
+    let ix = instruction::add_farm_reward(
+        &config.program_id,
+        &client.admin(),
+        &client.payer(),
+        &pool,
+        mint,
+        &token_program,
+        schedule,
+    );
+    let transaction = client.execute_admin(ix)?;
+
+    let mut value = farm_json(&farm, &client.fetch_farm(&farm)?);
+    value["reward_vault"] = json!(instruction::find_farm_vault_address(&config.program_id, &farm, mint).0.to_string());
+    Ok(with_transaction(value, transaction))
+}
+
+fn farm_set_schedule(
+    client: &Client,
+    token_a: &Pubkey,
+    token_b: &Pubkey,
+    reward_index: u8,
+    args: &ScheduleArgs,
+) -> Result<Value> {
+    let config = &client.config;
+    let (pool, _) = instruction::find_pool_address(&config.program_id, token_a, token_b);
+    let (farm, _) = instruction::find_farm_address(&config.program_id, &pool);
+    let schedule = reward_schedule(client, args)?;
+    let ix = instruction::set_farm_reward_schedule(&config.program_id, &client.admin(), &pool, reward_index, schedule);
+    let transaction = client.execute_admin(ix)?;
+
+    Ok(with_transaction(farm_json(&farm, &client.fetch_farm(&farm)?), transaction))
+}
+
+fn farm_stake(client: &Client, token_a: &Pubkey, token_b: &Pubkey, amount: u64, stake: bool) -> Result<Value> {
+    let config = &client.config;
+    let (keys, _) = client.pool_keys(token_a, token_b, None, None, None)?;
+    let (farm_address, _) = instruction::find_farm_address(&config.program_id, &keys.pool);
+    let farm = client.fetch_farm(&farm_address)?;
+    let user = UserKeys {
+        lp: client.associated_token_address(&client.payer(), &keys.lp_mint, &farm.lp_token_program),
+        ..user_keys(client, &keys)
+    };
+
+    let mut ixs = Vec::new();
+    if stake {
+        ixs.push(instruction::stake_lp(&config.program_id, &keys, &user, &farm.lp_token_program, amount));
+    } else {
+        client.ensure_associated_token_account(&user.user, &keys.lp_mint, &farm.lp_token_program, &mut ixs)?;
+        ixs.push(instruction::unstake_lp(&config.program_id, &keys, &user, &farm.lp_token_program, amount));
+    }
+    let transaction = client.execute(&ixs, &[])?;
+
+    Ok(with_transaction(
+        json!({
+            "farm": farm_address.to_string(),
+            "action": if stake { "stake" } else { "unstake" },
+            "amount": amount,
+        }),
+        transaction,
+    ))
+}
+
+fn farm_claim(client: &Client, token_a: &Pubkey, token_b: &Pubkey) -> Result<Value> {
+    let config = &client.config;
+    let (pool, _) = instruction::find_pool_address(&config.program_id, token_a, token_b);
+    let (farm_address, _) = instruction::find_farm_address(&config.program_id, &pool);
+    let farm = client.fetch_farm(&farm_address)?;
+    let owner = client.payer();
+    let (stake_address, _) = instruction::find_farm_stake_address(&config.program_id, &farm_address, &owner);
+    let Some(stake) = client.fetch_farm_stake(&stake_address)? else {
+        bail!("{owner} has never staked in {farm_address}");
+    };
+
+    let mut ixs = Vec::new();
+    let mut rewards = Vec::new();
+    for reward in farm.rewards() {
+        client.ensure_associated_token_account(&owner, &reward.mint, &reward.token_program, &mut ixs)?;
+        rewards.push(RewardKeys {
+            mint: reward.mint,
+            token_program: reward.token_program,
+            destination: client.associated_token_address(&owner, &reward.mint, &reward.token_program),
+        });
+    }
+    // Paid up to what each reward vault holds; the rest stays pending
+    let pending = farm.pending_rewards(&stake, client.unix_timestamp()?);
+    ixs.push(instruction::claim_farm_rewards(&config.program_id, &pool, &owner, &rewards));
+
+    Ok(with_transaction(
+        json!({
+            "farm": farm_address.to_string(),
+            "pending": rewards
+                .iter()
+                .zip(pending)
+                .map(|(reward, amount)| json!({ "mint": reward.mint.to_string(), "amount": amount }))
+                .collect::<Vec<_>>(),
+        }),
+        client.execute(&ixs, &[])?,
+    ))
+}
+
+fn farm_show(client: &Client, token_a: &Pubkey, token_b: &Pubkey, owner: Option<&Pubkey>) -> Result<Value> {
+    let config = &client.config;
+    let (pool, _) = instruction::find_pool_address(&config.program_id, token_a, token_b);
+    let (farm_address, _) = instruction::find_farm_address(&config.program_id, &pool);
+    let farm = client.fetch_farm(&farm_address)?;
+    let owner = owner.copied().unwrap_or_else(|| client.payer());
+    let (stake_address, _) = instruction::find_farm_stake_address(&config.program_id, &farm_address, &owner);
+
+    let mut value = farm_json(&farm_address, &farm);
+    value["stake"] = match client.fetch_farm_stake(&stake_address)? {
+        Some(stake) => stake_json(&stake_address, &farm, &stake, client.unix_timestamp()?),
+        None => Value::Null,
+    };
+    Ok(value)
+}
+
+fn farm_json(address: &Pubkey, farm: &Farm) -> Value {
+    json!({
+        "farm": address.to_string(),
+        "pool": farm.pool.to_string(),
+        "lp_mint": farm.lp_mint.to_string(),
+        "total_weight": farm.total_weight,
+        "rewards": farm
+            .rewards()
+            .iter()
+            .map(|reward| json!({
+                "mint": reward.mint.to_string(),
+                "emission_per_second": reward.emission_per_second,
+                "start_time": reward.start_time,
+                "end_time": reward.end_time,
+            }))
+            .collect::<Vec<_>>(),
+    })
+}
+
+fn stake_json(address: &Pubkey, farm: &Farm, stake: &FarmStake, now: i64) -> Value {
+    let pending = farm.pending_rewards(stake, now);
+    json!({
+        "stake": address.to_string(),
+        "owner": stake.owner.to_string(),
+        "amount": stake.amount,
+        "locked_weight": stake.locked_weight,
+        "pending": farm
+            .rewards()
+            .iter()
+            .zip(pending)
+            .map(|(reward, amount)| json!({ "mint": reward.mint.to_string(), "amount": amount }))
+            .collect::<Vec<_>>(),
+    })
+}
+
+// Locks join the pool's farm whenever it has one, for the boost
+fn lock_add(client: &Client, token_a: &Pubkey, token_b: &Pubkey, amount: u64, unlock_time: i64) -> Result<Value> {
+    let config = &client.config;
+    let (keys, _) = client.pool_keys(token_a, token_b, None, None, None)?;
+    let user = user_keys(client, &keys);
+    let (lock, _) = instruction::find_lp_lock_address(&config.program_id, &keys.pool, &user.user);
+    let (farm, _) = instruction::find_farm_address(&config.program_id, &keys.pool);
+
+    let mut ix =
+        instruction::lock_liquidity(&config.program_id, &keys, &user, &config.token_program_id, amount, unlock_time);
+    if client.account_exists(&farm)? {
+        ix = instruction::with_lock_farm(ix, &config.program_id, &keys.pool, &user.user);
+    }
+    let transaction = client.execute(&[ix], &[])?;
+
+    let value = match client.fetch_liquidity_lock(&lock)? {
+        Some(state) => lock_json(&lock, &state),
+        None => json!({ "lock": lock.to_string() }),
+    };
+    Ok(with_transaction(value, transaction))
+}
+
+fn lock_unlock(client: &Client, token_a: &Pubkey, token_b: &Pubkey) -> Result<Value> {
+    let config = &client.config;
+    let (keys, _) = client.pool_keys(token_a, token_b, None, None, None)?;
+    let user = user_keys(client, &keys);
+    let (lock, _) = instruction::find_lp_lock_address(&config.program_id, &keys.pool, &user.user);
+    let Some(state) = client.fetch_liquidity_lock(&lock)? else {
+        bail!("{} has no lock on {}", user.user, keys.pool);
+    };
+
+    let mut ixs = Vec::new();
+    client.ensure_associated_token_account(&user.user, &keys.lp_mint, &config.token_program_id, &mut ixs)?;
+    let mut ix = instruction::unlock_liquidity(&config.program_id, &keys, &user, &config.token_program_id);
+    if state.farm != Pubkey::default() {
+        ix = instruction::with_lock_farm(ix, &config.program_id, &keys.pool, &user.user);
+    }
+    ixs.push(ix);
+
+    Ok(with_transaction(lock_json(&lock, &state), client.execute(&ixs, &[])?))
+}
+
+fn lock_expire(client: &Client, token_a: &Pubkey, token_b: &Pubkey, owner: &Pubkey) -> Result<Value> {
+    let program_id = &client.config.program_id;
+    let (pool, _) = instruction::find_pool_address(program_id, token_a, token_b);
+    let (lock, _) = instruction::find_lp_lock_address(program_id, &pool, owner);
+    if client.fetch_liquidity_lock(&lock)?.is_none() {
+        bail!("{owner} has no lock on {pool}");
+    }
+    let transaction = client.execute(&[instruction::expire_liquidity_lock(program_id, &pool, owner)], &[])?;
+
+    let value = match client.fetch_liquidity_lock(&lock)? {
+        Some(state) => lock_json(&lock, &state),
+        None => json!({ "lock": lock.to_string() }),
+    };
+    Ok(with_transaction(value, transaction))
+}
+
+fn lock_list(client: &Client, token_a: &Pubkey, token_b: &Pubkey, owner: Option<&Pubkey>) -> Result<Value> {
+    let (pool, _) = instruction::find_pool_address(&client.config.program_id, token_a, token_b);
+    let locks = client.fetch_liquidity_locks(&pool, owner)?;
+    Ok(json!({
+        "pool": pool.to_string(),
+        "total_locked": locks.iter().map(|(_, lock)| lock.amount).sum::<u64>(),
+        "locks": locks.iter().map(|(address, lock)| lock_json(address, lock)).collect::<Vec<_>>(),
+    }))
+}
+
+fn lock_json(address: &Pubkey, lock: &LiquidityLock) -> Value {
+    let farm = lock.farm != Pubkey::default();
+    json!({
+        "lock": address.to_string(),
+        "owner": lock.owner.to_string(),
+        "amount": lock.amount,
+        "locked_at": lock.locked_at,
+        "unlock_time": lock.unlock_time,
+        "farm": farm.then(|| lock.farm.to_string()),
+        "farm_weight": lock.weight,
+    })
+}
+
+fn list(client: &Client) -> Result<Value> {
+    let pools = client.fetch_pools()?;
+    Ok(json!({
+        "program_id": client.config.program_id.to_string(),
+        "pools": pools.iter().map(|(address, pool)| pool_json(address, pool)).collect::<Vec<_>>(),
+    }))
+}
+
+fn inspect(client: &Client, address: &Pubkey) -> Result<Value> {
+    let pool = client.fetch_pool(address)?;
+    let mut value = pool_json(address, &pool);
+
+    if pool.reserve_a > 0 {
+        value["price_a_in_b"] = json!(pool.reserve_b as f64 / pool.reserve_a as f64);
+    }
+    for (label, mint, token_program) in [
+        ("vault_a", &pool.token_a, &pool.token_program_a),
+        ("vault_b", &pool.token_b, &pool.token_program_b),
+    ] {
+        value[label] = match client.find_vault(address, mint, token_program) {
+            Ok(vault) => json!({
+                "address": vault.to_string(),
+                "balance": client.token_balance(&vault)?,
+            }),
+            Err(err) => json!({ "error": err.to_string() }),
+        };
+    }
+
+    Ok(value)
+}
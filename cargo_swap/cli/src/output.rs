@@ -0,0 +1,56 @@
+use clap::ValueEnum;
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum OutputFormat {
+    Human,
+    Json,
+}
+
+pub fn print(format: OutputFormat, value: &Value) {
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(value).unwrap()),
+        OutputFormat::Human => print_human(value, 0),
+    }
+}
+
+fn scalar(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => "-".to_string(),
+        other => other.to_string(),
+    }
+}
+
+fn is_nested(value: &Value) -> bool {
+    matches!(value, Value::Object(map) if !map.is_empty())
+        || matches!(value, Value::Array(items) if !items.is_empty())
+}
+
+fn print_human(value: &Value, indent: usize) {
+    let pad = " ".repeat(indent);
+    match value {
+        Value::Object(map) => {
+            for (key, field) in map {
+                let label = key.replace('_', " ");
+                if is_nested(field) {
+                    println!("{pad}{label}:");
+                    print_human(field, indent + 2);
+                } else {
+                    println!("{pad}{label}: {}", scalar(field));
+                }
+            }
+        }
+        Value::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                if is_nested(item) {
+                    println!("{pad}[{i}]");
+                    print_human(item, indent + 2);
+                } else {
+                    println!("{pad}- {}", scalar(item));
+                }
+            }
+        }
+        other => println!("{pad}{}", scalar(other)),
+    }
+}
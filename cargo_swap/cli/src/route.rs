@@ -0,0 +1,93 @@
+use cargo_swap::{quote, Pool};
+use solana_sdk::pubkey::Pubkey;
+
+#[derive(Clone)]
+pub struct Hop<'a> {
+    pub pool_address: Pubkey,
+    pub pool: &'a Pool,
+    pub direction_a_to_b: bool,
+    pub amount_in: u64,
+    pub amount_out: u64,
+}
+
+impl Hop<'_> {
+    pub fn mint_in(&self) -> Pubkey {
+        if self.direction_a_to_b { self.pool.token_a } else { self.pool.token_b }
+    }
+
+    pub fn mint_out(&self) -> Pubkey {
+        if self.direction_a_to_b { self.pool.token_b } else { self.pool.token_a }
+    }
+}
+
+// Best-output path from `from` to `to` through at most `max_hops` active pools,
+// each hop quoted with the same math the program runs
+pub fn best_route<'a>(
+    pools: &'a [(Pubkey, Pool)],
+    from: &Pubkey,
+    to: &Pubkey,
+    amount_in: u64,
+    max_hops: usize,
+) -> Option<Vec<Hop<'a>>> {
+    let mut best: Option<Vec<Hop<'a>>> = None;
+    let mut path = Vec::new();
+    search(pools, from, to, amount_in, max_hops, &mut path, &mut best);
+    best
+}
+
+fn search<'a>(
+    pools: &'a [(Pubkey, Pool)],
+    current: &Pubkey,
+    to: &Pubkey,
+    amount_in: u64,
+    hops_left: usize,
+    path: &mut Vec<Hop<'a>>,
+    best: &mut Option<Vec<Hop<'a>>>,
+) {
+    if hops_left == 0 {
+        return;
+    }
+
+    for (address, pool) in pools {
+        if !pool.is_active || path.iter().any(|hop| hop.pool_address == *address) {
+            continue;
+        }
+        let direction_a_to_b = if pool.token_a == *current {
+            true
+        } else if pool.token_b == *current {
+            false
+        } else {
+            continue;
+        };
+
+        let Ok(quote) = quote::quote_swap_exact_in(pool, amount_in, direction_a_to_b) else {
+            continue;
+        };
+        if quote.amount_out == 0 {
+            continue;
+        }
+
+        let hop = Hop {
+            pool_address: *address,
+            pool,
+            direction_a_to_b,
+            amount_in,
+            amount_out: quote.amount_out,
+        };
+        let next = hop.mint_out();
+        path.push(hop);
+
+        if next == *to {
+            let improves = best
+                .as_ref()
+                .is_none_or(|b| b.last().unwrap().amount_out < quote.amount_out);
+            if improves {
+                *best = Some(path.clone());
+            }
+        } else if path.iter().all(|hop| hop.mint_in() != next) {
+            search(pools, &next, to, quote.amount_out, hops_left - 1, path, best);
+        }
+
+        path.pop();
+    }
+}
@@ -0,0 +1,439 @@
+use crate::config::Config;
+use anyhow::{anyhow, bail, Context, Result};
+use cargo_swap::{
+    instruction::PoolKeys, AccountType, CreationPolicy, DcaVault, Farm, FarmStake, LimitOrder, LiquidityLock,
+    LongTermOrder, Multisig, PendingChange, Pool, PoolRegistry,
+};
+use serde_json::{json, Value};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    rpc_client::RpcClient,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
+};
+use solana_program::program_pack::Pack;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_program,
+    transaction::Transaction,
+};
+
+// SPL token account size
+pub const TOKEN_ACCOUNT_LEN: usize = 165;
+
+pub struct Client {
+    pub rpc: RpcClient,
+    pub config: Config,
+    pub simulate: bool,
+}
+
+impl Client {
+    pub fn new(config: Config, simulate: bool) -> Self {
+        let rpc = RpcClient::new_with_commitment(config.rpc_url.clone(), config.commitment);
+        Client { rpc, config, simulate }
+    }
+
+    pub fn payer(&self) -> Pubkey {
+        self.config.keypair.pubkey()
+    }
+
+    // Admin instructions' authority: the --multisig, else the keypair
+    pub fn admin(&self) -> Pubkey {
+        self.config.multisig.unwrap_or_else(|| self.payer())
+    }
+
+    // Keys approving multisig instructions: the keypair and each --multisig-signer
+    pub fn multisig_members(&self) -> Vec<Pubkey> {
+        let mut members = vec![self.payer()];
+        members.extend(self.config.multisig_signers.iter().map(|keypair| keypair.pubkey()));
+        members
+    }
+
+    // Sends an instruction built with `admin()` as the admin, with the
+    // multisig members' signatures when going through a multisig
+    pub fn execute_admin(&self, instruction: Instruction) -> Result<Value> {
+        let Some(multisig) = self.config.multisig else {
+            return self.execute(&[instruction], &[]);
+        };
+        let instruction = cargo_swap::instruction::multisig_signers(instruction, &multisig, &self.multisig_members());
+        self.execute_as_members(&[instruction])
+    }
+
+    // Sends instructions signed by the keypair and every --multisig-signer
+    pub fn execute_as_members(&self, instructions: &[Instruction]) -> Result<Value> {
+        let signers: Vec<&Keypair> = self.config.multisig_signers.iter().collect();
+        self.execute(instructions, &signers)
+    }
+
+    pub fn associated_token_address(&self, owner: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(
+            &[owner.as_ref(), token_program.as_ref(), mint.as_ref()],
+            &self.config.ata_program_id,
+        )
+        .0
+    }
+
+    // Returns the owner's ATA, queueing its creation if it does not exist yet
+    pub fn ensure_associated_token_account(
+        &self,
+        owner: &Pubkey,
+        mint: &Pubkey,
+        token_program: &Pubkey,
+        instructions: &mut Vec<Instruction>,
+    ) -> Result<Pubkey> {
+        let address = self.associated_token_address(owner, mint, token_program);
+        let exists = self
+            .rpc
+            .get_account_with_commitment(&address, self.config.commitment)?
+            .value
+            .is_some();
+
+        if !exists {
+            instructions.push(Instruction {
+                program_id: self.config.ata_program_id,
+                accounts: vec![
+                    AccountMeta::new(self.payer(), true),
+                    AccountMeta::new(address, false),
+                    AccountMeta::new_readonly(*owner, false),
+                    AccountMeta::new_readonly(*mint, false),
+                    AccountMeta::new_readonly(system_program::id(), false),
+                    AccountMeta::new_readonly(*token_program, false),
+                ],
+                data: vec![],
+            });
+        }
+
+        Ok(address)
+    }
+
+    // A mint's token program is whichever program owns the mint account
+    pub fn mint_token_program(&self, mint: &Pubkey) -> Result<Pubkey> {
+        let account = self
+            .rpc
+            .get_account(mint)
+            .with_context(|| format!("fetching mint {mint}"))?;
+        Ok(account.owner)
+    }
+
+    pub fn token_balance(&self, account: &Pubkey) -> Result<u64> {
+        let data = match self.rpc.get_account_with_commitment(account, self.config.commitment)?.value {
+            Some(account) => account.data,
+            None => return Ok(0),
+        };
+        // amount follows mint (32) and owner (32) in the token account layout
+        let bytes = data
+            .get(64..72)
+            .ok_or_else(|| anyhow!("{account} is not a token account"))?;
+        Ok(u64::from_le_bytes(bytes.try_into()?))
+    }
+
+    pub fn fetch_pool(&self, address: &Pubkey) -> Result<Pool> {
+        let account = self
+            .rpc
+            .get_account(address)
+            .with_context(|| format!("fetching pool {address}"))?;
+        if account.owner != self.config.program_id {
+            bail!("{address} is not owned by the AMM program");
+        }
+        Pool::unpack_from_slice(&account.data).map_err(|e| anyhow!("decoding pool {address}: {e}"))
+    }
+
+    // The change pending for a pool or the program config, if any
+    pub fn fetch_pending_change(&self, target: &Pubkey) -> Result<(Pubkey, PendingChange)> {
+        let (address, _) = cargo_swap::instruction::find_pending_change_address(&self.config.program_id, target);
+        let account = self
+            .rpc
+            .get_account(&address)
+            .with_context(|| format!("no change pending for {target}"))?;
+        let pending = PendingChange::unpack_from_slice(&account.data)
+            .map_err(|e| anyhow!("decoding pending change {address}: {e}"))?;
+        Ok((address, pending))
+    }
+
+    pub fn fetch_multisig(&self, address: &Pubkey) -> Result<Multisig> {
+        let account = self
+            .rpc
+            .get_account(address)
+            .with_context(|| format!("fetching multisig {address}"))?;
+        Multisig::unpack_from_slice(&account.data).map_err(|e| anyhow!("decoding multisig {address}: {e}"))
+    }
+
+    // The pool creation policy, if the admin has set one
+    pub fn fetch_creation_policy(&self) -> Result<Option<CreationPolicy>> {
+        let (address, _) = cargo_swap::instruction::find_creation_policy_address(&self.config.program_id);
+        let Some(account) = self.rpc.get_account_with_commitment(&address, self.rpc.commitment())?.value else {
+            return Ok(None);
+        };
+        let policy = CreationPolicy::unpack_from_slice(&account.data)
+            .map_err(|e| anyhow!("decoding creation policy {address}: {e}"))?;
+        Ok(Some(policy))
+    }
+
+    pub fn fetch_farm(&self, address: &Pubkey) -> Result<Farm> {
+        let account = self
+            .rpc
+            .get_account(address)
+            .with_context(|| format!("no farm at {address}"))?;
+        Farm::unpack_from_slice(&account.data).map_err(|e| anyhow!("decoding farm {address}: {e}"))
+    }
+
+    // A farm stake, if its owner ever staked
+    pub fn fetch_farm_stake(&self, address: &Pubkey) -> Result<Option<FarmStake>> {
+        let Some(account) = self.rpc.get_account_with_commitment(address, self.rpc.commitment())?.value else {
+            return Ok(None);
+        };
+        let stake =
+            FarmStake::unpack_from_slice(&account.data).map_err(|e| anyhow!("decoding farm stake {address}: {e}"))?;
+        Ok(Some(stake))
+    }
+
+    // Whether anything lives at `address`, e.g. a pool's farm
+    pub fn account_exists(&self, address: &Pubkey) -> Result<bool> {
+        Ok(self.rpc.get_account_with_commitment(address, self.rpc.commitment())?.value.is_some())
+    }
+
+    // Cluster time of the latest slot, which dynamic fees are charged at
+    pub fn unix_timestamp(&self) -> Result<i64> {
+        let slot = self.rpc.get_slot_with_commitment(self.config.commitment)?;
+        self.rpc.get_block_time(slot).context("fetching cluster time")
+    }
+
+    pub fn fetch_registry(&self) -> Result<PoolRegistry> {
+        let (address, _) = cargo_swap::instruction::find_registry_address(&self.config.program_id);
+        let account = self
+            .rpc
+            .get_account(&address)
+            .context("fetching pool registry; run `cargo-swap registry init` first")?;
+        PoolRegistry::unpack_from_slice(&account.data).map_err(|e| anyhow!("decoding registry: {e}"))
+    }
+
+    pub fn fetch_pools(&self) -> Result<Vec<(Pubkey, Pool)>> {
+        let accounts = self.rpc.get_program_accounts_with_config(
+            &self.config.program_id,
+            RpcProgramAccountsConfig {
+                filters: Some(vec![
+                    RpcFilterType::DataSize(Pool::LEN as u64),
+                    RpcFilterType::Memcmp(Memcmp::new_raw_bytes(0, vec![AccountType::Pool as u8])),
+                ]),
+                account_config: RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    commitment: Some(self.config.commitment),
+                    ..RpcAccountInfoConfig::default()
+                },
+                ..RpcProgramAccountsConfig::default()
+            },
+        )?;
+
+        let mut pools: Vec<_> = accounts
+            .into_iter()
+            .filter_map(|(address, account)| {
+                Pool::unpack_from_slice(&account.data).ok().map(|pool| (address, pool))
+            })
+            .collect();
+        pools.sort_by_key(|(_, pool)| pool.pool_id);
+        Ok(pools)
+    }
+
+    pub fn fetch_limit_order(&self, address: &Pubkey) -> Result<LimitOrder> {
+        let account = self
+            .rpc
+            .get_account(address)
+            .with_context(|| format!("no open limit order at {address}"))?;
+        LimitOrder::unpack_from_slice(&account.data).map_err(|e| anyhow!("decoding limit order {address}: {e}"))
+    }
+
+    // Open orders on a pool, optionally only one owner's, oldest first
+    pub fn fetch_limit_orders(&self, pool: &Pubkey, owner: Option<&Pubkey>) -> Result<Vec<(Pubkey, LimitOrder)>> {
+        let mut orders: Vec<(_, LimitOrder)> = self.fetch_pool_orders(AccountType::LimitOrder, pool, owner)?;
+        orders.sort_by_key(|(_, order)| order.created_at);
+        Ok(orders)
+    }
+
+    pub fn fetch_long_term_order(&self, address: &Pubkey) -> Result<LongTermOrder> {
+        let account = self
+            .rpc
+            .get_account(address)
+            .with_context(|| format!("no open long-term order at {address}"))?;
+        LongTermOrder::unpack_from_slice(&account.data).map_err(|e| anyhow!("decoding long-term order {address}: {e}"))
+    }
+
+    pub fn fetch_long_term_orders(
+        &self,
+        pool: &Pubkey,
+        owner: Option<&Pubkey>,
+    ) -> Result<Vec<(Pubkey, LongTermOrder)>> {
+        let mut orders: Vec<(_, LongTermOrder)> = self.fetch_pool_orders(AccountType::LongTermOrder, pool, owner)?;
+        orders.sort_by_key(|(_, order)| order.start_time);
+        Ok(orders)
+    }
+
+    pub fn fetch_dca_vault(&self, address: &Pubkey) -> Result<DcaVault> {
+        let account = self
+            .rpc
+            .get_account(address)
+            .with_context(|| format!("no open DCA vault at {address}"))?;
+        DcaVault::unpack_from_slice(&account.data).map_err(|e| anyhow!("decoding DCA vault {address}: {e}"))
+    }
+
+    // Open DCA vaults on a pool, optionally only one owner's, soonest due first
+    pub fn fetch_dca_vaults(&self, pool: &Pubkey, owner: Option<&Pubkey>) -> Result<Vec<(Pubkey, DcaVault)>> {
+        let mut vaults: Vec<(_, DcaVault)> = self.fetch_pool_orders(AccountType::DcaVault, pool, owner)?;
+        vaults.sort_by_key(|(_, vault)| vault.next_execution);
+        Ok(vaults)
+    }
+
+    // A liquidity lock, if its owner has one on the pool
+    pub fn fetch_liquidity_lock(&self, address: &Pubkey) -> Result<Option<LiquidityLock>> {
+        let Some(account) = self.rpc.get_account_with_commitment(address, self.rpc.commitment())?.value else {
+            return Ok(None);
+        };
+        let lock = LiquidityLock::unpack_from_slice(&account.data)
+            .map_err(|e| anyhow!("decoding liquidity lock {address}: {e}"))?;
+        Ok(Some(lock))
+    }
+
+    // Liquidity locks on a pool, optionally only one owner's, latest unlock first
+    pub fn fetch_liquidity_locks(&self, pool: &Pubkey, owner: Option<&Pubkey>) -> Result<Vec<(Pubkey, LiquidityLock)>> {
+        let mut locks: Vec<(_, LiquidityLock)> = self.fetch_pool_orders(AccountType::LiquidityLock, pool, owner)?;
+        locks.sort_by_key(|(_, lock)| std::cmp::Reverse(lock.unlock_time));
+        Ok(locks)
+    }
+
+    // Order, DCA vault and lock accounts all keep their pool at offset 16 and owner at 48
+    fn fetch_pool_orders<T: Pack>(
+        &self,
+        account_type: AccountType,
+        pool: &Pubkey,
+        owner: Option<&Pubkey>,
+    ) -> Result<Vec<(Pubkey, T)>> {
+        let mut filters = vec![
+            RpcFilterType::DataSize(T::LEN as u64),
+            RpcFilterType::Memcmp(Memcmp::new_raw_bytes(0, vec![account_type as u8])),
+            RpcFilterType::Memcmp(Memcmp::new_raw_bytes(16, pool.to_bytes().to_vec())),
+        ];
+        if let Some(owner) = owner {
+            filters.push(RpcFilterType::Memcmp(Memcmp::new_raw_bytes(48, owner.to_bytes().to_vec())));
+        }
+        let accounts = self.rpc.get_program_accounts_with_config(
+            &self.config.program_id,
+            RpcProgramAccountsConfig {
+                filters: Some(filters),
+                account_config: RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    commitment: Some(self.config.commitment),
+                    ..RpcAccountInfoConfig::default()
+                },
+                ..RpcProgramAccountsConfig::default()
+            },
+        )?;
+
+        Ok(accounts
+            .into_iter()
+            .filter_map(|(address, account)| T::unpack_from_slice(&account.data).ok().map(|order| (address, order)))
+            .collect())
+    }
+
+    // Pools registered with CreatePool keep their vaults at a PDA; older pools
+    // use plain keypair accounts, so look those up by mint and owner.
+    // Token-2022 accounts grow with extensions, so only filter on size for the
+    // configured (legacy) token program.
+    pub fn find_vault(&self, pool: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Result<Pubkey> {
+        let (vault, _) = cargo_swap::instruction::find_vault_address(&self.config.program_id, pool, mint);
+        if self.rpc.get_account_with_commitment(&vault, self.config.commitment)?.value.is_some() {
+            return Ok(vault);
+        }
+
+        let mut filters = vec![
+            RpcFilterType::Memcmp(Memcmp::new_raw_bytes(0, mint.to_bytes().to_vec())),
+            RpcFilterType::Memcmp(Memcmp::new_raw_bytes(32, pool.to_bytes().to_vec())),
+        ];
+        if *token_program == self.config.token_program_id {
+            filters.push(RpcFilterType::DataSize(TOKEN_ACCOUNT_LEN as u64));
+        }
+        let accounts = self.rpc.get_program_accounts_with_config(
+            token_program,
+            RpcProgramAccountsConfig {
+                filters: Some(filters),
+                account_config: RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    commitment: Some(self.config.commitment),
+                    ..RpcAccountInfoConfig::default()
+                },
+                ..RpcProgramAccountsConfig::default()
+            },
+        )?;
+
+        match accounts.as_slice() {
+            [(address, _)] => Ok(*address),
+            [] => bail!("no vault for mint {mint} owned by pool {pool}"),
+            _ => bail!("several vaults for mint {mint} owned by pool {pool}; pass it explicitly"),
+        }
+    }
+
+    pub fn pool_keys(
+        &self,
+        token_a: &Pubkey,
+        token_b: &Pubkey,
+        lp_mint: Option<Pubkey>,
+        vault_a: Option<Pubkey>,
+        vault_b: Option<Pubkey>,
+    ) -> Result<(PoolKeys, Pool)> {
+        let (pool, _) =
+            cargo_swap::instruction::find_pool_address(&self.config.program_id, token_a, token_b);
+        let state = self.fetch_pool(&pool)?;
+        let token_program_a = state.token_program_a;
+        let token_program_b = state.token_program_b;
+
+        let keys = PoolKeys {
+            pool,
+            token_a: *token_a,
+            token_b: *token_b,
+            vault_a: match vault_a {
+                Some(vault) => vault,
+                None => self.find_vault(&pool, token_a, &token_program_a)?,
+            },
+            vault_b: match vault_b {
+                Some(vault) => vault,
+                None => self.find_vault(&pool, token_b, &token_program_b)?,
+            },
+            lp_mint: lp_mint
+                .unwrap_or_else(|| cargo_swap::instruction::find_lp_mint_address(&self.config.program_id, &pool).0),
+            token_program_a,
+            token_program_b,
+        };
+        Ok((keys, state))
+    }
+
+    // Sends the transaction, or only simulates it with --simulate
+    pub fn execute(&self, instructions: &[Instruction], extra_signers: &[&Keypair]) -> Result<Value> {
+        let mut signers: Vec<&Keypair> = vec![&self.config.keypair];
+        signers.extend_from_slice(extra_signers);
+
+        let blockhash = self.rpc.get_latest_blockhash()?;
+        let transaction = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&self.payer()),
+            &signers,
+            blockhash,
+        );
+
+        if self.simulate {
+            let result = self.rpc.simulate_transaction(&transaction)?.value;
+            return Ok(json!({
+                "simulated": true,
+                "success": result.err.is_none(),
+                "error": result.err.map(|e| e.to_string()),
+                "units_consumed": result.units_consumed,
+                "logs": result.logs.unwrap_or_default(),
+            }));
+        }
+
+        let signature = self
+            .rpc
+            .send_and_confirm_transaction(&transaction)
+            .context("sending transaction")?;
+        Ok(json!({ "signature": signature.to_string() }))
+    }
+}
@@ -0,0 +1,13 @@
+// honggfuzz target: `cargo hfuzz run amm-instructions` from this directory.
+// Crashes land in hfuzz_workspace/amm-instructions; copy them into
+// regressions/ to replay them under `cargo test`.
+
+use honggfuzz::fuzz;
+
+fn main() {
+    loop {
+        fuzz!(|data: &[u8]| {
+            cargo_swap_fuzz::run(data);
+        });
+    }
+}
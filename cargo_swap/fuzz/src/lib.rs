@@ -0,0 +1,394 @@
+// Fuzz harness for the AMM program
+//
+// An input decodes into a sequence of `FuzzStep`s: well-formed instructions
+// from the builders, optionally with their account lists shuffled or swapped
+// for other accounts, plus raw instruction bytes. Each step runs against a
+// fixed world of mints, users and pools, and the invariants are checked after
+// every step. Any violation panics so the fuzzer records the input.
+
+pub mod runtime;
+
+use arbitrary::{Arbitrary, Unstructured};
+use cargo_swap::{
+    instruction::{
+        self, find_pair_index_address, find_pool_index_address, find_registry_address, PoolKeys,
+        UserKeys,
+    },
+    GovernanceChange, PairIndex, Pool, PoolInfo, PoolRegistry, PoolType,
+};
+use runtime::{token_program_id, NativeAccount, Runtime};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_option::COption,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_program, sysvar,
+};
+use spl_token::state::{Account as TokenAccount, AccountState, Mint};
+use std::sync::OnceLock;
+
+pub const USERS: usize = 3;
+pub const MINTS: usize = 3;
+pub const USER_TOKENS: u64 = 1_000_000_000_000;
+pub const USER_LAMPORTS: u64 = 1_000_000_000_000;
+
+// Mint pairs of the pools in the world
+const PAIRS: [(usize, usize); 2] = [(0, 1), (1, 2)];
+
+#[derive(Debug, Arbitrary)]
+pub struct FuzzStep {
+    pub user: u8,
+    pub instruction: FuzzInstruction,
+    pub tweaks: Vec<AccountTweak>,
+}
+
+#[derive(Debug, Arbitrary)]
+pub enum FuzzInstruction {
+    InitPool { pool: u8, amount_a: u64, amount_b: u64, legacy: bool },
+    AddLiquidity { pool: u8, amount_a: u64, amount_b: u64, legacy: bool },
+    RemoveLiquidity { pool: u8, lp_amount: u64, legacy: bool },
+    Swap { pool: u8, amount_in: u64, direction_a_to_b: bool, legacy: bool },
+    InitializeRegistry,
+    CreatePool { pool: u8, stable: bool },
+    ProposeFeeChange { pool: u8, fee_rate: u16 },
+    DeactivatePool { pool_id: u8 },
+    Raw { data: Vec<u8>, accounts: Vec<(u8, bool)> },
+}
+
+// Changes applied to a built instruction's account list. Indexes wrap around
+// the account list and the world's keys respectively.
+#[derive(Debug, Arbitrary)]
+pub enum AccountTweak {
+    Swap(u8, u8),
+    Replace { index: u8, with: u8 },
+}
+
+#[derive(Clone)]
+pub struct PoolAccounts {
+    pub keys: PoolKeys,
+    // Per-user LP token accounts
+    pub lp: [Pubkey; USERS],
+}
+
+#[derive(Clone)]
+pub struct User {
+    pub key: Pubkey,
+    pub tokens: [Pubkey; MINTS],
+}
+
+#[derive(Clone)]
+pub struct World {
+    pub runtime: Runtime,
+    pub mints: [Pubkey; MINTS],
+    pub users: Vec<User>,
+    pub pools: Vec<PoolAccounts>,
+    pub keys: Vec<Pubkey>,
+}
+
+fn key(tag: &str, index: usize) -> Pubkey {
+    Pubkey::create_with_seed(&Pubkey::default(), &format!("{tag}{index}"), &Pubkey::default()).unwrap()
+}
+
+fn token_state<T: Pack>(state: T) -> NativeAccount {
+    let mut data = vec![0; T::LEN];
+    state.pack_into_slice(&mut data);
+    NativeAccount { lamports: 1, data, owner: token_program_id() }
+}
+
+// Unfunded account of the size and owner `create_account` will ask for
+fn unallocated(len: usize, owner: Pubkey) -> NativeAccount {
+    NativeAccount { lamports: 0, data: vec![0; len], owner }
+}
+
+impl World {
+    // Key derivation is slow, so the starting world is built once and cloned
+    pub fn new() -> Self {
+        static TEMPLATE: OnceLock<World> = OnceLock::new();
+        TEMPLATE.get_or_init(World::build).clone()
+    }
+
+    fn build() -> Self {
+        let mut runtime = Runtime::default();
+        let accounts = &mut runtime.accounts;
+        let mint_authority = key("mint_authority", 0);
+        let mints: [Pubkey; MINTS] = std::array::from_fn(|i| key("mint", i));
+
+        let users: Vec<User> = (0..USERS)
+            .map(|u| User { key: key("user", u), tokens: std::array::from_fn(|m| key(&format!("user{u}_token"), m)) })
+            .collect();
+
+        for (m, mint) in mints.iter().enumerate() {
+            accounts.insert(
+                *mint,
+                token_state(Mint {
+                    mint_authority: COption::Some(mint_authority),
+                    supply: USER_TOKENS * USERS as u64,
+                    decimals: 9,
+                    is_initialized: true,
+                    freeze_authority: COption::None,
+                }),
+            );
+            for user in &users {
+                accounts.insert(
+                    user.tokens[m],
+                    token_state(TokenAccount {
+                        mint: *mint,
+                        owner: user.key,
+                        amount: USER_TOKENS,
+                        state: AccountState::Initialized,
+                        ..TokenAccount::default()
+                    }),
+                );
+            }
+        }
+        for user in &users {
+            accounts.insert(
+                user.key,
+                NativeAccount { lamports: USER_LAMPORTS, data: vec![], owner: system_program::id() },
+            );
+        }
+
+        let pools: Vec<PoolAccounts> = PAIRS
+            .iter()
+            .enumerate()
+            .map(|(p, &(a, b))| {
+                let keys =
+                    PoolKeys::new(&cargo_swap::id(), &mints[a], &mints[b], &token_program_id(), &token_program_id());
+                // Vaults and the LP mint are created by CreatePool
+                accounts.insert(keys.pool, unallocated(Pool::LEN, cargo_swap::id()));
+                accounts.insert(keys.vault_a, unallocated(TokenAccount::LEN, token_program_id()));
+                accounts.insert(keys.vault_b, unallocated(TokenAccount::LEN, token_program_id()));
+                accounts.insert(keys.lp_mint, unallocated(Mint::LEN, token_program_id()));
+
+                let lp = std::array::from_fn(|u| key(&format!("user{u}_lp"), p));
+                for (u, lp_account) in lp.iter().enumerate() {
+                    accounts.insert(
+                        *lp_account,
+                        token_state(TokenAccount {
+                            mint: keys.lp_mint,
+                            owner: users[u].key,
+                            state: AccountState::Initialized,
+                            ..TokenAccount::default()
+                        }),
+                    );
+                }
+                PoolAccounts { keys, lp }
+            })
+            .collect();
+
+        let (registry, _) = find_registry_address(&cargo_swap::id());
+        accounts.insert(registry, unallocated(PoolRegistry::LEN, cargo_swap::id()));
+        // Each pair can be registered once, so ids never run past the pair count
+        for pool_id in 1..=PAIRS.len() as u64 {
+            let (pool_index, _) = find_pool_index_address(&cargo_swap::id(), pool_id);
+            accounts.insert(pool_index, unallocated(PoolInfo::LEN, cargo_swap::id()));
+        }
+        for pool in &pools {
+            let (pair_index, _) = find_pair_index_address(&cargo_swap::id(), &pool.keys.token_a, &pool.keys.token_b);
+            accounts.insert(pair_index, unallocated(PairIndex::LEN, cargo_swap::id()));
+        }
+
+        let mut rent = NativeAccount { lamports: 1, data: vec![], owner: sysvar::id() };
+        rent.data = bincode::serialize(&Rent::default()).unwrap();
+        accounts.insert(sysvar::rent::id(), rent);
+        for program in [system_program::id(), token_program_id()] {
+            accounts.insert(program, NativeAccount { lamports: 1, data: vec![], owner: Pubkey::default() });
+        }
+
+        let keys = accounts.keys().copied().collect();
+        World { runtime, mints, users, pools, keys }
+    }
+
+    fn instruction(&self, step: &FuzzStep) -> Instruction {
+        let program_id = cargo_swap::id();
+        let token_program = token_program_id();
+        let u = step.user as usize % USERS;
+        let user = &self.users[u];
+        let pool = |index: u8| {
+            let pool = &self.pools[index as usize % self.pools.len()];
+            let (a, b) = self.pair(pool);
+            let keys = UserKeys { user: user.key, token_a: user.tokens[a], token_b: user.tokens[b], lp: pool.lp[u] };
+            (pool.keys, keys)
+        };
+
+        match step.instruction {
+            FuzzInstruction::InitPool { pool: index, amount_a, amount_b, legacy } => {
+                let (pool, keys) = pool(index);
+                if legacy {
+                    instruction::legacy_init_pool(&program_id, &pool, &keys, &token_program, amount_a, amount_b)
+                } else {
+                    let pool_id = index as u64 % self.pools.len() as u64 + 1;
+                    instruction::init_pool(&program_id, pool_id, &pool, &keys, &token_program, amount_a, amount_b)
+                }
+            }
+            FuzzInstruction::AddLiquidity { pool: index, amount_a, amount_b, legacy } => {
+                let (pool, keys) = pool(index);
+                if legacy {
+                    instruction::legacy_add_liquidity(&program_id, &pool, &keys, &token_program, amount_a, amount_b)
+                } else {
+                    instruction::add_liquidity(&program_id, 0, &pool, &keys, &token_program, amount_a, amount_b)
+                }
+            }
+            FuzzInstruction::RemoveLiquidity { pool: index, lp_amount, legacy } => {
+                let (pool, keys) = pool(index);
+                if legacy {
+                    instruction::legacy_remove_liquidity(&program_id, &pool, &keys, &token_program, lp_amount)
+                } else {
+                    instruction::remove_liquidity(&program_id, 0, &pool, &keys, &token_program, lp_amount)
+                }
+            }
+            FuzzInstruction::Swap { pool: index, amount_in, direction_a_to_b, legacy } => {
+                let (pool, keys) = pool(index);
+                if legacy {
+                    instruction::legacy_swap(&program_id, &pool, &keys, amount_in, direction_a_to_b)
+                } else {
+                    instruction::swap(&program_id, 0, &pool, &keys, amount_in, direction_a_to_b)
+                }
+            }
+            FuzzInstruction::InitializeRegistry => {
+                instruction::initialize_registry(&program_id, &user.key, &token_program)
+            }
+            FuzzInstruction::CreatePool { pool: index, stable } => {
+                let (pool, _) = pool(index);
+                let pool_type = if stable { PoolType::Stable } else { PoolType::Standard };
+                let (registry, _) = find_registry_address(&program_id);
+                let pool_id = PoolRegistry::unpack_unchecked(&self.runtime.account(&registry).data)
+                    .map_or(1, |registry| registry.next_pool_id.max(1));
+                instruction::create_pool(&program_id, &user.key, &pool, pool_type, &token_program, pool_id)
+            }
+            FuzzInstruction::ProposeFeeChange { pool: index, fee_rate } => {
+                let (pool, _) = pool(index);
+                let change = GovernanceChange::FeeRate { fee_rate };
+                instruction::propose_change(&program_id, &user.key, &user.key, &pool.pool, change)
+            }
+            FuzzInstruction::DeactivatePool { pool_id } => {
+                let (pool_index, _) = find_pool_index_address(&program_id, pool_id as u64);
+                let registered = self.runtime.accounts.get(&pool_index);
+                let registered = registered.and_then(|info| PoolInfo::unpack(&info.data).ok());
+                let pool = registered.map_or(Pubkey::default(), |info| info.pool_address);
+                instruction::deactivate_pool(&program_id, &user.key, &token_program, &pool, pool_id as u64)
+            }
+            FuzzInstruction::Raw { ref data, ref accounts } => Instruction {
+                program_id,
+                accounts: accounts
+                    .iter()
+                    .map(|&(index, is_writable)| {
+                        AccountMeta { pubkey: self.keys[index as usize % self.keys.len()], is_signer: false, is_writable }
+                    })
+                    .collect(),
+                data: data.clone(),
+            },
+        }
+    }
+
+    fn pair(&self, pool: &PoolAccounts) -> (usize, usize) {
+        let index = |mint: &Pubkey| self.mints.iter().position(|m| m == mint).unwrap();
+        (index(&pool.keys.token_a), index(&pool.keys.token_b))
+    }
+
+    // Applies the tweaks, then strips signatures nobody in the step could give:
+    // only the acting user signs
+    fn tweak(&self, step: &FuzzStep, instruction: &mut Instruction) {
+        let accounts = &mut instruction.accounts;
+        if !accounts.is_empty() {
+            for tweak in &step.tweaks {
+                match *tweak {
+                    AccountTweak::Swap(i, j) => {
+                        let len = accounts.len();
+                        accounts.swap(i as usize % len, j as usize % len);
+                    }
+                    AccountTweak::Replace { index, with } => {
+                        let len = accounts.len();
+                        accounts[index as usize % len].pubkey = self.keys[with as usize % self.keys.len()];
+                    }
+                }
+            }
+        }
+
+        let user = self.users[step.user as usize % USERS].key;
+        for meta in accounts.iter_mut() {
+            meta.is_signer = meta.pubkey == user;
+        }
+    }
+
+    pub fn token_account(&self, key: &Pubkey) -> Option<TokenAccount> {
+        TokenAccount::unpack(&self.runtime.account(key).data).ok()
+    }
+
+    pub fn mint(&self, key: &Pubkey) -> Mint {
+        Mint::unpack(&self.runtime.account(key).data).unwrap()
+    }
+
+    pub fn pool(&self, key: &Pubkey) -> Option<Pool> {
+        Pool::unpack(&self.runtime.account(key).data).ok()
+    }
+
+    // Every initialized pool must hold at least its reserves, and its LP mint
+    // supply must match the recorded LP supply
+    fn check_pools(&self) {
+        for pool in &self.pools {
+            let Some(state) = self.pool(&pool.keys.pool) else {
+                continue;
+            };
+            let balance = |vault: &Pubkey| self.token_account(vault).map_or(0, |account| account.amount);
+            let (vault_a, vault_b) = (balance(&pool.keys.vault_a), balance(&pool.keys.vault_b));
+            assert!(vault_a >= state.reserve_a, "vault A holds {vault_a} < reserve {}", state.reserve_a);
+            assert!(vault_b >= state.reserve_b, "vault B holds {vault_b} < reserve {}", state.reserve_b);
+
+            let supply = self.mint(&pool.keys.lp_mint).supply;
+            assert_eq!(supply, state.total_lp_supply, "LP mint supply differs from the pool's LP supply");
+        }
+    }
+
+    fn balances(&self, user: &User) -> Vec<u64> {
+        let lp = self.pools.iter().map(|pool| pool.lp[self.user_index(user)]);
+        user.tokens.iter().copied().chain(lp).map(|key| self.token_account(&key).unwrap().amount).collect()
+    }
+
+    fn user_index(&self, user: &User) -> usize {
+        self.users.iter().position(|u| u.key == user.key).unwrap()
+    }
+
+    pub fn step(&mut self, step: &FuzzStep) {
+        let mut instruction = self.instruction(step);
+        self.tweak(step, &mut instruction);
+
+        let acting = step.user as usize % USERS;
+        let before: Vec<(Vec<u64>, u64)> = self
+            .users
+            .iter()
+            .map(|user| (self.balances(user), self.runtime.account(&user.key).lamports))
+            .collect();
+
+        let _ = self.runtime.process(&instruction);
+
+        // Nobody but the signer may lose tokens or lamports
+        for (u, user) in self.users.iter().enumerate().filter(|(u, _)| *u != acting) {
+            let (tokens, lamports) = &before[u];
+            for (before, after) in tokens.iter().zip(self.balances(user)) {
+                assert!(after >= *before, "user {u} lost tokens in a step signed by user {acting}: {step:?}");
+            }
+            assert!(self.runtime.account(&user.key).lamports >= *lamports, "user {u} lost lamports: {step:?}");
+        }
+        self.check_pools();
+    }
+}
+
+impl Default for World {
+    fn default() -> Self {
+        World::new()
+    }
+}
+
+// Entry point shared by the fuzz target and the regression replays
+pub fn run(data: &[u8]) {
+    let Ok(steps) = Vec::<FuzzStep>::arbitrary_take_rest(Unstructured::new(data)) else {
+        return;
+    };
+
+    let mut world = World::new();
+    for step in &steps {
+        world.step(step);
+    }
+}
@@ -0,0 +1,198 @@
+// In-memory runtime
+//
+// Runs the AMM processor natively and services its CPIs through syscall stubs:
+// token instructions go to the SPL token processor and `create_account` to a
+// small system program. Signer and writable privileges are enforced the same
+// way the real runtime does, so a handler can't move funds nobody signed for.
+
+use solana_program::{
+    account_info::AccountInfo,
+    clock::Clock,
+    entrypoint::{ProgramResult, SUCCESS},
+    instruction::Instruction,
+    program_error::ProgramError,
+    program_stubs::{self, SyscallStubs},
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction::{SystemError, SystemInstruction},
+    system_program,
+};
+use std::{collections::BTreeMap, str::FromStr, sync::Once};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NativeAccount {
+    pub lamports: u64,
+    pub data: Vec<u8>,
+    pub owner: Pubkey,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Runtime {
+    pub accounts: BTreeMap<Pubkey, NativeAccount>,
+}
+
+pub fn token_program_id() -> Pubkey {
+    Pubkey::from_str(cargo_swap::GORBCHAIN_SPL_TOKEN_PROGRAM).unwrap()
+}
+
+impl Runtime {
+    pub fn account(&self, key: &Pubkey) -> &NativeAccount {
+        &self.accounts[key]
+    }
+
+    // Executes one top-level instruction; on failure every account is rolled
+    // back, as a failed transaction would be
+    pub fn process(&mut self, instruction: &Instruction) -> ProgramResult {
+        install_stubs();
+        let snapshot = self.accounts.clone();
+        let result = self.execute(instruction);
+        if result.is_err() {
+            self.accounts = snapshot;
+        }
+        result
+    }
+
+    fn execute(&mut self, instruction: &Instruction) -> ProgramResult {
+        // A key listed several times shares one account, signer if any listing signs
+        let mut privileges: BTreeMap<Pubkey, (bool, bool)> = BTreeMap::new();
+        for meta in &instruction.accounts {
+            let entry = privileges.entry(meta.pubkey).or_default();
+            entry.0 |= meta.is_signer;
+            entry.1 |= meta.is_writable;
+        }
+
+        let before: BTreeMap<Pubkey, NativeAccount> = privileges
+            .keys()
+            .map(|key| (*key, self.accounts.entry(*key).or_default().clone()))
+            .collect();
+
+        {
+            let mut infos: BTreeMap<Pubkey, AccountInfo> = BTreeMap::new();
+            for (key, account) in self.accounts.iter_mut() {
+                if let Some(&(is_signer, is_writable)) = privileges.get(key) {
+                    infos.insert(
+                        *key,
+                        AccountInfo::new(
+                            key,
+                            is_signer,
+                            is_writable,
+                            &mut account.lamports,
+                            &mut account.data,
+                            &account.owner,
+                            false,
+                            0,
+                        ),
+                    );
+                }
+            }
+            let ordered: Vec<AccountInfo> =
+                instruction.accounts.iter().map(|meta| infos[&meta.pubkey].clone()).collect();
+
+            cargo_swap::process_instruction(&instruction.program_id, &ordered, &instruction.data)?;
+        }
+
+        // The runtime rejects changes to accounts the caller passed read-only
+        for (key, account) in &before {
+            if !privileges[key].1 && self.accounts[key] != *account {
+                return Err(ProgramError::InvalidArgument);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn install_stubs() {
+    static ONCE: Once = Once::new();
+    ONCE.call_once(|| {
+        program_stubs::set_syscall_stubs(Box::new(NativeStubs));
+    });
+}
+
+struct NativeStubs;
+
+impl SyscallStubs for NativeStubs {
+    fn sol_log(&self, _message: &str) {}
+
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        let pda_signers = signers_seeds
+            .iter()
+            .map(|seeds| Pubkey::create_program_address(seeds, &cargo_swap::id()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| ProgramError::InvalidSeeds)?;
+
+        let mut infos = Vec::with_capacity(instruction.accounts.len());
+        for meta in &instruction.accounts {
+            let mut info = account_infos
+                .iter()
+                .find(|info| *info.key == meta.pubkey)
+                .ok_or(ProgramError::NotEnoughAccountKeys)?
+                .clone();
+            if meta.is_signer && !info.is_signer && !pda_signers.contains(info.key) {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            if meta.is_writable && !info.is_writable {
+                return Err(ProgramError::InvalidArgument);
+            }
+            info.is_signer = meta.is_signer;
+            info.is_writable = meta.is_writable;
+            infos.push(info);
+        }
+
+        if instruction.program_id == token_program_id() {
+            spl_token::processor::Processor::process(&instruction.program_id, &infos, &instruction.data)
+        } else if instruction.program_id == system_program::id() {
+            process_system(&infos, &instruction.data)
+        } else {
+            Err(ProgramError::IncorrectProgramId)
+        }
+    }
+
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe { *(var_addr as *mut Clock) = Clock::default() };
+        SUCCESS
+    }
+
+    // InitializeAccount3, used for the pool vaults, reads rent from the sysvar
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe { *(var_addr as *mut Rent) = Rent::default() };
+        SUCCESS
+    }
+}
+
+// Accounts are preallocated with their final size and owner, since an
+// `AccountInfo` can't be resized or reassigned natively. Creating one funds it
+// after checking the request matches that layout.
+fn process_system(infos: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let instruction: SystemInstruction =
+        bincode::deserialize(data).map_err(|_| ProgramError::InvalidInstructionData)?;
+    let SystemInstruction::CreateAccount { lamports, space, owner } = instruction else {
+        return Err(ProgramError::InvalidInstructionData);
+    };
+    let [from, to] = infos else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    if !from.is_signer || !to.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if to.lamports() != 0 {
+        return Err(ProgramError::Custom(SystemError::AccountAlreadyInUse as u32));
+    }
+    if to.data_len() as u64 != space || *to.owner != owner {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    let remaining = from
+        .lamports()
+        .checked_sub(lamports)
+        .ok_or(ProgramError::Custom(SystemError::ResultWithNegativeLamports as u32))?;
+    **from.try_borrow_mut_lamports()? = remaining;
+    **to.try_borrow_mut_lamports()? = lamports;
+    to.try_borrow_mut_data()?.fill(0);
+    Ok(())
+}
@@ -0,0 +1,35 @@
+use solana_program::program_error::ProgramError;
+
+// Program-specific errors, surfaced as `ProgramError::Custom(code)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwapError {
+    MathOverflow = 0,
+    InsufficientLiquidity = 1,
+    InvalidFeeRate = 2,
+    InvalidVault = 3,
+    InvalidLpMint = 4,
+    UnsupportedAccountVersion = 5,
+    PoolMigrationRequired = 6,
+    UnsupportedMintExtension = 7,
+    NoNativeSide = 8,
+    Unauthorized = 9,
+    ProgramPaused = 10,
+    PriceBandExceeded = 11,
+    TimelockNotElapsed = 12,
+    InvalidMultisig = 13,
+    MintNotAllowed = 14,
+    PoolTypeDisabled = 15,
+    MintListFull = 16,
+    OrderNotFillable = 17,
+    NoIntervalsDue = 18,
+    SlippageExceeded = 19,
+    FarmRewardsFull = 20,
+    LiquidityLocked = 21,
+    PoolInactive = 22,
+}
+
+impl From<SwapError> for ProgramError {
+    fn from(e: SwapError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
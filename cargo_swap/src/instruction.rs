@@ -0,0 +1,1404 @@
+// Instruction builders for clients
+//
+// Account orders mirror the `next_account_info` sequence in each handler.
+// Pausable instructions also take the program config first, which `build`
+// prepends.
+// Pool instructions take each mint's token program from `PoolKeys`; their
+// `token_program` argument is the program that owns the LP mint.
+
+use crate::{
+    is_native_mint, CreationRules, DcaParams, GovernanceChange, LpMetadataField, MintStatus, OrderSchedule, PoolType,
+    RewardSchedule, TestProjectInstruction, CONFIG_SEED, CREATION_POLICY_SEED, DCA_SEED, FARM_SEED, FARM_STAKE_SEED,
+    FARM_VAULT_SEED, LIMIT_ORDER_SEED, LONG_TERM_ORDER_SEED, LP_LOCK_SEED, LP_MINT_SEED, MULTISIG_SEED, NATIVE_SEED,
+    ORDER_ESCROW_SEED, PAIR_INDEX_SEED, PENDING_CHANGE_SEED, POOL_INDEX_SEED, POOL_REGISTRY_SEEDS, VAULT_SEED,
+};
+use borsh::BorshSerialize;
+use solana_program::{
+    bpf_loader_upgradeable,
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program, sysvar,
+};
+
+// Addresses every pool instruction needs
+#[derive(Debug, Clone, Copy)]
+pub struct PoolKeys {
+    pub pool: Pubkey,
+    pub token_a: Pubkey,
+    pub token_b: Pubkey,
+    pub vault_a: Pubkey,
+    pub vault_b: Pubkey,
+    pub lp_mint: Pubkey,
+    pub token_program_a: Pubkey, // Token program that owns `token_a`
+    pub token_program_b: Pubkey,
+}
+
+impl PoolKeys {
+    // Keys of the pool for a mint pair, with the vaults and LP mint at the
+    // PDAs CreatePool creates them at
+    pub fn new(
+        program_id: &Pubkey,
+        token_a: &Pubkey,
+        token_b: &Pubkey,
+        token_program_a: &Pubkey,
+        token_program_b: &Pubkey,
+    ) -> Self {
+        let (pool, _) = find_pool_address(program_id, token_a, token_b);
+        PoolKeys {
+            pool,
+            token_a: *token_a,
+            token_b: *token_b,
+            vault_a: find_vault_address(program_id, &pool, token_a).0,
+            vault_b: find_vault_address(program_id, &pool, token_b).0,
+            lp_mint: find_lp_mint_address(program_id, &pool).0,
+            token_program_a: *token_program_a,
+            token_program_b: *token_program_b,
+        }
+    }
+
+    // Mint and token program a swap in `direction_a_to_b` takes in
+    fn input_side(&self, direction_a_to_b: bool) -> (Pubkey, Pubkey) {
+        if direction_a_to_b {
+            (self.token_a, self.token_program_a)
+        } else {
+            (self.token_b, self.token_program_b)
+        }
+    }
+
+    // Mint, token program and vault a swap in `direction_a_to_b` pays out of
+    fn output_side(&self, direction_a_to_b: bool) -> (Pubkey, Pubkey, Pubkey) {
+        if direction_a_to_b {
+            (self.token_b, self.token_program_b, self.vault_b)
+        } else {
+            (self.token_a, self.token_program_a, self.vault_a)
+        }
+    }
+}
+
+// User-side token accounts for a pool
+#[derive(Debug, Clone, Copy)]
+pub struct UserKeys {
+    pub user: Pubkey,
+    pub token_a: Pubkey,
+    pub token_b: Pubkey,
+    pub lp: Pubkey,
+}
+
+impl UserKeys {
+    // The user's (input, output) accounts for a swap in `direction_a_to_b`
+    fn swap_sides(&self, direction_a_to_b: bool) -> (Pubkey, Pubkey) {
+        if direction_a_to_b {
+            (self.token_a, self.token_b)
+        } else {
+            (self.token_b, self.token_a)
+        }
+    }
+}
+
+pub fn find_registry_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[POOL_REGISTRY_SEEDS, program_id.as_ref()], program_id)
+}
+
+pub fn find_pool_address(program_id: &Pubkey, token_a: &Pubkey, token_b: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"pool", token_a.as_ref(), token_b.as_ref()], program_id)
+}
+
+pub fn find_pool_index_address(program_id: &Pubkey, pool_id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[POOL_INDEX_SEED, &pool_id.to_le_bytes()], program_id)
+}
+
+// Pairs are ordered like pool PDAs, so look up both orders to find a pool
+pub fn find_pair_index_address(program_id: &Pubkey, token_a: &Pubkey, token_b: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[PAIR_INDEX_SEED, token_a.as_ref(), token_b.as_ref()], program_id)
+}
+
+pub fn find_vault_address(program_id: &Pubkey, pool: &Pubkey, mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[VAULT_SEED, pool.as_ref(), mint.as_ref()], program_id)
+}
+
+pub fn find_lp_mint_address(program_id: &Pubkey, pool: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[LP_MINT_SEED, pool.as_ref()], program_id)
+}
+
+pub fn find_associated_token_address(
+    ata_program: &Pubkey,
+    owner: &Pubkey,
+    mint: &Pubkey,
+    token_program: &Pubkey,
+) -> Pubkey {
+    Pubkey::find_program_address(&[owner.as_ref(), token_program.as_ref(), mint.as_ref()], ata_program).0
+}
+
+pub fn find_config_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CONFIG_SEED], program_id)
+}
+
+// Pending governance change for a target: a pool for fee changes, the
+// program config for guardian changes
+pub fn find_pending_change_address(program_id: &Pubkey, target: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[PENDING_CHANGE_SEED, target.as_ref()], program_id)
+}
+
+pub fn find_multisig_address(program_id: &Pubkey, create_key: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[MULTISIG_SEED, create_key.as_ref()], program_id)
+}
+
+pub fn find_creation_policy_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CREATION_POLICY_SEED], program_id)
+}
+
+// Limit orders are per owner, numbered by the owner's choice of `order_id`
+pub fn find_limit_order_address(program_id: &Pubkey, pool: &Pubkey, owner: &Pubkey, order_id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[LIMIT_ORDER_SEED, pool.as_ref(), owner.as_ref(), &order_id.to_le_bytes()],
+        program_id,
+    )
+}
+
+pub fn find_order_escrow_address(program_id: &Pubkey, order: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[ORDER_ESCROW_SEED, order.as_ref()], program_id)
+}
+
+// Long-term orders are numbered per owner like limit orders
+pub fn find_long_term_order_address(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    owner: &Pubkey,
+    order_id: u64,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[LONG_TERM_ORDER_SEED, pool.as_ref(), owner.as_ref(), &order_id.to_le_bytes()],
+        program_id,
+    )
+}
+
+// DCA vaults are numbered per owner too; their escrow is at
+// `find_order_escrow_address` of the vault
+pub fn find_dca_address(program_id: &Pubkey, pool: &Pubkey, owner: &Pubkey, dca_id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[DCA_SEED, pool.as_ref(), owner.as_ref(), &dca_id.to_le_bytes()], program_id)
+}
+
+// A pool's farm, and the farm's token account for the LP mint or a reward mint
+pub fn find_farm_address(program_id: &Pubkey, pool: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[FARM_SEED, pool.as_ref()], program_id)
+}
+
+pub fn find_farm_vault_address(program_id: &Pubkey, farm: &Pubkey, mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[FARM_VAULT_SEED, farm.as_ref(), mint.as_ref()], program_id)
+}
+
+pub fn find_farm_stake_address(program_id: &Pubkey, farm: &Pubkey, owner: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[FARM_STAKE_SEED, farm.as_ref(), owner.as_ref()], program_id)
+}
+
+// An owner's liquidity lock for a pool; its LP sits at
+// `find_order_escrow_address` of the lock
+pub fn find_lp_lock_address(program_id: &Pubkey, pool: &Pubkey, owner: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[LP_LOCK_SEED, pool.as_ref(), owner.as_ref()], program_id)
+}
+
+// The upgradeable loader's ProgramData account for `program_id`
+pub fn find_program_data_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id())
+}
+
+// Temporary wrapped-SOL account used by the native SOL instructions
+pub fn find_native_address(program_id: &Pubkey, pool: &Pubkey, user: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[NATIVE_SEED, pool.as_ref(), user.as_ref()], program_id)
+}
+
+fn build(program_id: &Pubkey, instruction: TestProjectInstruction, mut accounts: Vec<AccountMeta>) -> Instruction {
+    if instruction.pause_action().is_some() {
+        let (config, _) = find_config_address(program_id);
+        accounts.insert(0, AccountMeta::new_readonly(config, false));
+    }
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: instruction.try_to_vec().unwrap(),
+    }
+}
+
+pub fn initialize_registry(program_id: &Pubkey, payer: &Pubkey, token_program: &Pubkey) -> Instruction {
+    let (registry, bump) = find_registry_address(program_id);
+    build(
+        program_id,
+        TestProjectInstruction::InitializeRegistry { bump },
+        vec![
+            AccountMeta::new(registry, false),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(*token_program, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ],
+    )
+}
+
+// `pool_id` is the ID the registry will assign, i.e. its current `next_pool_id`.
+// Creates the pool's vaults and LP mint at the addresses in `pool`, which
+// must come from `PoolKeys::new`; `token_program` will own the LP mint, which
+// gets name and symbol metadata when that is Token-2022. A creation policy
+// with a fee also needs `pay_creation_fee`.
+pub fn create_pool(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    pool: &PoolKeys,
+    pool_type: PoolType,
+    token_program: &Pubkey,
+    pool_id: u64,
+) -> Instruction {
+    let (registry, _) = find_registry_address(program_id);
+    let (pool_index, _) = find_pool_index_address(program_id, pool_id);
+    let (pair_index, _) = find_pair_index_address(program_id, &pool.token_a, &pool.token_b);
+    build(
+        program_id,
+        TestProjectInstruction::CreatePool { token_a: pool.token_a, token_b: pool.token_b, pool_type },
+        vec![
+            AccountMeta::new(registry, false),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(*token_program, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new(pool_index, false),
+            AccountMeta::new(pair_index, false),
+            AccountMeta::new_readonly(pool.token_a, false),
+            AccountMeta::new_readonly(pool.token_b, false),
+            AccountMeta::new(pool.vault_a, false),
+            AccountMeta::new(pool.vault_b, false),
+            AccountMeta::new(pool.lp_mint, false),
+            AccountMeta::new_readonly(pool.token_program_a, false),
+            AccountMeta::new_readonly(pool.token_program_b, false),
+            AccountMeta::new_readonly(pool.pool, false),
+            AccountMeta::new_readonly(find_creation_policy_address(program_id).0, false),
+        ],
+    )
+}
+
+// Token account paying a creation fee charged in tokens
+#[derive(Debug, Clone, Copy)]
+pub struct TokenFee {
+    pub mint: Pubkey,
+    pub source: Pubkey,        // The payer's account for `mint`
+    pub token_program: Pubkey, // Token program that owns `mint`
+}
+
+// Adds the accounts paying the creation policy's fee to a `create_pool`
+// instruction: its treasury, then the payer's token account for token fees
+pub fn pay_creation_fee(mut instruction: Instruction, treasury: &Pubkey, token_fee: Option<&TokenFee>) -> Instruction {
+    instruction.accounts.push(AccountMeta::new(*treasury, false));
+    if let Some(fee) = token_fee {
+        instruction.accounts.extend([
+            AccountMeta::new_readonly(fee.mint, false),
+            AccountMeta::new(fee.source, false),
+            AccountMeta::new_readonly(fee.token_program, false),
+        ]);
+    }
+    instruction
+}
+
+fn init_pool_accounts(pool: &PoolKeys, user: &UserKeys, token_program: &Pubkey) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new(pool.pool, false),
+        AccountMeta::new_readonly(pool.token_a, false),
+        AccountMeta::new_readonly(pool.token_b, false),
+        AccountMeta::new(pool.vault_a, false),
+        AccountMeta::new(pool.vault_b, false),
+        AccountMeta::new(pool.lp_mint, false),
+        AccountMeta::new(user.user, true),
+        AccountMeta::new(user.token_a, false),
+        AccountMeta::new(user.token_b, false),
+        AccountMeta::new(user.lp, false),
+        AccountMeta::new_readonly(*token_program, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(pool.token_program_a, false),
+        AccountMeta::new_readonly(pool.token_program_b, false),
+    ]
+}
+
+fn add_liquidity_accounts(pool: &PoolKeys, user: &UserKeys, token_program: &Pubkey) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new(pool.pool, false),
+        AccountMeta::new_readonly(pool.token_a, false),
+        AccountMeta::new_readonly(pool.token_b, false),
+        AccountMeta::new(pool.vault_a, false),
+        AccountMeta::new(pool.vault_b, false),
+        AccountMeta::new(pool.lp_mint, false),
+        AccountMeta::new(user.token_a, false),
+        AccountMeta::new(user.token_b, false),
+        AccountMeta::new(user.lp, false),
+        AccountMeta::new_readonly(user.user, true),
+        AccountMeta::new_readonly(*token_program, false),
+        AccountMeta::new_readonly(pool.token_program_a, false),
+        AccountMeta::new_readonly(pool.token_program_b, false),
+    ]
+}
+
+fn remove_liquidity_accounts(pool: &PoolKeys, user: &UserKeys, token_program: &Pubkey) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new(pool.pool, false),
+        AccountMeta::new_readonly(pool.token_a, false),
+        AccountMeta::new_readonly(pool.token_b, false),
+        AccountMeta::new(pool.vault_a, false),
+        AccountMeta::new(pool.vault_b, false),
+        AccountMeta::new(pool.lp_mint, false),
+        AccountMeta::new(user.lp, false),
+        AccountMeta::new(user.token_a, false),
+        AccountMeta::new(user.token_b, false),
+        AccountMeta::new_readonly(user.user, true),
+        AccountMeta::new_readonly(*token_program, false),
+        AccountMeta::new_readonly(pool.token_program_a, false),
+        AccountMeta::new_readonly(pool.token_program_b, false),
+    ]
+}
+
+// `user.lp` is unused by swaps
+fn swap_accounts(pool: &PoolKeys, user: &UserKeys, direction_a_to_b: bool) -> Vec<AccountMeta> {
+    let (user_in, user_out) = user.swap_sides(direction_a_to_b);
+
+    vec![
+        AccountMeta::new(pool.pool, false),
+        AccountMeta::new_readonly(pool.token_a, false),
+        AccountMeta::new_readonly(pool.token_b, false),
+        AccountMeta::new(pool.vault_a, false),
+        AccountMeta::new(pool.vault_b, false),
+        AccountMeta::new(user_in, false),
+        AccountMeta::new(user_out, false),
+        AccountMeta::new_readonly(user.user, true),
+        AccountMeta::new_readonly(pool.token_program_a, false),
+        AccountMeta::new_readonly(pool.token_program_b, false),
+    ]
+}
+
+// Registry instructions take the registry header, then the pool index accounts
+fn registry_accounts(
+    program_id: &Pubkey,
+    user: &Pubkey,
+    token_program: &Pubkey,
+    pool_ids: &[u64],
+    writable: bool,
+) -> Vec<AccountMeta> {
+    let (registry, _) = find_registry_address(program_id);
+    let mut accounts = vec![
+        AccountMeta::new_readonly(registry, false),
+        AccountMeta::new_readonly(*user, true),
+        AccountMeta::new_readonly(*token_program, false),
+    ];
+    accounts.extend(pool_ids.iter().map(|pool_id| {
+        let (pool_index, _) = find_pool_index_address(program_id, *pool_id);
+        if writable {
+            AccountMeta::new(pool_index, false)
+        } else {
+            AccountMeta::new_readonly(pool_index, false)
+        }
+    }));
+    accounts
+}
+
+// Vaults and the LP mint must already exist from CreatePool
+pub fn init_pool(
+    program_id: &Pubkey,
+    pool_id: u64,
+    pool: &PoolKeys,
+    user: &UserKeys,
+    token_program: &Pubkey,
+    amount_a: u64,
+    amount_b: u64,
+) -> Instruction {
+    let mut accounts = init_pool_accounts(pool, user, token_program);
+    accounts.push(AccountMeta::new_readonly(find_pool_index_address(program_id, pool_id).0, false));
+    build(program_id, TestProjectInstruction::InitPool { pool_id, amount_a, amount_b }, accounts)
+}
+
+pub fn add_liquidity(
+    program_id: &Pubkey,
+    pool_id: u64,
+    pool: &PoolKeys,
+    user: &UserKeys,
+    token_program: &Pubkey,
+    amount_a: u64,
+    amount_b: u64,
+) -> Instruction {
+    build(
+        program_id,
+        TestProjectInstruction::AddLiquidity { pool_id, amount_a, amount_b },
+        add_liquidity_accounts(pool, user, token_program),
+    )
+}
+
+pub fn remove_liquidity(
+    program_id: &Pubkey,
+    pool_id: u64,
+    pool: &PoolKeys,
+    user: &UserKeys,
+    token_program: &Pubkey,
+    lp_amount: u64,
+) -> Instruction {
+    build(
+        program_id,
+        TestProjectInstruction::RemoveLiquidity { pool_id, lp_amount },
+        remove_liquidity_accounts(pool, user, token_program),
+    )
+}
+
+pub fn swap(
+    program_id: &Pubkey,
+    pool_id: u64,
+    pool: &PoolKeys,
+    user: &UserKeys,
+    amount_in: u64,
+    direction_a_to_b: bool,
+) -> Instruction {
+    build(
+        program_id,
+        TestProjectInstruction::Swap { pool_id, amount_in, direction_a_to_b },
+        swap_accounts(pool, user, direction_a_to_b),
+    )
+}
+
+// Makes a swap, add liquidity or remove liquidity instruction create the
+// user's missing output accounts (LP account for deposits) as ATAs of
+// `ata_program`, paid by the user. Those accounts must then be the user's ATAs;
+// see `find_associated_token_address`. Native SOL instructions don't support it.
+pub fn create_missing_accounts(mut instruction: Instruction, user: &Pubkey, ata_program: &Pubkey) -> Instruction {
+    for meta in instruction.accounts.iter_mut().filter(|meta| meta.pubkey == *user) {
+        meta.is_writable = true;
+    }
+    instruction.accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+    instruction.accounts.push(AccountMeta::new_readonly(*ata_program, false));
+    instruction
+}
+
+// User keys for a native SOL instruction: the pool's wrapped-SOL side uses
+// the temporary account, so the user needs no token account for it
+fn native_user_keys(program_id: &Pubkey, pool: &PoolKeys, user: &UserKeys) -> UserKeys {
+    let (native, _) = find_native_address(program_id, &pool.pool, &user.user);
+    let mut user = *user;
+    if is_native_mint(&pool.token_a) {
+        user.token_a = native;
+    } else {
+        user.token_b = native;
+    }
+    user
+}
+
+// Native SOL instructions pay from and refund to the user's lamports, and
+// append the system program and rent sysvar to the base accounts
+fn native_accounts(mut accounts: Vec<AccountMeta>, user: &Pubkey) -> Vec<AccountMeta> {
+    for meta in accounts.iter_mut().filter(|meta| meta.pubkey == *user) {
+        meta.is_writable = true;
+    }
+    accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+    accounts.push(AccountMeta::new_readonly(sysvar::rent::id(), false));
+    accounts
+}
+
+// Swaps native SOL in or out; one of the pool's mints must be wrapped SOL
+pub fn swap_native(
+    program_id: &Pubkey,
+    pool_id: u64,
+    pool: &PoolKeys,
+    user: &UserKeys,
+    amount_in: u64,
+    direction_a_to_b: bool,
+) -> Instruction {
+    let user = native_user_keys(program_id, pool, user);
+    build(
+        program_id,
+        TestProjectInstruction::SwapNative { pool_id, amount_in, direction_a_to_b },
+        native_accounts(swap_accounts(pool, &user, direction_a_to_b), &user.user),
+    )
+}
+
+pub fn add_liquidity_native(
+    program_id: &Pubkey,
+    pool_id: u64,
+    pool: &PoolKeys,
+    user: &UserKeys,
+    token_program: &Pubkey,
+    amount_a: u64,
+    amount_b: u64,
+) -> Instruction {
+    let user = native_user_keys(program_id, pool, user);
+    build(
+        program_id,
+        TestProjectInstruction::AddLiquidityNative { pool_id, amount_a, amount_b },
+        native_accounts(add_liquidity_accounts(pool, &user, token_program), &user.user),
+    )
+}
+
+pub fn remove_liquidity_native(
+    program_id: &Pubkey,
+    pool_id: u64,
+    pool: &PoolKeys,
+    user: &UserKeys,
+    token_program: &Pubkey,
+    lp_amount: u64,
+) -> Instruction {
+    let user = native_user_keys(program_id, pool, user);
+    build(
+        program_id,
+        TestProjectInstruction::RemoveLiquidityNative { pool_id, lp_amount },
+        native_accounts(remove_liquidity_accounts(pool, &user, token_program), &user.user),
+    )
+}
+
+// Returns up to `MAX_LIST_POOLS` pool infos as return data
+pub fn list_pools(program_id: &Pubkey, user: &Pubkey, token_program: &Pubkey, pool_ids: &[u64]) -> Instruction {
+    build(
+        program_id,
+        TestProjectInstruction::ListPools,
+        registry_accounts(program_id, user, token_program, pool_ids, false),
+    )
+}
+
+pub fn get_pool_info(program_id: &Pubkey, user: &Pubkey, token_program: &Pubkey, pool_id: u64) -> Instruction {
+    build(
+        program_id,
+        TestProjectInstruction::GetPoolInfo { pool_id },
+        registry_accounts(program_id, user, token_program, &[pool_id], false),
+    )
+}
+
+// Marks a registered pool inactive, in its index entry and in the pool account
+// `pool` (admin only)
+pub fn deactivate_pool(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    token_program: &Pubkey,
+    pool: &Pubkey,
+    pool_id: u64,
+) -> Instruction {
+    let mut accounts = registry_accounts(program_id, admin, token_program, &[pool_id], true);
+    accounts.push(AccountMeta::new(*pool, false));
+    accounts.push(AccountMeta::new_readonly(find_config_address(program_id).0, false));
+    build(program_id, TestProjectInstruction::DeactivatePool { pool_id }, accounts)
+}
+
+// Legacy instructions take the same accounts as their multi-pool counterparts
+
+// Takes the pair index in place of the pool index, and fails for a pair
+// CreatePool registered: those pools start through `init_pool`
+pub fn legacy_init_pool(
+    program_id: &Pubkey,
+    pool: &PoolKeys,
+    user: &UserKeys,
+    token_program: &Pubkey,
+    amount_a: u64,
+    amount_b: u64,
+) -> Instruction {
+    let (pair_index, _) = find_pair_index_address(program_id, &pool.token_a, &pool.token_b);
+    let mut accounts = init_pool_accounts(pool, user, token_program);
+    accounts.push(AccountMeta::new_readonly(pair_index, false));
+    build(program_id, TestProjectInstruction::LegacyInitPool { amount_a, amount_b }, accounts)
+}
+
+pub fn legacy_add_liquidity(
+    program_id: &Pubkey,
+    pool: &PoolKeys,
+    user: &UserKeys,
+    token_program: &Pubkey,
+    amount_a: u64,
+    amount_b: u64,
+) -> Instruction {
+    build(
+        program_id,
+        TestProjectInstruction::LegacyAddLiquidity { amount_a, amount_b },
+        add_liquidity_accounts(pool, user, token_program),
+    )
+}
+
+pub fn legacy_remove_liquidity(
+    program_id: &Pubkey,
+    pool: &PoolKeys,
+    user: &UserKeys,
+    token_program: &Pubkey,
+    lp_amount: u64,
+) -> Instruction {
+    build(
+        program_id,
+        TestProjectInstruction::LegacyRemoveLiquidity { lp_amount },
+        remove_liquidity_accounts(pool, user, token_program),
+    )
+}
+
+pub fn legacy_swap(
+    program_id: &Pubkey,
+    pool: &PoolKeys,
+    user: &UserKeys,
+    amount_in: u64,
+    direction_a_to_b: bool,
+) -> Instruction {
+    build(
+        program_id,
+        TestProjectInstruction::LegacySwap { amount_in, direction_a_to_b },
+        swap_accounts(pool, user, direction_a_to_b),
+    )
+}
+
+// Rewrites a pool created by an older program version in the current layout
+// and moves its legacy vaults into the vault PDAs; pass the PDAs themselves
+// for vaults already there
+pub fn migrate_pool(
+    program_id: &Pubkey,
+    pool: &PoolKeys,
+    legacy_vault_a: &Pubkey,
+    legacy_vault_b: &Pubkey,
+    payer: &Pubkey,
+) -> Instruction {
+    build(
+        program_id,
+        TestProjectInstruction::MigratePool,
+        vec![
+            AccountMeta::new(pool.pool, false),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(pool.token_program_a, false),
+            AccountMeta::new_readonly(pool.token_a, false),
+            AccountMeta::new_readonly(pool.token_b, false),
+            AccountMeta::new(*legacy_vault_a, false),
+            AccountMeta::new(*legacy_vault_b, false),
+            AccountMeta::new(pool.vault_a, false),
+            AccountMeta::new(pool.vault_b, false),
+        ],
+    )
+}
+
+// Sets the admin and guardian; `authority` must be the program's upgrade
+// authority
+pub fn initialize_config(program_id: &Pubkey, authority: &Pubkey, admin: &Pubkey, guardian: &Pubkey) -> Instruction {
+    let (config, _) = find_config_address(program_id);
+    let (program_data, _) = find_program_data_address(program_id);
+    build(
+        program_id,
+        TestProjectInstruction::InitializeConfig { admin: *admin, guardian: *guardian },
+        vec![
+            AccountMeta::new(config, false),
+            AccountMeta::new(*authority, true),
+            AccountMeta::new_readonly(program_data, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ],
+    )
+}
+
+// Rewrites a metadata field of a pool's Token-2022 LP mint; the payer covers
+// any extra rent
+pub fn update_lp_metadata(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    payer: &Pubkey,
+    pool: &PoolKeys,
+    field: LpMetadataField,
+    value: String,
+) -> Instruction {
+    let (config, _) = find_config_address(program_id);
+    build(
+        program_id,
+        TestProjectInstruction::UpdateLpMetadata { field, value },
+        vec![
+            AccountMeta::new_readonly(config, false),
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(pool.pool, false),
+            AccountMeta::new_readonly(pool.token_a, false),
+            AccountMeta::new_readonly(pool.token_b, false),
+            AccountMeta::new(pool.lp_mint, false),
+            AccountMeta::new_readonly(spl_token_2022::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ],
+    )
+}
+
+// Pauses the actions in `paused` (`PAUSE_*` bits) and unpauses the rest
+pub fn set_pause(program_id: &Pubkey, guardian: &Pubkey, paused: u8) -> Instruction {
+    let (config, _) = find_config_address(program_id);
+    build(
+        program_id,
+        TestProjectInstruction::SetPause { paused },
+        vec![AccountMeta::new(config, false), AccountMeta::new_readonly(*guardian, true)],
+    )
+}
+
+// Starts the timelock on `change`; the payer funds the pending change
+pub fn propose_change(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    payer: &Pubkey,
+    target: &Pubkey,
+    change: GovernanceChange,
+) -> Instruction {
+    let (config, _) = find_config_address(program_id);
+    let (pending, _) = find_pending_change_address(program_id, target);
+    build(
+        program_id,
+        TestProjectInstruction::ProposeChange { change },
+        vec![
+            AccountMeta::new_readonly(config, false),
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(pending, false),
+            AccountMeta::new_readonly(*target, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ],
+    )
+}
+
+// Applies the change pending for `target` and refunds its rent to
+// `receiver`. For fee-rate changes `pool_id` is the target pool's registry
+// id, whose index entry also gets the new fee; 0 for pools outside the
+// registry and any other change.
+pub fn execute_change(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    receiver: &Pubkey,
+    target: &Pubkey,
+    pool_id: u64,
+) -> Instruction {
+    let (config, _) = find_config_address(program_id);
+    let (pending, _) = find_pending_change_address(program_id, target);
+    let mut accounts = vec![
+        AccountMeta::new(config, false),
+        AccountMeta::new_readonly(*admin, true),
+        AccountMeta::new(*receiver, false),
+        AccountMeta::new(pending, false),
+        AccountMeta::new(*target, false),
+    ];
+    if pool_id != 0 {
+        accounts.push(AccountMeta::new(find_pool_index_address(program_id, pool_id).0, false));
+    }
+    build(program_id, TestProjectInstruction::ExecuteChange, accounts)
+}
+
+pub fn cancel_change(program_id: &Pubkey, admin: &Pubkey, receiver: &Pubkey, target: &Pubkey) -> Instruction {
+    let (config, _) = find_config_address(program_id);
+    let (pending, _) = find_pending_change_address(program_id, target);
+    build(
+        program_id,
+        TestProjectInstruction::CancelChange,
+        vec![
+            AccountMeta::new_readonly(config, false),
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new(*receiver, false),
+            AccountMeta::new(pending, false),
+        ],
+    )
+}
+
+// Turns an admin instruction built with a multisig as the admin into one the
+// multisig approves: the multisig no longer signs and the approving members
+// sign after the instruction's own accounts
+pub fn multisig_signers(mut instruction: Instruction, multisig: &Pubkey, signers: &[Pubkey]) -> Instruction {
+    for meta in instruction.accounts.iter_mut().filter(|meta| meta.pubkey == *multisig) {
+        meta.is_signer = false;
+    }
+    instruction.accounts.extend(signers.iter().map(|signer| AccountMeta::new_readonly(*signer, true)));
+    instruction
+}
+
+// Hands the admin role to `new_admin`, a key or a multisig
+pub fn set_admin(program_id: &Pubkey, admin: &Pubkey, new_admin: &Pubkey) -> Instruction {
+    let (config, _) = find_config_address(program_id);
+    build(
+        program_id,
+        TestProjectInstruction::SetAdmin { admin: *new_admin },
+        vec![AccountMeta::new(config, false), AccountMeta::new_readonly(*admin, true)],
+    )
+}
+
+// Creates a `threshold`-of-`signers` multisig at the PDA of `create_key`
+pub fn create_multisig(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    create_key: &Pubkey,
+    threshold: u8,
+    signers: &[Pubkey],
+) -> Instruction {
+    let (multisig, _) = find_multisig_address(program_id, create_key);
+    build(
+        program_id,
+        TestProjectInstruction::CreateMultisig { threshold, signers: signers.to_vec() },
+        vec![
+            AccountMeta::new(multisig, false),
+            AccountMeta::new_readonly(*create_key, false),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ],
+    )
+}
+
+// Membership changes are approved by `threshold` of the current `signers`
+pub fn add_multisig_signer(
+    program_id: &Pubkey,
+    multisig: &Pubkey,
+    signers: &[Pubkey],
+    signer: &Pubkey,
+) -> Instruction {
+    update_multisig(program_id, multisig, signers, TestProjectInstruction::AddMultisigSigner { signer: *signer })
+}
+
+pub fn remove_multisig_signer(
+    program_id: &Pubkey,
+    multisig: &Pubkey,
+    signers: &[Pubkey],
+    signer: &Pubkey,
+) -> Instruction {
+    update_multisig(program_id, multisig, signers, TestProjectInstruction::RemoveMultisigSigner { signer: *signer })
+}
+
+pub fn set_multisig_threshold(
+    program_id: &Pubkey,
+    multisig: &Pubkey,
+    signers: &[Pubkey],
+    threshold: u8,
+) -> Instruction {
+    update_multisig(program_id, multisig, signers, TestProjectInstruction::SetMultisigThreshold { threshold })
+}
+
+fn update_multisig(
+    program_id: &Pubkey,
+    multisig: &Pubkey,
+    signers: &[Pubkey],
+    instruction: TestProjectInstruction,
+) -> Instruction {
+    let mut accounts = vec![AccountMeta::new(*multisig, false)];
+    accounts.extend(signers.iter().map(|signer| AccountMeta::new_readonly(*signer, true)));
+    build(program_id, instruction, accounts)
+}
+
+// Sets the pool creation fee, paid in `fee_mint` (the default key for
+// lamports) to `treasury`, and the rule for each pool type. The payer funds
+// the policy account when it doesn't exist yet.
+pub fn set_creation_policy(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    payer: &Pubkey,
+    fee_mint: &Pubkey,
+    fee_amount: u64,
+    treasury: &Pubkey,
+    rules: CreationRules,
+) -> Instruction {
+    let (config, _) = find_config_address(program_id);
+    build(
+        program_id,
+        TestProjectInstruction::SetCreationPolicy { fee_mint: *fee_mint, fee_amount, treasury: *treasury, rules },
+        vec![
+            AccountMeta::new_readonly(config, false),
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(find_creation_policy_address(program_id).0, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ],
+    )
+}
+
+// Allowlists, denies or unlists a mint for pool creation
+pub fn set_mint_status(program_id: &Pubkey, admin: &Pubkey, mint: &Pubkey, status: MintStatus) -> Instruction {
+    let (config, _) = find_config_address(program_id);
+    build(
+        program_id,
+        TestProjectInstruction::SetMintStatus { mint: *mint, status },
+        vec![
+            AccountMeta::new_readonly(config, false),
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new(find_creation_policy_address(program_id).0, false),
+        ],
+    )
+}
+
+// Escrows `amount_in` of the user's input side in order `order_id`, to be
+// filled once the pool pays at least `min_amount_out` into the user's
+// account for the other side. The user also pays the order's rent and
+// `KEEPER_REWARD_LAMPORTS`.
+pub fn place_limit_order(
+    program_id: &Pubkey,
+    pool: &PoolKeys,
+    user: &UserKeys,
+    order_id: u64,
+    amount_in: u64,
+    min_amount_out: u64,
+    direction_a_to_b: bool,
+) -> Instruction {
+    let (order, _) = find_limit_order_address(program_id, &pool.pool, &user.user, order_id);
+    let (mint_in, token_program_in) = pool.input_side(direction_a_to_b);
+    let (source, destination) = user.swap_sides(direction_a_to_b);
+    build(
+        program_id,
+        TestProjectInstruction::PlaceLimitOrder { order_id, amount_in, min_amount_out, direction_a_to_b },
+        vec![
+            AccountMeta::new_readonly(pool.pool, false),
+            AccountMeta::new_readonly(mint_in, false),
+            AccountMeta::new(user.user, true),
+            AccountMeta::new(source, false),
+            AccountMeta::new_readonly(destination, false),
+            AccountMeta::new(order, false),
+            AccountMeta::new(find_order_escrow_address(program_id, &order).0, false),
+            AccountMeta::new_readonly(token_program_in, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ],
+    )
+}
+
+// Returns an order's escrow to the user's account for its input side and
+// closes the order
+pub fn cancel_limit_order(
+    program_id: &Pubkey,
+    pool: &PoolKeys,
+    user: &UserKeys,
+    order_id: u64,
+    direction_a_to_b: bool,
+) -> Instruction {
+    let (order, _) = find_limit_order_address(program_id, &pool.pool, &user.user, order_id);
+    let (mint_in, token_program_in) = pool.input_side(direction_a_to_b);
+    let (refund, _) = user.swap_sides(direction_a_to_b);
+    build(
+        program_id,
+        TestProjectInstruction::CancelLimitOrder,
+        vec![
+            AccountMeta::new(order, false),
+            AccountMeta::new(find_order_escrow_address(program_id, &order).0, false),
+            AccountMeta::new(user.user, true),
+            AccountMeta::new(refund, false),
+            AccountMeta::new_readonly(mint_in, false),
+            AccountMeta::new_readonly(token_program_in, false),
+        ],
+    )
+}
+
+// An order for the fill crank, with the accounts its proceeds go to
+#[derive(Debug, Clone, Copy)]
+pub struct OrderKeys {
+    pub order: Pubkey,
+    pub owner: Pubkey,
+    pub destination: Pubkey,
+    pub refund: Pubkey, // The owner's input-side account, for anything sent to the escrow
+}
+
+// Fills the `orders` the pool can, paying their keeper rewards to `keeper`
+pub fn fill_limit_orders(program_id: &Pubkey, pool: &PoolKeys, keeper: &Pubkey, orders: &[OrderKeys]) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(pool.pool, false),
+        AccountMeta::new_readonly(pool.token_a, false),
+        AccountMeta::new_readonly(pool.token_b, false),
+        AccountMeta::new(pool.vault_a, false),
+        AccountMeta::new(pool.vault_b, false),
+        AccountMeta::new_readonly(pool.token_program_a, false),
+        AccountMeta::new_readonly(pool.token_program_b, false),
+        AccountMeta::new(*keeper, true),
+    ];
+    for order in orders {
+        accounts.extend([
+            AccountMeta::new(order.order, false),
+            AccountMeta::new(find_order_escrow_address(program_id, &order.order).0, false),
+            AccountMeta::new(order.destination, false),
+            AccountMeta::new(order.owner, false),
+            AccountMeta::new(order.refund, false),
+        ]);
+    }
+    build(program_id, TestProjectInstruction::FillLimitOrders, accounts)
+}
+
+// Deposits `amount_in` of the user's input side into the pool vault as
+// long-term order `order_id`, sold over `schedule` by the execution crank
+pub fn place_long_term_order(
+    program_id: &Pubkey,
+    pool: &PoolKeys,
+    user: &UserKeys,
+    order_id: u64,
+    amount_in: u64,
+    schedule: OrderSchedule,
+    direction_a_to_b: bool,
+) -> Instruction {
+    let (order, _) = find_long_term_order_address(program_id, &pool.pool, &user.user, order_id);
+    let (mint_in, token_program_in) = pool.input_side(direction_a_to_b);
+    let vault_in = if direction_a_to_b { pool.vault_a } else { pool.vault_b };
+    let (source, _) = user.swap_sides(direction_a_to_b);
+    build(
+        program_id,
+        TestProjectInstruction::PlaceLongTermOrder { order_id, amount_in, schedule, direction_a_to_b },
+        vec![
+            AccountMeta::new_readonly(pool.pool, false),
+            AccountMeta::new_readonly(mint_in, false),
+            AccountMeta::new(vault_in, false),
+            AccountMeta::new(user.user, true),
+            AccountMeta::new(source, false),
+            AccountMeta::new(order, false),
+            AccountMeta::new_readonly(token_program_in, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ],
+    )
+}
+
+// Executes the due intervals of `orders`, all long-term orders of `pool`
+pub fn execute_long_term_orders(program_id: &Pubkey, pool: &Pubkey, orders: &[Pubkey]) -> Instruction {
+    let mut accounts = vec![AccountMeta::new(*pool, false)];
+    accounts.extend(orders.iter().map(|order| AccountMeta::new(*order, false)));
+    build(program_id, TestProjectInstruction::ExecuteLongTermOrders, accounts)
+}
+
+// Pays a long-term order's proceeds so far to the user's account for its
+// output side
+pub fn withdraw_long_term_proceeds(
+    program_id: &Pubkey,
+    pool: &PoolKeys,
+    user: &UserKeys,
+    order_id: u64,
+    direction_a_to_b: bool,
+) -> Instruction {
+    let (order, _) = find_long_term_order_address(program_id, &pool.pool, &user.user, order_id);
+    let (mint_out, token_program_out, vault_out) = pool.output_side(direction_a_to_b);
+    let (_, destination) = user.swap_sides(direction_a_to_b);
+    build(
+        program_id,
+        TestProjectInstruction::WithdrawLongTermProceeds,
+        vec![
+            AccountMeta::new_readonly(pool.pool, false),
+            AccountMeta::new(order, false),
+            AccountMeta::new_readonly(user.user, true),
+            AccountMeta::new_readonly(mint_out, false),
+            AccountMeta::new(vault_out, false),
+            AccountMeta::new(destination, false),
+            AccountMeta::new_readonly(token_program_out, false),
+        ],
+    )
+}
+
+// Closes a long-term order, returning its unsold input and proceeds to the
+// user's accounts for each side
+pub fn cancel_long_term_order(program_id: &Pubkey, pool: &PoolKeys, user: &UserKeys, order_id: u64) -> Instruction {
+    let (order, _) = find_long_term_order_address(program_id, &pool.pool, &user.user, order_id);
+    build(
+        program_id,
+        TestProjectInstruction::CancelLongTermOrder,
+        vec![
+            AccountMeta::new_readonly(pool.pool, false),
+            AccountMeta::new(order, false),
+            AccountMeta::new(user.user, true),
+            AccountMeta::new_readonly(pool.token_a, false),
+            AccountMeta::new_readonly(pool.token_b, false),
+            AccountMeta::new(pool.vault_a, false),
+            AccountMeta::new(pool.vault_b, false),
+            AccountMeta::new(user.token_a, false),
+            AccountMeta::new(user.token_b, false),
+            AccountMeta::new_readonly(pool.token_program_a, false),
+            AccountMeta::new_readonly(pool.token_program_b, false),
+        ],
+    )
+}
+
+// Opens DCA vault `dca_id` with `deposit` of the user's input side, swapped
+// into the user's account for the other side per `params`
+pub fn create_dca(
+    program_id: &Pubkey,
+    pool: &PoolKeys,
+    user: &UserKeys,
+    dca_id: u64,
+    deposit: u64,
+    params: DcaParams,
+    direction_a_to_b: bool,
+) -> Instruction {
+    let (dca, _) = find_dca_address(program_id, &pool.pool, &user.user, dca_id);
+    let (mint_in, token_program_in) = pool.input_side(direction_a_to_b);
+    let (source, destination) = user.swap_sides(direction_a_to_b);
+    build(
+        program_id,
+        TestProjectInstruction::CreateDca { dca_id, deposit, params, direction_a_to_b },
+        vec![
+            AccountMeta::new_readonly(pool.pool, false),
+            AccountMeta::new_readonly(mint_in, false),
+            AccountMeta::new(user.user, true),
+            AccountMeta::new(source, false),
+            AccountMeta::new_readonly(destination, false),
+            AccountMeta::new(dca, false),
+            AccountMeta::new(find_order_escrow_address(program_id, &dca).0, false),
+            AccountMeta::new_readonly(token_program_in, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ],
+    )
+}
+
+// Runs a DCA vault's due period as a swap from its escrow to `destination`,
+// the owner's account it was created with
+pub fn execute_dca(
+    program_id: &Pubkey,
+    pool: &PoolKeys,
+    dca: &Pubkey,
+    destination: &Pubkey,
+    direction_a_to_b: bool,
+) -> Instruction {
+    let (escrow, _) = find_order_escrow_address(program_id, dca);
+    let (token_a, token_b) = if direction_a_to_b { (escrow, *destination) } else { (*destination, escrow) };
+    let vault = UserKeys { user: *dca, token_a, token_b, lp: Pubkey::default() };
+    let mut accounts = swap_accounts(pool, &vault, direction_a_to_b);
+    // The vault signs inside the program and records the execution
+    accounts[7] = AccountMeta::new(*dca, false);
+    build(program_id, TestProjectInstruction::ExecuteDca, accounts)
+}
+
+// Returns a DCA vault's remaining input to the user's account for that side
+// and closes it
+pub fn close_dca(
+    program_id: &Pubkey,
+    pool: &PoolKeys,
+    user: &UserKeys,
+    dca_id: u64,
+    direction_a_to_b: bool,
+) -> Instruction {
+    let (dca, _) = find_dca_address(program_id, &pool.pool, &user.user, dca_id);
+    let (mint_in, token_program_in) = pool.input_side(direction_a_to_b);
+    let (refund, _) = user.swap_sides(direction_a_to_b);
+    build(
+        program_id,
+        TestProjectInstruction::CloseDca,
+        vec![
+            AccountMeta::new(dca, false),
+            AccountMeta::new(find_order_escrow_address(program_id, &dca).0, false),
+            AccountMeta::new(user.user, true),
+            AccountMeta::new(refund, false),
+            AccountMeta::new_readonly(mint_in, false),
+            AccountMeta::new_readonly(token_program_in, false),
+        ],
+    )
+}
+
+// Creates the pool's farm; `lp_token_program` owns the pool's LP mint
+pub fn create_farm(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    payer: &Pubkey,
+    pool: &PoolKeys,
+    lp_token_program: &Pubkey,
+) -> Instruction {
+    let (config, _) = find_config_address(program_id);
+    let (farm, _) = find_farm_address(program_id, &pool.pool);
+    build(
+        program_id,
+        TestProjectInstruction::CreateFarm,
+        vec![
+            AccountMeta::new_readonly(config, false),
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(pool.pool, false),
+            AccountMeta::new_readonly(pool.lp_mint, false),
+            AccountMeta::new(farm, false),
+            AccountMeta::new(find_farm_vault_address(program_id, &farm, &pool.lp_mint).0, false),
+            AccountMeta::new_readonly(*lp_token_program, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ],
+    )
+}
+
+// Adds a `reward_mint` stream to the pool's farm. The payer funds the rent of
+// its vault, which is funded with a plain transfer to
+// `find_farm_vault_address(farm, reward_mint)`.
+pub fn add_farm_reward(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    payer: &Pubkey,
+    pool: &Pubkey,
+    reward_mint: &Pubkey,
+    reward_token_program: &Pubkey,
+    schedule: RewardSchedule,
+) -> Instruction {
+    let (config, _) = find_config_address(program_id);
+    let (farm, _) = find_farm_address(program_id, pool);
+    build(
+        program_id,
+        TestProjectInstruction::AddFarmReward { schedule },
+        vec![
+            AccountMeta::new_readonly(config, false),
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(farm, false),
+            AccountMeta::new_readonly(*reward_mint, false),
+            AccountMeta::new(find_farm_vault_address(program_id, &farm, reward_mint).0, false),
+            AccountMeta::new_readonly(*reward_token_program, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ],
+    )
+}
+
+// Reschedules the farm's stream at `reward_index`, in the order added
+pub fn set_farm_reward_schedule(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    pool: &Pubkey,
+    reward_index: u8,
+    schedule: RewardSchedule,
+) -> Instruction {
+    let (config, _) = find_config_address(program_id);
+    build(
+        program_id,
+        TestProjectInstruction::SetFarmRewardSchedule { reward_index, schedule },
+        vec![
+            AccountMeta::new_readonly(config, false),
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new(find_farm_address(program_id, pool).0, false),
+        ],
+    )
+}
+
+// Stakes `amount` of the user's LP tokens in the pool's farm
+pub fn stake_lp(
+    program_id: &Pubkey,
+    pool: &PoolKeys,
+    user: &UserKeys,
+    lp_token_program: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let (farm, _) = find_farm_address(program_id, &pool.pool);
+    build(
+        program_id,
+        TestProjectInstruction::StakeLp { amount },
+        vec![
+            AccountMeta::new(farm, false),
+            AccountMeta::new_readonly(pool.lp_mint, false),
+            AccountMeta::new(find_farm_vault_address(program_id, &farm, &pool.lp_mint).0, false),
+            AccountMeta::new(user.user, true),
+            AccountMeta::new(user.lp, false),
+            AccountMeta::new(find_farm_stake_address(program_id, &farm, &user.user).0, false),
+            AccountMeta::new_readonly(*lp_token_program, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ],
+    )
+}
+
+// Unstakes `amount` of the user's LP tokens back to their LP account
+pub fn unstake_lp(
+    program_id: &Pubkey,
+    pool: &PoolKeys,
+    user: &UserKeys,
+    lp_token_program: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let (farm, _) = find_farm_address(program_id, &pool.pool);
+    build(
+        program_id,
+        TestProjectInstruction::UnstakeLp { amount },
+        vec![
+            AccountMeta::new(farm, false),
+            AccountMeta::new_readonly(pool.lp_mint, false),
+            AccountMeta::new(find_farm_vault_address(program_id, &farm, &pool.lp_mint).0, false),
+            AccountMeta::new_readonly(user.user, true),
+            AccountMeta::new(user.lp, false),
+            AccountMeta::new(find_farm_stake_address(program_id, &farm, &user.user).0, false),
+            AccountMeta::new_readonly(*lp_token_program, false),
+        ],
+    )
+}
+
+// A reward stream to claim from, with the account its rewards go to
+#[derive(Debug, Clone, Copy)]
+pub struct RewardKeys {
+    pub mint: Pubkey,
+    pub token_program: Pubkey,
+    pub destination: Pubkey,
+}
+
+// Claims the owner's pending rewards from each of `rewards`
+pub fn claim_farm_rewards(program_id: &Pubkey, pool: &Pubkey, owner: &Pubkey, rewards: &[RewardKeys]) -> Instruction {
+    let (farm, _) = find_farm_address(program_id, pool);
+    let mut accounts = vec![
+        AccountMeta::new(farm, false),
+        AccountMeta::new(find_farm_stake_address(program_id, &farm, owner).0, false),
+        AccountMeta::new_readonly(*owner, true),
+    ];
+    for reward in rewards {
+        accounts.extend([
+            AccountMeta::new_readonly(reward.mint, false),
+            AccountMeta::new(find_farm_vault_address(program_id, &farm, &reward.mint).0, false),
+            AccountMeta::new(reward.destination, false),
+            AccountMeta::new_readonly(reward.token_program, false),
+        ]);
+    }
+    build(program_id, TestProjectInstruction::ClaimFarmRewards, accounts)
+}
+
+// Locks `lp_amount` of the user's LP tokens until `unlock_time`, or extends
+// their lock with `lp_amount` zero
+pub fn lock_liquidity(
+    program_id: &Pubkey,
+    pool: &PoolKeys,
+    user: &UserKeys,
+    lp_token_program: &Pubkey,
+    lp_amount: u64,
+    unlock_time: i64,
+) -> Instruction {
+    let (lock, _) = find_lp_lock_address(program_id, &pool.pool, &user.user);
+    build(
+        program_id,
+        TestProjectInstruction::LockLiquidity { lp_amount, unlock_time },
+        vec![
+            AccountMeta::new_readonly(pool.pool, false),
+            AccountMeta::new_readonly(pool.lp_mint, false),
+            AccountMeta::new(user.user, true),
+            AccountMeta::new(user.lp, false),
+            AccountMeta::new(lock, false),
+            AccountMeta::new(find_order_escrow_address(program_id, &lock).0, false),
+            AccountMeta::new_readonly(*lp_token_program, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ],
+    )
+}
+
+// Returns the user's expired lock to their LP account
+pub fn unlock_liquidity(
+    program_id: &Pubkey,
+    pool: &PoolKeys,
+    user: &UserKeys,
+    lp_token_program: &Pubkey,
+) -> Instruction {
+    let (lock, _) = find_lp_lock_address(program_id, &pool.pool, &user.user);
+    build(
+        program_id,
+        TestProjectInstruction::UnlockLiquidity,
+        vec![
+            AccountMeta::new(lock, false),
+            AccountMeta::new(find_order_escrow_address(program_id, &lock).0, false),
+            AccountMeta::new(user.user, true),
+            AccountMeta::new(user.lp, false),
+            AccountMeta::new_readonly(pool.lp_mint, false),
+            AccountMeta::new_readonly(*lp_token_program, false),
+        ],
+    )
+}
+
+// Drops the owner's expired lock back to its LP amount in the pool's farm.
+// Anyone can send it.
+pub fn expire_liquidity_lock(program_id: &Pubkey, pool: &Pubkey, owner: &Pubkey) -> Instruction {
+    let (lock, _) = find_lp_lock_address(program_id, pool, owner);
+    let (farm, _) = find_farm_address(program_id, pool);
+    build(
+        program_id,
+        TestProjectInstruction::ExpireLiquidityLock,
+        vec![
+            AccountMeta::new(lock, false),
+            AccountMeta::new(farm, false),
+            AccountMeta::new(find_farm_stake_address(program_id, &farm, owner).0, false),
+        ],
+    )
+}
+
+// Adds the pool's farm and the owner's stake in it to a `lock_liquidity` or
+// `unlock_liquidity` instruction, so the lock earns boosted farm rewards.
+// Required once a lock has joined the farm.
+pub fn with_lock_farm(mut instruction: Instruction, program_id: &Pubkey, pool: &Pubkey, owner: &Pubkey) -> Instruction {
+    let (farm, _) = find_farm_address(program_id, pool);
+    instruction.accounts.extend([
+        AccountMeta::new(farm, false),
+        AccountMeta::new(find_farm_stake_address(program_id, &farm, owner).0, false),
+    ]);
+    instruction
+}
@@ -1,49 +1,183 @@
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
-    entrypoint,
     entrypoint::ProgramResult,
-    program::{invoke, invoke_signed},
+    clock::Clock,
+    log::sol_log_data,
+    program::{invoke, invoke_signed, set_return_data},
     program_error::ProgramError,
+    program_option::COption,
     program_pack::{IsInitialized, Pack, Sealed},
     pubkey::Pubkey,
     rent::Rent,
     system_instruction,
     sysvar::Sysvar,
 };
-use std::str::FromStr;
 // Remove the standard spl_token import and create manual instructions
 // use spl_token::{
 //     instruction::{burn, mint_to, transfer},
 // };
 use borsh::{BorshDeserialize, BorshSerialize};
+use bytemuck::{checked, CheckedBitPattern, NoUninit, Pod, Zeroable};
+use spl_token_2022::extension::{
+    default_account_state::DefaultAccountState, metadata_pointer, transfer_fee::TransferFeeConfig,
+    BaseStateWithExtensions, ExtensionType, StateWithExtensions,
+};
+use spl_token_metadata_interface::state::{Field, TokenMetadata};
+
+pub mod error;
+pub mod instruction;
+pub mod quote;
+
+use error::SwapError;
 
 // Program ID
 solana_program::declare_id!("CurLpsFfiH9GujAQu13nTjqpasTtFpRkMTZhcS6oyLwi");
 
 // GorbChain SPL Token Program ID
-const GORBCHAIN_SPL_TOKEN_PROGRAM: &str = "G22oYgZ6LnVcy7v8eSNi2xpNk1NcZiPD8CVKSTut7oZ6";
+pub const GORBCHAIN_SPL_TOKEN_PROGRAM: &str = "G22oYgZ6LnVcy7v8eSNi2xpNk1NcZiPD8CVKSTut7oZ6";
+pub const GORBCHAIN_SPL_TOKEN_PROGRAM_ID: Pubkey = solana_program::pubkey!("G22oYgZ6LnVcy7v8eSNi2xpNk1NcZiPD8CVKSTut7oZ6");
+
+// Associated token account programs user accounts can be created with: the
+// GorbChain deployment and the upstream SPL one
+pub const GORBCHAIN_ATA_PROGRAM_ID: Pubkey = solana_program::pubkey!("GoATGVNeSXerFerPqTJ8hcED1msPWHHLxao2vwBYqowm");
+pub const SPL_ATA_PROGRAM_ID: Pubkey = solana_program::pubkey!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+
+pub fn is_supported_ata_program(program_id: &Pubkey) -> bool {
+    *program_id == GORBCHAIN_ATA_PROGRAM_ID || *program_id == SPL_ATA_PROGRAM_ID
+}
+
+// Token programs a pool can hold tokens from
+pub fn is_supported_token_program(program_id: &Pubkey) -> bool {
+    *program_id == GORBCHAIN_SPL_TOKEN_PROGRAM_ID
+        || *program_id == spl_token::id()
+        || *program_id == spl_token_2022::id()
+}
+
+// Wrapped-SOL mints; the GorbChain program shares SPL Token's
+pub fn is_native_mint(mint: &Pubkey) -> bool {
+    *mint == spl_token::native_mint::id() || *mint == spl_token_2022::native_mint::id()
+}
 
 // Pool Registry PDA seeds
-const POOL_REGISTRY_SEEDS: &[u8] = b"pool_registry";
+pub const POOL_REGISTRY_SEEDS: &[u8] = b"pool_registry";
+
+// Per-pool index PDA seeds: [POOL_INDEX_SEED, pool_id (le bytes)]
+pub const POOL_INDEX_SEED: &[u8] = b"pool_index";
+
+// Mint pair index PDA seeds: [PAIR_INDEX_SEED, token_a, token_b]
+pub const PAIR_INDEX_SEED: &[u8] = b"pair_index";
+
+// Pool vault PDA seeds: [VAULT_SEED, pool, mint]
+pub const VAULT_SEED: &[u8] = b"vault";
+
+// Pool LP mint PDA seeds: [LP_MINT_SEED, pool]
+pub const LP_MINT_SEED: &[u8] = b"lp_mint";
+
+// Decimals of the LP mints CreatePool creates
+pub const LP_MINT_DECIMALS: u8 = 9;
+
+// Temporary wrapped-SOL account PDA seeds: [NATIVE_SEED, pool, user]
+pub const NATIVE_SEED: &[u8] = b"native";
+
+// Program config PDA seeds
+pub const CONFIG_SEED: &[u8] = b"config";
+
+// Pending governance change PDA seeds: [PENDING_CHANGE_SEED, target]
+pub const PENDING_CHANGE_SEED: &[u8] = b"pending_change";
+
+// How long a proposed change waits before it can be executed, so LPs can
+// exit ahead of a fee change they don't accept
+pub const CHANGE_DELAY_SECONDS: i64 = 2 * 24 * 60 * 60;
+
+// Admin multisig PDA seeds: [MULTISIG_SEED, create_key]
+pub const MULTISIG_SEED: &[u8] = b"multisig";
+
+// Most members a multisig can have
+pub const MAX_MULTISIG_SIGNERS: usize = 10;
+
+// Seed of the pool creation policy PDA
+pub const CREATION_POLICY_SEED: &[u8] = b"creation_policy";
+
+// Mints the creation policy can allow or deny
+pub const MAX_LISTED_MINTS: usize = 64;
+
+// Limit order PDA: [LIMIT_ORDER_SEED, pool, owner, order_id], with its
+// input escrowed in a token account at [ORDER_ESCROW_SEED, order]. DCA vaults
+// escrow their input the same way.
+pub const LIMIT_ORDER_SEED: &[u8] = b"limit_order";
+pub const ORDER_ESCROW_SEED: &[u8] = b"order_escrow";
+
+// Lamports an order holds on top of its rent, paid to whoever fills it
+pub const KEEPER_REWARD_LAMPORTS: u64 = 100_000;
+
+// Long-term order PDA: [LONG_TERM_ORDER_SEED, pool, owner, order_id]. Its
+// unsold input and unwithdrawn proceeds sit in the pool vaults, outside the
+// reserves.
+pub const LONG_TERM_ORDER_SEED: &[u8] = b"long_term_order";
+
+// Most intervals a long-term order splits into
+pub const MAX_ORDER_INTERVALS: u32 = 10_000;
+
+// Dollar-cost-averaging vault PDA: [DCA_SEED, pool, owner, dca_id]
+pub const DCA_SEED: &[u8] = b"dca";
+
+// LP farm PDA: [FARM_SEED, pool], holding staked LP and each reward in token
+// accounts at [FARM_VAULT_SEED, farm, mint]. Stakes are at [FARM_STAKE_SEED,
+// farm, owner].
+pub const FARM_SEED: &[u8] = b"farm";
+pub const FARM_VAULT_SEED: &[u8] = b"farm_vault";
+pub const FARM_STAKE_SEED: &[u8] = b"farm_stake";
+
+// Reward mints one farm can emit at the same time
+pub const MAX_FARM_REWARDS: usize = 4;
+
+// Liquidity lock PDA: [LP_LOCK_SEED, pool, owner], escrowing LP the same way
+// as limit orders
+pub const LP_LOCK_SEED: &[u8] = b"lp_lock";
+
+// A locked position earns farm rewards as if it staked its LP times a boost
+// growing linearly with the time left on the lock, up to 1 + MAX_LOCK_BOOST_BPS /
+// 10_000 at MAX_LOCK_SECONDS or more
+pub const MAX_LOCK_SECONDS: i64 = 365 * 24 * 60 * 60;
+pub const MAX_LOCK_BOOST_BPS: u64 = 10_000;
 
-// Manual instruction creation for GorbChain SPL Token program
-fn create_transfer_instruction(
+// Actions the guardian can pause, as bits of `ProgramConfig::paused`
+pub const PAUSE_SWAPS: u8 = 1;
+pub const PAUSE_DEPOSITS: u8 = 1 << 1; // Also pool creation
+pub const PAUSE_WITHDRAWALS: u8 = 1 << 2;
+pub const PAUSE_ALL: u8 = PAUSE_SWAPS | PAUSE_DEPOSITS | PAUSE_WITHDRAWALS;
+
+// Prefix of LP mint names; the symbol is built from the pair, type and fee
+pub const LP_NAME_PREFIX: &str = "CSWAP-LP";
+
+// Return data is capped at 1024 bytes, which fits this many `PoolInfo`s
+pub const MAX_LIST_POOLS: usize = 8;
+
+// Manual instruction creation for the token programs. The `spl_token` and
+// `spl_token_2022` builders reject program IDs other than their own, which
+// rules out GorbChain's token program, so these take the program ID instead.
+fn create_transfer_checked_instruction(
+    token_program: &Pubkey,
     source: &Pubkey,
+    mint: &Pubkey,
     destination: &Pubkey,
     authority: &Pubkey,
     amount: u64,
+    decimals: u8,
 ) -> solana_program::instruction::Instruction {
     let data = {
         let mut buf = Vec::new();
-        buf.push(3); // Transfer instruction discriminator
+        buf.push(12); // TransferChecked instruction discriminator
         buf.extend_from_slice(&amount.to_le_bytes());
+        buf.push(decimals);
         buf
     };
 
     solana_program::instruction::Instruction {
-        program_id: Pubkey::from_str(GORBCHAIN_SPL_TOKEN_PROGRAM).unwrap(),
+        program_id: *token_program,
         accounts: vec![
             solana_program::instruction::AccountMeta::new(*source, false),
+            solana_program::instruction::AccountMeta::new_readonly(*mint, false),
             solana_program::instruction::AccountMeta::new(*destination, false),
             solana_program::instruction::AccountMeta::new_readonly(*authority, true),
         ],
@@ -52,6 +186,7 @@ fn create_transfer_instruction(
 }
 
 fn create_mint_to_instruction(
+    token_program: &Pubkey,
     mint: &Pubkey,
     destination: &Pubkey,
     authority: &Pubkey,
@@ -65,7 +200,7 @@ fn create_mint_to_instruction(
     };
 
     solana_program::instruction::Instruction {
-        program_id: Pubkey::from_str(GORBCHAIN_SPL_TOKEN_PROGRAM).unwrap(),
+        program_id: *token_program,
         accounts: vec![
             solana_program::instruction::AccountMeta::new(*mint, false),
             solana_program::instruction::AccountMeta::new(*destination, false),
@@ -76,6 +211,7 @@ fn create_mint_to_instruction(
 }
 
 fn create_burn_instruction(
+    token_program: &Pubkey,
     account: &Pubkey,
     mint: &Pubkey,
     authority: &Pubkey,
@@ -89,7 +225,7 @@ fn create_burn_instruction(
     };
 
     solana_program::instruction::Instruction {
-        program_id: Pubkey::from_str(GORBCHAIN_SPL_TOKEN_PROGRAM).unwrap(),
+        program_id: *token_program,
         accounts: vec![
             solana_program::instruction::AccountMeta::new(*account, false),
             solana_program::instruction::AccountMeta::new(*mint, false),
@@ -100,18 +236,15 @@ fn create_burn_instruction(
 }
 
 fn create_initialize_account_instruction(
+    token_program: &Pubkey,
     account: &Pubkey,
     mint: &Pubkey,
     authority: &Pubkey,
 ) -> solana_program::instruction::Instruction {
-    let data = {
-        let mut buf = Vec::new();
-        buf.push(1); // InitializeAccount instruction discriminator
-        buf
-    };
+    let data = vec![1]; // InitializeAccount instruction discriminator
 
     solana_program::instruction::Instruction {
-        program_id: Pubkey::from_str(GORBCHAIN_SPL_TOKEN_PROGRAM).unwrap(),
+        program_id: *token_program,
         accounts: vec![
             solana_program::instruction::AccountMeta::new(*account, false),
             solana_program::instruction::AccountMeta::new_readonly(*mint, false),
@@ -122,22 +255,106 @@ fn create_initialize_account_instruction(
     }
 }
 
+// Takes the owner as data rather than as an account, for vaults created
+// before their pool account exists
+fn create_initialize_account3_instruction(
+    token_program: &Pubkey,
+    account: &Pubkey,
+    mint: &Pubkey,
+    authority: &Pubkey,
+) -> solana_program::instruction::Instruction {
+    let mut data = vec![18]; // InitializeAccount3 instruction discriminator
+    data.extend_from_slice(authority.as_ref());
+
+    solana_program::instruction::Instruction {
+        program_id: *token_program,
+        accounts: vec![
+            solana_program::instruction::AccountMeta::new(*account, false),
+            solana_program::instruction::AccountMeta::new_readonly(*mint, false),
+        ],
+        data,
+    }
+}
+
+fn create_initialize_mint_instruction(
+    token_program: &Pubkey,
+    mint: &Pubkey,
+    mint_authority: &Pubkey,
+    decimals: u8,
+) -> solana_program::instruction::Instruction {
+    // InitializeMint { decimals, mint_authority, freeze_authority: None }
+    let mut data = vec![0, decimals]; // InitializeMint instruction discriminator
+    data.extend_from_slice(mint_authority.as_ref());
+    data.push(0);
+    data.extend_from_slice(&[0; 32]);
+
+    solana_program::instruction::Instruction {
+        program_id: *token_program,
+        accounts: vec![
+            solana_program::instruction::AccountMeta::new(*mint, false),
+            solana_program::instruction::AccountMeta::new_readonly(solana_program::sysvar::rent::id(), false),
+        ],
+        data,
+    }
+}
+
+fn create_associated_token_account_idempotent_instruction(
+    ata_program: &Pubkey,
+    payer: &Pubkey,
+    account: &Pubkey,
+    owner: &Pubkey,
+    mint: &Pubkey,
+    token_program: &Pubkey,
+) -> solana_program::instruction::Instruction {
+    let data = vec![1]; // CreateIdempotent instruction discriminator
+
+    solana_program::instruction::Instruction {
+        program_id: *ata_program,
+        accounts: vec![
+            solana_program::instruction::AccountMeta::new(*payer, true),
+            solana_program::instruction::AccountMeta::new(*account, false),
+            solana_program::instruction::AccountMeta::new_readonly(*owner, false),
+            solana_program::instruction::AccountMeta::new_readonly(*mint, false),
+            solana_program::instruction::AccountMeta::new_readonly(solana_program::system_program::id(), false),
+            solana_program::instruction::AccountMeta::new_readonly(*token_program, false),
+        ],
+        data,
+    }
+}
+
+fn create_close_account_instruction(
+    token_program: &Pubkey,
+    account: &Pubkey,
+    destination: &Pubkey,
+    authority: &Pubkey,
+) -> solana_program::instruction::Instruction {
+    let data = vec![9]; // CloseAccount instruction discriminator
+
+    solana_program::instruction::Instruction {
+        program_id: *token_program,
+        accounts: vec![
+            solana_program::instruction::AccountMeta::new(*account, false),
+            solana_program::instruction::AccountMeta::new(*destination, false),
+            solana_program::instruction::AccountMeta::new_readonly(*authority, true),
+        ],
+        data,
+    }
+}
+
 // Entry point
-entrypoint!(process_instruction);
+#[cfg(not(feature = "no-entrypoint"))]
+solana_program::entrypoint!(process_instruction);
 
 // Pool Types
-#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+#[repr(u8)]
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Default, CheckedBitPattern, NoUninit)]
 pub enum PoolType {
+    #[default]
     Standard,      // 0.3% fee
     Stable,        // 0.01% fee for stable pairs
     Concentrated,  // Concentrated liquidity (future)
 }
 
-impl Default for PoolType {
-    fn default() -> Self {
-        PoolType::Standard
-    }
-}
 
 impl PoolType {
     pub fn default_fee_rate(&self) -> u16 {
@@ -149,9 +366,13 @@ impl PoolType {
     }
 }
 
-// Pool Info for Registry
+pub const POOL_INFO_VERSION: u8 = 1;
+pub const PAIR_INDEX_VERSION: u8 = 1;
+
+// Pool Info for Registry, stored in the pool's index PDA
 #[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
 pub struct PoolInfo {
+    pub version: u8,
     pub pool_id: u64,
     pub pool_address: Pubkey,
     pub token_a: Pubkey,
@@ -163,22 +384,205 @@ pub struct PoolInfo {
     pub tvl: u64,             // Total Value Locked
 }
 
-impl PoolInfo {
-    pub fn pack(&self, dst: &mut Vec<u8>) {
-        let data = self.try_to_vec().unwrap();
-        dst.extend_from_slice(&data);
+impl Sealed for PoolInfo {}
+
+impl IsInitialized for PoolInfo {
+    fn is_initialized(&self) -> bool {
+        self.pool_id > 0
     }
 }
 
-// Pool Registry
-#[derive(BorshSerialize, BorshDeserialize, Debug)]
+impl Pack for PoolInfo {
+    const LEN: usize = 1 + 8 + 32 + 32 + 32 + 1 + 2 + 1 + 8 + 8; // 125 bytes
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let info = PoolInfo::try_from_slice(src)
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+        if info.version != POOL_INFO_VERSION {
+            return Err(SwapError::UnsupportedAccountVersion.into());
+        }
+        Ok(info)
+    }
+
+    // Writes the Borsh layout field by field, so packing can't fail
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        write_fields(
+            dst,
+            &[
+                &[self.version],
+                &self.pool_id.to_le_bytes(),
+                self.pool_address.as_ref(),
+                self.token_a.as_ref(),
+                self.token_b.as_ref(),
+                &[self.pool_type as u8],
+                &self.fee_rate.to_le_bytes(),
+                &[self.is_active as u8],
+                &self.created_at.to_le_bytes(),
+                &self.tvl.to_le_bytes(),
+            ],
+        );
+    }
+}
+
+// Maps a mint pair to its registered pool ID
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct PairIndex {
+    pub version: u8,
+    pub pool_id: u64,
+}
+
+impl Sealed for PairIndex {}
+
+impl IsInitialized for PairIndex {
+    fn is_initialized(&self) -> bool {
+        self.pool_id > 0
+    }
+}
+
+impl Pack for PairIndex {
+    const LEN: usize = 1 + 8; // 9 bytes
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let index = PairIndex::try_from_slice(src)
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+        if index.version != PAIR_INDEX_VERSION {
+            return Err(SwapError::UnsupportedAccountVersion.into());
+        }
+        Ok(index)
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        write_fields(dst, &[&[self.version], &self.pool_id.to_le_bytes()]);
+    }
+}
+
+// Copies each field's bytes after the previous one. `Pack::pack` has already
+// checked `dst` is the account's `LEN`, which the fields add up to.
+fn write_fields(mut dst: &mut [u8], fields: &[&[u8]]) {
+    for field in fields {
+        let (head, rest) = dst.split_at_mut(field.len());
+        head.copy_from_slice(field);
+        dst = rest;
+    }
+}
+
+// Zero-copy account layouts
+//
+// `Pool` and `PoolRegistry` are `#[repr(C)]` with explicit padding, so they are
+// read and written in place through bytemuck instead of going through Borsh.
+// Enum and bool fields are checked on load, so a corrupt account is rejected
+// rather than reinterpreted.
+
+// Account discriminator, the first byte of every zero-copy account
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, CheckedBitPattern, NoUninit)]
+pub enum AccountType {
+    Uninitialized,
+    PoolRegistry,
+    Pool,
+    Config,
+    PendingChange,
+    Multisig,
+    CreationPolicy,
+    LimitOrder,
+    LongTermOrder,
+    DcaVault,
+    Farm,
+    FarmStake,
+    LiquidityLock,
+}
+
+// Leading two bytes of every zero-copy account
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, CheckedBitPattern, NoUninit)]
+pub struct AccountHeader {
+    pub account_type: AccountType,
+    pub version: u8,
+}
+
+pub const POOL_REGISTRY_VERSION: u8 = 1;
+pub const POOL_VERSION: u8 = 5;
+pub const CONFIG_VERSION: u8 = 1;
+pub const PENDING_CHANGE_VERSION: u8 = 3;
+pub const MULTISIG_VERSION: u8 = 1;
+pub const CREATION_POLICY_VERSION: u8 = 1;
+pub const LIMIT_ORDER_VERSION: u8 = 1;
+pub const LONG_TERM_ORDER_VERSION: u8 = 1;
+pub const DCA_VAULT_VERSION: u8 = 1;
+pub const FARM_VERSION: u8 = 1;
+pub const FARM_STAKE_VERSION: u8 = 1;
+pub const LIQUIDITY_LOCK_VERSION: u8 = 1;
+
+pub trait ZeroCopy: CheckedBitPattern + NoUninit {
+    const ACCOUNT_TYPE: AccountType;
+    const VERSION: u8;
+
+    fn header(&self) -> &AccountHeader;
+
+    fn new_header() -> AccountHeader {
+        AccountHeader { account_type: Self::ACCOUNT_TYPE, version: Self::VERSION }
+    }
+
+    // Rejects other account types and layout versions this program doesn't know
+    fn check_header(&self) -> ProgramResult {
+        let header = self.header();
+        if header.account_type != Self::ACCOUNT_TYPE {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if header.version != Self::VERSION {
+            return Err(SwapError::UnsupportedAccountVersion.into());
+        }
+        Ok(())
+    }
+
+    // Borrows an initialized account of this type in place
+    fn load(data: &[u8]) -> Result<&Self, ProgramError> {
+        let account: &Self = checked::try_from_bytes(data.get(..size_of::<Self>()).ok_or(ProgramError::InvalidAccountData)?)
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+        account.check_header()?;
+        Ok(account)
+    }
+
+    fn load_mut(data: &mut [u8]) -> Result<&mut Self, ProgramError> {
+        let account: &mut Self =
+            checked::try_from_bytes_mut(data.get_mut(..size_of::<Self>()).ok_or(ProgramError::InvalidAccountData)?)
+                .map_err(|_| ProgramError::InvalidAccountData)?;
+        account.check_header()?;
+        Ok(account)
+    }
+
+    // Copying read that doesn't require aligned data, for RPC account buffers
+    fn read(data: &[u8]) -> Result<Self, ProgramError> {
+        let account: Self = checked::try_pod_read_unaligned(data.get(..size_of::<Self>()).ok_or(ProgramError::InvalidAccountData)?)
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+        account.check_header()?;
+        Ok(account)
+    }
+}
+
+// Pool Registry header; pools live in their own index PDAs
+#[repr(C)]
+#[derive(Debug, Clone, Copy, CheckedBitPattern, NoUninit)]
 pub struct PoolRegistry {
-    pub pools: Vec<PoolInfo>,
-    pub next_pool_id: u64,
+    pub header: AccountHeader,
+    pub _padding: [u8; 2],
     pub total_pools: u32,
+    pub next_pool_id: u64,
     pub total_tvl: u64,
 }
 
+const _: () = assert!(size_of::<PoolRegistry>() == PoolRegistry::LEN);
+const _: () = assert!(align_of::<PoolRegistry>() == 8);
+
+impl ZeroCopy for PoolRegistry {
+    const ACCOUNT_TYPE: AccountType = AccountType::PoolRegistry;
+    const VERSION: u8 = POOL_REGISTRY_VERSION;
+
+    fn header(&self) -> &AccountHeader {
+        &self.header
+    }
+}
+
 impl Sealed for PoolRegistry {}
 
 impl IsInitialized for PoolRegistry {
@@ -188,1542 +592,5103 @@ impl IsInitialized for PoolRegistry {
 }
 
 impl Pack for PoolRegistry {
-    const LEN: usize = 10000; // Large enough for multiple pools
+    const LEN: usize = 2 + 2 + 4 + 8 + 8; // 24 bytes
 
     fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
-        let registry = PoolRegistry::try_from_slice(src)
-            .map_err(|_| ProgramError::InvalidAccountData)?;
-        Ok(registry)
+        Self::read(src)
     }
 
     fn pack_into_slice(&self, dst: &mut [u8]) {
-        let data = self.try_to_vec().unwrap();
-        dst[..data.len()].copy_from_slice(&data);
+        dst.copy_from_slice(bytemuck::bytes_of(self));
     }
 }
 
-// Instructions
-#[derive(BorshSerialize, BorshDeserialize, Debug)]
-pub enum TestProjectInstruction {
-    // Multi-pool instructions
-    CreatePool { token_a: Pubkey, token_b: Pubkey, pool_type: PoolType },
-    InitPool { pool_id: u64, amount_a: u64, amount_b: u64 },
-    AddLiquidity { pool_id: u64, amount_a: u64, amount_b: u64 },
-    RemoveLiquidity { pool_id: u64, lp_amount: u64 },
-    Swap { pool_id: u64, amount_in: u64, direction_a_to_b: bool },
-    
-    // Registry management
-    InitializeRegistry { bump: u8 },
-    ListPools,
-    GetPoolInfo { pool_id: u64 },
-    UpdatePoolFee { pool_id: u64, new_fee_rate: u16 },
-    DeactivatePool { pool_id: u64 },
-    
-    // Backward compatibility (legacy instructions)
-    LegacyInitPool { amount_a: u64, amount_b: u64 },
-    LegacyAddLiquidity { amount_a: u64, amount_b: u64 },
-    LegacyRemoveLiquidity { lp_amount: u64 },
-    LegacySwap { amount_in: u64, direction_a_to_b: bool },
+// Program-wide settings, created by the program's upgrade authority
+#[repr(C)]
+#[derive(Debug, Clone, Copy, CheckedBitPattern, NoUninit)]
+pub struct ProgramConfig {
+    pub header: AccountHeader,
+    pub bump: u8,
+    pub paused: u8,               // `PAUSE_*` bits
+    pub withdrawals_open: bool,   // Withdrawals ignore the pause
+    pub _padding: [u8; 3],
+    pub admin: Pubkey,            // Signs admin instructions such as UpdateLpMetadata; may be a `Multisig`
+    pub guardian: Pubkey,         // Pauses and unpauses, nothing else
 }
 
-// Enhanced Pool state
-#[derive(BorshSerialize, BorshDeserialize, Debug)]
-pub struct Pool {
-    pub pool_id: u64,
-    pub pool_type: PoolType,
-    pub token_a: Pubkey,
-    pub token_b: Pubkey,
-    pub bump: u8,
-    pub fee_rate: u16,        // Fee in basis points
-    pub reserve_a: u64,
-    pub reserve_b: u64,
-    pub total_lp_supply: u64,
-    pub created_at: i64,
-    pub is_active: bool,
+impl ProgramConfig {
+    pub fn is_paused(&self, action: u8) -> bool {
+        let paused = if self.withdrawals_open { self.paused & !PAUSE_WITHDRAWALS } else { self.paused };
+        paused & action != 0
+    }
 }
 
-impl Sealed for Pool {}
+const _: () = assert!(size_of::<ProgramConfig>() == ProgramConfig::LEN);
 
-impl IsInitialized for Pool {
+impl ZeroCopy for ProgramConfig {
+    const ACCOUNT_TYPE: AccountType = AccountType::Config;
+    const VERSION: u8 = CONFIG_VERSION;
+
+    fn header(&self) -> &AccountHeader {
+        &self.header
+    }
+}
+
+impl Sealed for ProgramConfig {}
+
+impl IsInitialized for ProgramConfig {
     fn is_initialized(&self) -> bool {
-        self.token_a != Pubkey::default() && self.is_active
+        self.header.account_type == AccountType::Config
     }
 }
 
-impl Pack for Pool {
-    const LEN: usize = 8 + 1 + 32 + 32 + 1 + 2 + 8 + 8 + 8 + 8 + 1; // 107 bytes
+impl Pack for ProgramConfig {
+    const LEN: usize = 2 + 1 + 1 + 1 + 3 + 32 + 32; // 72 bytes
 
     fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
-        let pool = Pool::try_from_slice(src)
-            .map_err(|_| ProgramError::InvalidAccountData)?;
-        Ok(pool)
+        Self::read(src)
     }
 
     fn pack_into_slice(&self, dst: &mut [u8]) {
-        let data = self.try_to_vec().unwrap();
-        dst[..data.len()].copy_from_slice(&data);
+        dst.copy_from_slice(bytemuck::bytes_of(self));
     }
 }
 
-// Program instruction processor
-pub fn process_instruction(
-    program_id: &Pubkey,
-    accounts: &[AccountInfo],
-    instruction_data: &[u8],
-) -> ProgramResult {
-    let instruction = TestProjectInstruction::try_from_slice(instruction_data)
-        .map_err(|_| ProgramError::InvalidInstructionData)?;
+// M-of-N admin authority. Set as the config's admin, admin instructions take
+// the multisig in the admin's place followed by `threshold` member signatures.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, CheckedBitPattern, NoUninit)]
+pub struct Multisig {
+    pub header: AccountHeader,
+    pub bump: u8,
+    pub threshold: u8,        // Member signatures an action needs
+    pub signer_count: u8,
+    pub _padding: [u8; 3],
+    pub create_key: Pubkey,   // Seed picked at creation
+    pub signers: [Pubkey; MAX_MULTISIG_SIGNERS], // The first `signer_count` are members
+}
 
-    match instruction {
-        // Multi-pool instructions
-        TestProjectInstruction::CreatePool { token_a, token_b, pool_type } => {
-            process_create_pool(program_id, accounts, token_a, token_b, pool_type)
+impl Multisig {
+    pub fn members(&self) -> &[Pubkey] {
+        &self.signers[..self.signer_count as usize]
+    }
+
+    // Replaces the members and threshold, which must leave a threshold of at
+    // least one that the distinct members can reach
+    pub fn set_members(&mut self, members: &[Pubkey], threshold: u8) -> ProgramResult {
+        let duplicate = members.iter().enumerate().any(|(i, member)| members[..i].contains(member));
+        if threshold == 0 || threshold as usize > members.len() || members.len() > MAX_MULTISIG_SIGNERS || duplicate {
+            return Err(SwapError::InvalidMultisig.into());
         }
-        TestProjectInstruction::InitPool { pool_id, amount_a, amount_b } => {
-            process_init_pool(program_id, accounts, pool_id, amount_a, amount_b)
+        self.signers = [Pubkey::default(); MAX_MULTISIG_SIGNERS];
+        self.signers[..members.len()].copy_from_slice(members);
+        self.signer_count = members.len() as u8;
+        self.threshold = threshold;
+        Ok(())
+    }
+
+    // Counts each member signing among `signer_infos` once
+    pub fn check_signers(&self, signer_infos: &[AccountInfo]) -> ProgramResult {
+        let approvals = self
+            .members()
+            .iter()
+            .filter(|member| signer_infos.iter().any(|info| info.is_signer && info.key == *member))
+            .count();
+        if approvals < self.threshold as usize {
+            return Err(ProgramError::MissingRequiredSignature);
         }
-        TestProjectInstruction::AddLiquidity { pool_id, amount_a, amount_b } => {
-            process_add_liquidity(program_id, accounts, pool_id, amount_a, amount_b)
+        Ok(())
+    }
+}
+
+const _: () = assert!(size_of::<Multisig>() == Multisig::LEN);
+
+impl ZeroCopy for Multisig {
+    const ACCOUNT_TYPE: AccountType = AccountType::Multisig;
+    const VERSION: u8 = MULTISIG_VERSION;
+
+    fn header(&self) -> &AccountHeader {
+        &self.header
+    }
+}
+
+impl Sealed for Multisig {}
+
+impl IsInitialized for Multisig {
+    fn is_initialized(&self) -> bool {
+        self.header.account_type == AccountType::Multisig
+    }
+}
+
+impl Pack for Multisig {
+    const LEN: usize = 2 + 1 + 1 + 1 + 3 + 32 + 32 * MAX_MULTISIG_SIGNERS; // 360 bytes
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        Self::read(src)
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        dst.copy_from_slice(bytemuck::bytes_of(self));
+    }
+}
+
+// Who may create pools of a type
+#[repr(u8)]
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Default, CheckedBitPattern, NoUninit)]
+pub enum CreationRule {
+    #[default]
+    Open,          // Any mints the policy doesn't deny
+    AllowlistOnly, // Both mints must be allowlisted
+    Disabled,
+}
+
+// The creation rule for each pool type
+#[repr(C)]
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Default, CheckedBitPattern, NoUninit)]
+pub struct CreationRules {
+    pub standard: CreationRule,
+    pub stable: CreationRule,
+    pub concentrated: CreationRule,
+}
+
+impl CreationRules {
+    pub fn rule(&self, pool_type: PoolType) -> CreationRule {
+        match pool_type {
+            PoolType::Standard => self.standard,
+            PoolType::Stable => self.stable,
+            PoolType::Concentrated => self.concentrated,
         }
-        TestProjectInstruction::RemoveLiquidity { pool_id, lp_amount } => {
-            process_remove_liquidity(program_id, accounts, pool_id, lp_amount)
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq)]
+pub enum MintStatus {
+    Unlisted,
+    Allowed,
+    Denied,
+}
+
+// Admin-set terms for CreatePool, at [CREATION_POLICY_SEED]. Until it exists
+// pool creation is free and open to any mints.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, CheckedBitPattern, NoUninit)]
+pub struct CreationPolicy {
+    pub header: AccountHeader,
+    pub bump: u8,
+    pub rules: CreationRules,
+    pub mint_count: u8,
+    pub _padding: [u8; 1],
+    pub fee_amount: u64,      // Charged per pool; 0 for none
+    pub allowed: u64,         // Bit i set: `mints[i]` is allowlisted, otherwise denied
+    pub fee_mint: Pubkey,     // Token the fee is paid in; the default key for lamports
+    pub treasury: Pubkey,     // Receives the fee: a wallet for lamports, a `fee_mint` token account otherwise
+    pub mints: [Pubkey; MAX_LISTED_MINTS], // The first `mint_count` are listed
+}
+
+impl CreationPolicy {
+    pub fn is_lamport_fee(&self) -> bool {
+        self.fee_mint == Pubkey::default()
+    }
+
+    pub fn mint_status(&self, mint: &Pubkey) -> MintStatus {
+        match self.mints[..self.mint_count as usize].iter().position(|listed| listed == mint) {
+            Some(i) if self.allowed & (1 << i) != 0 => MintStatus::Allowed,
+            Some(_) => MintStatus::Denied,
+            None => MintStatus::Unlisted,
         }
-        TestProjectInstruction::Swap { pool_id, amount_in, direction_a_to_b } => {
-            process_swap(program_id, accounts, pool_id, amount_in, direction_a_to_b)
+    }
+
+    // Lists, relists or unlists `mint`; unlisting moves the last entry into
+    // its slot
+    pub fn set_mint_status(&mut self, mint: &Pubkey, status: MintStatus) -> ProgramResult {
+        let count = self.mint_count as usize;
+        let index = match self.mints[..count].iter().position(|listed| listed == mint) {
+            Some(i) => i,
+            None if status == MintStatus::Unlisted => return Ok(()),
+            None if count == MAX_LISTED_MINTS => return Err(SwapError::MintListFull.into()),
+            None => {
+                self.mints[count] = *mint;
+                self.mint_count += 1;
+                count
+            }
+        };
+        match status {
+            MintStatus::Allowed => self.allowed |= 1 << index,
+            MintStatus::Denied => self.allowed &= !(1 << index),
+            MintStatus::Unlisted => {
+                let last = count - 1;
+                let last_allowed = self.allowed & (1 << last) != 0;
+                self.mints[index] = self.mints[last];
+                self.mints[last] = Pubkey::default();
+                self.allowed &= !(1 << index | 1 << last);
+                if last_allowed {
+                    self.allowed |= 1 << index;
+                }
+                self.mint_count -= 1;
+            }
         }
-        
-        // Registry management
-        TestProjectInstruction::InitializeRegistry { bump } => {
-            process_initialize_registry(program_id, accounts, instruction_data, bump)
+        Ok(())
+    }
+
+    // Denied mints never get a pool; allowlist-only types need both mints
+    // allowlisted
+    pub fn check_pool(&self, pool_type: PoolType, token_a: &Pubkey, token_b: &Pubkey) -> ProgramResult {
+        let statuses = [self.mint_status(token_a), self.mint_status(token_b)];
+        if statuses.contains(&MintStatus::Denied) {
+            return Err(SwapError::MintNotAllowed.into());
         }
-        TestProjectInstruction::ListPools => {
-            process_list_pools(program_id, accounts)
+        match self.rules.rule(pool_type) {
+            CreationRule::Open => Ok(()),
+            CreationRule::AllowlistOnly if statuses == [MintStatus::Allowed; 2] => Ok(()),
+            CreationRule::AllowlistOnly => Err(SwapError::MintNotAllowed.into()),
+            CreationRule::Disabled => Err(SwapError::PoolTypeDisabled.into()),
         }
-        TestProjectInstruction::GetPoolInfo { pool_id } => {
-            process_get_pool_info(program_id, accounts, pool_id)
+    }
+}
+
+const _: () = assert!(size_of::<CreationPolicy>() == CreationPolicy::LEN);
+const _: () = assert!(align_of::<CreationPolicy>() == 8);
+
+impl ZeroCopy for CreationPolicy {
+    const ACCOUNT_TYPE: AccountType = AccountType::CreationPolicy;
+    const VERSION: u8 = CREATION_POLICY_VERSION;
+
+    fn header(&self) -> &AccountHeader {
+        &self.header
+    }
+}
+
+impl Sealed for CreationPolicy {}
+
+impl IsInitialized for CreationPolicy {
+    fn is_initialized(&self) -> bool {
+        self.header.account_type == AccountType::CreationPolicy
+    }
+}
+
+impl Pack for CreationPolicy {
+    const LEN: usize = 2 + 1 + 3 + 1 + 1 + 8 + 8 + 32 + 32 + 32 * MAX_LISTED_MINTS; // 2136 bytes
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        Self::read(src)
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        dst.copy_from_slice(bytemuck::bytes_of(self));
+    }
+}
+
+// Parameters that only change through the timelock
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq)]
+pub enum GovernanceChange {
+    // A pool's swap fee, which also turns dynamic fees off; the target is the
+    // pool
+    FeeRate { fee_rate: u16 },
+    // Turns on volatility-based fees between the two bounds; the target is
+    // the pool
+    DynamicFee { min_fee_rate: u16, max_fee_rate: u16 },
+    // The pause guardian and whether withdrawals ignore pauses; the target is
+    // the program config
+    Guardian { guardian: Pubkey, withdrawals_open: bool },
+    // How far one slot's swaps may move a pool's price, 0 for no band; the
+    // target is the pool
+    PriceBand { max_price_move_bps: u16 },
+}
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, CheckedBitPattern, NoUninit)]
+pub enum ChangeKind {
+    FeeRate,
+    Guardian,
+    DynamicFee,
+    PriceBand,
+}
+
+// A proposed change waiting out its delay, at [PENDING_CHANGE_SEED, target].
+// Only one change per target can be pending. `kind` says which change it is;
+// each kind has its own value fields and leaves the others zeroed.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, CheckedBitPattern, NoUninit)]
+pub struct PendingChange {
+    pub header: AccountHeader,
+    pub bump: u8,
+    pub kind: ChangeKind,
+    pub withdrawals_open: bool,     // Guardian
+    pub _padding: [u8; 1],
+    pub fee_rate: u16,              // FeeRate
+    pub target: Pubkey,
+    pub guardian: Pubkey,           // Guardian
+    pub proposed_at: i64,
+    pub eta: i64,                   // Earliest time ExecuteChange accepts
+    pub min_fee_rate: u16,          // DynamicFee
+    pub max_fee_rate: u16,          // DynamicFee
+    pub max_price_move_bps: u16,    // PriceBand
+    pub _padding2: [u8; 2],
+}
+
+impl PendingChange {
+    pub fn new(bump: u8, target: Pubkey, change: GovernanceChange, proposed_at: i64) -> Self {
+        let mut pending = PendingChange {
+            header: PendingChange::new_header(),
+            bump,
+            kind: ChangeKind::FeeRate,
+            withdrawals_open: false,
+            _padding: [0; 1],
+            fee_rate: 0,
+            target,
+            guardian: Pubkey::default(),
+            proposed_at,
+            eta: proposed_at.saturating_add(CHANGE_DELAY_SECONDS),
+            min_fee_rate: 0,
+            max_fee_rate: 0,
+            max_price_move_bps: 0,
+            _padding2: [0; 2],
+        };
+        match change {
+            GovernanceChange::FeeRate { fee_rate } => pending.fee_rate = fee_rate,
+            GovernanceChange::Guardian { guardian, withdrawals_open } => {
+                pending.kind = ChangeKind::Guardian;
+                pending.guardian = guardian;
+                pending.withdrawals_open = withdrawals_open;
+            }
+            GovernanceChange::DynamicFee { min_fee_rate, max_fee_rate } => {
+                pending.kind = ChangeKind::DynamicFee;
+                pending.min_fee_rate = min_fee_rate;
+                pending.max_fee_rate = max_fee_rate;
+            }
+            GovernanceChange::PriceBand { max_price_move_bps } => {
+                pending.kind = ChangeKind::PriceBand;
+                pending.max_price_move_bps = max_price_move_bps;
+            }
+        }
+        pending
+    }
+
+    pub fn change(&self) -> GovernanceChange {
+        match self.kind {
+            ChangeKind::FeeRate => GovernanceChange::FeeRate { fee_rate: self.fee_rate },
+            ChangeKind::Guardian => {
+                GovernanceChange::Guardian { guardian: self.guardian, withdrawals_open: self.withdrawals_open }
+            }
+            ChangeKind::DynamicFee => {
+                GovernanceChange::DynamicFee { min_fee_rate: self.min_fee_rate, max_fee_rate: self.max_fee_rate }
+            }
+            ChangeKind::PriceBand => GovernanceChange::PriceBand { max_price_move_bps: self.max_price_move_bps },
+        }
+    }
+}
+
+const _: () = assert!(size_of::<PendingChange>() == PendingChange::LEN);
+
+impl ZeroCopy for PendingChange {
+    const ACCOUNT_TYPE: AccountType = AccountType::PendingChange;
+    const VERSION: u8 = PENDING_CHANGE_VERSION;
+
+    fn header(&self) -> &AccountHeader {
+        &self.header
+    }
+}
+
+impl Sealed for PendingChange {}
+
+impl IsInitialized for PendingChange {
+    fn is_initialized(&self) -> bool {
+        self.header.account_type == AccountType::PendingChange
+    }
+}
+
+impl Pack for PendingChange {
+    const LEN: usize = 2 + 1 + 1 + 1 + 1 + 2 + 32 + 32 + 8 + 8 + 2 + 2 + 2 + 2; // 96 bytes
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        Self::read(src)
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        dst.copy_from_slice(bytemuck::bytes_of(self));
+    }
+}
+
+// A resting order to swap `amount_in` of one pool token for at least
+// `min_amount_out` of the other. The input waits in the order's escrow until
+// a keeper fills the order or its owner cancels it.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, CheckedBitPattern, NoUninit)]
+pub struct LimitOrder {
+    pub header: AccountHeader,
+    pub bump: u8,
+    pub escrow_bump: u8,
+    pub direction_a_to_b: bool,
+    pub _padding: [u8; 3],
+    pub order_id: u64,        // Seed picked by the owner
+    pub pool: Pubkey,
+    pub owner: Pubkey,        // Gets the rent back and signs cancellations
+    pub destination: Pubkey,  // Owner's token account for the output side
+    pub amount_in: u64,       // Held in escrow, after any input transfer fee
+    pub min_amount_out: u64,  // The limit: least output the order accepts
+    pub created_at: i64,
+}
+
+const _: () = assert!(size_of::<LimitOrder>() == LimitOrder::LEN);
+const _: () = assert!(align_of::<LimitOrder>() == 8);
+
+impl ZeroCopy for LimitOrder {
+    const ACCOUNT_TYPE: AccountType = AccountType::LimitOrder;
+    const VERSION: u8 = LIMIT_ORDER_VERSION;
+
+    fn header(&self) -> &AccountHeader {
+        &self.header
+    }
+}
+
+impl Sealed for LimitOrder {}
+
+impl IsInitialized for LimitOrder {
+    fn is_initialized(&self) -> bool {
+        self.header.account_type == AccountType::LimitOrder
+    }
+}
+
+impl Pack for LimitOrder {
+    const LEN: usize = 2 + 1 + 1 + 1 + 3 + 8 + 32 * 3 + 8 + 8 + 8; // 136 bytes
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        Self::read(src)
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        dst.copy_from_slice(bytemuck::bytes_of(self));
+    }
+}
+
+// How a long-term order splits its input: `intervals` slices, one every
+// `interval_seconds`, each accepting down to `max_slippage_bps` below the
+// pool's TWAP since the order last executed (fee included)
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq)]
+pub struct OrderSchedule {
+    pub intervals: u32,
+    pub interval_seconds: i64,
+    pub max_slippage_bps: u16,
+}
+
+// A time-weighted order selling `amount_in` in `intervals` equal slices, one
+// every `interval_seconds` from `start_time`. The crank swaps one due slice
+// per run against the pool curve without moving tokens: the slice joins the
+// input reserve and its output leaves the other reserve for `proceeds`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, CheckedBitPattern, NoUninit)]
+pub struct LongTermOrder {
+    pub header: AccountHeader,
+    pub bump: u8,
+    pub direction_a_to_b: bool,
+    pub max_slippage_bps: u16,
+    pub _padding: [u8; 2],
+    pub order_id: u64,          // Seed picked by the owner
+    pub pool: Pubkey,
+    pub owner: Pubkey,          // Withdraws, cancels and gets the rent back
+    pub amount_in: u64,         // Deposited in the input vault, after any transfer fee
+    pub sold: u64,              // Input swapped into the reserves so far
+    pub proceeds: u64,          // Output swapped out of the reserves, not yet withdrawn
+    pub intervals: u32,
+    pub intervals_executed: u32,
+    pub interval_seconds: i64,
+    pub start_time: i64,
+    pub price_cumulative_last: [u8; 16], // The pool's accumulator for the input side at `observed_at`
+    pub observed_at: i64,
+}
+
+impl LongTermOrder {
+    // Input sold in interval `index`; the slices add up to `amount_in`
+    pub fn interval_amount(&self, index: u32) -> u64 {
+        let amount_in = self.amount_in as u128;
+        let intervals = self.intervals as u128;
+        let index = index as u128;
+        (amount_in * (index + 1) / intervals - amount_in * index / intervals) as u64
+    }
+
+    // Intervals that have ended by `now`, executed or not
+    pub fn intervals_due(&self, now: i64) -> u32 {
+        let elapsed = now.saturating_sub(self.start_time).max(0);
+        (elapsed / self.interval_seconds).min(self.intervals as i64) as u32
+    }
+}
+
+const _: () = assert!(size_of::<LongTermOrder>() == LongTermOrder::LEN);
+const _: () = assert!(align_of::<LongTermOrder>() == 8);
+
+impl ZeroCopy for LongTermOrder {
+    const ACCOUNT_TYPE: AccountType = AccountType::LongTermOrder;
+    const VERSION: u8 = LONG_TERM_ORDER_VERSION;
+
+    fn header(&self) -> &AccountHeader {
+        &self.header
+    }
+}
+
+impl Sealed for LongTermOrder {}
+
+impl IsInitialized for LongTermOrder {
+    fn is_initialized(&self) -> bool {
+        self.header.account_type == AccountType::LongTermOrder
+    }
+}
+
+impl Pack for LongTermOrder {
+    const LEN: usize = 2 + 1 + 1 + 2 + 2 + 8 + 32 * 2 + 8 * 3 + 4 + 4 + 8 + 8 + 16 + 8; // 152 bytes
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        Self::read(src)
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        dst.copy_from_slice(bytemuck::bytes_of(self));
+    }
+}
+
+// A DCA vault's recurring swap: `amount_per_period` every `interval_seconds`,
+// accepting down to `max_slippage_bps` below the pool's TWAP since the last
+// execution (fee included)
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq)]
+pub struct DcaParams {
+    pub amount_per_period: u64,
+    pub interval_seconds: i64,
+    pub max_slippage_bps: u16,
+}
+
+// Holds a user's input in its escrow and swaps a fixed amount of it through
+// the pool each period, once a crank runs after `next_execution`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, CheckedBitPattern, NoUninit)]
+pub struct DcaVault {
+    pub header: AccountHeader,
+    pub bump: u8,
+    pub escrow_bump: u8,
+    pub direction_a_to_b: bool,
+    pub _padding: [u8; 1],
+    pub max_slippage_bps: u16,
+    pub dca_id: u64,                     // Seed picked by the owner
+    pub pool: Pubkey,
+    pub owner: Pubkey,                   // Closes the vault and gets the rent back
+    pub destination: Pubkey,             // Owner's token account for the output side
+    pub amount_per_period: u64,
+    pub interval_seconds: i64,
+    pub next_execution: i64,
+    pub periods_executed: u64,
+    pub price_cumulative_last: [u8; 16], // The pool's accumulator for the input side at `observed_at`
+    pub observed_at: i64,
+}
+
+const _: () = assert!(size_of::<DcaVault>() == DcaVault::LEN);
+const _: () = assert!(align_of::<DcaVault>() == 8);
+
+impl ZeroCopy for DcaVault {
+    const ACCOUNT_TYPE: AccountType = AccountType::DcaVault;
+    const VERSION: u8 = DCA_VAULT_VERSION;
+
+    fn header(&self) -> &AccountHeader {
+        &self.header
+    }
+}
+
+impl Sealed for DcaVault {}
+
+impl IsInitialized for DcaVault {
+    fn is_initialized(&self) -> bool {
+        self.header.account_type == AccountType::DcaVault
+    }
+}
+
+impl Pack for DcaVault {
+    const LEN: usize = 2 + 1 + 1 + 1 + 1 + 2 + 8 + 32 * 3 + 8 * 4 + 16 + 8; // 168 bytes
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        Self::read(src)
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        dst.copy_from_slice(bytemuck::bytes_of(self));
+    }
+}
+
+// A reward stream paying `emission_per_second` between `start_time` and
+// `end_time`, shared among the LP staked at each moment
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq)]
+pub struct RewardSchedule {
+    pub emission_per_second: u64,
+    pub start_time: i64,
+    pub end_time: i64,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+pub struct FarmReward {
+    pub mint: Pubkey,
+    pub token_program: Pubkey,
+    pub emission_per_second: u64,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub reward_per_share: [u8; 16], // Q64.64 rewards per unit of stake weight since the stream was added; wraps
+    pub vault_bump: u8,
+    pub _padding: [u8; 7],
+}
+
+// Stakes a pool's LP tokens and pays out up to `MAX_FARM_REWARDS` reward
+// streams in proportion to stake weight and time. Staked LP weighs its amount
+// and locked LP its boosted amount. Each stream's accumulator is brought up
+// to `updated_at` before the total weight or a schedule changes.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, CheckedBitPattern, NoUninit)]
+pub struct Farm {
+    pub header: AccountHeader,
+    pub bump: u8,
+    pub stake_vault_bump: u8,
+    pub reward_count: u8,
+    pub _padding: [u8; 3],
+    pub pool: Pubkey,
+    pub lp_mint: Pubkey,
+    pub lp_token_program: Pubkey,
+    pub total_weight: u64,
+    pub updated_at: i64,
+    pub rewards: [FarmReward; MAX_FARM_REWARDS], // The first `reward_count` are in use
+}
+
+impl Farm {
+    pub fn rewards(&self) -> &[FarmReward] {
+        &self.rewards[..self.reward_count as usize]
+    }
+
+    // Credits every stream with what it emitted since `updated_at`. Emissions
+    // while nothing is staked stay in the reward vault.
+    pub fn accrue(&mut self, now: i64) {
+        let (updated_at, total_weight) = (self.updated_at, self.total_weight);
+        for reward in &mut self.rewards[..self.reward_count as usize] {
+            let emitted = quote::emitted_rewards(reward, updated_at, now);
+            let per_share = u128::from_le_bytes(reward.reward_per_share)
+                .wrapping_add(quote::reward_per_share_q64(emitted, total_weight));
+            reward.reward_per_share = per_share.to_le_bytes();
+        }
+        self.updated_at = self.updated_at.max(now);
+    }
+
+    // Moves what `stake` earned since it was last settled into its pending
+    // rewards. Call after `accrue` and before changing the stake.
+    pub fn settle(&self, stake: &mut FarmStake) {
+        for (i, reward) in self.rewards().iter().enumerate() {
+            let per_share = u128::from_le_bytes(reward.reward_per_share);
+            let earned = per_share.wrapping_sub(u128::from_le_bytes(stake.reward_per_share_paid[i]));
+            stake.pending[i] = stake.pending[i].saturating_add(quote::earned_rewards(stake.weight(), earned));
+            stake.reward_per_share_paid[i] = per_share.to_le_bytes();
+        }
+    }
+
+    // What `stake` could claim from each stream at `now`
+    pub fn pending_rewards(&self, stake: &FarmStake, now: i64) -> [u64; MAX_FARM_REWARDS] {
+        let (mut farm, mut stake) = (*self, *stake);
+        farm.accrue(now);
+        farm.settle(&mut stake);
+        stake.pending
+    }
+}
+
+const _: () = assert!(size_of::<Farm>() == Farm::LEN);
+const _: () = assert!(align_of::<Farm>() == 8);
+
+impl ZeroCopy for Farm {
+    const ACCOUNT_TYPE: AccountType = AccountType::Farm;
+    const VERSION: u8 = FARM_VERSION;
+
+    fn header(&self) -> &AccountHeader {
+        &self.header
+    }
+}
+
+impl Sealed for Farm {}
+
+impl IsInitialized for Farm {
+    fn is_initialized(&self) -> bool {
+        self.header.account_type == AccountType::Farm
+    }
+}
+
+impl Pack for Farm {
+    const LEN: usize = 2 + 1 + 1 + 1 + 3 + 32 * 3 + 8 + 8 + 112 * MAX_FARM_REWARDS; // 568 bytes
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        Self::read(src)
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        dst.copy_from_slice(bytemuck::bytes_of(self));
+    }
+}
+
+// One owner's LP staked in a farm, with each stream's accumulator as of the
+// last settlement and the rewards settled but not yet claimed
+#[repr(C)]
+#[derive(Debug, Clone, Copy, CheckedBitPattern, NoUninit)]
+pub struct FarmStake {
+    pub header: AccountHeader,
+    pub bump: u8,
+    pub _padding: [u8; 5],
+    pub farm: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub locked_weight: u64, // The boosted weight of the owner's liquidity lock, if it joined the farm
+    pub reward_per_share_paid: [[u8; 16]; MAX_FARM_REWARDS],
+    pub pending: [u64; MAX_FARM_REWARDS],
+}
+
+impl FarmStake {
+    // What the stake counts for in the farm's `total_weight`
+    pub fn weight(&self) -> u64 {
+        self.amount.saturating_add(self.locked_weight)
+    }
+}
+
+const _: () = assert!(size_of::<FarmStake>() == FarmStake::LEN);
+const _: () = assert!(align_of::<FarmStake>() == 8);
+
+impl ZeroCopy for FarmStake {
+    const ACCOUNT_TYPE: AccountType = AccountType::FarmStake;
+    const VERSION: u8 = FARM_STAKE_VERSION;
+
+    fn header(&self) -> &AccountHeader {
+        &self.header
+    }
+}
+
+impl Sealed for FarmStake {}
+
+impl IsInitialized for FarmStake {
+    fn is_initialized(&self) -> bool {
+        self.header.account_type == AccountType::FarmStake
+    }
+}
+
+impl Pack for FarmStake {
+    const LEN: usize = 2 + 1 + 5 + 32 * 2 + 8 + 8 + 16 * MAX_FARM_REWARDS + 8 * MAX_FARM_REWARDS; // 184 bytes
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        Self::read(src)
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        dst.copy_from_slice(bytemuck::bytes_of(self));
+    }
+}
+
+// LP an owner escrowed until `unlock_time`. Once it joins the pool's farm its
+// `weight` counts in the owner's stake there, fixed at the boost for the time
+// left when it was last locked or extended and back to `amount` once the lock
+// expires.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, CheckedBitPattern, NoUninit)]
+pub struct LiquidityLock {
+    pub header: AccountHeader,
+    pub bump: u8,
+    pub escrow_bump: u8,
+    pub _padding: [u8; 4],
+    pub amount: u64,
+    pub pool: Pubkey,
+    pub owner: Pubkey,
+    pub farm: Pubkey, // Default until the lock joins the pool's farm
+    pub weight: u64,
+    pub locked_at: i64,
+    pub unlock_time: i64,
+}
+
+const _: () = assert!(size_of::<LiquidityLock>() == LiquidityLock::LEN);
+const _: () = assert!(align_of::<LiquidityLock>() == 8);
+
+impl ZeroCopy for LiquidityLock {
+    const ACCOUNT_TYPE: AccountType = AccountType::LiquidityLock;
+    const VERSION: u8 = LIQUIDITY_LOCK_VERSION;
+
+    fn header(&self) -> &AccountHeader {
+        &self.header
+    }
+}
+
+impl Sealed for LiquidityLock {}
+
+impl IsInitialized for LiquidityLock {
+    fn is_initialized(&self) -> bool {
+        self.header.account_type == AccountType::LiquidityLock
+    }
+}
+
+impl Pack for LiquidityLock {
+    const LEN: usize = 2 + 1 + 1 + 4 + 8 + 32 * 3 + 8 + 8 + 8; // 136 bytes
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        Self::read(src)
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        dst.copy_from_slice(bytemuck::bytes_of(self));
+    }
+}
+
+// Logged with `sol_log_data` at each step of a change, Borsh-encoded
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+pub enum GovernanceEvent {
+    ChangeProposed { target: Pubkey, change: GovernanceChange, eta: i64 },
+    ChangeExecuted { target: Pubkey, change: GovernanceChange },
+    ChangeCancelled { target: Pubkey, change: GovernanceChange },
+}
+
+// LP mint metadata fields the admin can rewrite
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq)]
+pub enum LpMetadataField {
+    Name,
+    Symbol,
+    Uri,
+}
+
+impl From<LpMetadataField> for Field {
+    fn from(field: LpMetadataField) -> Self {
+        match field {
+            LpMetadataField::Name => Field::Name,
+            LpMetadataField::Symbol => Field::Symbol,
+            LpMetadataField::Uri => Field::Uri,
+        }
+    }
+}
+
+// Instructions
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub enum TestProjectInstruction {
+    // Multi-pool instructions
+    CreatePool { token_a: Pubkey, token_b: Pubkey, pool_type: PoolType },
+    InitPool { pool_id: u64, amount_a: u64, amount_b: u64 },
+    AddLiquidity { pool_id: u64, amount_a: u64, amount_b: u64 },
+    RemoveLiquidity { pool_id: u64, lp_amount: u64 },
+    Swap { pool_id: u64, amount_in: u64, direction_a_to_b: bool },
+    
+    // Registry management
+    InitializeRegistry { bump: u8 },
+    ListPools,
+    GetPoolInfo { pool_id: u64 },
+    UpdatePoolFee { pool_id: u64, new_fee_rate: u16 },
+    DeactivatePool { pool_id: u64 },
+    
+    // Backward compatibility (legacy instructions)
+    LegacyInitPool { amount_a: u64, amount_b: u64 },
+    LegacyAddLiquidity { amount_a: u64, amount_b: u64 },
+    LegacyRemoveLiquidity { lp_amount: u64 },
+    LegacySwap { amount_in: u64, direction_a_to_b: bool },
+
+    // Account maintenance
+    MigratePool,
+
+    // Native SOL modes: the user's token account for the pool's wrapped-SOL
+    // side is replaced by a temporary account funded from the user's lamports
+    // and closed back to the user afterwards
+    SwapNative { pool_id: u64, amount_in: u64, direction_a_to_b: bool },
+    AddLiquidityNative { pool_id: u64, amount_a: u64, amount_b: u64 },
+    RemoveLiquidityNative { pool_id: u64, lp_amount: u64 },
+
+    // Admin
+    InitializeConfig { admin: Pubkey, guardian: Pubkey },
+    UpdateLpMetadata { field: LpMetadataField, value: String },
+    SetGuardian { guardian: Pubkey, withdrawals_open: bool },
+    SetPause { paused: u8 },
+    SetPriceBand { max_price_move_bps: u16 },
+
+    // Timelocked governance
+    ProposeChange { change: GovernanceChange },
+    ExecuteChange,
+    CancelChange,
+
+    // Admin multisig; a multisig changes itself with its members' signatures
+    SetAdmin { admin: Pubkey },
+    CreateMultisig { threshold: u8, signers: Vec<Pubkey> },
+    AddMultisigSigner { signer: Pubkey },
+    RemoveMultisigSigner { signer: Pubkey },
+    SetMultisigThreshold { threshold: u8 },
+
+    // Pool creation policy
+    SetCreationPolicy { fee_mint: Pubkey, fee_amount: u64, treasury: Pubkey, rules: CreationRules },
+    SetMintStatus { mint: Pubkey, status: MintStatus },
+
+    // Limit orders resting against a pool
+    PlaceLimitOrder { order_id: u64, amount_in: u64, min_amount_out: u64, direction_a_to_b: bool },
+    CancelLimitOrder,
+    FillLimitOrders,
+
+    // Time-weighted long-term orders, executed by a crank
+    PlaceLongTermOrder { order_id: u64, amount_in: u64, schedule: OrderSchedule, direction_a_to_b: bool },
+    ExecuteLongTermOrders,
+    WithdrawLongTermProceeds,
+    CancelLongTermOrder,
+
+    // Dollar-cost-averaging vaults, executed by a crank
+    CreateDca { dca_id: u64, deposit: u64, params: DcaParams, direction_a_to_b: bool },
+    ExecuteDca,
+    CloseDca,
+
+    // LP farms; the admin creates them and schedules their rewards
+    CreateFarm,
+    AddFarmReward { schedule: RewardSchedule },
+    SetFarmRewardSchedule { reward_index: u8, schedule: RewardSchedule },
+    StakeLp { amount: u64 },
+    UnstakeLp { amount: u64 },
+    ClaimFarmRewards,
+
+    // Time-locked LP, boosted in the pool's farm
+    LockLiquidity { lp_amount: u64, unlock_time: i64 },
+    UnlockLiquidity,
+    ExpireLiquidityLock,
+}
+
+impl TestProjectInstruction {
+    // The `PAUSE_*` bit covering an instruction. Those instructions take the
+    // program config as an extra first account.
+    pub fn pause_action(&self) -> Option<u8> {
+        use TestProjectInstruction::*;
+        match self {
+            Swap { .. }
+            | LegacySwap { .. }
+            | SwapNative { .. }
+            | PlaceLimitOrder { .. }
+            | FillLimitOrders
+            | PlaceLongTermOrder { .. }
+            | ExecuteLongTermOrders
+            | CreateDca { .. }
+            | ExecuteDca => Some(PAUSE_SWAPS),
+            CreatePool { .. }
+            | InitPool { .. }
+            | AddLiquidity { .. }
+            | LegacyInitPool { .. }
+            | LegacyAddLiquidity { .. }
+            | AddLiquidityNative { .. }
+            | StakeLp { .. }
+            | LockLiquidity { .. } => Some(PAUSE_DEPOSITS),
+            RemoveLiquidity { .. }
+            | LegacyRemoveLiquidity { .. }
+            | RemoveLiquidityNative { .. }
+            | CancelLimitOrder
+            | WithdrawLongTermProceeds
+            | CancelLongTermOrder
+            | CloseDca
+            | UnstakeLp { .. }
+            | ClaimFarmRewards
+            | UnlockLiquidity => Some(PAUSE_WITHDRAWALS),
+            _ => None,
+        }
+    }
+}
+
+// Enhanced Pool state
+#[repr(C)]
+#[derive(Debug, Clone, Copy, CheckedBitPattern, NoUninit)]
+pub struct Pool {
+    pub header: AccountHeader,
+    pub bump: u8,
+    pub pool_type: PoolType,
+    pub is_active: bool,
+    pub _padding: [u8; 1],
+    pub fee_rate: u16,        // Fee in basis points
+    pub pool_id: u64,
+    pub token_a: Pubkey,
+    pub token_b: Pubkey,
+    pub token_program_a: Pubkey, // Token program that owns `token_a`
+    pub token_program_b: Pubkey,
+    pub reserve_a: u64,
+    pub reserve_b: u64,
+    pub total_lp_supply: u64,
+    pub created_at: i64,
+    pub max_price_move_bps: u16, // Price band per slot, 0 if disabled
+    pub dynamic_fee: bool,       // Swaps pay a volatility-based fee between the bounds below instead of `fee_rate`
+    pub _padding2: [u8; 1],
+    pub min_fee_rate: u16,       // Dynamic fee bounds in basis points
+    pub max_fee_rate: u16,
+    pub price_ref_slot: u64,     // Slot the reference reserves were taken in
+    pub price_ref_reserve_a: u64, // Reserves before that slot's first swap
+    pub price_ref_reserve_b: u64,
+    pub volatility_bps: u64,     // Recent price moves, as of `volatility_updated_at`; see `quote::dynamic_fee_rate`
+    pub volatility_updated_at: i64,
+    pub price_a_cumulative: [u8; 16], // Little-endian u128 TWAP accumulators, B per A and A per B, as of
+    pub price_b_cumulative: [u8; 16], // `price_cumulative_updated_at`; see `quote::price_cumulatives`
+    pub price_cumulative_updated_at: i64,
+}
+
+const _: () = assert!(size_of::<Pool>() == Pool::LEN);
+const _: () = assert!(align_of::<Pool>() == 8);
+
+impl ZeroCopy for Pool {
+    const ACCOUNT_TYPE: AccountType = AccountType::Pool;
+    const VERSION: u8 = POOL_VERSION;
+
+    fn header(&self) -> &AccountHeader {
+        &self.header
+    }
+}
+
+impl Sealed for Pool {}
+
+impl IsInitialized for Pool {
+    fn is_initialized(&self) -> bool {
+        self.token_a != Pubkey::default()
+    }
+}
+
+impl Pack for Pool {
+    const LEN: usize = 2 + 1 + 1 + 1 + 1 + 2 + 8 + 32 * 4 + 8 * 4 + 2 + 1 + 1 + 2 + 2 + 8 * 5 + 16 * 2 + 8; // 264 bytes
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        Self::read(src)
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        dst.copy_from_slice(bytemuck::bytes_of(self));
+    }
+}
+
+// Pool layout written by the original single-pool program
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct LegacySinglePool {
+    pub token_a: Pubkey,
+    pub token_b: Pubkey,
+    pub bump: u8,
+    pub reserve_a: u64,
+    pub reserve_b: u64,
+    pub total_lp_supply: u64,
+}
+
+impl LegacySinglePool {
+    pub const LEN: usize = 32 + 32 + 1 + 8 + 8 + 8; // 89 bytes
+}
+
+// Borsh pool layout used before the versioned header
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct LegacyBorshPool {
+    pub pool_id: u64,
+    pub pool_type: PoolType,
+    pub token_a: Pubkey,
+    pub token_b: Pubkey,
+    pub bump: u8,
+    pub fee_rate: u16,
+    pub reserve_a: u64,
+    pub reserve_b: u64,
+    pub total_lp_supply: u64,
+    pub created_at: i64,
+    pub is_active: bool,
+}
+
+impl LegacyBorshPool {
+    pub const LEN: usize = 8 + 1 + 32 + 32 + 1 + 2 + 8 + 8 + 8 + 8 + 1; // 107 bytes
+}
+
+// The single-pool program's pools migrate through the Borsh layout
+impl From<LegacySinglePool> for LegacyBorshPool {
+    fn from(legacy: LegacySinglePool) -> Self {
+        LegacyBorshPool {
+            pool_id: 0,
+            pool_type: PoolType::Standard,
+            token_a: legacy.token_a,
+            token_b: legacy.token_b,
+            bump: legacy.bump,
+            fee_rate: PoolType::Standard.default_fee_rate(),
+            reserve_a: legacy.reserve_a,
+            reserve_b: legacy.reserve_b,
+            total_lp_supply: legacy.total_lp_supply,
+            created_at: 0, // Not recorded by the old program
+            is_active: true,
+        }
+    }
+}
+
+impl From<LegacyBorshPool> for Pool {
+    fn from(legacy: LegacyBorshPool) -> Self {
+        Pool {
+            header: Pool::new_header(),
+            bump: legacy.bump,
+            pool_type: legacy.pool_type,
+            is_active: legacy.is_active,
+            _padding: [0; 1],
+            fee_rate: legacy.fee_rate,
+            pool_id: legacy.pool_id,
+            token_a: legacy.token_a,
+            token_b: legacy.token_b,
+            token_program_a: GORBCHAIN_SPL_TOKEN_PROGRAM_ID,
+            token_program_b: GORBCHAIN_SPL_TOKEN_PROGRAM_ID,
+            reserve_a: legacy.reserve_a,
+            reserve_b: legacy.reserve_b,
+            total_lp_supply: legacy.total_lp_supply,
+            created_at: legacy.created_at,
+            max_price_move_bps: 0,
+            dynamic_fee: false,
+            _padding2: [0; 1],
+            min_fee_rate: 0,
+            max_fee_rate: 0,
+            price_ref_slot: 0,
+            price_ref_reserve_a: 0,
+            price_ref_reserve_b: 0,
+            volatility_bps: 0,
+            volatility_updated_at: 0,
+            price_a_cumulative: [0; 16],
+            price_b_cumulative: [0; 16],
+            price_cumulative_updated_at: 0,
+        }
+    }
+}
+
+// Whether a pool account still uses a layout `MigratePool` converts
+fn is_legacy_pool_len(len: usize) -> bool {
+    matches!(len, LegacySinglePool::LEN | LegacyBorshPool::LEN)
+}
+
+// Program instruction processor
+pub fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let instruction = TestProjectInstruction::try_from_slice(instruction_data)
+        .map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    // Paused actions are refused before any handler runs
+    let accounts = match instruction.pause_action() {
+        Some(action) => check_not_paused(program_id, accounts, action)?,
+        None => accounts,
+    };
+
+    match instruction {
+        // Multi-pool instructions
+        TestProjectInstruction::CreatePool { token_a, token_b, pool_type } => {
+            process_create_pool(program_id, accounts, token_a, token_b, pool_type)
+        }
+        TestProjectInstruction::InitPool { pool_id, amount_a, amount_b } => {
+            process_init_pool(program_id, accounts, Some(pool_id), amount_a, amount_b)
+        }
+        TestProjectInstruction::AddLiquidity { pool_id, amount_a, amount_b } => {
+            process_add_liquidity(program_id, accounts, pool_id, amount_a, amount_b)
+        }
+        TestProjectInstruction::RemoveLiquidity { pool_id, lp_amount } => {
+            process_remove_liquidity(program_id, accounts, pool_id, lp_amount)
+        }
+        TestProjectInstruction::Swap { pool_id, amount_in, direction_a_to_b } => {
+            process_swap(program_id, accounts, pool_id, amount_in, direction_a_to_b, 0, &[])
+        }
+        
+        // Registry management
+        TestProjectInstruction::InitializeRegistry { bump } => {
+            process_initialize_registry(program_id, accounts, instruction_data, bump)
+        }
+        TestProjectInstruction::ListPools => {
+            process_list_pools(program_id, accounts)
+        }
+        TestProjectInstruction::GetPoolInfo { pool_id } => {
+            process_get_pool_info(program_id, accounts, pool_id)
+        }
+        // Fee changes go through the timelock: this takes ProposeChange's
+        // accounts and proposes the new fee for the pool passed there
+        TestProjectInstruction::UpdatePoolFee { new_fee_rate, .. } => {
+            process_propose_change(program_id, accounts, GovernanceChange::FeeRate { fee_rate: new_fee_rate })
+        }
+        TestProjectInstruction::DeactivatePool { pool_id } => {
+            process_deactivate_pool(program_id, accounts, pool_id)
+        }
+        
+        // Legacy instructions (backward compatibility) share the multi-pool
+        // handlers; legacy pools have no registry ID
+        TestProjectInstruction::LegacyInitPool { amount_a, amount_b } => {
+            process_init_pool(program_id, accounts, None, amount_a, amount_b)
+        }
+        TestProjectInstruction::LegacyAddLiquidity { amount_a, amount_b } => {
+            process_add_liquidity(program_id, accounts, 0, amount_a, amount_b)
+        }
+        TestProjectInstruction::LegacyRemoveLiquidity { lp_amount } => {
+            process_remove_liquidity(program_id, accounts, 0, lp_amount)
+        }
+        TestProjectInstruction::LegacySwap { amount_in, direction_a_to_b } => {
+            process_swap(program_id, accounts, 0, amount_in, direction_a_to_b, 0, &[])
+        }
+
+        // Account maintenance
+        TestProjectInstruction::MigratePool => {
+            process_migrate_pool(program_id, accounts)
+        }
+
+        // Native SOL modes wrap the matching handler
+        TestProjectInstruction::SwapNative { pool_id, amount_in, direction_a_to_b } => {
+            // Only an input on the native side needs lamports wrapped
+            let wrap = |native_a: bool| if native_a == direction_a_to_b { amount_in } else { 0 };
+            process_native(program_id, accounts, NativeSlots::SWAP, direction_a_to_b, wrap, |accounts| {
+                process_swap(program_id, accounts, pool_id, amount_in, direction_a_to_b, 0, &[])
+            })
+        }
+        TestProjectInstruction::AddLiquidityNative { pool_id, amount_a, amount_b } => {
+            let wrap = |native_a: bool| if native_a { amount_a } else { amount_b };
+            process_native(program_id, accounts, NativeSlots::LIQUIDITY, true, wrap, |accounts| {
+                process_add_liquidity(program_id, accounts, pool_id, amount_a, amount_b)
+            })
+        }
+        TestProjectInstruction::RemoveLiquidityNative { pool_id, lp_amount } => {
+            process_native(program_id, accounts, NativeSlots::REMOVE_LIQUIDITY, true, |_| 0, |accounts| {
+                process_remove_liquidity(program_id, accounts, pool_id, lp_amount)
+            })
+        }
+
+        // Admin
+        TestProjectInstruction::InitializeConfig { admin, guardian } => {
+            process_initialize_config(program_id, accounts, admin, guardian)
+        }
+        TestProjectInstruction::UpdateLpMetadata { field, value } => {
+            process_update_lp_metadata(program_id, accounts, field, value)
+        }
+        // Proposes a guardian change, like UpdatePoolFee
+        TestProjectInstruction::SetGuardian { guardian, withdrawals_open } => {
+            process_propose_change(program_id, accounts, GovernanceChange::Guardian { guardian, withdrawals_open })
+        }
+        TestProjectInstruction::SetPause { paused } => {
+            process_set_pause(program_id, accounts, paused)
+        }
+        // Proposes a price band change, like UpdatePoolFee
+        TestProjectInstruction::SetPriceBand { max_price_move_bps } => {
+            process_propose_change(program_id, accounts, GovernanceChange::PriceBand { max_price_move_bps })
+        }
+
+        // Timelocked governance
+        TestProjectInstruction::ProposeChange { change } => {
+            process_propose_change(program_id, accounts, change)
+        }
+        TestProjectInstruction::ExecuteChange => {
+            process_execute_change(program_id, accounts)
+        }
+        TestProjectInstruction::CancelChange => {
+            process_cancel_change(program_id, accounts)
+        }
+
+        // Admin multisig
+        TestProjectInstruction::SetAdmin { admin } => {
+            process_set_admin(program_id, accounts, admin)
+        }
+        TestProjectInstruction::CreateMultisig { threshold, signers } => {
+            process_create_multisig(program_id, accounts, threshold, &signers)
+        }
+        TestProjectInstruction::AddMultisigSigner { signer } => {
+            process_update_multisig(program_id, accounts, |members, threshold| {
+                members.push(signer);
+                Ok(threshold)
+            })
+        }
+        TestProjectInstruction::RemoveMultisigSigner { signer } => {
+            process_update_multisig(program_id, accounts, |members, threshold| {
+                let position = members.iter().position(|member| *member == signer);
+                members.remove(position.ok_or(SwapError::InvalidMultisig)?);
+                Ok(threshold)
+            })
+        }
+        TestProjectInstruction::SetMultisigThreshold { threshold } => {
+            process_update_multisig(program_id, accounts, |_, _| Ok(threshold))
+        }
+
+        // Pool creation policy
+        TestProjectInstruction::SetCreationPolicy { fee_mint, fee_amount, treasury, rules } => {
+            process_set_creation_policy(program_id, accounts, fee_mint, fee_amount, treasury, rules)
+        }
+        TestProjectInstruction::SetMintStatus { mint, status } => {
+            process_set_mint_status(program_id, accounts, mint, status)
+        }
+
+        // Limit orders
+        TestProjectInstruction::PlaceLimitOrder { order_id, amount_in, min_amount_out, direction_a_to_b } => {
+            process_place_limit_order(program_id, accounts, order_id, amount_in, min_amount_out, direction_a_to_b)
+        }
+        TestProjectInstruction::CancelLimitOrder => {
+            process_cancel_limit_order(program_id, accounts)
+        }
+        TestProjectInstruction::FillLimitOrders => {
+            process_fill_limit_orders(program_id, accounts)
+        }
+
+        // Long-term orders
+        TestProjectInstruction::PlaceLongTermOrder { order_id, amount_in, schedule, direction_a_to_b } => {
+            process_place_long_term_order(program_id, accounts, order_id, amount_in, schedule, direction_a_to_b)
+        }
+        TestProjectInstruction::ExecuteLongTermOrders => {
+            process_execute_long_term_orders(program_id, accounts)
+        }
+        TestProjectInstruction::WithdrawLongTermProceeds => {
+            process_withdraw_long_term_proceeds(program_id, accounts)
+        }
+        TestProjectInstruction::CancelLongTermOrder => {
+            process_cancel_long_term_order(program_id, accounts)
+        }
+
+        // DCA vaults
+        TestProjectInstruction::CreateDca { dca_id, deposit, params, direction_a_to_b } => {
+            process_create_dca(program_id, accounts, dca_id, deposit, params, direction_a_to_b)
+        }
+        TestProjectInstruction::ExecuteDca => {
+            process_execute_dca(program_id, accounts)
+        }
+        TestProjectInstruction::CloseDca => {
+            process_close_dca(program_id, accounts)
+        }
+
+        // LP farms
+        TestProjectInstruction::CreateFarm => {
+            process_create_farm(program_id, accounts)
+        }
+        TestProjectInstruction::AddFarmReward { schedule } => {
+            process_add_farm_reward(program_id, accounts, schedule)
+        }
+        TestProjectInstruction::SetFarmRewardSchedule { reward_index, schedule } => {
+            process_set_farm_reward_schedule(program_id, accounts, reward_index, schedule)
+        }
+        TestProjectInstruction::StakeLp { amount } => {
+            process_stake_lp(program_id, accounts, amount)
+        }
+        TestProjectInstruction::UnstakeLp { amount } => {
+            process_unstake_lp(program_id, accounts, amount)
+        }
+        TestProjectInstruction::ClaimFarmRewards => {
+            process_claim_farm_rewards(program_id, accounts)
+        }
+
+        // Liquidity locks
+        TestProjectInstruction::LockLiquidity { lp_amount, unlock_time } => {
+            process_lock_liquidity(program_id, accounts, lp_amount, unlock_time)
+        }
+        TestProjectInstruction::UnlockLiquidity => {
+            process_unlock_liquidity(program_id, accounts)
+        }
+        TestProjectInstruction::ExpireLiquidityLock => {
+            process_expire_liquidity_lock(program_id, accounts)
+        }
+    }
+}
+
+fn process_create_pool(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    token_a: Pubkey,
+    token_b: Pubkey,
+    pool_type: PoolType,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let registry_info = next_account_info(account_info_iter)?;
+    let user_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
+    let pool_index_info = next_account_info(account_info_iter)?;
+    let pair_index_info = next_account_info(account_info_iter)?;
+    let token_a_info = next_account_info(account_info_iter)?;
+    let token_b_info = next_account_info(account_info_iter)?;
+    let vault_a_info = next_account_info(account_info_iter)?;
+    let vault_b_info = next_account_info(account_info_iter)?;
+    let lp_mint_info = next_account_info(account_info_iter)?;
+    let token_program_a_info = next_account_info(account_info_iter)?;
+    let token_program_b_info = next_account_info(account_info_iter)?;
+    let pool_info = next_account_info(account_info_iter)?;
+    let policy_info = next_account_info(account_info_iter)?;
+
+    check_registry(program_id, registry_info)?;
+    let new_pool_id = PoolRegistry::load(&registry_info.data.borrow())?.next_pool_id;
+
+    // One pool per ordered mint pair, same as the pool PDA
+    let (pair_index_pubkey, pair_bump) = Pubkey::find_program_address(
+        &[PAIR_INDEX_SEED, token_a.as_ref(), token_b.as_ref()],
+        program_id,
+    );
+    if pair_index_pubkey != *pair_index_info.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if pair_index_info.lamports() > 0 {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+
+    let pool_id_bytes = new_pool_id.to_le_bytes();
+    let (pool_index_pubkey, index_bump) = Pubkey::find_program_address(
+        &[POOL_INDEX_SEED, &pool_id_bytes],
+        program_id,
+    );
+    if pool_index_pubkey != *pool_index_info.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    // Derive pool address
+    let (pool_pubkey, bump) = Pubkey::find_program_address(
+        &[b"pool", token_a.as_ref(), token_b.as_ref()],
+        program_id,
+    );
+    if pool_pubkey != *pool_info.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    if *token_a_info.key != token_a || *token_b_info.key != token_b {
+        return Err(ProgramError::InvalidArgument);
+    }
+    check_token_program(token_program_a_info, token_a_info)?;
+    check_token_program(token_program_b_info, token_b_info)?;
+    if !is_supported_token_program(token_program_info.key) {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    check_mint_extensions(token_a_info)?;
+    check_mint_extensions(token_b_info)?;
+
+    if let Some(policy) = load_creation_policy(program_id, policy_info)? {
+        policy.check_pool(pool_type, &token_a, &token_b)?;
+        pay_creation_fee(&policy, user_info, system_program_info, account_info_iter)?;
+    }
+
+    let rent = Rent::from_account_info(rent_info)?;
+
+    // Vaults and the LP mint are PDAs of the pool with the pool as authority,
+    // so nobody else ever holds a key to them
+    for (vault_info, mint_info, vault_program_info) in [
+        (vault_a_info, token_a_info, token_program_a_info),
+        (vault_b_info, token_b_info, token_program_b_info),
+    ] {
+        let (vault_pubkey, vault_bump) = Pubkey::find_program_address(
+            &[VAULT_SEED, pool_pubkey.as_ref(), mint_info.key.as_ref()],
+            program_id,
+        );
+        if vault_pubkey != *vault_info.key {
+            return Err(SwapError::InvalidVault.into());
+        }
+        create_pda_account(
+            vault_program_info.key,
+            user_info,
+            vault_info,
+            system_program_info,
+            &rent,
+            token_account_len(vault_program_info.key, mint_info)?,
+            &[VAULT_SEED, pool_pubkey.as_ref(), mint_info.key.as_ref(), &[vault_bump]],
+        )?;
+        invoke(
+            &create_initialize_account3_instruction(
+                vault_program_info.key,
+                vault_info.key,
+                mint_info.key,
+                &pool_pubkey,
+            ),
+            &[vault_info.clone(), mint_info.clone(), vault_program_info.clone()],
+        )?;
+    }
+
+    let (lp_mint_pubkey, lp_mint_bump) =
+        Pubkey::find_program_address(&[LP_MINT_SEED, pool_pubkey.as_ref()], program_id);
+    if lp_mint_pubkey != *lp_mint_info.key {
+        return Err(SwapError::InvalidLpMint.into());
+    }
+    // Token-2022 LP mints describe themselves: a metadata pointer to the mint
+    // itself, then token metadata with the pool as update authority
+    let lp_metadata = if *token_program_info.key == spl_token_2022::id() {
+        Some(lp_metadata(token_a_info, token_b_info, pool_type)?)
+    } else {
+        None
+    };
+    let lp_mint_len = match lp_metadata {
+        Some(_) => ExtensionType::try_calculate_account_len::<spl_token_2022::state::Mint>(&[
+            ExtensionType::MetadataPointer,
+        ])?,
+        None => spl_token::state::Mint::LEN,
+    };
+    create_pda_account(
+        token_program_info.key,
+        user_info,
+        lp_mint_info,
+        system_program_info,
+        &rent,
+        lp_mint_len,
+        &[LP_MINT_SEED, pool_pubkey.as_ref(), &[lp_mint_bump]],
+    )?;
+    if let Some(metadata) = &lp_metadata {
+        // The token program grows the mint for the metadata itself, but
+        // expects the rent for it to be there already
+        let top_up = rent
+            .minimum_balance(lp_mint_len + metadata.tlv_size_of()?)
+            .saturating_sub(lp_mint_info.lamports());
+        invoke(
+            &system_instruction::transfer(user_info.key, lp_mint_info.key, top_up),
+            &[user_info.clone(), lp_mint_info.clone(), system_program_info.clone()],
+        )?;
+        invoke(
+            &metadata_pointer::instruction::initialize(
+                token_program_info.key,
+                lp_mint_info.key,
+                Some(pool_pubkey),
+                Some(*lp_mint_info.key),
+            )?,
+            &[lp_mint_info.clone(), token_program_info.clone()],
+        )?;
+    }
+    invoke(
+        &create_initialize_mint_instruction(token_program_info.key, lp_mint_info.key, &pool_pubkey, LP_MINT_DECIMALS),
+        &[lp_mint_info.clone(), rent_info.clone(), token_program_info.clone()],
+    )?;
+    if let Some(metadata) = lp_metadata {
+        invoke_signed(
+            &spl_token_metadata_interface::instruction::initialize(
+                token_program_info.key,
+                lp_mint_info.key,
+                &pool_pubkey,
+                lp_mint_info.key,
+                &pool_pubkey,
+                metadata.name,
+                metadata.symbol,
+                metadata.uri,
+            ),
+            &[lp_mint_info.clone(), pool_info.clone(), token_program_info.clone()],
+            &[&[b"pool", token_a.as_ref(), token_b.as_ref(), &[bump]]],
+        )?;
+    }
+
+    create_pda_account(
+        program_id,
+        user_info,
+        pool_index_info,
+        system_program_info,
+        &rent,
+        PoolInfo::LEN,
+        &[POOL_INDEX_SEED, &pool_id_bytes, &[index_bump]],
+    )?;
+    create_pda_account(
+        program_id,
+        user_info,
+        pair_index_info,
+        system_program_info,
+        &rent,
+        PairIndex::LEN,
+        &[PAIR_INDEX_SEED, token_a.as_ref(), token_b.as_ref(), &[pair_bump]],
+    )?;
+
+    // Register the pool under a new ID; the pool account itself is created
+    // and funded by InitPool, which copies the type and fee from here
+    let pool_info = PoolInfo {
+        version: POOL_INFO_VERSION,
+        pool_id: new_pool_id,
+        pool_address: pool_pubkey,
+        token_a,
+        token_b,
+        pool_type,
+        fee_rate: pool_type.default_fee_rate(),
+        is_active: true,
+        created_at: solana_program::clock::Clock::get()?.unix_timestamp,
+        tvl: 0,
+    };
+    PoolInfo::pack(pool_info, &mut pool_index_info.data.borrow_mut())?;
+    PairIndex::pack(
+        PairIndex { version: PAIR_INDEX_VERSION, pool_id: new_pool_id },
+        &mut pair_index_info.data.borrow_mut(),
+    )?;
+
+    let mut registry_data = registry_info.try_borrow_mut_data()?;
+    let registry = PoolRegistry::load_mut(&mut registry_data)?;
+    registry.next_pool_id += 1;
+    registry.total_pools += 1;
+
+    Ok(())
+}
+
+// `pool_id` is the registered pool's ID, whose index account follows the
+// token programs; legacy single pools have none and start as standard pools
+fn process_init_pool(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    pool_id: Option<u64>,
+    amount_a: u64,
+    amount_b: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let pool_info = next_account_info(account_info_iter)?;
+    let token_a_info = next_account_info(account_info_iter)?;
+    let token_b_info = next_account_info(account_info_iter)?;
+    let vault_a_info = next_account_info(account_info_iter)?;
+    let vault_b_info = next_account_info(account_info_iter)?;
+    let lp_mint_info = next_account_info(account_info_iter)?;
+    let user_info = next_account_info(account_info_iter)?;
+    let user_token_a_info = next_account_info(account_info_iter)?;
+    let user_token_b_info = next_account_info(account_info_iter)?;
+    let user_lp_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
+    let token_program_a_info = next_account_info(account_info_iter)?;
+    let token_program_b_info = next_account_info(account_info_iter)?;
+
+    // Derive pool address and bump
+    let (pool_pubkey, bump) = Pubkey::find_program_address(
+        &[b"pool", token_a_info.key.as_ref(), token_b_info.key.as_ref()],
+        program_id,
+    );
+
+    if pool_pubkey != *pool_info.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    let (pool_type, fee_rate) = match pool_id {
+        Some(pool_id) => {
+            let pool_index_info = next_account_info(account_info_iter)?;
+            check_pool_index(program_id, pool_id, pool_index_info)?;
+            let registered = PoolInfo::unpack(&pool_index_info.data.borrow())?;
+            if registered.pool_address != pool_pubkey {
+                return Err(ProgramError::InvalidArgument);
+            }
+            if !registered.is_active {
+                return Err(SwapError::PoolInactive.into());
+            }
+            (registered.pool_type, registered.fee_rate)
+        }
+        None => {
+            // A pool CreatePool registered starts as registered, through InitPool
+            let pair_index_info = next_account_info(account_info_iter)?;
+            let (pair_index_pubkey, _) = Pubkey::find_program_address(
+                &[PAIR_INDEX_SEED, token_a_info.key.as_ref(), token_b_info.key.as_ref()],
+                program_id,
+            );
+            if pair_index_pubkey != *pair_index_info.key {
+                return Err(ProgramError::InvalidSeeds);
+            }
+            if pair_index_info.owner == program_id {
+                return Err(ProgramError::AccountAlreadyInitialized);
+            }
+            (PoolType::Standard, PoolType::Standard.default_fee_rate())
+        }
+    };
+
+    // The pool records which token program owns each mint; the LP mint may
+    // use either program too
+    check_token_program(token_program_a_info, token_a_info)?;
+    check_token_program(token_program_b_info, token_b_info)?;
+    check_token_program(token_program_info, lp_mint_info)?;
+
+    // Vaults and the LP mint are created by CreatePool at their PDAs
+    for (vault_info, mint_info, vault_program_info) in [
+        (vault_a_info, token_a_info, token_program_a_info),
+        (vault_b_info, token_b_info, token_program_b_info),
+    ] {
+        check_vault(program_id, pool_info.key, mint_info.key, vault_program_info.key, vault_info)?;
+    }
+    let (lp_mint_pubkey, _) = Pubkey::find_program_address(&[LP_MINT_SEED, pool_info.key.as_ref()], program_id);
+    if lp_mint_pubkey != *lp_mint_info.key {
+        return Err(SwapError::InvalidLpMint.into());
+    }
+    check_lp_mint(pool_info.key, token_program_info, lp_mint_info)?;
+
+    // Create pool account
+    let rent = Rent::from_account_info(rent_info)?;
+    let space = Pool::LEN;
+    let lamports = rent.minimum_balance(space);
+
+    let pool_signer_seeds: &[&[_]] = &[
+        b"pool",
+        token_a_info.key.as_ref(),
+        token_b_info.key.as_ref(),
+        &[bump],
+    ];
+
+    invoke_signed(
+        &system_instruction::create_account(
+            user_info.key,
+            pool_info.key,
+            lamports,
+            space as u64,
+            program_id,
+        ),
+        &[
+            user_info.clone(),
+            pool_info.clone(),
+            system_program_info.clone(),
+        ],
+        &[pool_signer_seeds],
+    )?;
+
+    // Transfer tokens to vaults
+    transfer_checked(
+        token_program_a_info,
+        user_token_a_info,
+        token_a_info,
+        vault_a_info,
+        user_info,
+        amount_a,
+        &[],
+    )?;
+    transfer_checked(
+        token_program_b_info,
+        user_token_b_info,
+        token_b_info,
+        vault_b_info,
+        user_info,
+        amount_b,
+        &[],
+    )?;
+
+    // Reserves start at what the vaults actually received after transfer fees
+    let received_a = received_amount(token_a_info, amount_a)?;
+    let received_b = received_amount(token_b_info, amount_b)?;
+
+    // Calculate liquidity
+    let liquidity: u64 = (received_a as u128)
+        .checked_mul(received_b as u128)
+        .unwrap()
+        .integer_sqrt() as u64;
+
+    // Mint LP tokens
+    invoke_signed(
+        &create_mint_to_instruction(
+            token_program_info.key,
+            lp_mint_info.key,
+            user_lp_info.key,
+            pool_info.key,
+            liquidity,
+        ),
+        &[
+            lp_mint_info.clone(),
+            user_lp_info.clone(),
+            pool_info.clone(),
+            token_program_info.clone(),
+        ],
+        &[pool_signer_seeds],
+    )?;
+
+    // Initialize pool state; the price accumulators start with the reserves
+    let now = solana_program::clock::Clock::get()?.unix_timestamp;
+    let pool = Pool {
+        header: Pool::new_header(),
+        _padding: [0; 1],
+        pool_id: pool_id.unwrap_or(0),
+        pool_type,
+        token_a: *token_a_info.key,
+        token_b: *token_b_info.key,
+        token_program_a: *token_program_a_info.key,
+        token_program_b: *token_program_b_info.key,
+        bump,
+        fee_rate,
+        reserve_a: received_a,
+        reserve_b: received_b,
+        total_lp_supply: liquidity,
+        created_at: now,
+        is_active: true,
+        max_price_move_bps: 0,
+        dynamic_fee: false,
+        _padding2: [0; 1],
+        min_fee_rate: 0,
+        max_fee_rate: 0,
+        price_ref_slot: 0,
+        price_ref_reserve_a: 0,
+        price_ref_reserve_b: 0,
+        volatility_bps: 0,
+        volatility_updated_at: 0,
+        price_a_cumulative: [0; 16],
+        price_b_cumulative: [0; 16],
+        price_cumulative_updated_at: now,
+    };
+
+    Pool::pack(pool, &mut pool_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+fn process_add_liquidity(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    _pool_id: u64,
+    amount_a: u64,
+    amount_b: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let pool_info = next_account_info(account_info_iter)?;
+    let token_a_info = next_account_info(account_info_iter)?;
+    let token_b_info = next_account_info(account_info_iter)?;
+    let vault_a_info = next_account_info(account_info_iter)?;
+    let vault_b_info = next_account_info(account_info_iter)?;
+    let lp_mint_info = next_account_info(account_info_iter)?;
+    let user_token_a_info = next_account_info(account_info_iter)?;
+    let user_token_b_info = next_account_info(account_info_iter)?;
+    let user_lp_info = next_account_info(account_info_iter)?;
+    let user_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+    let token_program_a_info = next_account_info(account_info_iter)?;
+    let token_program_b_info = next_account_info(account_info_iter)?;
+    let ata_accounts = next_ata_accounts(account_info_iter)?;
+
+    let pool = load_pool(pool_info)?;
+
+    // Verify pool seeds
+    let (pool_pubkey, _) = Pubkey::find_program_address(
+        &[b"pool", token_a_info.key.as_ref(), token_b_info.key.as_ref()],
+        program_id,
+    );
+
+    if pool_pubkey != *pool_info.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    check_pool_token_programs(&pool, token_program_a_info, token_program_b_info)?;
+    check_vaults(program_id, pool_info.key, &pool, vault_a_info, vault_b_info)?;
+    check_lp_mint(pool_info.key, token_program_info, lp_mint_info)?;
+    if let Some(ata_accounts) = ata_accounts {
+        create_user_account(ata_accounts, user_info, user_lp_info, lp_mint_info, token_program_info)?;
+    }
+
+    // Calculate final amounts maintaining ratio
+    let quote = quote::quote_add_liquidity(&pool, amount_a, amount_b)?;
+    let final_amount_a = quote.amount_a;
+    let final_amount_b = quote.amount_b;
+
+    // LP tokens are minted for what the vaults receive after transfer fees
+    let received_a = received_amount(token_a_info, final_amount_a)?;
+    let received_b = received_amount(token_b_info, final_amount_b)?;
+    let liquidity = quote::lp_for_deposit(&pool, received_a, received_b)?;
+
+    // Transfer tokens to vaults
+    transfer_checked(
+        token_program_a_info,
+        user_token_a_info,
+        token_a_info,
+        vault_a_info,
+        user_info,
+        final_amount_a,
+        &[],
+    )?;
+    transfer_checked(
+        token_program_b_info,
+        user_token_b_info,
+        token_b_info,
+        vault_b_info,
+        user_info,
+        final_amount_b,
+        &[],
+    )?;
+
+    // Mint LP tokens
+    let pool_signer_seeds: &[&[_]] = &[
+        b"pool",
+        pool.token_a.as_ref(),
+        pool.token_b.as_ref(),
+        &[pool.bump],
+    ];
+
+    invoke_signed(
+        &create_mint_to_instruction(
+            token_program_info.key,
+            lp_mint_info.key,
+            user_lp_info.key,
+            pool_info.key,
+            liquidity,
+        ),
+        &[
+            lp_mint_info.clone(),
+            user_lp_info.clone(),
+            pool_info.clone(),
+            token_program_info.clone(),
+        ],
+        &[pool_signer_seeds],
+    )?;
+
+    // Update pool state
+    let mut pool_data = pool_info.try_borrow_mut_data()?;
+    let pool = Pool::load_mut(&mut pool_data)?;
+    accrue_prices(pool, Clock::get()?.unix_timestamp);
+    pool.reserve_a = pool.reserve_a.checked_add(received_a).ok_or(SwapError::MathOverflow)?;
+    pool.reserve_b = pool.reserve_b.checked_add(received_b).ok_or(SwapError::MathOverflow)?;
+    pool.total_lp_supply = pool.total_lp_supply.checked_add(liquidity).ok_or(SwapError::MathOverflow)?;
+
+    Ok(())
+}
+
+fn process_remove_liquidity(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    _pool_id: u64,
+    lp_amount: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let pool_info = next_account_info(account_info_iter)?;
+    let token_a_info = next_account_info(account_info_iter)?;
+    let token_b_info = next_account_info(account_info_iter)?;
+    let vault_a_info = next_account_info(account_info_iter)?;
+    let vault_b_info = next_account_info(account_info_iter)?;
+    let lp_mint_info = next_account_info(account_info_iter)?;
+    let user_lp_info = next_account_info(account_info_iter)?;
+    let user_token_a_info = next_account_info(account_info_iter)?;
+    let user_token_b_info = next_account_info(account_info_iter)?;
+    let user_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+    let token_program_a_info = next_account_info(account_info_iter)?;
+    let token_program_b_info = next_account_info(account_info_iter)?;
+    let ata_accounts = next_ata_accounts(account_info_iter)?;
+
+    let pool = load_pool_for_exit(pool_info)?;
+
+    // Verify pool seeds
+    let (pool_pubkey, _) = Pubkey::find_program_address(
+        &[b"pool", token_a_info.key.as_ref(), token_b_info.key.as_ref()],
+        program_id,
+    );
+
+    if pool_pubkey != *pool_info.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    check_pool_token_programs(&pool, token_program_a_info, token_program_b_info)?;
+    check_vaults(program_id, pool_info.key, &pool, vault_a_info, vault_b_info)?;
+    check_lp_mint(pool_info.key, token_program_info, lp_mint_info)?;
+    if let Some(ata_accounts) = ata_accounts {
+        create_user_account(ata_accounts, user_info, user_token_a_info, token_a_info, token_program_a_info)?;
+        create_user_account(ata_accounts, user_info, user_token_b_info, token_b_info, token_program_b_info)?;
+    }
+
+    // Calculate amounts to withdraw
+    let quote = quote::quote_remove_liquidity(&pool, lp_amount)?;
+    let amount_a = quote.amount_a;
+    let amount_b = quote.amount_b;
+
+    // Burn LP tokens
+    invoke(
+        &create_burn_instruction(
+            token_program_info.key,
+            user_lp_info.key,
+            lp_mint_info.key,
+            user_info.key,
+            lp_amount,
+        ),
+        &[
+            user_lp_info.clone(),
+            lp_mint_info.clone(),
+            user_info.clone(),
+            token_program_info.clone(),
+        ],
+    )?;
+
+    // Transfer tokens from vaults to user
+    let pool_signer_seeds: &[&[_]] = &[
+        b"pool",
+        pool.token_a.as_ref(),
+        pool.token_b.as_ref(),
+        &[pool.bump],
+    ];
+
+    transfer_checked(
+        token_program_a_info,
+        vault_a_info,
+        token_a_info,
+        user_token_a_info,
+        pool_info,
+        amount_a,
+        &[pool_signer_seeds],
+    )?;
+    transfer_checked(
+        token_program_b_info,
+        vault_b_info,
+        token_b_info,
+        user_token_b_info,
+        pool_info,
+        amount_b,
+        &[pool_signer_seeds],
+    )?;
+
+    // Update pool state
+    let mut pool_data = pool_info.try_borrow_mut_data()?;
+    let pool = Pool::load_mut(&mut pool_data)?;
+    accrue_prices(pool, Clock::get()?.unix_timestamp);
+    pool.reserve_a = quote.reserve_a_after;
+    pool.reserve_b = quote.reserve_b_after;
+    pool.total_lp_supply = quote.lp_supply_after;
+
+    Ok(())
+}
+
+// Swaps `amount_in` from the user's input account. In-program callers can
+// demand `min_amount_out` and sign for a PDA user with `user_signer_seeds`.
+fn process_swap(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    _pool_id: u64,
+    amount_in: u64,
+    direction_a_to_b: bool,
+    min_amount_out: u64,
+    user_signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let pool_info = next_account_info(account_info_iter)?;
+    let token_a_info = next_account_info(account_info_iter)?;
+    let token_b_info = next_account_info(account_info_iter)?;
+    let vault_a_info = next_account_info(account_info_iter)?;
+    let vault_b_info = next_account_info(account_info_iter)?;
+    let user_in_info = next_account_info(account_info_iter)?;
+    let user_out_info = next_account_info(account_info_iter)?;
+    let user_info = next_account_info(account_info_iter)?;
+    let token_program_a_info = next_account_info(account_info_iter)?;
+    let token_program_b_info = next_account_info(account_info_iter)?;
+    let ata_accounts = next_ata_accounts(account_info_iter)?;
+
+    let pool = load_pool(pool_info)?;
+
+    // Verify pool seeds
+    let (pool_pubkey, _) = Pubkey::find_program_address(
+        &[b"pool", token_a_info.key.as_ref(), token_b_info.key.as_ref()],
+        program_id,
+    );
+
+    if pool_pubkey != *pool_info.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    check_pool_token_programs(&pool, token_program_a_info, token_program_b_info)?;
+    check_vaults(program_id, pool_info.key, &pool, vault_a_info, vault_b_info)?;
+
+    let (mint_in_info, vault_in_info, program_in_info, mint_out_info, vault_out_info, program_out_info) =
+        if direction_a_to_b {
+            (token_a_info, vault_a_info, token_program_a_info, token_b_info, vault_b_info, token_program_b_info)
+        } else {
+            (token_b_info, vault_b_info, token_program_b_info, token_a_info, vault_a_info, token_program_a_info)
+        };
+    if let Some(ata_accounts) = ata_accounts {
+        create_user_account(ata_accounts, user_info, user_out_info, mint_out_info, program_out_info)?;
+    }
+
+    // Price the swap on what the vault receives after the input transfer fee.
+    // The vault always sends the full `amount_out`; any output fee is withheld
+    // from the user's side.
+    let received_in = received_amount(mint_in_info, amount_in)?;
+    let swap = price_swap(&pool, received_in, direction_a_to_b)?;
+    let amount_out = swap.quote.amount_out;
+    if amount_out < min_amount_out {
+        return Err(SwapError::SlippageExceeded.into());
+    }
+
+    // Transfer input tokens to vault
+    transfer_checked(
+        program_in_info,
+        user_in_info,
+        mint_in_info,
+        vault_in_info,
+        user_info,
+        amount_in,
+        user_signer_seeds,
+    )?;
+
+    // Transfer output tokens from vault to user
+    let pool_signer_seeds: &[&[_]] = &[
+        b"pool",
+        pool.token_a.as_ref(),
+        pool.token_b.as_ref(),
+        &[pool.bump],
+    ];
+
+    transfer_checked(
+        program_out_info,
+        vault_out_info,
+        mint_out_info,
+        user_out_info,
+        pool_info,
+        amount_out,
+        &[pool_signer_seeds],
+    )?;
+
+    record_swap(pool_info, &pool, &swap)
+}
+
+// A swap priced the way the program charges it at the current clock
+struct PricedSwap {
+    quote: quote::SwapQuote,
+    now: i64,
+    price_band: Option<(u64, (u64, u64))>, // Slot and its reference reserves
+}
+
+fn price_swap(pool: &Pool, received_in: u64, direction_a_to_b: bool) -> Result<PricedSwap, ProgramError> {
+    // Dynamic-fee pools charge the fee their volatility sets before this swap
+    let now = Clock::get()?.unix_timestamp;
+    let pricing = if pool.dynamic_fee { quote::with_current_fee(pool, now) } else { *pool };
+    let quote = quote::quote_swap_exact_in(&pricing, received_in, direction_a_to_b)?;
+
+    // Trades in one slot together may only move the price so far; pools
+    // without a band don't track it
+    let price_band = if pool.max_price_move_bps > 0 {
+        let slot = Clock::get()?.slot;
+        let reference = quote::price_reference(pool, slot);
+        quote::check_price_band(pool, reference, quote.reserve_a_after, quote.reserve_b_after)?;
+        Some((slot, reference))
+    } else {
+        None
+    };
+
+    Ok(PricedSwap { quote, now, price_band })
+}
+
+// Updates the pool in place after a priced swap's transfers: reserves, the
+// price and volatility accumulators and the slot's price reference
+fn record_swap(pool_info: &AccountInfo, pool: &Pool, swap: &PricedSwap) -> ProgramResult {
+    let quote = &swap.quote;
+    let mut pool_data = pool_info.try_borrow_mut_data()?;
+    let state = Pool::load_mut(&mut pool_data)?;
+    let now = swap.now;
+    accrue_prices(state, now);
+    state.reserve_a = quote.reserve_a_after;
+    state.reserve_b = quote.reserve_b_after;
+    if pool.dynamic_fee {
+        let moved =
+            quote::price_move_bps((pool.reserve_a, pool.reserve_b), (quote.reserve_a_after, quote.reserve_b_after));
+        state.volatility_bps = quote::decayed_volatility(pool, now).saturating_add(moved);
+        state.volatility_updated_at = now;
+    }
+    if let Some((slot, (reference_a, reference_b))) = swap.price_band {
+        state.price_ref_slot = slot;
+        state.price_ref_reserve_a = reference_a;
+        state.price_ref_reserve_b = reference_b;
+    }
+
+    Ok(())
+}
+
+// Carries the TWAP accumulators forward to `now` at the current reserves.
+// Every reserve update calls this first, so a price only counts for the time
+// it was the pool's price.
+fn accrue_prices(state: &mut Pool, now: i64) {
+    let (price_a_cumulative, price_b_cumulative) = quote::price_cumulatives(state, now);
+    state.price_a_cumulative = price_a_cumulative.to_le_bytes();
+    state.price_b_cumulative = price_b_cumulative.to_le_bytes();
+    state.price_cumulative_updated_at = now;
+}
+
+fn process_initialize_registry(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    _instruction_data: &[u8],
+    bump: u8,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let registry_info = next_account_info(account_info_iter)?;
+    let user_info = next_account_info(account_info_iter)?;
+    let _token_program_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
+
+    // Derive registry address and bump
+    let (registry_pubkey, expected_bump) = Pubkey::find_program_address(
+        &[POOL_REGISTRY_SEEDS, program_id.as_ref()],
+        program_id,
+    );
+
+    if registry_pubkey != *registry_info.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    if bump != expected_bump {
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    // Create registry account
+    let rent = Rent::from_account_info(rent_info)?;
+    let space = PoolRegistry::LEN;
+    let lamports = rent.minimum_balance(space);
+
+    let registry_signer_seeds: &[&[_]] = &[
+        POOL_REGISTRY_SEEDS,
+        program_id.as_ref(),
+        &[bump],
+    ];
+
+    invoke_signed(
+        &system_instruction::create_account(
+            user_info.key,
+            registry_info.key,
+            lamports,
+            space as u64,
+            program_id,
+        ),
+        &[
+            user_info.clone(),
+            registry_info.clone(),
+            system_program_info.clone(),
+        ],
+        &[registry_signer_seeds],
+    )?;
+
+    // Initialize registry state
+    let registry = PoolRegistry {
+        header: PoolRegistry::new_header(),
+        _padding: [0; 2],
+        next_pool_id: 1, // Start from 1 for new pool IDs
+        total_pools: 0,
+        total_tvl: 0,
+    };
+
+    PoolRegistry::pack(registry, &mut registry_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+// Returns the `PoolInfo`s of the index accounts passed after the fixed
+// accounts, so clients page through the registry by choosing pool IDs
+fn process_list_pools(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let registry_info = next_account_info(account_info_iter)?;
+    let _user_info = next_account_info(account_info_iter)?;
+    let _token_program_info = next_account_info(account_info_iter)?;
+
+    check_registry(program_id, registry_info)?;
+
+    let pool_index_infos = account_info_iter.as_slice();
+    if pool_index_infos.len() > MAX_LIST_POOLS {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let mut pools = Vec::with_capacity(pool_index_infos.len());
+    for pool_index_info in pool_index_infos {
+        let pool_info = PoolInfo::unpack(&pool_index_info.data.borrow())?;
+        check_pool_index(program_id, pool_info.pool_id, pool_index_info)?;
+        pools.push(pool_info);
+    }
+
+    set_return_data(&pools.try_to_vec().unwrap());
+
+    Ok(())
+}
+
+fn process_get_pool_info(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    pool_id: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let registry_info = next_account_info(account_info_iter)?;
+    let _user_info = next_account_info(account_info_iter)?;
+    let _token_program_info = next_account_info(account_info_iter)?;
+    let pool_index_info = next_account_info(account_info_iter)?;
+
+    check_registry(program_id, registry_info)?;
+    check_pool_index(program_id, pool_id, pool_index_info)?;
+
+    // Return the serialized pool info
+    let pool_info = PoolInfo::unpack(&pool_index_info.data.borrow())?;
+    set_return_data(&pool_info.try_to_vec().unwrap());
+
+    Ok(())
+}
+
+// Admin only; the pool account and then the program config follow the pool
+// index account. A pool InitPool has not created yet only has its index entry.
+fn process_deactivate_pool(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    pool_id: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let registry_info = next_account_info(account_info_iter)?;
+    let admin_info = next_account_info(account_info_iter)?;
+    let _token_program_info = next_account_info(account_info_iter)?;
+    let pool_index_info = next_account_info(account_info_iter)?;
+    let pool_account_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+
+    check_registry(program_id, registry_info)?;
+    check_admin(program_id, config_info, admin_info, account_info_iter.as_slice())?;
+    check_pool_index(program_id, pool_id, pool_index_info)?;
+
+    let mut pool_info = PoolInfo::unpack(&pool_index_info.data.borrow())?;
+    if pool_info.pool_address != *pool_account_info.key {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    // Deactivate pool; handlers check the pool account's own flag
+    pool_info.is_active = false;
+    if pool_account_info.owner == program_id {
+        load_pool_for_exit(pool_account_info)?;
+        Pool::load_mut(&mut pool_account_info.try_borrow_mut_data()?)?.is_active = false;
+    }
+
+    PoolInfo::pack(pool_info, &mut pool_index_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+// Creates the program config. Only the program's upgrade authority can, so
+// whoever deploys the program picks the admin and guardian.
+fn process_initialize_config(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    admin: Pubkey,
+    guardian: Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let config_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+    let program_data_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
+
+    if !authority_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if upgrade_authority(program_id, program_data_info)? != Some(*authority_info.key) {
+        return Err(SwapError::Unauthorized.into());
+    }
+
+    let (config_pubkey, bump) = Pubkey::find_program_address(&[CONFIG_SEED], program_id);
+    if config_pubkey != *config_info.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if config_info.lamports() > 0 {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+
+    let rent = Rent::from_account_info(rent_info)?;
+    create_pda_account(
+        program_id,
+        authority_info,
+        config_info,
+        system_program_info,
+        &rent,
+        ProgramConfig::LEN,
+        &[CONFIG_SEED, &[bump]],
+    )?;
+
+    let config = ProgramConfig {
+        header: ProgramConfig::new_header(),
+        bump,
+        paused: 0,
+        withdrawals_open: false,
+        _padding: [0; 3],
+        admin,
+        guardian,
+    };
+    ProgramConfig::pack(config, &mut config_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+// Rewrites the name, symbol or URI in a Token-2022 LP mint's metadata. The
+// pool signs as update authority; the payer covers any growth of the mint.
+fn process_update_lp_metadata(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    field: LpMetadataField,
+    value: String,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let config_info = next_account_info(account_info_iter)?;
+    let admin_info = next_account_info(account_info_iter)?;
+    let payer_info = next_account_info(account_info_iter)?;
+    let pool_info = next_account_info(account_info_iter)?;
+    let token_a_info = next_account_info(account_info_iter)?;
+    let token_b_info = next_account_info(account_info_iter)?;
+    let lp_mint_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
+
+    check_admin(program_id, config_info, admin_info, account_info_iter.as_slice())?;
+
+    let (pool_pubkey, bump) = Pubkey::find_program_address(
+        &[b"pool", token_a_info.key.as_ref(), token_b_info.key.as_ref()],
+        program_id,
+    );
+    if pool_pubkey != *pool_info.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    let (lp_mint_pubkey, _) = Pubkey::find_program_address(&[LP_MINT_SEED, pool_info.key.as_ref()], program_id);
+    if lp_mint_pubkey != *lp_mint_info.key {
+        return Err(SwapError::InvalidLpMint.into());
+    }
+    if *token_program_info.key != spl_token_2022::id() || lp_mint_info.owner != token_program_info.key {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    // Size of the mint once the token program has rewritten the metadata
+    let new_len = {
+        let data = lp_mint_info.try_borrow_data()?;
+        let mint = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+        let mut metadata = mint.get_variable_len_extension::<TokenMetadata>()?;
+        let old_size = metadata.tlv_size_of()?;
+        metadata.update(field.into(), value.clone());
+        (data.len() + metadata.tlv_size_of()?).saturating_sub(old_size)
+    };
+    let rent = Rent::from_account_info(rent_info)?;
+    let top_up = rent.minimum_balance(new_len).saturating_sub(lp_mint_info.lamports());
+    if top_up > 0 {
+        invoke(
+            &system_instruction::transfer(payer_info.key, lp_mint_info.key, top_up),
+            &[payer_info.clone(), lp_mint_info.clone(), system_program_info.clone()],
+        )?;
+    }
+
+    invoke_signed(
+        &spl_token_metadata_interface::instruction::update_field(
+            token_program_info.key,
+            lp_mint_info.key,
+            pool_info.key,
+            field.into(),
+            value,
+        ),
+        &[lp_mint_info.clone(), pool_info.clone(), token_program_info.clone()],
+        &[&[b"pool", token_a_info.key.as_ref(), token_b_info.key.as_ref(), &[bump]]],
+    )
+}
+
+// Sets which actions are paused; 0 unpauses everything
+fn process_set_pause(program_id: &Pubkey, accounts: &[AccountInfo], paused: u8) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let config_info = next_account_info(account_info_iter)?;
+    let guardian_info = next_account_info(account_info_iter)?;
+
+    check_config(program_id, config_info)?;
+    if !guardian_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if paused & !PAUSE_ALL != 0 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    let mut config_data = config_info.try_borrow_mut_data()?;
+    let config = ProgramConfig::load_mut(&mut config_data)?;
+    if config.guardian != *guardian_info.key {
+        return Err(SwapError::Unauthorized.into());
+    }
+    config.paused = paused;
+
+    Ok(())
+}
+
+fn check_registry(program_id: &Pubkey, registry_info: &AccountInfo) -> ProgramResult {
+    let (registry_pubkey, _) = Pubkey::find_program_address(
+        &[POOL_REGISTRY_SEEDS, program_id.as_ref()],
+        program_id,
+    );
+    if registry_pubkey != *registry_info.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    Ok(())
+}
+
+// Unregistered IDs have no index account, which fails the owner check
+fn check_pool_index(program_id: &Pubkey, pool_id: u64, pool_index_info: &AccountInfo) -> ProgramResult {
+    let (pool_index_pubkey, _) = Pubkey::find_program_address(
+        &[POOL_INDEX_SEED, &pool_id.to_le_bytes()],
+        program_id,
+    );
+    if pool_index_pubkey != *pool_index_info.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if pool_index_info.owner != program_id {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(())
+}
+
+// Records a change that ExecuteChange applies once `CHANGE_DELAY_SECONDS`
+// have passed. The payer funds the pending change account.
+fn process_propose_change(program_id: &Pubkey, accounts: &[AccountInfo], change: GovernanceChange) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let config_info = next_account_info(account_info_iter)?;
+    let admin_info = next_account_info(account_info_iter)?;
+    let payer_info = next_account_info(account_info_iter)?;
+    let pending_info = next_account_info(account_info_iter)?;
+    let target_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
+
+    check_admin(program_id, config_info, admin_info, account_info_iter.as_slice())?;
+    match change {
+        GovernanceChange::FeeRate { fee_rate } => check_fee_change(program_id, target_info, fee_rate, fee_rate)?,
+        GovernanceChange::DynamicFee { min_fee_rate, max_fee_rate } => {
+            check_fee_change(program_id, target_info, min_fee_rate, max_fee_rate)?
         }
-        TestProjectInstruction::UpdatePoolFee { pool_id, new_fee_rate } => {
-            process_update_pool_fee(program_id, accounts, pool_id, new_fee_rate)
+        GovernanceChange::Guardian { .. } => {
+            if target_info.key != config_info.key {
+                return Err(ProgramError::InvalidArgument);
+            }
         }
-        TestProjectInstruction::DeactivatePool { pool_id } => {
-            process_deactivate_pool(program_id, accounts, pool_id)
+        GovernanceChange::PriceBand { .. } => check_pool_target(program_id, target_info)?,
+    }
+
+    let (pending_pubkey, bump) =
+        Pubkey::find_program_address(&[PENDING_CHANGE_SEED, target_info.key.as_ref()], program_id);
+    if pending_pubkey != *pending_info.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if pending_info.lamports() > 0 {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+
+    let rent = Rent::from_account_info(rent_info)?;
+    create_pda_account(
+        program_id,
+        payer_info,
+        pending_info,
+        system_program_info,
+        &rent,
+        PendingChange::LEN,
+        &[PENDING_CHANGE_SEED, target_info.key.as_ref(), &[bump]],
+    )?;
+
+    let pending = PendingChange::new(bump, *target_info.key, change, Clock::get()?.unix_timestamp);
+    PendingChange::pack(pending, &mut pending_info.data.borrow_mut())?;
+
+    emit_event(&GovernanceEvent::ChangeProposed { target: pending.target, change, eta: pending.eta });
+    Ok(())
+}
+
+// Fee changes target a pool and keep fees in [min, max] below 100%; a fixed
+// fee is a range of one
+fn check_fee_change(program_id: &Pubkey, pool_info: &AccountInfo, min_fee_rate: u16, max_fee_rate: u16) -> ProgramResult {
+    if min_fee_rate > max_fee_rate || max_fee_rate as u64 >= quote::FEE_DENOMINATOR {
+        return Err(SwapError::InvalidFeeRate.into());
+    }
+    check_pool_target(program_id, pool_info)
+}
+
+fn check_pool_target(program_id: &Pubkey, pool_info: &AccountInfo) -> ProgramResult {
+    if pool_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    load_pool(pool_info)?;
+    Ok(())
+}
+
+// Applies a pending change whose delay has passed and closes it, returning
+// its rent to the receiver. Fee changes to registered pools also update the
+// pool's index entry, passed after the target.
+fn process_execute_change(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let config_info = next_account_info(account_info_iter)?;
+    let admin_info = next_account_info(account_info_iter)?;
+    let receiver_info = next_account_info(account_info_iter)?;
+    let pending_info = next_account_info(account_info_iter)?;
+    let target_info = next_account_info(account_info_iter)?;
+
+    let pending = load_pending_change(program_id, pending_info)?;
+    if pending.target != *target_info.key {
+        return Err(ProgramError::InvalidArgument);
+    }
+    let change = pending.change();
+    let pool_index_info = match change {
+        GovernanceChange::FeeRate { .. } if load_pool(target_info)?.pool_id != 0 => {
+            Some(next_account_info(account_info_iter)?)
         }
-        
-        // Legacy instructions (backward compatibility)
-        TestProjectInstruction::LegacyInitPool { amount_a, amount_b } => {
-            process_legacy_init_pool(program_id, accounts, amount_a, amount_b)
+        _ => None,
+    };
+
+    check_admin(program_id, config_info, admin_info, account_info_iter.as_slice())?;
+    if Clock::get()?.unix_timestamp < pending.eta {
+        return Err(SwapError::TimelockNotElapsed.into());
+    }
+
+    match change {
+        GovernanceChange::FeeRate { fee_rate } => {
+            let pool_id = {
+                let mut pool_data = target_info.try_borrow_mut_data()?;
+                let pool = Pool::load_mut(&mut pool_data)?;
+                pool.fee_rate = fee_rate;
+                pool.dynamic_fee = false;
+                pool.pool_id
+            };
+            if let Some(pool_index_info) = pool_index_info {
+                check_pool_index(program_id, pool_id, pool_index_info)?;
+                let mut pool_info = PoolInfo::unpack(&pool_index_info.data.borrow())?;
+                pool_info.fee_rate = fee_rate;
+                PoolInfo::pack(pool_info, &mut pool_index_info.data.borrow_mut())?;
+            }
         }
-        TestProjectInstruction::LegacyAddLiquidity { amount_a, amount_b } => {
-            process_legacy_add_liquidity(program_id, accounts, amount_a, amount_b)
+        GovernanceChange::DynamicFee { min_fee_rate, max_fee_rate } => {
+            let mut pool_data = target_info.try_borrow_mut_data()?;
+            let pool = Pool::load_mut(&mut pool_data)?;
+            pool.dynamic_fee = true;
+            pool.min_fee_rate = min_fee_rate;
+            pool.max_fee_rate = max_fee_rate;
         }
-        TestProjectInstruction::LegacyRemoveLiquidity { lp_amount } => {
-            process_legacy_remove_liquidity(program_id, accounts, lp_amount)
+        GovernanceChange::Guardian { guardian, withdrawals_open } => {
+            let mut config_data = config_info.try_borrow_mut_data()?;
+            let config = ProgramConfig::load_mut(&mut config_data)?;
+            config.guardian = guardian;
+            config.withdrawals_open = withdrawals_open;
         }
-        TestProjectInstruction::LegacySwap { amount_in, direction_a_to_b } => {
-            process_legacy_swap(program_id, accounts, amount_in, direction_a_to_b)
+        GovernanceChange::PriceBand { max_price_move_bps } => {
+            let mut pool_data = target_info.try_borrow_mut_data()?;
+            Pool::load_mut(&mut pool_data)?.max_price_move_bps = max_price_move_bps;
         }
     }
+
+    close_program_account(pending_info, receiver_info)?;
+    emit_event(&GovernanceEvent::ChangeExecuted { target: pending.target, change });
+    Ok(())
 }
 
-fn process_create_pool(
+// Drops a pending change at any point before it is executed
+fn process_cancel_change(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let config_info = next_account_info(account_info_iter)?;
+    let admin_info = next_account_info(account_info_iter)?;
+    let receiver_info = next_account_info(account_info_iter)?;
+    let pending_info = next_account_info(account_info_iter)?;
+
+    check_admin(program_id, config_info, admin_info, account_info_iter.as_slice())?;
+    let pending = load_pending_change(program_id, pending_info)?;
+
+    close_program_account(pending_info, receiver_info)?;
+    emit_event(&GovernanceEvent::ChangeCancelled { target: pending.target, change: pending.change() });
+    Ok(())
+}
+
+// Hands the admin role to another key or a multisig
+fn process_set_admin(program_id: &Pubkey, accounts: &[AccountInfo], admin: Pubkey) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let config_info = next_account_info(account_info_iter)?;
+    let admin_info = next_account_info(account_info_iter)?;
+
+    check_admin(program_id, config_info, admin_info, account_info_iter.as_slice())?;
+
+    let mut config_data = config_info.try_borrow_mut_data()?;
+    ProgramConfig::load_mut(&mut config_data)?.admin = admin;
+
+    Ok(())
+}
+
+// Creates a multisig at [MULTISIG_SEED, create_key]; any key works as the
+// seed and it doesn't sign
+fn process_create_multisig(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
-    token_a: Pubkey,
-    token_b: Pubkey,
-    pool_type: PoolType,
+    threshold: u8,
+    signers: &[Pubkey],
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
-    let registry_info = next_account_info(account_info_iter)?;
-    let user_info = next_account_info(account_info_iter)?;
-    let token_program_info = next_account_info(account_info_iter)?;
+    let multisig_info = next_account_info(account_info_iter)?;
+    let create_key_info = next_account_info(account_info_iter)?;
+    let payer_info = next_account_info(account_info_iter)?;
     let system_program_info = next_account_info(account_info_iter)?;
     let rent_info = next_account_info(account_info_iter)?;
 
-    // Derive pool registry address and bump
-    let (registry_pubkey, registry_bump) = Pubkey::find_program_address(
-        &[POOL_REGISTRY_SEEDS, program_id.as_ref()],
-        program_id,
-    );
-
-    if registry_pubkey != *registry_info.key {
+    let (multisig_pubkey, bump) =
+        Pubkey::find_program_address(&[MULTISIG_SEED, create_key_info.key.as_ref()], program_id);
+    if multisig_pubkey != *multisig_info.key {
         return Err(ProgramError::InvalidSeeds);
     }
+    if multisig_info.lamports() > 0 {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
 
-    // Create new pool ID
-    let new_pool_id = {
-        let mut registry = PoolRegistry::unpack(&registry_info.data.borrow())?;
-        let new_id = registry.next_pool_id;
-        registry.next_pool_id += 1;
-        registry.total_pools += 1;
-        PoolRegistry::pack(registry, &mut registry_info.data.borrow_mut())?;
-        new_id
+    let mut multisig = Multisig {
+        header: Multisig::new_header(),
+        bump,
+        threshold: 0,
+        signer_count: 0,
+        _padding: [0; 3],
+        create_key: *create_key_info.key,
+        signers: [Pubkey::default(); MAX_MULTISIG_SIGNERS],
     };
+    multisig.set_members(signers, threshold)?;
 
-    // Derive pool address and bump
-    let (pool_pubkey, bump) = Pubkey::find_program_address(
-        &[b"pool", token_a.as_ref(), token_b.as_ref()],
-        program_id,
-    );
-
-    // Create pool account
     let rent = Rent::from_account_info(rent_info)?;
-    let space = Pool::LEN;
-    let lamports = rent.minimum_balance(space);
+    create_pda_account(
+        program_id,
+        payer_info,
+        multisig_info,
+        system_program_info,
+        &rent,
+        Multisig::LEN,
+        &[MULTISIG_SEED, create_key_info.key.as_ref(), &[bump]],
+    )?;
+    Multisig::pack(multisig, &mut multisig_info.data.borrow_mut())?;
 
-    let pool_signer_seeds: &[&[_]] = &[
-        b"pool",
-        token_a.as_ref(),
-        token_b.as_ref(),
-        &[bump],
-    ];
+    Ok(())
+}
 
-    invoke_signed(
-        &system_instruction::create_account(
-            user_info.key,
-            &pool_pubkey,
-            lamports,
-            space as u64,
-            program_id,
-        ),
-        &[
-            user_info.clone(),
-            system_program_info.clone(),
-        ],
-        &[pool_signer_seeds],
-    )?;
+// Applies `update` to a multisig's members and threshold, approved by its
+// current members as the trailing signers
+fn process_update_multisig(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    update: impl FnOnce(&mut Vec<Pubkey>, u8) -> Result<u8, ProgramError>,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let multisig_info = next_account_info(account_info_iter)?;
 
-    // Initialize pool state
-    let pool = Pool {
-        pool_id: new_pool_id,
-        pool_type: pool_type.clone(),
-        token_a,
-        token_b,
-        bump,
-        fee_rate: pool_type.default_fee_rate(),
-        reserve_a: 0,
-        reserve_b: 0,
-        total_lp_supply: 0,
-        created_at: solana_program::clock::Clock::get()?.unix_timestamp,
-        is_active: true,
-    };
+    let multisig = load_multisig(program_id, multisig_info)?;
+    multisig.check_signers(account_info_iter.as_slice())?;
 
-    // We need to get the pool account info to pack the data
-    // For now, let's return success as the pool creation is handled differently
-    // The actual pool initialization will be done in a separate InitPool instruction
+    let mut members = multisig.members().to_vec();
+    let threshold = update(&mut members, multisig.threshold)?;
+    let mut multisig_data = multisig_info.try_borrow_mut_data()?;
+    Multisig::load_mut(&mut multisig_data)?.set_members(&members, threshold)
+}
 
-    Ok(())
+fn load_multisig(program_id: &Pubkey, multisig_info: &AccountInfo) -> Result<Multisig, ProgramError> {
+    if multisig_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Ok(*Multisig::load(&multisig_info.try_borrow_data()?)?)
 }
 
-fn process_init_pool(
+// Creates the creation policy on first use and replaces its fee and rules.
+// The payer funds the account; the mint list is kept.
+fn process_set_creation_policy(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
-    pool_id: u64,
-    amount_a: u64,
-    amount_b: u64,
+    fee_mint: Pubkey,
+    fee_amount: u64,
+    treasury: Pubkey,
+    rules: CreationRules,
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
-    let pool_info = next_account_info(account_info_iter)?;
-    let token_a_info = next_account_info(account_info_iter)?;
-    let token_b_info = next_account_info(account_info_iter)?;
-    let vault_a_info = next_account_info(account_info_iter)?;
-    let vault_b_info = next_account_info(account_info_iter)?;
-    let lp_mint_info = next_account_info(account_info_iter)?;
-    let user_info = next_account_info(account_info_iter)?;
-    let user_token_a_info = next_account_info(account_info_iter)?;
-    let user_token_b_info = next_account_info(account_info_iter)?;
-    let user_lp_info = next_account_info(account_info_iter)?;
-    let token_program_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+    let admin_info = next_account_info(account_info_iter)?;
+    let payer_info = next_account_info(account_info_iter)?;
+    let policy_info = next_account_info(account_info_iter)?;
     let system_program_info = next_account_info(account_info_iter)?;
     let rent_info = next_account_info(account_info_iter)?;
 
-    // Derive pool address and bump
-    let (pool_pubkey, bump) = Pubkey::find_program_address(
-        &[b"pool", token_a_info.key.as_ref(), token_b_info.key.as_ref()],
-        program_id,
-    );
+    check_admin(program_id, config_info, admin_info, account_info_iter.as_slice())?;
+    if fee_amount > 0 && treasury == Pubkey::default() {
+        return Err(ProgramError::InvalidArgument);
+    }
 
-    if pool_pubkey != *pool_info.key {
+    let bump = check_creation_policy_address(program_id, policy_info)?;
+    if policy_info.owner != program_id {
+        let rent = Rent::from_account_info(rent_info)?;
+        create_pda_account(
+            program_id,
+            payer_info,
+            policy_info,
+            system_program_info,
+            &rent,
+            CreationPolicy::LEN,
+            &[CREATION_POLICY_SEED, &[bump]],
+        )?;
+        let policy = CreationPolicy {
+            header: CreationPolicy::new_header(),
+            bump,
+            rules: CreationRules::default(),
+            mint_count: 0,
+            _padding: [0; 1],
+            fee_amount: 0,
+            allowed: 0,
+            fee_mint: Pubkey::default(),
+            treasury: Pubkey::default(),
+            mints: [Pubkey::default(); MAX_LISTED_MINTS],
+        };
+        CreationPolicy::pack(policy, &mut policy_info.data.borrow_mut())?;
+    }
+
+    let mut policy_data = policy_info.try_borrow_mut_data()?;
+    let policy = CreationPolicy::load_mut(&mut policy_data)?;
+    policy.fee_mint = fee_mint;
+    policy.fee_amount = fee_amount;
+    policy.treasury = treasury;
+    policy.rules = rules;
+
+    Ok(())
+}
+
+fn process_set_mint_status(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    mint: Pubkey,
+    status: MintStatus,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let config_info = next_account_info(account_info_iter)?;
+    let admin_info = next_account_info(account_info_iter)?;
+    let policy_info = next_account_info(account_info_iter)?;
+
+    check_admin(program_id, config_info, admin_info, account_info_iter.as_slice())?;
+    check_creation_policy_address(program_id, policy_info)?;
+    if policy_info.owner != program_id {
+        return Err(ProgramError::UninitializedAccount);
+    }
+
+    let mut policy_data = policy_info.try_borrow_mut_data()?;
+    CreationPolicy::load_mut(&mut policy_data)?.set_mint_status(&mint, status)
+}
+
+fn check_creation_policy_address(program_id: &Pubkey, policy_info: &AccountInfo) -> Result<u8, ProgramError> {
+    let (policy_pubkey, bump) = Pubkey::find_program_address(&[CREATION_POLICY_SEED], program_id);
+    if policy_pubkey != *policy_info.key {
         return Err(ProgramError::InvalidSeeds);
     }
+    Ok(bump)
+}
 
-    // Create pool account
-    let rent = Rent::from_account_info(rent_info)?;
-    let space = Pool::LEN;
-    let lamports = rent.minimum_balance(space);
+// The policy CreatePool applies, none before the admin first sets one
+fn load_creation_policy(
+    program_id: &Pubkey,
+    policy_info: &AccountInfo,
+) -> Result<Option<CreationPolicy>, ProgramError> {
+    check_creation_policy_address(program_id, policy_info)?;
+    if policy_info.owner != program_id {
+        return Ok(None);
+    }
+    Ok(Some(*CreationPolicy::load(&policy_info.try_borrow_data()?)?))
+}
 
-    let pool_signer_seeds: &[&[_]] = &[
-        b"pool",
-        token_a_info.key.as_ref(),
-        token_b_info.key.as_ref(),
-        &[bump],
-    ];
+// Moves the creation fee from the payer to the treasury. The treasury follows
+// CreatePool's accounts, then for token fees the fee mint, the payer's token
+// account and the mint's token program.
+fn pay_creation_fee<'a>(
+    policy: &CreationPolicy,
+    payer_info: &AccountInfo<'a>,
+    system_program_info: &AccountInfo<'a>,
+    fee_account_infos: &mut std::slice::Iter<AccountInfo<'a>>,
+) -> ProgramResult {
+    if policy.fee_amount == 0 {
+        return Ok(());
+    }
+    let treasury_info = next_account_info(fee_account_infos)?;
+    if *treasury_info.key != policy.treasury {
+        return Err(ProgramError::InvalidArgument);
+    }
+    if policy.is_lamport_fee() {
+        return invoke(
+            &system_instruction::transfer(payer_info.key, treasury_info.key, policy.fee_amount),
+            &[payer_info.clone(), treasury_info.clone(), system_program_info.clone()],
+        );
+    }
 
-    invoke_signed(
-        &system_instruction::create_account(
-            user_info.key,
-            pool_info.key,
-            lamports,
-            space as u64,
-            program_id,
-        ),
-        &[
-            user_info.clone(),
-            pool_info.clone(),
-            system_program_info.clone(),
-        ],
-        &[pool_signer_seeds],
-    )?;
+    let fee_mint_info = next_account_info(fee_account_infos)?;
+    let source_info = next_account_info(fee_account_infos)?;
+    let fee_token_program_info = next_account_info(fee_account_infos)?;
+    if *fee_mint_info.key != policy.fee_mint {
+        return Err(ProgramError::InvalidArgument);
+    }
+    check_token_program(fee_token_program_info, fee_mint_info)?;
+    transfer_checked(
+        fee_token_program_info,
+        source_info,
+        fee_mint_info,
+        treasury_info,
+        payer_info,
+        policy.fee_amount,
+        &[],
+    )
+}
 
-    // Create vault accounts as regular accounts (not PDAs)
-    // Create vault A account
-    invoke_signed(
-        &system_instruction::create_account(
-            user_info.key,
-            vault_a_info.key,
-            rent.minimum_balance(165), // Token account size
-            165,
-            &Pubkey::from_str(GORBCHAIN_SPL_TOKEN_PROGRAM).unwrap(),
-        ),
-        &[
-            user_info.clone(),
-            vault_a_info.clone(),
-            system_program_info.clone(),
-        ],
-        &[pool_signer_seeds],
-    )?;
+// Opens an order at [LIMIT_ORDER_SEED, pool, owner, order_id] and moves
+// `amount_in` of the input side from the owner's account into its escrow.
+// The owner pays the rent and the keeper reward.
+fn process_place_limit_order(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    order_id: u64,
+    amount_in: u64,
+    min_amount_out: u64,
+    direction_a_to_b: bool,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let pool_info = next_account_info(account_info_iter)?;
+    let mint_in_info = next_account_info(account_info_iter)?;
+    let owner_info = next_account_info(account_info_iter)?;
+    let source_info = next_account_info(account_info_iter)?;
+    let destination_info = next_account_info(account_info_iter)?;
+    let order_info = next_account_info(account_info_iter)?;
+    let escrow_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
 
-    // Create vault B account
-    invoke_signed(
-        &system_instruction::create_account(
-            user_info.key,
-            vault_b_info.key,
-            rent.minimum_balance(165), // Token account size
-            165,
-            &Pubkey::from_str(GORBCHAIN_SPL_TOKEN_PROGRAM).unwrap(),
-        ),
-        &[
-            user_info.clone(),
-            vault_b_info.clone(),
-            system_program_info.clone(),
-        ],
-        &[pool_signer_seeds],
-    )?;
+    if pool_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let pool = load_pool(pool_info)?;
+    if !owner_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if amount_in == 0 || min_amount_out == 0 {
+        return Err(ProgramError::InvalidArgument);
+    }
 
-    // Initialize vault A as token account
-    invoke_signed(
-        &create_initialize_account_instruction(
-            vault_a_info.key,
-            token_a_info.key,
-            pool_info.key, // Authority is pool
-        ),
-        &[
-            vault_a_info.clone(),
-            token_a_info.clone(),
-            pool_info.clone(), // Pool is signer
-            rent_info.clone(),
-        ],
-        &[pool_signer_seeds],
-    )?;
+    let (mint_in, program_in, mint_out, program_out) = if direction_a_to_b {
+        (pool.token_a, pool.token_program_a, pool.token_b, pool.token_program_b)
+    } else {
+        (pool.token_b, pool.token_program_b, pool.token_a, pool.token_program_a)
+    };
+    if *mint_in_info.key != mint_in {
+        return Err(ProgramError::InvalidArgument);
+    }
+    if *token_program_info.key != program_in {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    // Fills pay out to the owner, never to someone else's account
+    let destination = unpack_token_state::<spl_token::state::Account>(destination_info, &program_out)?;
+    if destination.mint != mint_out || destination.owner != *owner_info.key {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let order_id_bytes = order_id.to_le_bytes();
+    let order_seeds: &[&[u8]] = &[LIMIT_ORDER_SEED, pool_info.key.as_ref(), owner_info.key.as_ref(), &order_id_bytes];
+    let (order_pubkey, bump) = Pubkey::find_program_address(order_seeds, program_id);
+    if order_pubkey != *order_info.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if order_info.lamports() > 0 {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+    let (escrow_pubkey, escrow_bump) =
+        Pubkey::find_program_address(&[ORDER_ESCROW_SEED, order_pubkey.as_ref()], program_id);
+    if escrow_pubkey != *escrow_info.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
 
-    // Initialize vault B as token account
-    invoke_signed(
-        &create_initialize_account_instruction(
-            vault_b_info.key,
-            token_b_info.key,
-            pool_info.key, // Authority is pool
-        ),
-        &[
-            vault_b_info.clone(),
-            token_b_info.clone(),
-            pool_info.clone(), // Pool is signer
-            rent_info.clone(),
-        ],
-        &[pool_signer_seeds],
+    let rent = Rent::from_account_info(rent_info)?;
+    create_pda_account(
+        program_id,
+        owner_info,
+        order_info,
+        system_program_info,
+        &rent,
+        LimitOrder::LEN,
+        &[order_seeds, &[&[bump]]].concat(),
     )?;
-
-    // Transfer tokens to vaults
     invoke(
-        &create_transfer_instruction(
-            user_token_a_info.key,
-            vault_a_info.key,
-            user_info.key,
-            amount_a,
-        ),
-        &[
-            user_token_a_info.clone(),
-            vault_a_info.clone(),
-            user_info.clone(),
-            token_program_info.clone(),
-        ],
+        &system_instruction::transfer(owner_info.key, order_info.key, KEEPER_REWARD_LAMPORTS),
+        &[owner_info.clone(), order_info.clone(), system_program_info.clone()],
     )?;
 
-    invoke(
-        &create_transfer_instruction(
-            user_token_b_info.key,
-            vault_b_info.key,
-            user_info.key,
-            amount_b,
-        ),
-        &[
-            user_token_b_info.clone(),
-            vault_b_info.clone(),
-            user_info.clone(),
-            token_program_info.clone(),
-        ],
+    // The escrow is a PDA of the order with the order as authority
+    create_pda_account(
+        token_program_info.key,
+        owner_info,
+        escrow_info,
+        system_program_info,
+        &rent,
+        token_account_len(token_program_info.key, mint_in_info)?,
+        &[ORDER_ESCROW_SEED, order_pubkey.as_ref(), &[escrow_bump]],
     )?;
-
-    // Calculate liquidity
-    let liquidity: u64 = (amount_a as u128)
-        .checked_mul(amount_b as u128)
-        .unwrap()
-        .integer_sqrt() as u64;
-
-    // Mint LP tokens
-    invoke_signed(
-        &create_mint_to_instruction(
-            lp_mint_info.key,
-            user_lp_info.key,
-            pool_info.key,
-            liquidity,
+    invoke(
+        &create_initialize_account3_instruction(
+            token_program_info.key,
+            escrow_info.key,
+            mint_in_info.key,
+            &order_pubkey,
         ),
-        &[
-            lp_mint_info.clone(),
-            user_lp_info.clone(),
-            pool_info.clone(),
-            token_program_info.clone(),
-        ],
-        &[pool_signer_seeds],
+        &[escrow_info.clone(), mint_in_info.clone(), token_program_info.clone()],
     )?;
+    transfer_checked(token_program_info, source_info, mint_in_info, escrow_info, owner_info, amount_in, &[])?;
 
-    // Initialize pool state
-    let pool = Pool {
-        pool_id,
-        pool_type: PoolType::Standard, // Default to Standard for now
-        token_a: *token_a_info.key,
-        token_b: *token_b_info.key,
+    let order = LimitOrder {
+        header: LimitOrder::new_header(),
         bump,
-        fee_rate: 30, // Default fee rate
-        reserve_a: amount_a,
-        reserve_b: amount_b,
-        total_lp_supply: liquidity,
-        created_at: solana_program::clock::Clock::get()?.unix_timestamp,
-        is_active: true,
+        escrow_bump,
+        direction_a_to_b,
+        _padding: [0; 3],
+        order_id,
+        pool: *pool_info.key,
+        owner: *owner_info.key,
+        destination: *destination_info.key,
+        amount_in: received_amount(mint_in_info, amount_in)?,
+        min_amount_out,
+        created_at: Clock::get()?.unix_timestamp,
     };
-
-    Pool::pack(pool, &mut pool_info.data.borrow_mut())?;
+    LimitOrder::pack(order, &mut order_info.data.borrow_mut())?;
 
     Ok(())
 }
 
-fn process_add_liquidity(
-    program_id: &Pubkey,
-    accounts: &[AccountInfo],
-    pool_id: u64,
-    amount_a: u64,
-    amount_b: u64,
-) -> ProgramResult {
+// Returns an order's escrow to the owner's `refund` account and closes the
+// order, refunding its rent and the unspent keeper reward
+fn process_cancel_limit_order(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let order_info = next_account_info(account_info_iter)?;
+    let escrow_info = next_account_info(account_info_iter)?;
+    let owner_info = next_account_info(account_info_iter)?;
+    let refund_info = next_account_info(account_info_iter)?;
+    let mint_in_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    let order = load_limit_order(program_id, order_info, escrow_info)?;
+    if !owner_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if order.owner != *owner_info.key {
+        return Err(SwapError::Unauthorized.into());
+    }
+    check_token_program(token_program_info, mint_in_info)?;
+    // Anything sent to the escrow besides the order goes back too, so it can close
+    let escrowed = unpack_token_state::<spl_token::state::Account>(escrow_info, token_program_info.key)?.amount;
+
+    let order_id_bytes = order.order_id.to_le_bytes();
+    let order_seeds: &[&[u8]] =
+        &[LIMIT_ORDER_SEED, order.pool.as_ref(), order.owner.as_ref(), &order_id_bytes, &[order.bump]];
+    transfer_checked(token_program_info, escrow_info, mint_in_info, refund_info, order_info, escrowed, &[order_seeds])?;
+    close_escrow(token_program_info, order_info, escrow_info, owner_info, order_seeds)?;
+    close_program_account(order_info, owner_info)
+}
+
+// Permissionless crank: fills each order whose limit the pool meets, as a
+// swap of its escrow into the pool. Orders come as trailing groups of
+// [order, escrow, destination, owner, refund], where refund is the owner's
+// input-side account for anything sent to the escrow besides the order.
+// Unfillable orders and ones whose accounts a transfer would fail on are
+// skipped; the keeper collects the reward of every filled order.
+fn process_fill_limit_orders(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
     let pool_info = next_account_info(account_info_iter)?;
     let token_a_info = next_account_info(account_info_iter)?;
     let token_b_info = next_account_info(account_info_iter)?;
     let vault_a_info = next_account_info(account_info_iter)?;
     let vault_b_info = next_account_info(account_info_iter)?;
-    let lp_mint_info = next_account_info(account_info_iter)?;
-    let user_token_a_info = next_account_info(account_info_iter)?;
-    let user_token_b_info = next_account_info(account_info_iter)?;
-    let user_lp_info = next_account_info(account_info_iter)?;
-    let user_info = next_account_info(account_info_iter)?;
-    let token_program_info = next_account_info(account_info_iter)?;
-
-    let mut pool = Pool::unpack(&pool_info.data.borrow())?;
-
-    // Verify pool seeds
-    let (pool_pubkey, _) = Pubkey::find_program_address(
-        &[b"pool", token_a_info.key.as_ref(), token_b_info.key.as_ref()],
-        program_id,
-    );
+    let token_program_a_info = next_account_info(account_info_iter)?;
+    let token_program_b_info = next_account_info(account_info_iter)?;
+    let keeper_info = next_account_info(account_info_iter)?;
 
-    if pool_pubkey != *pool_info.key {
-        return Err(ProgramError::InvalidSeeds);
+    if pool_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let pool = load_pool(pool_info)?;
+    if *token_a_info.key != pool.token_a || *token_b_info.key != pool.token_b {
+        return Err(ProgramError::InvalidArgument);
+    }
+    check_pool_token_programs(&pool, token_program_a_info, token_program_b_info)?;
+    check_vaults(program_id, pool_info.key, &pool, vault_a_info, vault_b_info)?;
+    if !keeper_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
     }
 
-    let reserve_a = pool.reserve_a;
-    let reserve_b = pool.reserve_b;
-    let supply = pool.total_lp_supply;
+    let order_infos = account_info_iter.as_slice();
+    if order_infos.is_empty() || !order_infos.len().is_multiple_of(5) {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
 
-    // Calculate final amounts maintaining ratio
-    let (final_amount_a, final_amount_b) = if reserve_a > 0 && reserve_b > 0 {
-        let required_b = (amount_a as u128)
-            .checked_mul(reserve_b as u128).unwrap()
-            .checked_div(reserve_a as u128).unwrap() as u64;
-        if required_b <= amount_b {
-            (amount_a, required_b)
-        } else {
-            let required_a = (amount_b as u128)
-                .checked_mul(reserve_a as u128).unwrap()
-                .checked_div(reserve_b as u128).unwrap() as u64;
-            (required_a, amount_b)
+    let pool_signer_seeds: &[&[_]] = &[b"pool", pool.token_a.as_ref(), pool.token_b.as_ref(), &[pool.bump]];
+    let mut filled = 0;
+    for infos in order_infos.chunks(5) {
+        let [order_info, escrow_info, destination_info, owner_info, refund_info] = infos else {
+            unreachable!()
+        };
+        // Another crank may have filled or the owner cancelled the order
+        let Ok(order) = load_limit_order(program_id, order_info, escrow_info) else {
+            continue;
+        };
+        if order.pool != *pool_info.key || order.destination != *destination_info.key || order.owner != *owner_info.key
+        {
+            continue;
         }
-    } else {
-        (amount_a, amount_b)
-    };
 
-    // Transfer tokens to vaults
-    invoke(
-        &create_transfer_instruction(
-            user_token_a_info.key,
-            vault_a_info.key,
-            user_info.key,
-            final_amount_a,
-        ),
-        &[
-            user_token_a_info.clone(),
-            vault_a_info.clone(),
-            user_info.clone(),
-            token_program_info.clone(),
-        ],
-    )?;
+        let (mint_in_info, vault_in_info, program_in_info, mint_out_info, vault_out_info, program_out_info) =
+            if order.direction_a_to_b {
+                (token_a_info, vault_a_info, token_program_a_info, token_b_info, vault_b_info, token_program_b_info)
+            } else {
+                (token_b_info, vault_b_info, token_program_b_info, token_a_info, vault_a_info, token_program_a_info)
+            };
+        // A failed transfer would abort the whole crank, so orders with a
+        // closed or frozen account are left for their owner to cancel
+        let (Some(escrowed), Some(_), Some(_)) = (
+            open_token_balance(escrow_info, program_in_info.key, mint_in_info.key, order_info.key),
+            open_token_balance(destination_info, program_out_info.key, mint_out_info.key, &order.owner),
+            open_token_balance(refund_info, program_in_info.key, mint_in_info.key, &order.owner),
+        ) else {
+            continue;
+        };
+        if escrowed < order.amount_in {
+            continue;
+        }
 
-    invoke(
-        &create_transfer_instruction(
-            user_token_b_info.key,
-            vault_b_info.key,
-            user_info.key,
-            final_amount_b,
-        ),
-        &[
-            user_token_b_info.clone(),
-            vault_b_info.clone(),
-            user_info.clone(),
-            token_program_info.clone(),
-        ],
-    )?;
+        // Earlier fills moved the pool; an order fills once the pool pays
+        // at least its limit, within the fee and price band it would charge
+        let pool = load_pool(pool_info)?;
+        let received_in = received_amount(mint_in_info, order.amount_in)?;
+        let Ok(swap) = price_swap(&pool, received_in, order.direction_a_to_b) else {
+            continue;
+        };
+        if swap.quote.amount_out < order.min_amount_out {
+            continue;
+        }
 
-    // Calculate liquidity to mint
-    let liquidity = if supply == 0 {
-        (final_amount_a as u128)
-            .checked_mul(final_amount_b as u128).unwrap()
-            .integer_sqrt() as u64
-    } else {
-        (final_amount_a as u128)
-            .checked_mul(supply as u128).unwrap()
-            .checked_div(reserve_a as u128).unwrap() as u64
-    };
+        let order_id_bytes = order.order_id.to_le_bytes();
+        let order_seeds: &[&[u8]] =
+            &[LIMIT_ORDER_SEED, order.pool.as_ref(), order.owner.as_ref(), &order_id_bytes, &[order.bump]];
+        transfer_checked(
+            program_in_info,
+            escrow_info,
+            mint_in_info,
+            vault_in_info,
+            order_info,
+            order.amount_in,
+            &[order_seeds],
+        )?;
+        transfer_checked(
+            program_out_info,
+            vault_out_info,
+            mint_out_info,
+            destination_info,
+            pool_info,
+            swap.quote.amount_out,
+            &[pool_signer_seeds],
+        )?;
+        record_swap(pool_info, &pool, &swap)?;
 
-    // Mint LP tokens
-    let pool_signer_seeds: &[&[_]] = &[
-        b"pool",
-        pool.token_a.as_ref(),
-        pool.token_b.as_ref(),
-        &[pool.bump],
-    ];
+        // Anything sent to the escrow besides the order goes back, so it can close
+        let extra = escrowed - order.amount_in;
+        if extra > 0 {
+            let seeds = &[order_seeds];
+            transfer_checked(program_in_info, escrow_info, mint_in_info, refund_info, order_info, extra, seeds)?;
+        }
+        close_escrow(program_in_info, order_info, escrow_info, owner_info, order_seeds)?;
+        let reward = KEEPER_REWARD_LAMPORTS.min(order_info.lamports());
+        **order_info.try_borrow_mut_lamports()? -= reward;
+        **keeper_info.try_borrow_mut_lamports()? =
+            keeper_info.lamports().checked_add(reward).ok_or(SwapError::MathOverflow)?;
+        close_program_account(order_info, owner_info)?;
+        filled += 1;
+    }
 
-    invoke_signed(
-        &create_mint_to_instruction(
-            lp_mint_info.key,
-            user_lp_info.key,
-            pool_info.key,
-            liquidity,
-        ),
-        &[
-            lp_mint_info.clone(),
-            user_lp_info.clone(),
-            pool_info.clone(),
-            token_program_info.clone(),
-        ],
-        &[pool_signer_seeds],
-    )?;
+    if filled == 0 {
+        return Err(SwapError::OrderNotFillable.into());
+    }
+    Ok(())
+}
 
-    // Update pool state
-    pool.reserve_a = pool.reserve_a.checked_add(final_amount_a).unwrap();
-    pool.reserve_b = pool.reserve_b.checked_add(final_amount_b).unwrap();
-    pool.total_lp_supply = pool.total_lp_supply.checked_add(liquidity).unwrap();
+// The balance of `info` if it is an unfrozen `mint` account of
+// `owner` under `token_program`, so transfers in and out of it go through
+fn open_token_balance(info: &AccountInfo, token_program: &Pubkey, mint: &Pubkey, owner: &Pubkey) -> Option<u64> {
+    let account = unpack_token_state::<spl_token::state::Account>(info, token_program).ok()?;
+    let open = account.mint == *mint && account.owner == *owner && !account.is_frozen();
+    open.then_some(account.amount)
+}
 
-    Pool::pack(pool, &mut pool_info.data.borrow_mut())?;
+// Loads an open order and checks `escrow_info` is its escrow
+fn load_limit_order(
+    program_id: &Pubkey,
+    order_info: &AccountInfo,
+    escrow_info: &AccountInfo,
+) -> Result<LimitOrder, ProgramError> {
+    if order_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let order = *LimitOrder::load(&order_info.try_borrow_data()?)?;
+    check_escrow(program_id, order_info.key, order.escrow_bump, escrow_info)?;
+    Ok(order)
+}
 
+// Checks `escrow_info` is the input escrow of the order or vault at `holder`
+fn check_escrow(program_id: &Pubkey, holder: &Pubkey, escrow_bump: u8, escrow_info: &AccountInfo) -> ProgramResult {
+    let escrow = Pubkey::create_program_address(&[ORDER_ESCROW_SEED, holder.as_ref(), &[escrow_bump]], program_id)
+        .map_err(|_| ProgramError::InvalidSeeds)?;
+    if escrow != *escrow_info.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
     Ok(())
 }
 
-fn process_remove_liquidity(
+// Closes an emptied order or vault escrow, returning its rent to the owner
+fn close_escrow<'a>(
+    token_program_info: &AccountInfo<'a>,
+    order_info: &AccountInfo<'a>,
+    escrow_info: &AccountInfo<'a>,
+    owner_info: &AccountInfo<'a>,
+    order_seeds: &[&[u8]],
+) -> ProgramResult {
+    invoke_signed(
+        &create_close_account_instruction(token_program_info.key, escrow_info.key, owner_info.key, order_info.key),
+        &[escrow_info.clone(), owner_info.clone(), order_info.clone(), token_program_info.clone()],
+        &[order_seeds],
+    )
+}
+
+fn process_place_long_term_order(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
-    pool_id: u64,
-    lp_amount: u64,
+    order_id: u64,
+    amount_in: u64,
+    schedule: OrderSchedule,
+    direction_a_to_b: bool,
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
     let pool_info = next_account_info(account_info_iter)?;
-    let token_a_info = next_account_info(account_info_iter)?;
-    let token_b_info = next_account_info(account_info_iter)?;
-    let vault_a_info = next_account_info(account_info_iter)?;
-    let vault_b_info = next_account_info(account_info_iter)?;
-    let lp_mint_info = next_account_info(account_info_iter)?;
-    let user_lp_info = next_account_info(account_info_iter)?;
-    let user_token_a_info = next_account_info(account_info_iter)?;
-    let user_token_b_info = next_account_info(account_info_iter)?;
-    let user_info = next_account_info(account_info_iter)?;
+    let mint_in_info = next_account_info(account_info_iter)?;
+    let vault_in_info = next_account_info(account_info_iter)?;
+    let owner_info = next_account_info(account_info_iter)?;
+    let source_info = next_account_info(account_info_iter)?;
+    let order_info = next_account_info(account_info_iter)?;
     let token_program_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
 
-    let mut pool = Pool::unpack(&pool_info.data.borrow())?;
+    if pool_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let pool = load_pool(pool_info)?;
+    if !owner_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let OrderSchedule { intervals, interval_seconds, max_slippage_bps } = schedule;
+    if intervals == 0
+        || intervals > MAX_ORDER_INTERVALS
+        || interval_seconds <= 0
+        || max_slippage_bps as u64 > quote::FEE_DENOMINATOR
+    {
+        return Err(ProgramError::InvalidArgument);
+    }
 
-    // Verify pool seeds
-    let (pool_pubkey, _) = Pubkey::find_program_address(
-        &[b"pool", token_a_info.key.as_ref(), token_b_info.key.as_ref()],
-        program_id,
-    );
+    let (mint_in, program_in) =
+        if direction_a_to_b { (pool.token_a, pool.token_program_a) } else { (pool.token_b, pool.token_program_b) };
+    if *mint_in_info.key != mint_in {
+        return Err(ProgramError::InvalidArgument);
+    }
+    if *token_program_info.key != program_in {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    check_vault(program_id, pool_info.key, &mint_in, &program_in, vault_in_info)?;
+    // Every interval sells something
+    let received_in = received_amount(mint_in_info, amount_in)?;
+    if received_in < intervals as u64 {
+        return Err(ProgramError::InvalidArgument);
+    }
 
-    if pool_pubkey != *pool_info.key {
+    let order_id_bytes = order_id.to_le_bytes();
+    let order_seeds: &[&[u8]] =
+        &[LONG_TERM_ORDER_SEED, pool_info.key.as_ref(), owner_info.key.as_ref(), &order_id_bytes];
+    let (order_pubkey, bump) = Pubkey::find_program_address(order_seeds, program_id);
+    if order_pubkey != *order_info.key {
         return Err(ProgramError::InvalidSeeds);
     }
+    if order_info.lamports() > 0 {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
 
-    let reserve_a = pool.reserve_a;
-    let reserve_b = pool.reserve_b;
-    let supply = pool.total_lp_supply;
+    let rent = Rent::from_account_info(rent_info)?;
+    create_pda_account(
+        program_id,
+        owner_info,
+        order_info,
+        system_program_info,
+        &rent,
+        LongTermOrder::LEN,
+        &[order_seeds, &[&[bump]]].concat(),
+    )?;
+    // The input waits in the pool's vault, outside the reserves
+    transfer_checked(token_program_info, source_info, mint_in_info, vault_in_info, owner_info, amount_in, &[])?;
+
+    let now = Clock::get()?.unix_timestamp;
+    let (price_a_cumulative, price_b_cumulative) = quote::price_cumulatives(&pool, now);
+    let cumulative = if direction_a_to_b { price_a_cumulative } else { price_b_cumulative };
+    let order = LongTermOrder {
+        header: LongTermOrder::new_header(),
+        bump,
+        direction_a_to_b,
+        max_slippage_bps,
+        _padding: [0; 2],
+        order_id,
+        pool: *pool_info.key,
+        owner: *owner_info.key,
+        amount_in: received_in,
+        sold: 0,
+        proceeds: 0,
+        intervals,
+        intervals_executed: 0,
+        interval_seconds,
+        start_time: now,
+        price_cumulative_last: cumulative.to_le_bytes(),
+        observed_at: now,
+    };
+    LongTermOrder::pack(order, &mut order_info.data.borrow_mut())?;
 
-    // Calculate amounts to withdraw
-    let amount_a = (lp_amount as u128)
-        .checked_mul(reserve_a as u128).unwrap()
-        .checked_div(supply as u128).unwrap() as u64;
-    let amount_b = (lp_amount as u128)
-        .checked_mul(reserve_b as u128).unwrap()
-        .checked_div(supply as u128).unwrap() as u64;
+    Ok(())
+}
 
-    // Burn LP tokens
-    invoke(
-        &create_burn_instruction(
-            user_lp_info.key,
-            lp_mint_info.key,
-            user_info.key,
-            lp_amount,
-        ),
-        &[
-            user_lp_info.clone(),
-            lp_mint_info.clone(),
-            user_info.clone(),
-            token_program_info.clone(),
-        ],
-    )?;
+// Permissionless crank: swaps the next due interval of each trailing order
+// against the pool, as its own trade at the fee and price band a swap would
+// get. An interval must fetch at least its TWAP-bound minimum since the
+// order last executed, so moving the price around a run doesn't pay. Orders
+// behind on intervals, or that can't execute now, catch up on later runs.
+fn process_execute_long_term_orders(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let (pool_info, order_infos) = accounts.split_first().ok_or(ProgramError::NotEnoughAccountKeys)?;
+    if pool_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    load_pool(pool_info)?;
+    if order_infos.is_empty() {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
 
-    // Transfer tokens from vaults to user
-    let pool_signer_seeds: &[&[_]] = &[
-        b"pool",
-        pool.token_a.as_ref(),
-        pool.token_b.as_ref(),
-        &[pool.bump],
-    ];
+    let now = Clock::get()?.unix_timestamp;
+    let (mut executed, mut slipped) = (0, false);
+    for order_info in order_infos {
+        let mut order = load_long_term_order(program_id, order_info)?;
+        if order.pool != *pool_info.key {
+            return Err(ProgramError::InvalidArgument);
+        }
+        if order.intervals_executed >= order.intervals_due(now) {
+            continue;
+        }
 
-    invoke_signed(
-        &create_transfer_instruction(
-            vault_a_info.key,
-            user_token_a_info.key,
-            pool_info.key,
-            amount_a,
-        ),
-        &[
-            vault_a_info.clone(),
-            user_token_a_info.clone(),
-            pool_info.clone(),
-            token_program_info.clone(),
-        ],
-        &[pool_signer_seeds],
-    )?;
+        // Accumulators are read before the swap moves the price; a second
+        // run in the same second has no window and waits
+        let pool = load_pool(pool_info)?;
+        let (price_a_cumulative, price_b_cumulative) = quote::price_cumulatives(&pool, now);
+        let cumulative = if order.direction_a_to_b { price_a_cumulative } else { price_b_cumulative };
+        let elapsed = now - order.observed_at;
+        let Some(twap) = quote::twap_q64(u128::from_le_bytes(order.price_cumulative_last), cumulative, elapsed) else {
+            continue;
+        };
+        let amount = order.interval_amount(order.intervals_executed);
+        let Ok(swap) = price_swap(&pool, amount, order.direction_a_to_b) else {
+            continue;
+        };
+        if swap.quote.amount_out < quote::min_amount_out_at(amount, twap, order.max_slippage_bps) {
+            slipped = true;
+            continue;
+        }
+        record_swap(pool_info, &pool, &swap)?;
+
+        order.sold = order.sold.checked_add(amount).ok_or(SwapError::MathOverflow)?;
+        order.proceeds = order.proceeds.checked_add(swap.quote.amount_out).ok_or(SwapError::MathOverflow)?;
+        order.intervals_executed += 1;
+        order.price_cumulative_last = cumulative.to_le_bytes();
+        order.observed_at = now;
+        LongTermOrder::pack(order, &mut order_info.data.borrow_mut())?;
+        executed += 1;
+    }
 
-    invoke_signed(
-        &create_transfer_instruction(
-            vault_b_info.key,
-            user_token_b_info.key,
-            pool_info.key,
-            amount_b,
-        ),
-        &[
-            vault_b_info.clone(),
-            user_token_b_info.clone(),
-            pool_info.clone(),
-            token_program_info.clone(),
-        ],
+    match executed {
+        0 if slipped => Err(SwapError::SlippageExceeded.into()),
+        0 => Err(SwapError::NoIntervalsDue.into()),
+        _ => Ok(()),
+    }
+}
+
+// Pays an order's proceeds so far to `destination` and leaves it running
+fn process_withdraw_long_term_proceeds(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let pool_info = next_account_info(account_info_iter)?;
+    let order_info = next_account_info(account_info_iter)?;
+    let owner_info = next_account_info(account_info_iter)?;
+    let mint_out_info = next_account_info(account_info_iter)?;
+    let vault_out_info = next_account_info(account_info_iter)?;
+    let destination_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    let (pool, mut order) = load_owned_long_term_order(program_id, pool_info, order_info, owner_info)?;
+    let (mint_out, program_out) =
+        if order.direction_a_to_b { (pool.token_b, pool.token_program_b) } else { (pool.token_a, pool.token_program_a) };
+    if *mint_out_info.key != mint_out {
+        return Err(ProgramError::InvalidArgument);
+    }
+    if *token_program_info.key != program_out {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    check_vault(program_id, pool_info.key, &mint_out, &program_out, vault_out_info)?;
+
+    let proceeds = order.proceeds;
+    order.proceeds = 0;
+    LongTermOrder::pack(order, &mut order_info.data.borrow_mut())?;
+    let pool_signer_seeds: &[&[_]] = &[b"pool", pool.token_a.as_ref(), pool.token_b.as_ref(), &[pool.bump]];
+    transfer_checked(
+        token_program_info,
+        vault_out_info,
+        mint_out_info,
+        destination_info,
+        pool_info,
+        proceeds,
         &[pool_signer_seeds],
-    )?;
+    )
+}
 
-    // Update pool state
-    pool.reserve_a = pool.reserve_a.checked_sub(amount_a).unwrap();
-    pool.reserve_b = pool.reserve_b.checked_sub(amount_b).unwrap();
-    pool.total_lp_supply = pool.total_lp_supply.checked_sub(lp_amount).unwrap();
+// Stops an order where the crank left it: the unsold input and unwithdrawn
+// proceeds go to the owner's accounts for each side and the order closes.
+// Finished orders close the same way.
+fn process_cancel_long_term_order(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let pool_info = next_account_info(account_info_iter)?;
+    let order_info = next_account_info(account_info_iter)?;
+    let owner_info = next_account_info(account_info_iter)?;
+    let token_a_info = next_account_info(account_info_iter)?;
+    let token_b_info = next_account_info(account_info_iter)?;
+    let vault_a_info = next_account_info(account_info_iter)?;
+    let vault_b_info = next_account_info(account_info_iter)?;
+    let owner_a_info = next_account_info(account_info_iter)?;
+    let owner_b_info = next_account_info(account_info_iter)?;
+    let token_program_a_info = next_account_info(account_info_iter)?;
+    let token_program_b_info = next_account_info(account_info_iter)?;
+
+    let (pool, order) = load_owned_long_term_order(program_id, pool_info, order_info, owner_info)?;
+    if *token_a_info.key != pool.token_a || *token_b_info.key != pool.token_b {
+        return Err(ProgramError::InvalidArgument);
+    }
+    check_pool_token_programs(&pool, token_program_a_info, token_program_b_info)?;
+    check_vaults(program_id, pool_info.key, &pool, vault_a_info, vault_b_info)?;
+
+    let unsold = order.amount_in.checked_sub(order.sold).ok_or(SwapError::MathOverflow)?;
+    let (amount_a, amount_b) =
+        if order.direction_a_to_b { (unsold, order.proceeds) } else { (order.proceeds, unsold) };
+    let pool_signer_seeds: &[&[_]] = &[b"pool", pool.token_a.as_ref(), pool.token_b.as_ref(), &[pool.bump]];
+    if amount_a > 0 {
+        transfer_checked(
+            token_program_a_info,
+            vault_a_info,
+            token_a_info,
+            owner_a_info,
+            pool_info,
+            amount_a,
+            &[pool_signer_seeds],
+        )?;
+    }
+    if amount_b > 0 {
+        transfer_checked(
+            token_program_b_info,
+            vault_b_info,
+            token_b_info,
+            owner_b_info,
+            pool_info,
+            amount_b,
+            &[pool_signer_seeds],
+        )?;
+    }
+    close_program_account(order_info, owner_info)
+}
 
-    Pool::pack(pool, &mut pool_info.data.borrow_mut())?;
+fn load_long_term_order(program_id: &Pubkey, order_info: &AccountInfo) -> Result<LongTermOrder, ProgramError> {
+    if order_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Ok(*LongTermOrder::load(&order_info.try_borrow_data()?)?)
+}
 
-    Ok(())
+// Loads an order of `pool_info` along with its pool, for its signing owner
+fn load_owned_long_term_order(
+    program_id: &Pubkey,
+    pool_info: &AccountInfo,
+    order_info: &AccountInfo,
+    owner_info: &AccountInfo,
+) -> Result<(Pool, LongTermOrder), ProgramError> {
+    if pool_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let pool = load_pool_for_exit(pool_info)?;
+    let order = load_long_term_order(program_id, order_info)?;
+    if order.pool != *pool_info.key {
+        return Err(ProgramError::InvalidArgument);
+    }
+    if !owner_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if order.owner != *owner_info.key {
+        return Err(SwapError::Unauthorized.into());
+    }
+    Ok((pool, order))
 }
 
-fn process_swap(
+fn process_create_dca(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
-    pool_id: u64,
-    amount_in: u64,
+    dca_id: u64,
+    deposit: u64,
+    params: DcaParams,
     direction_a_to_b: bool,
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
     let pool_info = next_account_info(account_info_iter)?;
-    let token_a_info = next_account_info(account_info_iter)?;
-    let token_b_info = next_account_info(account_info_iter)?;
-    let vault_a_info = next_account_info(account_info_iter)?;
-    let vault_b_info = next_account_info(account_info_iter)?;
-    let user_in_info = next_account_info(account_info_iter)?;
-    let user_out_info = next_account_info(account_info_iter)?;
-    let user_info = next_account_info(account_info_iter)?;
+    let mint_in_info = next_account_info(account_info_iter)?;
+    let owner_info = next_account_info(account_info_iter)?;
+    let source_info = next_account_info(account_info_iter)?;
+    let destination_info = next_account_info(account_info_iter)?;
+    let dca_info = next_account_info(account_info_iter)?;
+    let escrow_info = next_account_info(account_info_iter)?;
     let token_program_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
 
-    let mut pool = Pool::unpack(&pool_info.data.borrow())?;
-
-    // Verify pool seeds
-    let (pool_pubkey, _) = Pubkey::find_program_address(
-        &[b"pool", token_a_info.key.as_ref(), token_b_info.key.as_ref()],
-        program_id,
-    );
-
-    if pool_pubkey != *pool_info.key {
-        return Err(ProgramError::InvalidSeeds);
+    if pool_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let pool = load_pool(pool_info)?;
+    if !owner_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let DcaParams { amount_per_period, interval_seconds, max_slippage_bps } = params;
+    if deposit == 0
+        || amount_per_period == 0
+        || interval_seconds <= 0
+        || max_slippage_bps as u64 > quote::FEE_DENOMINATOR
+    {
+        return Err(ProgramError::InvalidArgument);
     }
 
-    let (reserve_in, reserve_out) = if direction_a_to_b {
-        (pool.reserve_a, pool.reserve_b)
+    let (mint_in, program_in, mint_out, program_out) = if direction_a_to_b {
+        (pool.token_a, pool.token_program_a, pool.token_b, pool.token_program_b)
     } else {
-        (pool.reserve_b, pool.reserve_a)
+        (pool.token_b, pool.token_program_b, pool.token_a, pool.token_program_a)
     };
+    if *mint_in_info.key != mint_in {
+        return Err(ProgramError::InvalidArgument);
+    }
+    if *token_program_info.key != program_in {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    // Swaps pay out to the owner, never to someone else's account
+    let destination = unpack_token_state::<spl_token::state::Account>(destination_info, &program_out)?;
+    if destination.mint != mint_out || destination.owner != *owner_info.key {
+        return Err(ProgramError::InvalidArgument);
+    }
 
-    // Transfer input tokens to vault
-    if direction_a_to_b {
-        invoke(
-            &create_transfer_instruction(
-                user_in_info.key,
-                vault_a_info.key,
-                user_info.key,
-                amount_in,
-            ),
-            &[
-                user_in_info.clone(),
-                vault_a_info.clone(),
-                user_info.clone(),
-                token_program_info.clone(),
-            ],
-        )?;
-    } else {
-        invoke(
-            &create_transfer_instruction(
-                user_in_info.key,
-                vault_b_info.key,
-                user_info.key,
-                amount_in,
-            ),
-            &[
-                user_in_info.clone(),
-                vault_b_info.clone(),
-                user_info.clone(),
-                token_program_info.clone(),
-            ],
-        )?;
+    let dca_id_bytes = dca_id.to_le_bytes();
+    let dca_seeds: &[&[u8]] = &[DCA_SEED, pool_info.key.as_ref(), owner_info.key.as_ref(), &dca_id_bytes];
+    let (dca_pubkey, bump) = Pubkey::find_program_address(dca_seeds, program_id);
+    if dca_pubkey != *dca_info.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if dca_info.lamports() > 0 {
+        return Err(ProgramError::AccountAlreadyInitialized);
     }
+    let (escrow_pubkey, escrow_bump) =
+        Pubkey::find_program_address(&[ORDER_ESCROW_SEED, dca_pubkey.as_ref()], program_id);
+    if escrow_pubkey != *escrow_info.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    let rent = Rent::from_account_info(rent_info)?;
+    create_pda_account(
+        program_id,
+        owner_info,
+        dca_info,
+        system_program_info,
+        &rent,
+        DcaVault::LEN,
+        &[dca_seeds, &[&[bump]]].concat(),
+    )?;
+    create_pda_account(
+        token_program_info.key,
+        owner_info,
+        escrow_info,
+        system_program_info,
+        &rent,
+        token_account_len(token_program_info.key, mint_in_info)?,
+        &[ORDER_ESCROW_SEED, dca_pubkey.as_ref(), &[escrow_bump]],
+    )?;
+    invoke(
+        &create_initialize_account3_instruction(token_program_info.key, escrow_info.key, mint_in_info.key, &dca_pubkey),
+        &[escrow_info.clone(), mint_in_info.clone(), token_program_info.clone()],
+    )?;
+    transfer_checked(token_program_info, source_info, mint_in_info, escrow_info, owner_info, deposit, &[])?;
 
-    // Calculate output amount (with 0.3% fee)
-    let amount_in_with_fee = (amount_in as u128).checked_mul(997).unwrap();
-    let numerator = amount_in_with_fee.checked_mul(reserve_out as u128).unwrap();
-    let denominator = (reserve_in as u128)
-        .checked_mul(1000).unwrap()
-        .checked_add(amount_in_with_fee).unwrap();
-    let amount_out = (numerator / denominator) as u64;
+    // The first period's TWAP is measured from now
+    let now = Clock::get()?.unix_timestamp;
+    let (price_a_cumulative, price_b_cumulative) = quote::price_cumulatives(&pool, now);
+    let vault = DcaVault {
+        header: DcaVault::new_header(),
+        bump,
+        escrow_bump,
+        direction_a_to_b,
+        _padding: [0; 1],
+        max_slippage_bps,
+        dca_id,
+        pool: *pool_info.key,
+        owner: *owner_info.key,
+        destination: *destination_info.key,
+        amount_per_period,
+        interval_seconds,
+        next_execution: now.checked_add(interval_seconds).ok_or(SwapError::MathOverflow)?,
+        periods_executed: 0,
+        price_cumulative_last: if direction_a_to_b { price_a_cumulative } else { price_b_cumulative }.to_le_bytes(),
+        observed_at: now,
+    };
+    DcaVault::pack(vault, &mut dca_info.data.borrow_mut())?;
 
-    // Transfer output tokens from vault to user
-    let pool_signer_seeds: &[&[_]] = &[
-        b"pool",
-        pool.token_a.as_ref(),
-        pool.token_b.as_ref(),
-        &[pool.bump],
-    ];
+    Ok(())
+}
 
-    if direction_a_to_b {
-        invoke_signed(
-            &create_transfer_instruction(
-                vault_b_info.key,
-                user_out_info.key,
-                pool_info.key,
-                amount_out,
-            ),
-            &[
-                vault_b_info.clone(),
-                user_out_info.clone(),
-                pool_info.clone(),
-                token_program_info.clone(),
-            ],
-            &[pool_signer_seeds],
-        )?;
-    } else {
-        invoke_signed(
-            &create_transfer_instruction(
-                vault_a_info.key,
-                user_out_info.key,
-                pool_info.key,
-                amount_out,
-            ),
-            &[
-                vault_a_info.clone(),
-                user_out_info.clone(),
-                pool_info.clone(),
-                token_program_info.clone(),
-            ],
-            &[pool_signer_seeds],
-        )?;
+// Permissionless crank: once a vault's period is due, swaps its
+// `amount_per_period` (or what's left) through `process_swap` with the vault
+// signing as the user. The output must come within the vault's slippage of
+// the pool's TWAP since the last execution. A late crank runs one period and
+// skips the rest.
+fn process_execute_dca(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    // Laid out as `process_swap` reads them, with the vault as the user
+    let account_info_iter = &mut accounts.iter();
+    let pool_info = next_account_info(account_info_iter)?;
+    let token_a_info = next_account_info(account_info_iter)?;
+    let token_b_info = next_account_info(account_info_iter)?;
+    let _vault_a_info = next_account_info(account_info_iter)?;
+    let _vault_b_info = next_account_info(account_info_iter)?;
+    let escrow_info = next_account_info(account_info_iter)?;
+    let destination_info = next_account_info(account_info_iter)?;
+    let dca_info = next_account_info(account_info_iter)?;
+    let token_program_a_info = next_account_info(account_info_iter)?;
+    let token_program_b_info = next_account_info(account_info_iter)?;
+
+    if pool_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let pool = load_pool(pool_info)?;
+    let vault = load_dca_vault(program_id, dca_info, escrow_info)?;
+    if vault.pool != *pool_info.key || vault.destination != *destination_info.key {
+        return Err(ProgramError::InvalidArgument);
+    }
+    let now = Clock::get()?.unix_timestamp;
+    if now < vault.next_execution {
+        return Err(SwapError::NoIntervalsDue.into());
     }
 
-    // Update pool reserves
-    if direction_a_to_b {
-        pool.reserve_a = pool.reserve_a.checked_add(amount_in).unwrap();
-        pool.reserve_b = pool.reserve_b.checked_sub(amount_out).unwrap();
+    let (mint_in_info, program_in_info) = if vault.direction_a_to_b {
+        (token_a_info, token_program_a_info)
     } else {
-        pool.reserve_b = pool.reserve_b.checked_add(amount_in).unwrap();
-        pool.reserve_a = pool.reserve_a.checked_sub(amount_out).unwrap();
+        (token_b_info, token_program_b_info)
+    };
+    let escrowed = unpack_token_state::<spl_token::state::Account>(escrow_info, program_in_info.key)?.amount;
+    let amount_in = vault.amount_per_period.min(escrowed);
+    if amount_in == 0 {
+        return Err(ProgramError::InsufficientFunds);
     }
 
-    Pool::pack(pool, &mut pool_info.data.borrow_mut())?;
+    // Accumulators are read before the swap moves the price
+    let (price_a_cumulative, price_b_cumulative) = quote::price_cumulatives(&pool, now);
+    let cumulative = if vault.direction_a_to_b { price_a_cumulative } else { price_b_cumulative };
+    let twap = quote::twap_q64(u128::from_le_bytes(vault.price_cumulative_last), cumulative, now - vault.observed_at)
+        .ok_or(SwapError::NoIntervalsDue)?;
+    let min_amount_out =
+        quote::min_amount_out_at(received_amount(mint_in_info, amount_in)?, twap, vault.max_slippage_bps);
+
+    let dca_id_bytes = vault.dca_id.to_le_bytes();
+    let dca_seeds: &[&[u8]] = &[DCA_SEED, vault.pool.as_ref(), vault.owner.as_ref(), &dca_id_bytes, &[vault.bump]];
+    process_swap(program_id, accounts, 0, amount_in, vault.direction_a_to_b, min_amount_out, &[dca_seeds])?;
+
+    let mut dca_data = dca_info.try_borrow_mut_data()?;
+    let state = DcaVault::load_mut(&mut dca_data)?;
+    let periods = (now - state.next_execution) / state.interval_seconds + 1;
+    state.next_execution = state.next_execution.saturating_add(periods.saturating_mul(state.interval_seconds));
+    state.periods_executed += 1;
+    state.price_cumulative_last = cumulative.to_le_bytes();
+    state.observed_at = now;
 
     Ok(())
 }
 
-fn process_initialize_registry(
-    program_id: &Pubkey,
-    accounts: &[AccountInfo],
-    instruction_data: &[u8],
-    bump: u8,
-) -> ProgramResult {
+// Returns what's left in a vault's escrow to the owner's `refund` account and
+// closes the vault
+fn process_close_dca(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
-    let registry_info = next_account_info(account_info_iter)?;
-    let user_info = next_account_info(account_info_iter)?;
+    let dca_info = next_account_info(account_info_iter)?;
+    let escrow_info = next_account_info(account_info_iter)?;
+    let owner_info = next_account_info(account_info_iter)?;
+    let refund_info = next_account_info(account_info_iter)?;
+    let mint_in_info = next_account_info(account_info_iter)?;
     let token_program_info = next_account_info(account_info_iter)?;
+
+    let vault = load_dca_vault(program_id, dca_info, escrow_info)?;
+    if !owner_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if vault.owner != *owner_info.key {
+        return Err(SwapError::Unauthorized.into());
+    }
+    check_token_program(token_program_info, mint_in_info)?;
+    let escrowed = unpack_token_state::<spl_token::state::Account>(escrow_info, token_program_info.key)?.amount;
+
+    let dca_id_bytes = vault.dca_id.to_le_bytes();
+    let dca_seeds: &[&[u8]] = &[DCA_SEED, vault.pool.as_ref(), vault.owner.as_ref(), &dca_id_bytes, &[vault.bump]];
+    transfer_checked(token_program_info, escrow_info, mint_in_info, refund_info, dca_info, escrowed, &[dca_seeds])?;
+    close_escrow(token_program_info, dca_info, escrow_info, owner_info, dca_seeds)?;
+    close_program_account(dca_info, owner_info)
+}
+
+// Loads a DCA vault and checks `escrow_info` is its escrow
+fn load_dca_vault(
+    program_id: &Pubkey,
+    dca_info: &AccountInfo,
+    escrow_info: &AccountInfo,
+) -> Result<DcaVault, ProgramError> {
+    if dca_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let vault = *DcaVault::load(&dca_info.try_borrow_data()?)?;
+    check_escrow(program_id, dca_info.key, vault.escrow_bump, escrow_info)?;
+    Ok(vault)
+}
+
+// Creates a pool's farm and the vault its LP is staked in. Reward streams
+// are added to it afterwards.
+fn process_create_farm(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let config_info = next_account_info(account_info_iter)?;
+    let admin_info = next_account_info(account_info_iter)?;
+    let payer_info = next_account_info(account_info_iter)?;
+    let pool_info = next_account_info(account_info_iter)?;
+    let lp_mint_info = next_account_info(account_info_iter)?;
+    let farm_info = next_account_info(account_info_iter)?;
+    let stake_vault_info = next_account_info(account_info_iter)?;
+    let lp_token_program_info = next_account_info(account_info_iter)?;
     let system_program_info = next_account_info(account_info_iter)?;
     let rent_info = next_account_info(account_info_iter)?;
 
-    // Derive registry address and bump
-    let (registry_pubkey, expected_bump) = Pubkey::find_program_address(
-        &[POOL_REGISTRY_SEEDS, program_id.as_ref()],
-        program_id,
-    );
-
-    if registry_pubkey != *registry_info.key {
-        return Err(ProgramError::InvalidSeeds);
+    check_admin(program_id, config_info, admin_info, account_info_iter.as_slice())?;
+    if pool_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    load_pool(pool_info)?;
+    let (lp_mint_pubkey, _) = Pubkey::find_program_address(&[LP_MINT_SEED, pool_info.key.as_ref()], program_id);
+    if lp_mint_pubkey != *lp_mint_info.key {
+        return Err(SwapError::InvalidLpMint.into());
     }
+    check_lp_mint(pool_info.key, lp_token_program_info, lp_mint_info)?;
 
-    if bump != expected_bump {
+    let (farm_pubkey, bump) = Pubkey::find_program_address(&[FARM_SEED, pool_info.key.as_ref()], program_id);
+    if farm_pubkey != *farm_info.key {
         return Err(ProgramError::InvalidSeeds);
     }
+    if farm_info.lamports() > 0 {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
 
-    // Create registry account
-    let rent = Rent::from_account_info(rent_info)?;
-    let space = PoolRegistry::LEN;
-    let lamports = rent.minimum_balance(space);
-
-    let registry_signer_seeds: &[&[_]] = &[
-        POOL_REGISTRY_SEEDS,
-        program_id.as_ref(),
-        &[bump],
-    ];
+    let stake_vault_bump = find_farm_vault(program_id, farm_info.key, lp_mint_info.key, stake_vault_info)?;
 
-    invoke_signed(
-        &system_instruction::create_account(
-            user_info.key,
-            registry_info.key,
-            lamports,
-            space as u64,
-            program_id,
+    let rent = Rent::from_account_info(rent_info)?;
+    create_pda_account(
+        program_id,
+        payer_info,
+        farm_info,
+        system_program_info,
+        &rent,
+        Farm::LEN,
+        &[FARM_SEED, pool_info.key.as_ref(), &[bump]],
+    )?;
+    // Farm vaults are PDAs of the farm with the farm as authority
+    create_pda_account(
+        lp_token_program_info.key,
+        payer_info,
+        stake_vault_info,
+        system_program_info,
+        &rent,
+        token_account_len(lp_token_program_info.key, lp_mint_info)?,
+        &[FARM_VAULT_SEED, farm_pubkey.as_ref(), lp_mint_info.key.as_ref(), &[stake_vault_bump]],
+    )?;
+    invoke(
+        &create_initialize_account3_instruction(
+            lp_token_program_info.key,
+            stake_vault_info.key,
+            lp_mint_info.key,
+            &farm_pubkey,
         ),
-        &[
-            user_info.clone(),
-            registry_info.clone(),
-            system_program_info.clone(),
-        ],
-        &[registry_signer_seeds],
+        &[stake_vault_info.clone(), lp_mint_info.clone(), lp_token_program_info.clone()],
     )?;
 
-    // Initialize registry state
-    let registry = PoolRegistry {
-        pools: Vec::new(),
-        next_pool_id: 1, // Start from 1 for new pool IDs
-        total_pools: 0,
-        total_tvl: 0,
+    let farm = Farm {
+        header: Farm::new_header(),
+        bump,
+        stake_vault_bump,
+        reward_count: 0,
+        _padding: [0; 3],
+        pool: *pool_info.key,
+        lp_mint: *lp_mint_info.key,
+        lp_token_program: *lp_token_program_info.key,
+        total_weight: 0,
+        updated_at: Clock::get()?.unix_timestamp,
+        rewards: [FarmReward::zeroed(); MAX_FARM_REWARDS],
     };
-
-    PoolRegistry::pack(registry, &mut registry_info.data.borrow_mut())?;
+    Farm::pack(farm, &mut farm_info.data.borrow_mut())?;
 
     Ok(())
 }
 
-fn process_list_pools(
-    program_id: &Pubkey,
-    accounts: &[AccountInfo],
-) -> ProgramResult {
+// Adds a reward stream paying out of a new vault for `reward_mint`. Anyone
+// funds it by transferring tokens into the vault.
+fn process_add_farm_reward(program_id: &Pubkey, accounts: &[AccountInfo], schedule: RewardSchedule) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
-    let registry_info = next_account_info(account_info_iter)?;
-    let user_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+    let admin_info = next_account_info(account_info_iter)?;
+    let payer_info = next_account_info(account_info_iter)?;
+    let farm_info = next_account_info(account_info_iter)?;
+    let reward_mint_info = next_account_info(account_info_iter)?;
+    let reward_vault_info = next_account_info(account_info_iter)?;
     let token_program_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
 
-    let registry = PoolRegistry::unpack(&registry_info.data.borrow())?;
-
-    // Serialize pools to return
-    let mut pools_data = Vec::new();
-    for pool_info in registry.pools {
-        let mut pool_data = Vec::new();
-        pool_info.pack(&mut pool_data);
-        pools_data.push(pool_data);
+    check_admin(program_id, config_info, admin_info, account_info_iter.as_slice())?;
+    check_reward_schedule(&schedule)?;
+    let farm = load_farm(program_id, farm_info)?;
+    check_token_program(token_program_info, reward_mint_info)?;
+    // Stake and rewards can't share a vault
+    let listed = farm.rewards().iter().any(|reward| reward.mint == *reward_mint_info.key);
+    if *reward_mint_info.key == farm.lp_mint || listed {
+        return Err(ProgramError::InvalidArgument);
     }
+    if farm.rewards().len() == MAX_FARM_REWARDS {
+        return Err(SwapError::FarmRewardsFull.into());
+    }
+
+    let vault_bump = find_farm_vault(program_id, farm_info.key, reward_mint_info.key, reward_vault_info)?;
+
+    let rent = Rent::from_account_info(rent_info)?;
+    create_pda_account(
+        token_program_info.key,
+        payer_info,
+        reward_vault_info,
+        system_program_info,
+        &rent,
+        token_account_len(token_program_info.key, reward_mint_info)?,
+        &[FARM_VAULT_SEED, farm_info.key.as_ref(), reward_mint_info.key.as_ref(), &[vault_bump]],
+    )?;
+    invoke(
+        &create_initialize_account3_instruction(
+            token_program_info.key,
+            reward_vault_info.key,
+            reward_mint_info.key,
+            farm_info.key,
+        ),
+        &[reward_vault_info.clone(), reward_mint_info.clone(), token_program_info.clone()],
+    )?;
+
+    // The stream only emits from now on
+    let mut farm_data = farm_info.try_borrow_mut_data()?;
+    let farm = Farm::load_mut(&mut farm_data)?;
+    farm.accrue(Clock::get()?.unix_timestamp);
+    farm.rewards[farm.reward_count as usize] = FarmReward {
+        mint: *reward_mint_info.key,
+        token_program: *token_program_info.key,
+        emission_per_second: schedule.emission_per_second,
+        start_time: schedule.start_time,
+        end_time: schedule.end_time,
+        reward_per_share: [0; 16],
+        vault_bump,
+        _padding: [0; 7],
+    };
+    farm.reward_count += 1;
 
-    // Return the serialized pools
     Ok(())
 }
 
-fn process_get_pool_info(
+// Reschedules a stream from now on; what it emitted so far stays earned
+fn process_set_farm_reward_schedule(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
-    pool_id: u64,
+    reward_index: u8,
+    schedule: RewardSchedule,
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
-    let registry_info = next_account_info(account_info_iter)?;
-    let user_info = next_account_info(account_info_iter)?;
-    let token_program_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+    let admin_info = next_account_info(account_info_iter)?;
+    let farm_info = next_account_info(account_info_iter)?;
+
+    check_admin(program_id, config_info, admin_info, account_info_iter.as_slice())?;
+    check_reward_schedule(&schedule)?;
+    load_farm(program_id, farm_info)?;
+
+    let mut farm_data = farm_info.try_borrow_mut_data()?;
+    let farm = Farm::load_mut(&mut farm_data)?;
+    if reward_index >= farm.reward_count {
+        return Err(ProgramError::InvalidArgument);
+    }
+    farm.accrue(Clock::get()?.unix_timestamp);
+    let reward = &mut farm.rewards[reward_index as usize];
+    reward.emission_per_second = schedule.emission_per_second;
+    reward.start_time = schedule.start_time;
+    reward.end_time = schedule.end_time;
 
-    let registry = PoolRegistry::unpack(&registry_info.data.borrow())?;
+    Ok(())
+}
+
+// Moves LP from `source` into the farm, opening the owner's stake on first use
+fn process_stake_lp(program_id: &Pubkey, accounts: &[AccountInfo], amount: u64) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let farm_info = next_account_info(account_info_iter)?;
+    let lp_mint_info = next_account_info(account_info_iter)?;
+    let stake_vault_info = next_account_info(account_info_iter)?;
+    let owner_info = next_account_info(account_info_iter)?;
+    let source_info = next_account_info(account_info_iter)?;
+    let stake_info = next_account_info(account_info_iter)?;
+    let lp_token_program_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
+
+    let farm = load_farm(program_id, farm_info)?;
+    check_farm_lp_accounts(program_id, farm_info, &farm, lp_mint_info, stake_vault_info, lp_token_program_info)?;
+    if !owner_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if amount == 0 {
+        return Err(ProgramError::InvalidArgument);
+    }
 
-    // Find the pool by ID
-    let pool_info = registry.pools.iter()
-        .find(|p| p.pool_id == pool_id)
-        .ok_or(ProgramError::InvalidAccountData)?;
+    open_farm_stake(program_id, farm_info, owner_info, stake_info, system_program_info, rent_info)?;
 
-    // Serialize pool info to return
-    let mut pool_data = Vec::new();
-    pool_info.pack(&mut pool_data);
+    transfer_checked(lp_token_program_info, source_info, lp_mint_info, stake_vault_info, owner_info, amount, &[])?;
+    let received = received_amount(lp_mint_info, amount)?;
+
+    let mut farm_data = farm_info.try_borrow_mut_data()?;
+    let farm = Farm::load_mut(&mut farm_data)?;
+    let mut stake_data = stake_info.try_borrow_mut_data()?;
+    let stake = FarmStake::load_mut(&mut stake_data)?;
+    farm.accrue(Clock::get()?.unix_timestamp);
+    farm.settle(stake);
+    stake.amount = stake.amount.checked_add(received).ok_or(SwapError::MathOverflow)?;
+    farm.total_weight = farm.total_weight.checked_add(received).ok_or(SwapError::MathOverflow)?;
 
-    // Return the serialized pool info
     Ok(())
 }
 
-fn process_update_pool_fee(
-    program_id: &Pubkey,
-    accounts: &[AccountInfo],
-    pool_id: u64,
-    new_fee_rate: u16,
-) -> ProgramResult {
+// Returns staked LP to `destination`; rewards earned so far stay claimable
+fn process_unstake_lp(program_id: &Pubkey, accounts: &[AccountInfo], amount: u64) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
-    let registry_info = next_account_info(account_info_iter)?;
-    let user_info = next_account_info(account_info_iter)?;
-    let token_program_info = next_account_info(account_info_iter)?;
+    let farm_info = next_account_info(account_info_iter)?;
+    let lp_mint_info = next_account_info(account_info_iter)?;
+    let stake_vault_info = next_account_info(account_info_iter)?;
+    let owner_info = next_account_info(account_info_iter)?;
+    let destination_info = next_account_info(account_info_iter)?;
+    let stake_info = next_account_info(account_info_iter)?;
+    let lp_token_program_info = next_account_info(account_info_iter)?;
+
+    let farm = load_farm(program_id, farm_info)?;
+    check_farm_lp_accounts(program_id, farm_info, &farm, lp_mint_info, stake_vault_info, lp_token_program_info)?;
+    let stake = load_farm_stake(program_id, farm_info, stake_info, owner_info)?;
+    if amount == 0 {
+        return Err(ProgramError::InvalidArgument);
+    }
+    if amount > stake.amount {
+        return Err(ProgramError::InsufficientFunds);
+    }
+
+    {
+        let mut farm_data = farm_info.try_borrow_mut_data()?;
+        let farm = Farm::load_mut(&mut farm_data)?;
+        let mut stake_data = stake_info.try_borrow_mut_data()?;
+        let stake = FarmStake::load_mut(&mut stake_data)?;
+        farm.accrue(Clock::get()?.unix_timestamp);
+        farm.settle(stake);
+        stake.amount -= amount;
+        farm.total_weight -= amount;
+    }
 
-    let mut registry = PoolRegistry::unpack(&registry_info.data.borrow())?;
+    let farm_seeds: &[&[u8]] = &[FARM_SEED, farm.pool.as_ref(), &[farm.bump]];
+    transfer_checked(
+        lp_token_program_info,
+        stake_vault_info,
+        lp_mint_info,
+        destination_info,
+        farm_info,
+        amount,
+        &[farm_seeds],
+    )
+}
 
-    // Find the pool by ID
-    let pool_info = registry.pools.iter_mut()
-        .find(|p| p.pool_id == pool_id)
-        .ok_or(ProgramError::InvalidAccountData)?;
+// Pays the owner's pending rewards for each `[reward_mint, reward_vault,
+// destination, token_program]` group passed, up to what each vault holds.
+// The rest stays pending.
+fn process_claim_farm_rewards(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let farm_info = next_account_info(account_info_iter)?;
+    let stake_info = next_account_info(account_info_iter)?;
+    let owner_info = next_account_info(account_info_iter)?;
+
+    let farm = load_farm(program_id, farm_info)?;
+    load_farm_stake(program_id, farm_info, stake_info, owner_info)?;
+    let groups = account_info_iter.as_slice();
+    if !groups.len().is_multiple_of(4) {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
 
-    // Update fee rate
-    pool_info.fee_rate = new_fee_rate;
+    let mut pending = {
+        let mut farm_data = farm_info.try_borrow_mut_data()?;
+        let farm = Farm::load_mut(&mut farm_data)?;
+        let mut stake_data = stake_info.try_borrow_mut_data()?;
+        let stake = FarmStake::load_mut(&mut stake_data)?;
+        farm.accrue(Clock::get()?.unix_timestamp);
+        farm.settle(stake);
+        stake.pending
+    };
 
-    // Re-pack and update
-    PoolRegistry::pack(registry, &mut registry_info.data.borrow_mut())?;
+    let farm_seeds: &[&[u8]] = &[FARM_SEED, farm.pool.as_ref(), &[farm.bump]];
+    for group in groups.chunks(4) {
+        let [mint_info, vault_info, destination_info, token_program_info] = group else {
+            unreachable!();
+        };
+        let index = farm
+            .rewards()
+            .iter()
+            .position(|reward| reward.mint == *mint_info.key)
+            .ok_or(ProgramError::InvalidArgument)?;
+        let reward = &farm.rewards[index];
+        if *token_program_info.key != reward.token_program {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        check_farm_vault(program_id, farm_info.key, mint_info.key, reward.vault_bump, vault_info)?;
+
+        let balance = unpack_token_state::<spl_token::state::Account>(vault_info, token_program_info.key)?.amount;
+        let amount = pending[index].min(balance);
+        if amount > 0 {
+            let seeds = &[farm_seeds];
+            transfer_checked(token_program_info, vault_info, mint_info, destination_info, farm_info, amount, seeds)?;
+            pending[index] -= amount;
+        }
+    }
+
+    let mut stake_data = stake_info.try_borrow_mut_data()?;
+    FarmStake::load_mut(&mut stake_data)?.pending = pending;
 
     Ok(())
 }
 
-fn process_deactivate_pool(
-    program_id: &Pubkey,
-    accounts: &[AccountInfo],
-    pool_id: u64,
-) -> ProgramResult {
-    let account_info_iter = &mut accounts.iter();
-    let registry_info = next_account_info(account_info_iter)?;
-    let user_info = next_account_info(account_info_iter)?;
-    let token_program_info = next_account_info(account_info_iter)?;
+fn check_reward_schedule(schedule: &RewardSchedule) -> ProgramResult {
+    if schedule.end_time < schedule.start_time {
+        return Err(ProgramError::InvalidArgument);
+    }
+    Ok(())
+}
 
-    let mut registry = PoolRegistry::unpack(&registry_info.data.borrow())?;
+fn load_farm(program_id: &Pubkey, farm_info: &AccountInfo) -> Result<Farm, ProgramError> {
+    if farm_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Ok(*Farm::load(&farm_info.try_borrow_data()?)?)
+}
 
-    // Find the pool by ID
-    let pool_info = registry.pools.iter_mut()
-        .find(|p| p.pool_id == pool_id)
-        .ok_or(ProgramError::InvalidAccountData)?;
+// Loads the owner's stake in the farm at `farm_info`, signed by the owner
+fn load_farm_stake(
+    program_id: &Pubkey,
+    farm_info: &AccountInfo,
+    stake_info: &AccountInfo,
+    owner_info: &AccountInfo,
+) -> Result<FarmStake, ProgramError> {
+    if stake_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let stake = *FarmStake::load(&stake_info.try_borrow_data()?)?;
+    if stake.farm != *farm_info.key {
+        return Err(ProgramError::InvalidArgument);
+    }
+    if !owner_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if stake.owner != *owner_info.key {
+        return Err(SwapError::Unauthorized.into());
+    }
+    Ok(stake)
+}
 
-    // Deactivate pool
-    pool_info.is_active = false;
+// Creates the owner's stake in the farm unless it exists, and loads it
+fn open_farm_stake<'a>(
+    program_id: &Pubkey,
+    farm_info: &AccountInfo<'a>,
+    owner_info: &AccountInfo<'a>,
+    stake_info: &AccountInfo<'a>,
+    system_program_info: &AccountInfo<'a>,
+    rent_info: &AccountInfo<'a>,
+) -> Result<FarmStake, ProgramError> {
+    if stake_info.owner != program_id {
+        let stake_seeds: &[&[u8]] = &[FARM_STAKE_SEED, farm_info.key.as_ref(), owner_info.key.as_ref()];
+        let (stake_pubkey, bump) = Pubkey::find_program_address(stake_seeds, program_id);
+        if stake_pubkey != *stake_info.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        let rent = Rent::from_account_info(rent_info)?;
+        create_pda_account(
+            program_id,
+            owner_info,
+            stake_info,
+            system_program_info,
+            &rent,
+            FarmStake::LEN,
+            &[stake_seeds, &[&[bump]]].concat(),
+        )?;
+        // Settling the empty stake starts it at the current accumulators
+        let stake = FarmStake {
+            header: FarmStake::new_header(),
+            bump,
+            _padding: [0; 5],
+            farm: *farm_info.key,
+            owner: *owner_info.key,
+            amount: 0,
+            locked_weight: 0,
+            reward_per_share_paid: [[0; 16]; MAX_FARM_REWARDS],
+            pending: [0; MAX_FARM_REWARDS],
+        };
+        FarmStake::pack(stake, &mut stake_info.data.borrow_mut())?;
+    }
+    load_farm_stake(program_id, farm_info, stake_info, owner_info)
+}
 
-    // Re-pack and update
-    PoolRegistry::pack(registry, &mut registry_info.data.borrow_mut())?;
+fn check_farm_lp_accounts(
+    program_id: &Pubkey,
+    farm_info: &AccountInfo,
+    farm: &Farm,
+    lp_mint_info: &AccountInfo,
+    stake_vault_info: &AccountInfo,
+    lp_token_program_info: &AccountInfo,
+) -> ProgramResult {
+    if *lp_mint_info.key != farm.lp_mint {
+        return Err(SwapError::InvalidLpMint.into());
+    }
+    if *lp_token_program_info.key != farm.lp_token_program {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    check_farm_vault(program_id, farm_info.key, lp_mint_info.key, farm.stake_vault_bump, stake_vault_info)
+}
 
+fn check_farm_vault(
+    program_id: &Pubkey,
+    farm: &Pubkey,
+    mint: &Pubkey,
+    bump: u8,
+    vault_info: &AccountInfo,
+) -> ProgramResult {
+    let vault = Pubkey::create_program_address(&[FARM_VAULT_SEED, farm.as_ref(), mint.as_ref(), &[bump]], program_id)
+        .map_err(|_| ProgramError::InvalidSeeds)?;
+    if vault != *vault_info.key {
+        return Err(SwapError::InvalidVault.into());
+    }
     Ok(())
 }
 
-fn process_legacy_init_pool(
+// Checks `vault_info` is the farm's token account address for `mint` and
+// returns its bump
+fn find_farm_vault(
+    program_id: &Pubkey,
+    farm: &Pubkey,
+    mint: &Pubkey,
+    vault_info: &AccountInfo,
+) -> Result<u8, ProgramError> {
+    let (vault, bump) = Pubkey::find_program_address(&[FARM_VAULT_SEED, farm.as_ref(), mint.as_ref()], program_id);
+    if vault != *vault_info.key {
+        return Err(SwapError::InvalidVault.into());
+    }
+    Ok(bump)
+}
+
+// Escrows LP in the owner's lock for the pool until `unlock_time`, opening
+// the lock on first use. Locking more or extending re-weighs the whole lock
+// at the time left, and the unlock time never moves earlier. Passing the
+// pool's farm and the owner's stake in it after the other accounts joins the
+// lock to the farm at its boosted weight; a lock that joined must pass them.
+fn process_lock_liquidity(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
-    amount_a: u64,
-    amount_b: u64,
+    lp_amount: u64,
+    unlock_time: i64,
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
     let pool_info = next_account_info(account_info_iter)?;
-    let token_a_info = next_account_info(account_info_iter)?;
-    let token_b_info = next_account_info(account_info_iter)?;
-    let vault_a_info = next_account_info(account_info_iter)?;
-    let vault_b_info = next_account_info(account_info_iter)?;
     let lp_mint_info = next_account_info(account_info_iter)?;
-    let user_info = next_account_info(account_info_iter)?;
-    let user_token_a_info = next_account_info(account_info_iter)?;
-    let user_token_b_info = next_account_info(account_info_iter)?;
-    let user_lp_info = next_account_info(account_info_iter)?;
-    let token_program_info = next_account_info(account_info_iter)?;
+    let owner_info = next_account_info(account_info_iter)?;
+    let source_info = next_account_info(account_info_iter)?;
+    let lock_info = next_account_info(account_info_iter)?;
+    let escrow_info = next_account_info(account_info_iter)?;
+    let lp_token_program_info = next_account_info(account_info_iter)?;
     let system_program_info = next_account_info(account_info_iter)?;
     let rent_info = next_account_info(account_info_iter)?;
+    let farm_accounts = next_lock_farm_accounts(account_info_iter)?;
 
-    // Derive pool address and bump
-    let (pool_pubkey, bump) = Pubkey::find_program_address(
-        &[b"pool", token_a_info.key.as_ref(), token_b_info.key.as_ref()],
-        program_id,
-    );
+    if pool_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    load_pool(pool_info)?;
+    let (lp_mint_pubkey, _) = Pubkey::find_program_address(&[LP_MINT_SEED, pool_info.key.as_ref()], program_id);
+    if lp_mint_pubkey != *lp_mint_info.key {
+        return Err(SwapError::InvalidLpMint.into());
+    }
+    check_lp_mint(pool_info.key, lp_token_program_info, lp_mint_info)?;
+    if !owner_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let now = Clock::get()?.unix_timestamp;
+    if unlock_time <= now {
+        return Err(ProgramError::InvalidArgument);
+    }
 
-    if pool_pubkey != *pool_info.key {
+    let lock_seeds: &[&[u8]] = &[LP_LOCK_SEED, pool_info.key.as_ref(), owner_info.key.as_ref()];
+    let (lock_pubkey, bump) = Pubkey::find_program_address(lock_seeds, program_id);
+    if lock_pubkey != *lock_info.key {
         return Err(ProgramError::InvalidSeeds);
     }
+    if lock_info.owner != program_id {
+        if lp_amount == 0 {
+            return Err(ProgramError::InvalidArgument);
+        }
+        let (escrow_pubkey, escrow_bump) =
+            Pubkey::find_program_address(&[ORDER_ESCROW_SEED, lock_pubkey.as_ref()], program_id);
+        if escrow_pubkey != *escrow_info.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
 
-    // Create pool account
-    let rent = Rent::from_account_info(rent_info)?;
-    let space = Pool::LEN;
-    let lamports = rent.minimum_balance(space);
-
-    let pool_signer_seeds: &[&[_]] = &[
-        b"pool",
-        token_a_info.key.as_ref(),
-        token_b_info.key.as_ref(),
-        &[bump],
-    ];
-
-    invoke_signed(
-        &system_instruction::create_account(
-            user_info.key,
-            pool_info.key,
-            lamports,
-            space as u64,
+        let rent = Rent::from_account_info(rent_info)?;
+        create_pda_account(
             program_id,
-        ),
-        &[
-            user_info.clone(),
-            pool_info.clone(),
-            system_program_info.clone(),
-        ],
-        &[pool_signer_seeds],
-    )?;
+            owner_info,
+            lock_info,
+            system_program_info,
+            &rent,
+            LiquidityLock::LEN,
+            &[lock_seeds, &[&[bump]]].concat(),
+        )?;
+        create_pda_account(
+            lp_token_program_info.key,
+            owner_info,
+            escrow_info,
+            system_program_info,
+            &rent,
+            token_account_len(lp_token_program_info.key, lp_mint_info)?,
+            &[ORDER_ESCROW_SEED, lock_pubkey.as_ref(), &[escrow_bump]],
+        )?;
+        invoke(
+            &create_initialize_account3_instruction(
+                lp_token_program_info.key,
+                escrow_info.key,
+                lp_mint_info.key,
+                &lock_pubkey,
+            ),
+            &[escrow_info.clone(), lp_mint_info.clone(), lp_token_program_info.clone()],
+        )?;
 
-    // Create vault accounts as regular accounts (not PDAs)
-    // Create vault A account
-    invoke_signed(
-        &system_instruction::create_account(
-            user_info.key,
-            vault_a_info.key,
-            rent.minimum_balance(165), // Token account size
-            165,
-            &Pubkey::from_str(GORBCHAIN_SPL_TOKEN_PROGRAM).unwrap(),
-        ),
-        &[
-            user_info.clone(),
-            vault_a_info.clone(),
-            system_program_info.clone(),
-        ],
-        &[pool_signer_seeds],
-    )?;
+        let lock = LiquidityLock {
+            header: LiquidityLock::new_header(),
+            bump,
+            escrow_bump,
+            _padding: [0; 4],
+            amount: 0,
+            pool: *pool_info.key,
+            owner: *owner_info.key,
+            farm: Pubkey::default(),
+            weight: 0,
+            locked_at: now,
+            unlock_time,
+        };
+        LiquidityLock::pack(lock, &mut lock_info.data.borrow_mut())?;
+    }
+    let lock = load_liquidity_lock(program_id, lock_info, escrow_info)?;
+    if unlock_time < lock.unlock_time {
+        return Err(ProgramError::InvalidArgument);
+    }
 
-    // Create vault B account
-    invoke_signed(
-        &system_instruction::create_account(
-            user_info.key,
-            vault_b_info.key,
-            rent.minimum_balance(165), // Token account size
-            165,
-            &Pubkey::from_str(GORBCHAIN_SPL_TOKEN_PROGRAM).unwrap(),
-        ),
-        &[
-            user_info.clone(),
-            vault_b_info.clone(),
-            system_program_info.clone(),
-        ],
-        &[pool_signer_seeds],
-    )?;
+    let received = if lp_amount > 0 {
+        transfer_checked(lp_token_program_info, source_info, lp_mint_info, escrow_info, owner_info, lp_amount, &[])?;
+        received_amount(lp_mint_info, lp_amount)?
+    } else {
+        0
+    };
+    let amount = lock.amount.checked_add(received).ok_or(SwapError::MathOverflow)?;
+    let weight = quote::locked_weight(amount, unlock_time - now);
+
+    let joined = lock.farm != Pubkey::default();
+    let farm = match farm_accounts {
+        Some((farm_info, stake_info)) => {
+            let farm = load_farm(program_id, farm_info)?;
+            if farm.pool != *pool_info.key || (joined && lock.farm != *farm_info.key) {
+                return Err(ProgramError::InvalidArgument);
+            }
+            open_farm_stake(program_id, farm_info, owner_info, stake_info, system_program_info, rent_info)?;
+            let old_weight = if joined { expire_lock_boost(farm_info, stake_info, &lock, now)? } else { 0 };
+            reweigh_locked_stake(farm_info, stake_info, now, old_weight, weight)?;
+            *farm_info.key
+        }
+        None if joined => return Err(ProgramError::NotEnoughAccountKeys),
+        None => Pubkey::default(),
+    };
 
-    // Initialize vault A as token account
-    invoke_signed(
-        &create_initialize_account_instruction(
-            vault_a_info.key,
-            token_a_info.key,
-            pool_info.key, // Authority is pool
-        ),
-        &[
-            vault_a_info.clone(),
-            token_a_info.clone(),
-            pool_info.clone(), // Pool is signer
-            rent_info.clone(),
-        ],
-        &[pool_signer_seeds],
-    )?;
+    let mut lock_data = lock_info.try_borrow_mut_data()?;
+    let lock = LiquidityLock::load_mut(&mut lock_data)?;
+    lock.amount = amount;
+    lock.farm = farm;
+    lock.weight = weight;
+    lock.locked_at = now;
+    lock.unlock_time = unlock_time;
 
-    // Initialize vault B as token account
-    invoke_signed(
-        &create_initialize_account_instruction(
-            vault_b_info.key,
-            token_b_info.key,
-            pool_info.key, // Authority is pool
-        ),
-        &[
-            vault_b_info.clone(),
-            token_b_info.clone(),
-            pool_info.clone(), // Pool is signer
-            rent_info.clone(),
-        ],
-        &[pool_signer_seeds],
-    )?;
+    Ok(())
+}
 
-    // Transfer tokens to vaults
-    invoke(
-        &create_transfer_instruction(
-            user_token_a_info.key,
-            vault_a_info.key,
-            user_info.key,
-            amount_a,
-        ),
-        &[
-            user_token_a_info.clone(),
-            vault_a_info.clone(),
-            user_info.clone(),
-            token_program_info.clone(),
-        ],
-    )?;
+// Once its unlock time has passed, returns a lock's LP to `destination`,
+// takes its weight out of the farm it joined and closes it
+fn process_unlock_liquidity(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let lock_info = next_account_info(account_info_iter)?;
+    let escrow_info = next_account_info(account_info_iter)?;
+    let owner_info = next_account_info(account_info_iter)?;
+    let destination_info = next_account_info(account_info_iter)?;
+    let lp_mint_info = next_account_info(account_info_iter)?;
+    let lp_token_program_info = next_account_info(account_info_iter)?;
+    let farm_accounts = next_lock_farm_accounts(account_info_iter)?;
 
-    invoke(
-        &create_transfer_instruction(
-            user_token_b_info.key,
-            vault_b_info.key,
-            user_info.key,
-            amount_b,
-        ),
-        &[
-            user_token_b_info.clone(),
-            vault_b_info.clone(),
-            user_info.clone(),
-            token_program_info.clone(),
-        ],
-    )?;
+    let lock = load_liquidity_lock(program_id, lock_info, escrow_info)?;
+    if !owner_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if lock.owner != *owner_info.key {
+        return Err(SwapError::Unauthorized.into());
+    }
+    check_token_program(lp_token_program_info, lp_mint_info)?;
+    let now = Clock::get()?.unix_timestamp;
+    if now < lock.unlock_time {
+        return Err(SwapError::LiquidityLocked.into());
+    }
 
-    // Calculate liquidity
-    let liquidity: u64 = (amount_a as u128)
-        .checked_mul(amount_b as u128)
-        .unwrap()
-        .integer_sqrt() as u64;
+    if lock.farm != Pubkey::default() {
+        let (farm_info, stake_info) = farm_accounts.ok_or(ProgramError::NotEnoughAccountKeys)?;
+        if *farm_info.key != lock.farm {
+            return Err(ProgramError::InvalidArgument);
+        }
+        load_farm(program_id, farm_info)?;
+        load_farm_stake(program_id, farm_info, stake_info, owner_info)?;
+        let weight = expire_lock_boost(farm_info, stake_info, &lock, now)?;
+        reweigh_locked_stake(farm_info, stake_info, now, weight, 0)?;
+    }
 
-    // Mint LP tokens
-    invoke_signed(
-        &create_mint_to_instruction(
-            lp_mint_info.key,
-            user_lp_info.key,
-            pool_info.key,
-            liquidity,
-        ),
-        &[
-            lp_mint_info.clone(),
-            user_lp_info.clone(),
-            pool_info.clone(),
-            token_program_info.clone(),
-        ],
-        &[pool_signer_seeds],
+    let escrowed = unpack_token_state::<spl_token::state::Account>(escrow_info, lp_token_program_info.key)?.amount;
+    let lock_seeds: &[&[u8]] = &[LP_LOCK_SEED, lock.pool.as_ref(), lock.owner.as_ref(), &[lock.bump]];
+    transfer_checked(
+        lp_token_program_info,
+        escrow_info,
+        lp_mint_info,
+        destination_info,
+        lock_info,
+        escrowed,
+        &[lock_seeds],
     )?;
+    close_escrow(lp_token_program_info, lock_info, escrow_info, owner_info, lock_seeds)?;
+    close_program_account(lock_info, owner_info)
+}
 
-    // Initialize pool state
-    let pool = Pool {
-        pool_id: 0, // Legacy pools don't have a global ID, so we'll set it to 0
-        pool_type: PoolType::Standard,
-        token_a: *token_a_info.key,
-        token_b: *token_b_info.key,
-        bump,
-        fee_rate: 30,
-        reserve_a: amount_a,
-        reserve_b: amount_b,
-        total_lp_supply: liquidity,
-        created_at: solana_program::clock::Clock::get()?.unix_timestamp,
-        is_active: true,
-    };
+// Drops an expired lock's weight in the farm it joined back to its LP
+// amount, so the boost stops at `unlock_time`. Anyone can call it.
+fn process_expire_liquidity_lock(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let lock_info = next_account_info(account_info_iter)?;
+    let farm_info = next_account_info(account_info_iter)?;
+    let stake_info = next_account_info(account_info_iter)?;
 
-    Pool::pack(pool, &mut pool_info.data.borrow_mut())?;
+    if lock_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let lock = *LiquidityLock::load(&lock_info.try_borrow_data()?)?;
+    if lock.farm == Pubkey::default() || lock.farm != *farm_info.key {
+        return Err(ProgramError::InvalidArgument);
+    }
+    load_farm(program_id, farm_info)?;
+    if stake_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let stake = *FarmStake::load(&stake_info.try_borrow_data()?)?;
+    if stake.farm != *farm_info.key || stake.owner != lock.owner {
+        return Err(ProgramError::InvalidArgument);
+    }
+    let now = Clock::get()?.unix_timestamp;
+    if now < lock.unlock_time {
+        return Err(SwapError::LiquidityLocked.into());
+    }
+    if lock.weight == lock.amount {
+        return Err(ProgramError::InvalidArgument);
+    }
 
+    let weight = expire_lock_boost(farm_info, stake_info, &lock, now)?;
+    LiquidityLock::load_mut(&mut lock_info.try_borrow_mut_data()?)?.weight = weight;
     Ok(())
 }
 
-fn process_legacy_add_liquidity(
+// Loads a liquidity lock and checks `escrow_info` is its escrow
+fn load_liquidity_lock(
     program_id: &Pubkey,
-    accounts: &[AccountInfo],
-    amount_a: u64,
-    amount_b: u64,
-) -> ProgramResult {
-    let account_info_iter = &mut accounts.iter();
-    let pool_info = next_account_info(account_info_iter)?;
-    let token_a_info = next_account_info(account_info_iter)?;
-    let token_b_info = next_account_info(account_info_iter)?;
-    let vault_a_info = next_account_info(account_info_iter)?;
-    let vault_b_info = next_account_info(account_info_iter)?;
-    let lp_mint_info = next_account_info(account_info_iter)?;
-    let user_token_a_info = next_account_info(account_info_iter)?;
-    let user_token_b_info = next_account_info(account_info_iter)?;
-    let user_lp_info = next_account_info(account_info_iter)?;
-    let user_info = next_account_info(account_info_iter)?;
-    let token_program_info = next_account_info(account_info_iter)?;
-
-    let mut pool = Pool::unpack(&pool_info.data.borrow())?;
+    lock_info: &AccountInfo,
+    escrow_info: &AccountInfo,
+) -> Result<LiquidityLock, ProgramError> {
+    if lock_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let lock = *LiquidityLock::load(&lock_info.try_borrow_data()?)?;
+    check_escrow(program_id, lock_info.key, lock.escrow_bump, escrow_info)?;
+    Ok(lock)
+}
 
-    // Verify pool seeds
-    let (pool_pubkey, _) = Pubkey::find_program_address(
-        &[b"pool", token_a_info.key.as_ref(), token_b_info.key.as_ref()],
-        program_id,
-    );
+// The optional `[farm, stake]` pair at the end of a lock instruction's accounts
+fn next_lock_farm_accounts<'a, 'b>(
+    account_info_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+) -> Result<Option<(&'a AccountInfo<'b>, &'a AccountInfo<'b>)>, ProgramError> {
+    let Some(farm_info) = account_info_iter.next() else {
+        return Ok(None);
+    };
+    Ok(Some((farm_info, next_account_info(account_info_iter)?)))
+}
 
-    if pool_pubkey != *pool_info.key {
-        return Err(ProgramError::InvalidSeeds);
+// The weight a joined lock counts for in the farm at `now`. Once it has
+// expired, its boosted weight is swapped for its amount as of `unlock_time`,
+// or as of the farm's last update if that came later.
+fn expire_lock_boost(
+    farm_info: &AccountInfo,
+    stake_info: &AccountInfo,
+    lock: &LiquidityLock,
+    now: i64,
+) -> Result<u64, ProgramError> {
+    if now < lock.unlock_time || lock.weight == lock.amount {
+        return Ok(lock.weight);
     }
+    reweigh_locked_stake(farm_info, stake_info, lock.unlock_time, lock.weight, lock.amount)?;
+    Ok(lock.amount)
+}
 
-    let reserve_a = pool.reserve_a;
-    let reserve_b = pool.reserve_b;
-    let supply = pool.total_lp_supply;
+// Swaps a lock's `old_weight` in the stake and farm for `new_weight` at `at`,
+// after settling what the stake earned until then. The farm's rewards since
+// `at` accrue at the new weight, unless it was already updated past `at`.
+fn reweigh_locked_stake(
+    farm_info: &AccountInfo,
+    stake_info: &AccountInfo,
+    at: i64,
+    old_weight: u64,
+    new_weight: u64,
+) -> ProgramResult {
+    let mut farm_data = farm_info.try_borrow_mut_data()?;
+    let farm = Farm::load_mut(&mut farm_data)?;
+    let mut stake_data = stake_info.try_borrow_mut_data()?;
+    let stake = FarmStake::load_mut(&mut stake_data)?;
+    farm.accrue(at);
+    farm.settle(stake);
+    stake.locked_weight = (stake.locked_weight - old_weight).checked_add(new_weight).ok_or(SwapError::MathOverflow)?;
+    farm.total_weight = (farm.total_weight - old_weight).checked_add(new_weight).ok_or(SwapError::MathOverflow)?;
+    Ok(())
+}
 
-    // Calculate final amounts maintaining ratio
-    let (final_amount_a, final_amount_b) = if reserve_a > 0 && reserve_b > 0 {
-        let required_b = (amount_a as u128)
-            .checked_mul(reserve_b as u128).unwrap()
-            .checked_div(reserve_a as u128).unwrap() as u64;
-        if required_b <= amount_b {
-            (amount_a, required_b)
-        } else {
-            let required_a = (amount_b as u128)
-                .checked_mul(reserve_a as u128).unwrap()
-                .checked_div(reserve_b as u128).unwrap() as u64;
-            (required_a, amount_b)
-        }
-    } else {
-        (amount_a, amount_b)
-    };
+fn load_pending_change(program_id: &Pubkey, pending_info: &AccountInfo) -> Result<PendingChange, ProgramError> {
+    if pending_info.owner != program_id {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    Ok(*PendingChange::load(&pending_info.try_borrow_data()?)?)
+}
 
-    // Transfer tokens to vaults
-    invoke(
-        &create_transfer_instruction(
-            user_token_a_info.key,
-            vault_a_info.key,
-            user_info.key,
-            final_amount_a,
-        ),
-        &[
-            user_token_a_info.clone(),
-            vault_a_info.clone(),
-            user_info.clone(),
-            token_program_info.clone(),
-        ],
-    )?;
+// Moves a program-owned account's lamports to `destination` and hands the
+// emptied account back to the system program
+fn close_program_account(account_info: &AccountInfo, destination_info: &AccountInfo) -> ProgramResult {
+    let lamports = destination_info
+        .lamports()
+        .checked_add(account_info.lamports())
+        .ok_or(SwapError::MathOverflow)?;
+    **destination_info.try_borrow_mut_lamports()? = lamports;
+    **account_info.try_borrow_mut_lamports()? = 0;
+    account_info.realloc(0, false)?;
+    account_info.assign(&solana_program::system_program::id());
+    Ok(())
+}
 
-    invoke(
-        &create_transfer_instruction(
-            user_token_b_info.key,
-            vault_b_info.key,
-            user_info.key,
-            final_amount_b,
-        ),
-        &[
-            user_token_b_info.clone(),
-            vault_b_info.clone(),
-            user_info.clone(),
-            token_program_info.clone(),
-        ],
-    )?;
+fn emit_event(event: &GovernanceEvent) {
+    sol_log_data(&[&event.try_to_vec().unwrap()]);
+}
 
-    // Calculate liquidity to mint
-    let liquidity = if supply == 0 {
-        (final_amount_a as u128)
-            .checked_mul(final_amount_b as u128).unwrap()
-            .integer_sqrt() as u64
-    } else {
-        (final_amount_a as u128)
-            .checked_mul(supply as u128).unwrap()
-            .checked_div(reserve_a as u128).unwrap() as u64
-    };
+fn check_config(program_id: &Pubkey, config_info: &AccountInfo) -> ProgramResult {
+    let (config_pubkey, _) = Pubkey::find_program_address(&[CONFIG_SEED], program_id);
+    if config_pubkey != *config_info.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if config_info.owner != program_id {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    Ok(())
+}
 
-    // Mint LP tokens
-    let pool_signer_seeds: &[&[_]] = &[
-        b"pool",
-        pool.token_a.as_ref(),
-        pool.token_b.as_ref(),
-        &[pool.bump],
-    ];
+// Splits the program config off the front of a pausable instruction's
+// accounts and refuses `action` while it is paused. Until the config is
+// created nothing can be paused.
+fn check_not_paused<'a, 'b>(
+    program_id: &Pubkey,
+    accounts: &'a [AccountInfo<'b>],
+    action: u8,
+) -> Result<&'a [AccountInfo<'b>], ProgramError> {
+    let (config_info, accounts) = accounts.split_first().ok_or(ProgramError::NotEnoughAccountKeys)?;
+    let (config_pubkey, _) = Pubkey::find_program_address(&[CONFIG_SEED], program_id);
+    if config_pubkey != *config_info.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if config_info.owner == program_id && ProgramConfig::load(&config_info.try_borrow_data()?)?.is_paused(action) {
+        return Err(SwapError::ProgramPaused.into());
+    }
+    Ok(accounts)
+}
+
+// Admin instructions must be signed by the admin recorded in the config. A
+// multisig admin is passed unsigned, with its members' signatures among
+// `signer_infos`, the accounts after the instruction's own.
+fn check_admin(
+    program_id: &Pubkey,
+    config_info: &AccountInfo,
+    admin_info: &AccountInfo,
+    signer_infos: &[AccountInfo],
+) -> ProgramResult {
+    check_config(program_id, config_info)?;
+    if ProgramConfig::load(&config_info.try_borrow_data()?)?.admin != *admin_info.key {
+        return Err(SwapError::Unauthorized.into());
+    }
+    if admin_info.owner == program_id {
+        return load_multisig(program_id, admin_info)?.check_signers(signer_infos);
+    }
+    if !admin_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    Ok(())
+}
 
+// Upgrade authority recorded in the program's ProgramData account, which the
+// upgradeable loader keeps at a PDA of the program ID
+fn upgrade_authority(program_id: &Pubkey, program_data_info: &AccountInfo) -> Result<Option<Pubkey>, ProgramError> {
+    let (program_data_pubkey, _) =
+        Pubkey::find_program_address(&[program_id.as_ref()], &solana_program::bpf_loader_upgradeable::id());
+    if program_data_pubkey != *program_data_info.key
+        || *program_data_info.owner != solana_program::bpf_loader_upgradeable::id()
+    {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    // Bincode `UpgradeableLoaderState::ProgramData { slot, upgrade_authority_address }`:
+    // a u32 variant tag, the u64 slot, then an option tag and the key
+    let data = program_data_info.try_borrow_data()?;
+    let header = data.get(..45).ok_or(ProgramError::InvalidAccountData)?;
+    if header[..4] != 3u32.to_le_bytes() {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    match header[12] {
+        0 => Ok(None),
+        1 => Ok(Some(Pubkey::try_from(&header[13..45]).unwrap())),
+        _ => Err(ProgramError::InvalidAccountData),
+    }
+}
+
+// Creates a PDA of this program owned by `owner`
+fn create_pda_account<'a>(
+    owner: &Pubkey,
+    payer_info: &AccountInfo<'a>,
+    account_info: &AccountInfo<'a>,
+    system_program_info: &AccountInfo<'a>,
+    rent: &Rent,
+    space: usize,
+    signer_seeds: &[&[u8]],
+) -> ProgramResult {
     invoke_signed(
-        &create_mint_to_instruction(
-            lp_mint_info.key,
-            user_lp_info.key,
-            pool_info.key,
-            liquidity,
+        &system_instruction::create_account(
+            payer_info.key,
+            account_info.key,
+            rent.minimum_balance(space),
+            space as u64,
+            owner,
         ),
         &[
-            lp_mint_info.clone(),
-            user_lp_info.clone(),
-            pool_info.clone(),
-            token_program_info.clone(),
+            payer_info.clone(),
+            account_info.clone(),
+            system_program_info.clone(),
         ],
-        &[pool_signer_seeds],
-    )?;
-
-    // Update pool state
-    pool.reserve_a = pool.reserve_a.checked_add(final_amount_a).unwrap();
-    pool.reserve_b = pool.reserve_b.checked_add(final_amount_b).unwrap();
-    pool.total_lp_supply = pool.total_lp_supply.checked_add(liquidity).unwrap();
-
-    Pool::pack(pool, &mut pool_info.data.borrow_mut())?;
-
-    Ok(())
+        &[signer_seeds],
+    )
 }
 
-fn process_legacy_remove_liquidity(
-    program_id: &Pubkey,
-    accounts: &[AccountInfo],
-    lp_amount: u64,
-) -> ProgramResult {
+// Converts a pool written in a pre-versioned layout to the current one. The
+// account is reallocated to `Pool::LEN` and the payer tops up its rent.
+fn process_migrate_pool(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
     let pool_info = next_account_info(account_info_iter)?;
+    let payer_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
     let token_a_info = next_account_info(account_info_iter)?;
     let token_b_info = next_account_info(account_info_iter)?;
+    let legacy_vault_a_info = next_account_info(account_info_iter)?;
+    let legacy_vault_b_info = next_account_info(account_info_iter)?;
     let vault_a_info = next_account_info(account_info_iter)?;
     let vault_b_info = next_account_info(account_info_iter)?;
-    let lp_mint_info = next_account_info(account_info_iter)?;
-    let user_lp_info = next_account_info(account_info_iter)?;
-    let user_token_a_info = next_account_info(account_info_iter)?;
-    let user_token_b_info = next_account_info(account_info_iter)?;
-    let user_info = next_account_info(account_info_iter)?;
-    let token_program_info = next_account_info(account_info_iter)?;
 
-    let mut pool = Pool::unpack(&pool_info.data.borrow())?;
+    if pool_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    if !payer_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
 
-    // Verify pool seeds
-    let (pool_pubkey, _) = Pubkey::find_program_address(
-        &[b"pool", token_a_info.key.as_ref(), token_b_info.key.as_ref()],
-        program_id,
-    );
+    let mut pool: Pool = {
+        let data = pool_info.try_borrow_data()?;
+        match data.len() {
+            LegacySinglePool::LEN => LegacyBorshPool::from(
+                LegacySinglePool::try_from_slice(&data).map_err(|_| ProgramError::InvalidAccountData)?,
+            )
+            .into(),
+            LegacyBorshPool::LEN => LegacyBorshPool::try_from_slice(&data)
+                .map_err(|_| ProgramError::InvalidAccountData)?
+                .into(),
+            Pool::LEN => {
+                Pool::load(&data)?;
+                return Err(ProgramError::AccountAlreadyInitialized);
+            }
+            _ => return Err(ProgramError::InvalidAccountData),
+        }
+    };
 
+    // Only the pool PDA for its own mints can be rewritten as a pool
+    let pool_pubkey = Pubkey::create_program_address(
+        &[b"pool", pool.token_a.as_ref(), pool.token_b.as_ref(), &[pool.bump]],
+        program_id,
+    )
+    .map_err(|_| ProgramError::InvalidSeeds)?;
     if pool_pubkey != *pool_info.key {
         return Err(ProgramError::InvalidSeeds);
     }
+    // Older layouts had no price accumulators; they start from the migration
+    pool.price_cumulative_updated_at = Clock::get()?.unix_timestamp;
 
-    let reserve_a = pool.reserve_a;
-    let reserve_b = pool.reserve_b;
-    let supply = pool.total_lp_supply;
+    // Legacy vaults were keypair accounts; their balances move to the vault
+    // PDAs every handler checks, and the emptied accounts are closed
+    let rent = Rent::from_account_info(rent_info)?;
+    let pool_seeds: &[&[u8]] = &[b"pool", pool.token_a.as_ref(), pool.token_b.as_ref(), &[pool.bump]];
+    if *token_program_info.key != pool.token_program_a {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    for (mint_info, legacy_vault_info, vault_info, mint) in [
+        (token_a_info, legacy_vault_a_info, vault_a_info, pool.token_a),
+        (token_b_info, legacy_vault_b_info, vault_b_info, pool.token_b),
+    ] {
+        if *mint_info.key != mint {
+            return Err(ProgramError::InvalidArgument);
+        }
+        let legacy_vault = unpack_token_state::<spl_token::state::Account>(legacy_vault_info, token_program_info.key)?;
+        if legacy_vault.mint != mint || legacy_vault.owner != pool_pubkey {
+            return Err(SwapError::InvalidVault.into());
+        }
+        let (vault_pubkey, vault_bump) =
+            Pubkey::find_program_address(&[VAULT_SEED, pool_pubkey.as_ref(), mint.as_ref()], program_id);
+        if vault_pubkey != *vault_info.key {
+            return Err(SwapError::InvalidVault.into());
+        }
+        if legacy_vault_info.key == vault_info.key {
+            continue;
+        }
+        if vault_info.owner != token_program_info.key {
+            create_pda_account(
+                token_program_info.key,
+                payer_info,
+                vault_info,
+                system_program_info,
+                &rent,
+                token_account_len(token_program_info.key, mint_info)?,
+                &[VAULT_SEED, pool_pubkey.as_ref(), mint.as_ref(), &[vault_bump]],
+            )?;
+            invoke(
+                &create_initialize_account3_instruction(token_program_info.key, vault_info.key, &mint, &pool_pubkey),
+                &[vault_info.clone(), mint_info.clone(), token_program_info.clone()],
+            )?;
+        }
+        transfer_checked(
+            token_program_info,
+            legacy_vault_info,
+            mint_info,
+            vault_info,
+            pool_info,
+            legacy_vault.amount,
+            &[pool_seeds],
+        )?;
+        close_escrow(token_program_info, pool_info, legacy_vault_info, payer_info, pool_seeds)?;
+    }
 
-    // Calculate amounts to withdraw
-    let amount_a = (lp_amount as u128)
-        .checked_mul(reserve_a as u128).unwrap()
-        .checked_div(supply as u128).unwrap() as u64;
-    let amount_b = (lp_amount as u128)
-        .checked_mul(reserve_b as u128).unwrap()
-        .checked_div(supply as u128).unwrap() as u64;
+    let shortfall = rent.minimum_balance(Pool::LEN).saturating_sub(pool_info.lamports());
+    if shortfall > 0 {
+        invoke(
+            &system_instruction::transfer(payer_info.key, pool_info.key, shortfall),
+            &[
+                payer_info.clone(),
+                pool_info.clone(),
+                system_program_info.clone(),
+            ],
+        )?;
+    }
 
-    // Burn LP tokens
+    pool_info.realloc(Pool::LEN, true)?;
+    Pool::pack(pool, &mut pool_info.try_borrow_mut_data()?)?;
+
+    Ok(())
+}
+
+// Where a native-mode handler's accounts sit: the user token accounts for
+// sides A and B (for swaps, input and output), the signing user, and how many
+// accounts the wrapped handler reads before the system program and rent sysvar
+struct NativeSlots {
+    user_token_a: usize,
+    user_token_b: usize,
+    user: usize,
+    len: usize,
+}
+
+impl NativeSlots {
+    const SWAP: Self = NativeSlots { user_token_a: 5, user_token_b: 6, user: 7, len: 10 };
+    const LIQUIDITY: Self = NativeSlots { user_token_a: 6, user_token_b: 7, user: 9, len: 13 };
+    const REMOVE_LIQUIDITY: Self = NativeSlots { user_token_a: 7, user_token_b: 8, user: 9, len: 13 };
+}
+
+// Runs `handler` with the user's token account for the pool's native side
+// swapped for a temporary wrapped-SOL account at the [NATIVE_SEED, pool, user]
+// PDA. `wrap` gives the lamports to wrap for a native side A (true) or B, and
+// `a_first` says whether side A's account comes first (false for B->A swaps).
+// Whatever the account holds afterwards is unwrapped back to the user.
+fn process_native<'a>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo<'a>],
+    slots: NativeSlots,
+    a_first: bool,
+    wrap: impl FnOnce(bool) -> u64,
+    handler: impl FnOnce(&[AccountInfo<'a>]) -> ProgramResult,
+) -> ProgramResult {
+    if accounts.len() < slots.len + 2 {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    let pool_info = &accounts[0];
+    let user_info = &accounts[slots.user];
+    let system_program_info = &accounts[slots.len];
+    let rent_info = &accounts[slots.len + 1];
+    if !user_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    // The wrapped handler checks the pool is active where it needs to be
+    let pool = load_pool_for_exit(pool_info)?;
+    let native_a = if is_native_mint(&pool.token_a) {
+        true
+    } else if is_native_mint(&pool.token_b) {
+        false
+    } else {
+        return Err(SwapError::NoNativeSide.into());
+    };
+    let (mint_info, token_program_info) = if native_a {
+        (&accounts[1], &accounts[slots.len - 2])
+    } else {
+        (&accounts[2], &accounts[slots.len - 1])
+    };
+    let temp_info = if native_a == a_first {
+        &accounts[slots.user_token_a]
+    } else {
+        &accounts[slots.user_token_b]
+    };
+
+    let (temp_key, temp_bump) =
+        Pubkey::find_program_address(&[NATIVE_SEED, pool_info.key.as_ref(), user_info.key.as_ref()], program_id);
+    if temp_key != *temp_info.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    let temp_signer_seeds: &[&[_]] = &[NATIVE_SEED, pool_info.key.as_ref(), user_info.key.as_ref(), &[temp_bump]];
+
+    // Create the temporary account holding rent plus the wrapped amount. It is
+    // closed at the end of every instruction, so any lamports already there
+    // were sent by someone else and are simply wrapped along.
+    let rent = Rent::from_account_info(rent_info)?;
+    let space = token_account_len(token_program_info.key, mint_info)?;
+    let required = rent
+        .minimum_balance(space)
+        .checked_add(wrap(native_a))
+        .ok_or(SwapError::MathOverflow)?;
+    let top_up = required.saturating_sub(temp_info.lamports());
+    if top_up > 0 {
+        invoke(
+            &system_instruction::transfer(user_info.key, temp_info.key, top_up),
+            &[user_info.clone(), temp_info.clone(), system_program_info.clone()],
+        )?;
+    }
+    // A temporary account closed earlier in the same transaction keeps its
+    // (zeroed) data until the transaction ends
+    if temp_info.data_is_empty() {
+        invoke_signed(
+            &system_instruction::allocate(temp_info.key, space as u64),
+            &[temp_info.clone(), system_program_info.clone()],
+            &[temp_signer_seeds],
+        )?;
+    }
+    invoke_signed(
+        &system_instruction::assign(temp_info.key, token_program_info.key),
+        &[temp_info.clone(), system_program_info.clone()],
+        &[temp_signer_seeds],
+    )?;
     invoke(
-        &create_burn_instruction(
-            user_lp_info.key,
-            lp_mint_info.key,
-            user_info.key,
-            lp_amount,
-        ),
+        &create_initialize_account_instruction(token_program_info.key, temp_info.key, mint_info.key, user_info.key),
         &[
-            user_lp_info.clone(),
-            lp_mint_info.clone(),
+            temp_info.clone(),
+            mint_info.clone(),
             user_info.clone(),
+            rent_info.clone(),
             token_program_info.clone(),
         ],
     )?;
 
-    // Transfer tokens from vaults to user
-    let pool_signer_seeds: &[&[_]] = &[
-        b"pool",
-        pool.token_a.as_ref(),
-        pool.token_b.as_ref(),
-        &[pool.bump],
-    ];
+    // The base handler doesn't see the trailing system program and rent sysvar
+    handler(&accounts[..slots.len])?;
 
-    invoke_signed(
-        &create_transfer_instruction(
-            vault_a_info.key,
-            user_token_a_info.key,
-            pool_info.key,
-            amount_a,
-        ),
-        &[
-            vault_a_info.clone(),
-            user_token_a_info.clone(),
-            pool_info.clone(),
-            token_program_info.clone(),
-        ],
-        &[pool_signer_seeds],
-    )?;
+    // Unwrap: closing a native account returns all of its lamports
+    invoke(
+        &create_close_account_instruction(token_program_info.key, temp_info.key, user_info.key, user_info.key),
+        &[temp_info.clone(), user_info.clone(), user_info.clone(), token_program_info.clone()],
+    )
+}
 
-    invoke_signed(
-        &create_transfer_instruction(
-            vault_b_info.key,
-            user_token_b_info.key,
-            pool_info.key,
-            amount_b,
+// Optional trailing [system_program, associated_token_program] accounts ask
+// the handler to create the user's missing output accounts
+fn next_ata_accounts<'a, 'b>(
+    account_info_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+) -> Result<Option<(&'a AccountInfo<'b>, &'a AccountInfo<'b>)>, ProgramError> {
+    let Some(system_program_info) = account_info_iter.next() else {
+        return Ok(None);
+    };
+    let ata_program_info = next_account_info(account_info_iter)?;
+    if *system_program_info.key != solana_program::system_program::id()
+        || !is_supported_ata_program(ata_program_info.key)
+    {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Ok(Some((system_program_info, ata_program_info)))
+}
+
+// Creates a missing user account as the user's ATA for `mint_info`, paid by
+// the user. Existing accounts are left alone and checked by the transfers.
+fn create_user_account<'a>(
+    (system_program_info, ata_program_info): (&AccountInfo<'a>, &AccountInfo<'a>),
+    user_info: &AccountInfo<'a>,
+    account_info: &AccountInfo<'a>,
+    mint_info: &AccountInfo<'a>,
+    token_program_info: &AccountInfo<'a>,
+) -> ProgramResult {
+    if account_info.lamports() > 0 {
+        return Ok(());
+    }
+    let (address, _) = Pubkey::find_program_address(
+        &[user_info.key.as_ref(), token_program_info.key.as_ref(), mint_info.key.as_ref()],
+        ata_program_info.key,
+    );
+    if address != *account_info.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    invoke(
+        &create_associated_token_account_idempotent_instruction(
+            ata_program_info.key,
+            user_info.key,
+            account_info.key,
+            user_info.key,
+            mint_info.key,
+            token_program_info.key,
         ),
         &[
-            vault_b_info.clone(),
-            user_token_b_info.clone(),
-            pool_info.clone(),
+            user_info.clone(),
+            account_info.clone(),
+            mint_info.clone(),
+            system_program_info.clone(),
             token_program_info.clone(),
+            ata_program_info.clone(),
         ],
-        &[pool_signer_seeds],
-    )?;
-
-    // Update pool state
-    pool.reserve_a = pool.reserve_a.checked_sub(amount_a).unwrap();
-    pool.reserve_b = pool.reserve_b.checked_sub(amount_b).unwrap();
-    pool.total_lp_supply = pool.total_lp_supply.checked_sub(lp_amount).unwrap();
+    )
+}
 
-    Pool::pack(pool, &mut pool_info.data.borrow_mut())?;
+// Copies an initialized, active pool out of its account. Handlers write
+// updates back in place with `Pool::load_mut` once their CPIs are done.
+fn load_pool(pool_info: &AccountInfo) -> Result<Pool, ProgramError> {
+    let pool = load_pool_for_exit(pool_info)?;
+    if !pool.is_active {
+        return Err(SwapError::PoolInactive.into());
+    }
+    Ok(pool)
+}
 
-    Ok(())
+// Like `load_pool`, but also loads a deactivated pool, so LPs and order
+// owners can still take their funds out of it
+fn load_pool_for_exit(pool_info: &AccountInfo) -> Result<Pool, ProgramError> {
+    let data = pool_info.try_borrow_data()?;
+    if is_legacy_pool_len(data.len()) {
+        return Err(SwapError::PoolMigrationRequired.into());
+    }
+    let pool = *Pool::load(&data)?;
+    if !pool.is_initialized() {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    Ok(pool)
 }
 
-fn process_legacy_swap(
+// Vaults must be the pool's own token accounts for its two mints, otherwise a
+// caller could route the input leg to an account they control
+fn check_vaults(
     program_id: &Pubkey,
-    accounts: &[AccountInfo],
-    amount_in: u64,
-    direction_a_to_b: bool,
+    pool_key: &Pubkey,
+    pool: &Pool,
+    vault_a_info: &AccountInfo,
+    vault_b_info: &AccountInfo,
 ) -> ProgramResult {
-    let account_info_iter = &mut accounts.iter();
-    let pool_info = next_account_info(account_info_iter)?;
-    let token_a_info = next_account_info(account_info_iter)?;
-    let token_b_info = next_account_info(account_info_iter)?;
-    let vault_a_info = next_account_info(account_info_iter)?;
-    let vault_b_info = next_account_info(account_info_iter)?;
-    let user_in_info = next_account_info(account_info_iter)?;
-    let user_out_info = next_account_info(account_info_iter)?;
-    let user_info = next_account_info(account_info_iter)?;
-    let token_program_info = next_account_info(account_info_iter)?;
-
-    let mut pool = Pool::unpack(&pool_info.data.borrow())?;
+    check_vault(program_id, pool_key, &pool.token_a, &pool.token_program_a, vault_a_info)?;
+    check_vault(program_id, pool_key, &pool.token_b, &pool.token_program_b, vault_b_info)
+}
 
-    // Verify pool seeds
-    let (pool_pubkey, _) = Pubkey::find_program_address(
-        &[b"pool", token_a_info.key.as_ref(), token_b_info.key.as_ref()],
-        program_id,
-    );
+// A pool's vault for `mint` is its token account at [VAULT_SEED, pool, mint];
+// any other account the pool owns holds tokens outside the reserves
+fn check_vault(
+    program_id: &Pubkey,
+    pool_key: &Pubkey,
+    mint: &Pubkey,
+    token_program: &Pubkey,
+    vault_info: &AccountInfo,
+) -> ProgramResult {
+    let (vault_pubkey, _) = Pubkey::find_program_address(&[VAULT_SEED, pool_key.as_ref(), mint.as_ref()], program_id);
+    if vault_pubkey != *vault_info.key {
+        return Err(SwapError::InvalidVault.into());
+    }
+    let vault = unpack_token_state::<spl_token::state::Account>(vault_info, token_program)?;
+    if vault.mint != *mint || vault.owner != *pool_key {
+        return Err(SwapError::InvalidVault.into());
+    }
+    Ok(())
+}
 
-    if pool_pubkey != *pool_info.key {
-        return Err(ProgramError::InvalidSeeds);
+// Only the pool can mint its LP token, so a mint it doesn't control is fake
+fn check_lp_mint(pool_key: &Pubkey, token_program_info: &AccountInfo, lp_mint_info: &AccountInfo) -> ProgramResult {
+    check_token_program(token_program_info, lp_mint_info)?;
+    let lp_mint = unpack_token_state::<spl_token::state::Mint>(lp_mint_info, token_program_info.key)?;
+    if lp_mint.mint_authority != COption::Some(*pool_key) {
+        return Err(SwapError::InvalidLpMint.into());
     }
+    Ok(())
+}
 
-    let (reserve_in, reserve_out) = if direction_a_to_b {
-        (pool.reserve_a, pool.reserve_b)
-    } else {
-        (pool.reserve_b, pool.reserve_a)
-    };
+// The token program passed for a mint must be a supported one that owns it
+fn check_token_program(token_program_info: &AccountInfo, mint_info: &AccountInfo) -> ProgramResult {
+    if !is_supported_token_program(token_program_info.key) || mint_info.owner != token_program_info.key {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Ok(())
+}
 
-    // Transfer input tokens to vault
-    if direction_a_to_b {
-        invoke(
-            &create_transfer_instruction(
-                user_in_info.key,
-                vault_a_info.key,
-                user_info.key,
-                amount_in,
-            ),
-            &[
-                user_in_info.clone(),
-                vault_a_info.clone(),
-                user_info.clone(),
-                token_program_info.clone(),
-            ],
-        )?;
-    } else {
-        invoke(
-            &create_transfer_instruction(
-                user_in_info.key,
-                vault_b_info.key,
-                user_info.key,
-                amount_in,
-            ),
-            &[
-                user_in_info.clone(),
-                vault_b_info.clone(),
-                user_info.clone(),
-                token_program_info.clone(),
-            ],
-        )?;
+fn check_pool_token_programs(
+    pool: &Pool,
+    token_program_a_info: &AccountInfo,
+    token_program_b_info: &AccountInfo,
+) -> ProgramResult {
+    if *token_program_a_info.key != pool.token_program_a || *token_program_b_info.key != pool.token_program_b {
+        return Err(ProgramError::IncorrectProgramId);
     }
+    Ok(())
+}
 
-    // Calculate output amount (with 0.3% fee)
-    let amount_in_with_fee = (amount_in as u128).checked_mul(997).unwrap();
-    let numerator = amount_in_with_fee.checked_mul(reserve_out as u128).unwrap();
-    let denominator = (reserve_in as u128)
-        .checked_mul(1000).unwrap()
-        .checked_add(amount_in_with_fee).unwrap();
-    let amount_out = (numerator / denominator) as u64;
+// Token-2022 accounts need room for the extensions their mint requires
+fn token_account_len(token_program: &Pubkey, mint_info: &AccountInfo) -> Result<usize, ProgramError> {
+    if *token_program != spl_token_2022::id() {
+        return Ok(spl_token::state::Account::LEN);
+    }
+    let data = mint_info.try_borrow_data()?;
+    let mint = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    let extensions = ExtensionType::get_required_init_account_extensions(&mint.get_extension_types()?);
+    ExtensionType::try_calculate_account_len::<spl_token_2022::state::Account>(&extensions)
+}
 
-    // Transfer output tokens from vault to user
-    let pool_signer_seeds: &[&[_]] = &[
-        b"pool",
-        pool.token_a.as_ref(),
-        pool.token_b.as_ref(),
-        &[pool.bump],
-    ];
+// Moves tokens with `TransferChecked`, which both token programs accept and
+// Token-2022 requires for mints with extensions. Decimals come from the mint.
+fn transfer_checked<'a>(
+    token_program_info: &AccountInfo<'a>,
+    source_info: &AccountInfo<'a>,
+    mint_info: &AccountInfo<'a>,
+    destination_info: &AccountInfo<'a>,
+    authority_info: &AccountInfo<'a>,
+    amount: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    let decimals = unpack_token_state::<spl_token::state::Mint>(mint_info, token_program_info.key)?.decimals;
+    invoke_signed(
+        &create_transfer_checked_instruction(
+            token_program_info.key,
+            source_info.key,
+            mint_info.key,
+            destination_info.key,
+            authority_info.key,
+            amount,
+            decimals,
+        ),
+        &[
+            source_info.clone(),
+            mint_info.clone(),
+            destination_info.clone(),
+            authority_info.clone(),
+            token_program_info.clone(),
+        ],
+        signer_seeds,
+    )
+}
 
-    if direction_a_to_b {
-        invoke_signed(
-            &create_transfer_instruction(
-                vault_b_info.key,
-                user_out_info.key,
-                pool_info.key,
-                amount_out,
-            ),
-            &[
-                vault_b_info.clone(),
-                user_out_info.clone(),
-                pool_info.clone(),
-                token_program_info.clone(),
-            ],
-            &[pool_signer_seeds],
-        )?;
-    } else {
-        invoke_signed(
-            &create_transfer_instruction(
-                vault_a_info.key,
-                user_out_info.key,
-                pool_info.key,
-                amount_out,
-            ),
-            &[
-                vault_a_info.clone(),
-                user_out_info.clone(),
-                pool_info.clone(),
-                token_program_info.clone(),
-            ],
-            &[pool_signer_seeds],
-        )?;
+// Pools only hold tokens whose extensions they handle: transfer fees, which
+// reserves account for, and extensions that don't change how transfers work.
+// Anything else is refused, e.g. transfer hooks, whose extra accounts no
+// transfer passes, confidential transfers, tokens that refuse transfers or
+// that a permanent delegate could pull out of the vaults, and new accounts
+// that start frozen.
+fn check_mint_extensions(mint_info: &AccountInfo) -> ProgramResult {
+    if *mint_info.owner != spl_token_2022::id() {
+        return Ok(());
+    }
+    let data = mint_info.try_borrow_data()?;
+    let mint = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    for extension in mint.get_extension_types()? {
+        let supported = match extension {
+            ExtensionType::TransferFeeConfig
+            | ExtensionType::MintCloseAuthority
+            | ExtensionType::InterestBearingConfig
+            | ExtensionType::MetadataPointer
+            | ExtensionType::TokenMetadata
+            | ExtensionType::GroupPointer
+            | ExtensionType::TokenGroup
+            | ExtensionType::GroupMemberPointer
+            | ExtensionType::TokenGroupMember => true,
+            ExtensionType::DefaultAccountState => {
+                mint.get_extension::<DefaultAccountState>()?.state != spl_token_2022::state::AccountState::Frozen as u8
+            }
+            _ => false,
+        };
+        if !supported {
+            return Err(SwapError::UnsupportedMintExtension.into());
+        }
     }
+    Ok(())
+}
 
-    // Update pool reserves
-    if direction_a_to_b {
-        pool.reserve_a = pool.reserve_a.checked_add(amount_in).unwrap();
-        pool.reserve_b = pool.reserve_b.checked_sub(amount_out).unwrap();
-    } else {
-        pool.reserve_b = pool.reserve_b.checked_add(amount_in).unwrap();
-        pool.reserve_a = pool.reserve_a.checked_sub(amount_out).unwrap();
+// Name and symbol for a pool's LP mint, e.g. "CSWAP-LP SOL/USDC" and
+// "SOL-USDC-STD30" for a standard pool with a 0.3% fee
+fn lp_metadata(
+    token_a_info: &AccountInfo,
+    token_b_info: &AccountInfo,
+    pool_type: PoolType,
+) -> Result<TokenMetadata, ProgramError> {
+    let symbol_a = mint_symbol(token_a_info)?;
+    let symbol_b = mint_symbol(token_b_info)?;
+    let pool_type_code = match pool_type {
+        PoolType::Standard => "STD",
+        PoolType::Stable => "STB",
+        PoolType::Concentrated => "CL",
+    };
+    Ok(TokenMetadata {
+        name: format!("{LP_NAME_PREFIX} {symbol_a}/{symbol_b}"),
+        symbol: format!("{symbol_a}-{symbol_b}-{pool_type_code}{}", pool_type.default_fee_rate()),
+        ..TokenMetadata::default()
+    })
+}
+
+// A mint's own metadata symbol where it stores one, otherwise the start of
+// its address
+fn mint_symbol(mint_info: &AccountInfo) -> Result<String, ProgramError> {
+    if *mint_info.owner == spl_token_2022::id() {
+        let data = mint_info.try_borrow_data()?;
+        let mint = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+        if let Ok(metadata) = mint.get_variable_len_extension::<TokenMetadata>()
+            && !metadata.symbol.is_empty()
+        {
+            return Ok(metadata.symbol.chars().take(10).collect());
+        }
     }
+    Ok(mint_info.key.to_string().chars().take(4).collect())
+}
 
-    Pool::pack(pool, &mut pool_info.data.borrow_mut())?;
+// Amount that reaches the destination of a transfer after any Token-2022
+// transfer fee, which the token program withholds in the destination account
+fn received_amount(mint_info: &AccountInfo, amount: u64) -> Result<u64, ProgramError> {
+    if *mint_info.owner != spl_token_2022::id() {
+        return Ok(amount);
+    }
+    let data = mint_info.try_borrow_data()?;
+    let mint = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    let Ok(transfer_fee_config) = mint.get_extension::<TransferFeeConfig>() else {
+        return Ok(amount);
+    };
+    let fee = transfer_fee_config
+        .calculate_epoch_fee(solana_program::clock::Clock::get()?.epoch, amount)
+        .ok_or(SwapError::MathOverflow)?;
+    Ok(amount.checked_sub(fee).ok_or(SwapError::MathOverflow)?)
+}
 
-    Ok(())
+// Reads the base SPL layout, ignoring any Token-2022 extension data after it
+fn unpack_token_state<T: Pack + IsInitialized>(info: &AccountInfo, token_program: &Pubkey) -> Result<T, ProgramError> {
+    if info.owner != token_program {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let data = info.data.borrow();
+    let state = T::unpack_from_slice(data.get(..T::LEN).ok_or(ProgramError::InvalidAccountData)?)?;
+    if !state.is_initialized() {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    Ok(state)
 }
 
 // Integer square root implementation for u128
-trait IntegerSqrt {
+pub trait IntegerSqrt {
     fn integer_sqrt(self) -> Self;
 }
 
@@ -1733,7 +5698,7 @@ impl IntegerSqrt for u128 {
             return self;
         }
         let mut x = self;
-        let mut y = (self + 1) / 2;
+        let mut y = self.div_ceil(2);
         while y < x {
             x = y;
             y = (x + self / x) / 2;
@@ -0,0 +1,419 @@
+// Off-chain quote engine
+//
+// Pure functions over a `Pool` snapshot. The on-chain handlers call these same
+// functions to compute amounts, so a quote produced here is exactly what the
+// program will do given the same pool state. This is a module of the program
+// crate, not a standalone library: it uses the crate's state types and
+// `SwapError` and returns `solana_program`'s `ProgramError`. Clients such as
+// the CLI run it by depending on the crate with the `no-entrypoint` feature.
+
+use crate::{error::SwapError, FarmReward, IntegerSqrt, Pool, MAX_LOCK_BOOST_BPS, MAX_LOCK_SECONDS};
+use solana_program::program_error::ProgramError;
+
+// Fee rates are expressed in basis points
+pub const FEE_DENOMINATOR: u64 = 10_000;
+
+// A dynamic-fee pool's volatility decays linearly to zero over this long
+pub const VOLATILITY_DECAY_SECONDS: i64 = 5 * 60;
+
+// Volatility at which a dynamic-fee pool charges its maximum fee
+pub const VOLATILITY_SATURATION_BPS: u64 = 1_000;
+
+/// Result of a swap quote.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SwapQuote {
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub fee: u64,               // Portion of amount_in kept by LPs
+    pub price_impact_bps: u16,  // Execution price vs. spot price, excluding fee
+    pub reserve_a_after: u64,
+    pub reserve_b_after: u64,
+}
+
+/// Result of an add or remove liquidity quote.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LiquidityQuote {
+    pub amount_a: u64,
+    pub amount_b: u64,
+    pub lp_amount: u64,
+    pub reserve_a_after: u64,
+    pub reserve_b_after: u64,
+    pub lp_supply_after: u64,
+}
+
+fn reserves_for(pool: &Pool, direction_a_to_b: bool) -> (u64, u64) {
+    if direction_a_to_b {
+        (pool.reserve_a, pool.reserve_b)
+    } else {
+        (pool.reserve_b, pool.reserve_a)
+    }
+}
+
+fn reserves_after(
+    pool: &Pool,
+    direction_a_to_b: bool,
+    amount_in: u64,
+    amount_out: u64,
+) -> Result<(u64, u64), ProgramError> {
+    let (reserve_in, reserve_out) = reserves_for(pool, direction_a_to_b);
+    let reserve_in_after = reserve_in
+        .checked_add(amount_in)
+        .ok_or(SwapError::MathOverflow)?;
+    let reserve_out_after = reserve_out
+        .checked_sub(amount_out)
+        .ok_or(SwapError::InsufficientLiquidity)?;
+
+    if direction_a_to_b {
+        Ok((reserve_in_after, reserve_out_after))
+    } else {
+        Ok((reserve_out_after, reserve_in_after))
+    }
+}
+
+fn fee_amount(amount_in: u64, fee_rate: u16) -> u64 {
+    ((amount_in as u128) * (fee_rate as u128) / (FEE_DENOMINATOR as u128)) as u64
+}
+
+/// Quote a swap of exactly `amount_in` input tokens.
+pub fn quote_swap_exact_in(
+    pool: &Pool,
+    amount_in: u64,
+    direction_a_to_b: bool,
+) -> Result<SwapQuote, ProgramError> {
+    if pool.fee_rate as u64 >= FEE_DENOMINATOR {
+        return Err(SwapError::InvalidFeeRate.into());
+    }
+
+    let (reserve_in, reserve_out) = reserves_for(pool, direction_a_to_b);
+
+    let amount_in_with_fee = (amount_in as u128)
+        .checked_mul((FEE_DENOMINATOR - pool.fee_rate as u64) as u128)
+        .ok_or(SwapError::MathOverflow)?;
+    let numerator = amount_in_with_fee
+        .checked_mul(reserve_out as u128)
+        .ok_or(SwapError::MathOverflow)?;
+    let denominator = (reserve_in as u128)
+        .checked_mul(FEE_DENOMINATOR as u128)
+        .and_then(|d| d.checked_add(amount_in_with_fee))
+        .ok_or(SwapError::MathOverflow)?;
+    if denominator == 0 {
+        return Err(SwapError::InsufficientLiquidity.into());
+    }
+    let amount_out = (numerator / denominator) as u64;
+
+    // For x*y=k the execution price is reserve_in / (reserve_in + input) of the
+    // spot price, so the impact is input / (reserve_in + input) on post-fee input
+    let price_impact_bps =
+        (amount_in_with_fee * FEE_DENOMINATOR as u128 / denominator) as u16;
+
+    let (reserve_a_after, reserve_b_after) =
+        reserves_after(pool, direction_a_to_b, amount_in, amount_out)?;
+
+    Ok(SwapQuote {
+        amount_in,
+        amount_out,
+        fee: fee_amount(amount_in, pool.fee_rate),
+        price_impact_bps,
+        reserve_a_after,
+        reserve_b_after,
+    })
+}
+
+/// Quote the smallest input that yields at least `amount_out` output tokens.
+pub fn quote_swap_exact_out(
+    pool: &Pool,
+    amount_out: u64,
+    direction_a_to_b: bool,
+) -> Result<SwapQuote, ProgramError> {
+    if pool.fee_rate as u64 >= FEE_DENOMINATOR {
+        return Err(SwapError::InvalidFeeRate.into());
+    }
+
+    let (reserve_in, reserve_out) = reserves_for(pool, direction_a_to_b);
+    if amount_out >= reserve_out {
+        return Err(SwapError::InsufficientLiquidity.into());
+    }
+
+    // amount_in = ceil(reserve_in * amount_out * D / ((reserve_out - amount_out) * (D - fee)))
+    let numerator = (reserve_in as u128)
+        .checked_mul(amount_out as u128)
+        .and_then(|n| n.checked_mul(FEE_DENOMINATOR as u128))
+        .ok_or(SwapError::MathOverflow)?;
+    let denominator = ((reserve_out - amount_out) as u128)
+        .checked_mul((FEE_DENOMINATOR - pool.fee_rate as u64) as u128)
+        .ok_or(SwapError::MathOverflow)?;
+    let amount_in: u64 = numerator
+        .div_ceil(denominator)
+        .try_into()
+        .map_err(|_| SwapError::MathOverflow)?;
+
+    // Price the input through the exact-in path so fee, impact and reserves
+    // match what `Swap { amount_in }` would do
+    let quote = quote_swap_exact_in(pool, amount_in, direction_a_to_b)?;
+    debug_assert!(quote.amount_out >= amount_out);
+    Ok(quote)
+}
+
+/// A dynamic-fee pool's volatility at `now`: the stored accumulator, decayed
+/// linearly over `VOLATILITY_DECAY_SECONDS` since it was last updated.
+pub fn decayed_volatility(pool: &Pool, now: i64) -> u64 {
+    let elapsed = now.saturating_sub(pool.volatility_updated_at).max(0);
+    if elapsed >= VOLATILITY_DECAY_SECONDS {
+        return 0;
+    }
+    let remaining = (VOLATILITY_DECAY_SECONDS - elapsed) as u128;
+    (pool.volatility_bps as u128 * remaining / VOLATILITY_DECAY_SECONDS as u128) as u64
+}
+
+/// Fee a swap pays at `now`: `fee_rate` for static pools; for dynamic-fee
+/// pools the minimum fee plus the share of the range that the decayed
+/// volatility is of `VOLATILITY_SATURATION_BPS`, up to the maximum fee.
+pub fn dynamic_fee_rate(pool: &Pool, now: i64) -> u16 {
+    if !pool.dynamic_fee {
+        return pool.fee_rate;
+    }
+    let volatility = decayed_volatility(pool, now).min(VOLATILITY_SATURATION_BPS);
+    let range = pool.max_fee_rate.saturating_sub(pool.min_fee_rate) as u64;
+    pool.min_fee_rate + (range * volatility / VOLATILITY_SATURATION_BPS) as u16
+}
+
+/// The pool as a swap at `now` prices it, with `fee_rate` set to the fee
+/// `dynamic_fee_rate` charges. Quote against this to predict a dynamic fee.
+pub fn with_current_fee(pool: &Pool, now: i64) -> Pool {
+    Pool { fee_rate: dynamic_fee_rate(pool, now), ..*pool }
+}
+
+/// How far going from `before` to `after` reserves moves the B-per-A price,
+/// in basis points of the earlier price, saturating at `u64::MAX`.
+pub fn price_move_bps((before_a, before_b): (u64, u64), (after_a, after_b): (u64, u64)) -> u64 {
+    if before_a == 0 || before_b == 0 {
+        return 0;
+    }
+    // price_after / price_before = (b_after * a_before) / (a_after * b_before)
+    let after = after_b as u128 * before_a as u128;
+    let before = after_a as u128 * before_b as u128;
+    if before == 0 {
+        return u64::MAX;
+    }
+    let bps = FEE_DENOMINATOR as u128;
+    let moved = after.abs_diff(before);
+    let move_bps = match moved.checked_mul(bps) {
+        Some(scaled) => scaled / before,
+        // Only moves far beyond any fee bound overflow, so rounding is fine
+        None => moved / (before / bps).max(1),
+    };
+    move_bps.try_into().unwrap_or(u64::MAX)
+}
+
+/// Reserves a pool's price band is measured from in `slot`: those from
+/// before the slot's first swap, which are the current ones until it runs.
+pub fn price_reference(pool: &Pool, slot: u64) -> (u64, u64) {
+    if pool.price_ref_slot == slot && pool.price_ref_reserve_a > 0 {
+        (pool.price_ref_reserve_a, pool.price_ref_reserve_b)
+    } else {
+        (pool.reserve_a, pool.reserve_b)
+    }
+}
+
+/// Fails with `PriceBandExceeded` if going from the `reference` reserves to
+/// the given ones moves the B-per-A price by more than the pool's
+/// `max_price_move_bps`. A band of 0 disables the check.
+pub fn check_price_band(
+    pool: &Pool,
+    (reference_a, reference_b): (u64, u64),
+    reserve_a_after: u64,
+    reserve_b_after: u64,
+) -> Result<(), ProgramError> {
+    if pool.max_price_move_bps == 0 || reference_a == 0 || reference_b == 0 {
+        return Ok(());
+    }
+
+    // price_after / price_reference = (b_after * a_ref) / (a_after * b_ref)
+    let after = (reserve_b_after as u128) * (reference_a as u128);
+    let reference = (reserve_a_after as u128) * (reference_b as u128);
+
+    // reference * band / 10_000, split so it can't overflow
+    let band = pool.max_price_move_bps as u128;
+    let bps = FEE_DENOMINATOR as u128;
+    let allowed = (reference / bps)
+        .saturating_mul(band)
+        .saturating_add(reference % bps * band / bps);
+    if after.abs_diff(reference) > allowed {
+        return Err(SwapError::PriceBandExceeded.into());
+    }
+    Ok(())
+}
+
+/// Spot prices in Q64.64 fixed point: B per A, then A per B. Zero while
+/// either reserve is empty.
+pub fn spot_prices_q64(reserve_a: u64, reserve_b: u64) -> (u128, u128) {
+    if reserve_a == 0 || reserve_b == 0 {
+        return (0, 0);
+    }
+    (
+        ((reserve_b as u128) << 64) / reserve_a as u128,
+        ((reserve_a as u128) << 64) / reserve_b as u128,
+    )
+}
+
+/// The pool's price accumulators (B per A, A per B) carried forward to `now`
+/// at its current reserves. Each sums the Q64.64 spot price per second and
+/// wraps, so only the difference between two readings means anything.
+pub fn price_cumulatives(pool: &Pool, now: i64) -> (u128, u128) {
+    let elapsed = now.saturating_sub(pool.price_cumulative_updated_at).max(0) as u128;
+    let (price_a, price_b) = spot_prices_q64(pool.reserve_a, pool.reserve_b);
+    (
+        u128::from_le_bytes(pool.price_a_cumulative).wrapping_add(price_a.wrapping_mul(elapsed)),
+        u128::from_le_bytes(pool.price_b_cumulative).wrapping_add(price_b.wrapping_mul(elapsed)),
+    )
+}
+
+/// Time-weighted average Q64.64 price between two accumulator readings taken
+/// `seconds` apart, or `None` for an empty window. Exact while the average
+/// price times `seconds` fits in 128 bits, e.g. prices under 2^32 for a year.
+pub fn twap_q64(earlier: u128, later: u128, seconds: i64) -> Option<u128> {
+    (seconds > 0).then(|| later.wrapping_sub(earlier) / seconds as u128)
+}
+
+/// Least output to accept for `amount_in` at a Q64.64 price, allowing
+/// `slippage_bps` below it, saturating at `u64::MAX`.
+pub fn min_amount_out_at(amount_in: u64, price_q64: u128, slippage_bps: u16) -> u64 {
+    let amount = amount_in as u128;
+    let expected = (price_q64 >> 64)
+        .saturating_mul(amount)
+        .saturating_add(((price_q64 as u64 as u128) * amount) >> 64);
+    let bps = FEE_DENOMINATOR as u128;
+    let kept = bps.saturating_sub(slippage_bps as u128);
+    let min = (expected / bps).saturating_mul(kept).saturating_add(expected % bps * kept / bps);
+    min.try_into().unwrap_or(u64::MAX)
+}
+
+/// Rewards a farm stream emits between `from` and `to`, counting only the
+/// part inside its schedule, saturating at `u64::MAX`.
+pub fn emitted_rewards(reward: &FarmReward, from: i64, to: i64) -> u64 {
+    let seconds = to.min(reward.end_time).saturating_sub(from.max(reward.start_time)).max(0);
+    (reward.emission_per_second as u128 * seconds as u128).try_into().unwrap_or(u64::MAX)
+}
+
+/// Q64.64 rewards per staked unit when `emitted` is shared over
+/// `total_staked`, rounded down. Zero with nothing staked.
+pub fn reward_per_share_q64(emitted: u64, total_staked: u64) -> u128 {
+    if total_staked == 0 {
+        return 0;
+    }
+    ((emitted as u128) << 64) / total_staked as u128
+}
+
+/// Rewards `amount` staked earns while the per-share accumulator grows by
+/// `per_share_q64`, rounded down so stakers never share out more than was
+/// emitted. Saturates at `u64::MAX`.
+pub fn earned_rewards(amount: u64, per_share_q64: u128) -> u64 {
+    let amount = amount as u128;
+    let earned = (per_share_q64 >> 64)
+        .saturating_mul(amount)
+        .saturating_add(((per_share_q64 as u64 as u128) * amount) >> 64);
+    earned.try_into().unwrap_or(u64::MAX)
+}
+
+/// Farm weight of `amount` LP locked for `lock_seconds` more: the amount plus
+/// a boost growing linearly to `MAX_LOCK_BOOST_BPS` of it at
+/// `MAX_LOCK_SECONDS`, rounded down. Saturates at `u64::MAX`.
+pub fn locked_weight(amount: u64, lock_seconds: i64) -> u64 {
+    let seconds = lock_seconds.clamp(0, MAX_LOCK_SECONDS) as u128;
+    let boost = amount as u128 * MAX_LOCK_BOOST_BPS as u128 * seconds
+        / (FEE_DENOMINATOR as u128 * MAX_LOCK_SECONDS as u128);
+    (amount as u128 + boost).try_into().unwrap_or(u64::MAX)
+}
+
+/// Quote a deposit of up to `amount_a` / `amount_b`, keeping the pool ratio.
+pub fn quote_add_liquidity(
+    pool: &Pool,
+    amount_a: u64,
+    amount_b: u64,
+) -> Result<LiquidityQuote, ProgramError> {
+    let reserve_a = pool.reserve_a;
+    let reserve_b = pool.reserve_b;
+    let supply = pool.total_lp_supply;
+
+    // Calculate final amounts maintaining ratio
+    let (final_amount_a, final_amount_b) = if reserve_a > 0 && reserve_b > 0 {
+        let required_b = ((amount_a as u128) * (reserve_b as u128) / (reserve_a as u128))
+            .try_into()
+            .map_err(|_| SwapError::MathOverflow)?;
+        if required_b <= amount_b {
+            (amount_a, required_b)
+        } else {
+            let required_a = ((amount_b as u128) * (reserve_a as u128) / (reserve_b as u128)) as u64;
+            (required_a, amount_b)
+        }
+    } else {
+        (amount_a, amount_b)
+    };
+
+    // Calculate liquidity to mint
+    let lp_amount = lp_for_deposit(pool, final_amount_a, final_amount_b)?;
+
+    Ok(LiquidityQuote {
+        amount_a: final_amount_a,
+        amount_b: final_amount_b,
+        lp_amount,
+        reserve_a_after: reserve_a
+            .checked_add(final_amount_a)
+            .ok_or(SwapError::MathOverflow)?,
+        reserve_b_after: reserve_b
+            .checked_add(final_amount_b)
+            .ok_or(SwapError::MathOverflow)?,
+        lp_supply_after: supply
+            .checked_add(lp_amount)
+            .ok_or(SwapError::MathOverflow)?,
+    })
+}
+
+/// LP tokens minted for depositing exactly `amount_a` and `amount_b`, without
+/// adjusting them to the pool ratio.
+pub fn lp_for_deposit(pool: &Pool, amount_a: u64, amount_b: u64) -> Result<u64, ProgramError> {
+    let reserve_a = pool.reserve_a;
+    let reserve_b = pool.reserve_b;
+    let supply = pool.total_lp_supply;
+
+    if supply == 0 {
+        return Ok(((amount_a as u128) * (amount_b as u128)).integer_sqrt() as u64);
+    }
+    if reserve_a == 0 || reserve_b == 0 {
+        return Err(SwapError::InsufficientLiquidity.into());
+    }
+    // Mint against the smaller side so the floored deposit can't dilute
+    // existing holders in either token
+    let lp_a = (amount_a as u128) * (supply as u128) / (reserve_a as u128);
+    let lp_b = (amount_b as u128) * (supply as u128) / (reserve_b as u128);
+    lp_a.min(lp_b)
+        .try_into()
+        .map_err(|_| SwapError::MathOverflow.into())
+}
+
+/// Quote the underlying tokens returned for burning `lp_amount` LP tokens.
+pub fn quote_remove_liquidity(
+    pool: &Pool,
+    lp_amount: u64,
+) -> Result<LiquidityQuote, ProgramError> {
+    let supply = pool.total_lp_supply;
+    if lp_amount > supply {
+        return Err(SwapError::InsufficientLiquidity.into());
+    }
+    if supply == 0 {
+        return Err(SwapError::InsufficientLiquidity.into());
+    }
+
+    // Calculate amounts to withdraw
+    let amount_a = ((lp_amount as u128) * (pool.reserve_a as u128) / (supply as u128)) as u64;
+    let amount_b = ((lp_amount as u128) * (pool.reserve_b as u128) / (supply as u128)) as u64;
+
+    Ok(LiquidityQuote {
+        amount_a,
+        amount_b,
+        lp_amount,
+        reserve_a_after: pool.reserve_a - amount_a,
+        reserve_b_after: pool.reserve_b - amount_b,
+        lp_supply_after: supply - lp_amount,
+    })
+}
@@ -0,0 +1,246 @@
+mod common;
+
+use cargo_swap::{
+    error::SwapError, instruction, GovernanceChange, LpMetadataField, PoolType, ProgramConfig, CHANGE_DELAY_SECONDS,
+    PAUSE_ALL, PAUSE_SWAPS,
+};
+use common::*;
+use solana_program::{
+    clock::Clock, instruction::InstructionError, program_pack::Pack, pubkey::Pubkey, system_instruction,
+};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+use spl_token_2022::extension::{
+    metadata_pointer::MetadataPointer, BaseStateWithExtensions, ExtensionType, StateWithExtensions,
+};
+
+fn custom_error(error: SwapError) -> TransactionError {
+    TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+}
+
+// Token-2022 mint carrying its own token metadata with the given symbol
+async fn create_mint_with_symbol(ctx: &mut ProgramTestContext, symbol: &str) -> Pubkey {
+    let payer = ctx.payer.pubkey();
+    let mint = create_mint_2022(ctx, &payer, 6, &[ExtensionType::MetadataPointer], |mint| {
+        vec![spl_token_2022::extension::metadata_pointer::instruction::initialize(
+            &spl_token_2022::id(),
+            mint,
+            Some(payer),
+            Some(*mint),
+        )
+        .unwrap()]
+    })
+    .await;
+    let ixs = [
+        // Covers the rent of the metadata the token program appends
+        system_instruction::transfer(&payer, &mint, 10_000_000),
+        spl_token_metadata_interface::instruction::initialize(
+            &spl_token_2022::id(),
+            &mint,
+            &payer,
+            &mint,
+            &payer,
+            "Stable Coin".to_string(),
+            symbol.to_string(),
+            String::new(),
+        ),
+    ];
+    process(ctx, &ixs, &[]).await.unwrap();
+    mint
+}
+
+#[tokio::test]
+async fn create_pool_attaches_lp_metadata() {
+    let mut ctx = start().await;
+    let payer = ctx.payer.pubkey();
+    let token_a = create_mint(&mut ctx, &payer, 9).await;
+    let token_b = create_mint_with_symbol(&mut ctx, "USDC").await;
+    let keys = instruction::PoolKeys::new(
+        &cargo_swap::id(),
+        &token_a,
+        &token_b,
+        &token_program_id(),
+        &spl_token_2022::id(),
+    );
+    create_pool_with_lp_program(&mut ctx, &keys, PoolType::Stable, &spl_token_2022::id()).await;
+
+    // Mints without metadata go by the start of their address
+    let prefix: String = token_a.to_string().chars().take(4).collect();
+    let metadata = get_token_metadata(&mut ctx, &keys.lp_mint).await;
+    assert_eq!(metadata.name, format!("CSWAP-LP {prefix}/USDC"));
+    assert_eq!(metadata.symbol, format!("{prefix}-USDC-STB1"));
+    assert_eq!(Option::<Pubkey>::from(metadata.update_authority), Some(keys.pool));
+
+    let account = ctx.banks_client.get_account(keys.lp_mint).await.unwrap().unwrap();
+    let mint = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&account.data).unwrap();
+    let pointer = mint.get_extension::<MetadataPointer>().unwrap();
+    assert_eq!(Option::<Pubkey>::from(pointer.metadata_address), Some(keys.lp_mint));
+
+    // SPL Token LP mints stay plain mints
+    let fixture = pool_fixture(&mut ctx).await;
+    let account = ctx.banks_client.get_account(fixture.keys.lp_mint).await.unwrap().unwrap();
+    assert_eq!(account.data.len(), spl_token::state::Mint::LEN);
+}
+
+#[tokio::test]
+async fn update_lp_metadata_requires_admin() {
+    let mut ctx = start().await;
+    let payer = ctx.payer.pubkey();
+    let admin = Keypair::new();
+    process(&mut ctx, &[system_instruction::transfer(&payer, &admin.pubkey(), 1_000_000_000)], &[])
+        .await
+        .unwrap();
+    initialize_config(&mut ctx, &admin.pubkey(), &payer).await;
+    let token_a = create_mint(&mut ctx, &payer, 9).await;
+    let token_b = create_mint(&mut ctx, &payer, 6).await;
+    let fixture = pool_fixture_with_lp_program(&mut ctx, token_a, token_b, &spl_token_2022::id()).await;
+
+    let uri = "https://example.com/lp/".repeat(4);
+    let ix = instruction::update_lp_metadata(
+        &cargo_swap::id(),
+        &payer,
+        &payer,
+        &fixture.keys,
+        LpMetadataField::Uri,
+        uri.clone(),
+    );
+    let error = transaction_error(process(&mut ctx, &[ix], &[]).await);
+    assert_eq!(error, custom_error(SwapError::Unauthorized));
+
+    // The mint grows for the longer URI, paid by the admin
+    let ix = instruction::update_lp_metadata(
+        &cargo_swap::id(),
+        &admin.pubkey(),
+        &admin.pubkey(),
+        &fixture.keys,
+        LpMetadataField::Uri,
+        uri.clone(),
+    );
+    process(&mut ctx, &[ix], &[&admin]).await.unwrap();
+    let metadata = get_token_metadata(&mut ctx, &fixture.keys.lp_mint).await;
+    assert_eq!(metadata.uri, uri);
+
+    // Existing LP accounts keep working with the grown mint
+    init_pool(&mut ctx, &fixture, 1, 1_000_000, 1_000_000).await;
+    assert_eq!(token_balance(&mut ctx, &fixture.user.lp).await, 1_000_000);
+}
+
+#[tokio::test]
+async fn initialize_config_requires_upgrade_authority() {
+    let mut ctx = start().await;
+    let payer = ctx.payer.pubkey();
+    let impostor = Keypair::new();
+    set_upgrade_authority(&mut ctx, &Pubkey::new_unique());
+    process(&mut ctx, &[system_instruction::transfer(&payer, &impostor.pubkey(), 1_000_000_000)], &[])
+        .await
+        .unwrap();
+
+    let ix = instruction::initialize_config(&cargo_swap::id(), &impostor.pubkey(), &impostor.pubkey(), &payer);
+    let error = transaction_error(process(&mut ctx, &[ix], &[&impostor]).await);
+    assert_eq!(error, custom_error(SwapError::Unauthorized));
+
+    let (config, _) = instruction::find_config_address(&cargo_swap::id());
+    assert!(ctx.banks_client.get_account(config).await.unwrap().is_none());
+
+    let admin = Pubkey::new_unique();
+    initialize_config(&mut ctx, &admin, &payer).await;
+    let account = ctx.banks_client.get_account(config).await.unwrap().unwrap();
+    assert_eq!(ProgramConfig::unpack(&account.data).unwrap().admin, admin);
+}
+
+#[tokio::test]
+async fn guardian_pause_blocks_pool_actions() {
+    let mut ctx = start().await;
+    let payer = ctx.payer.pubkey();
+    let guardian = Keypair::new();
+    initialize_config(&mut ctx, &payer, &guardian.pubkey()).await;
+    let fixture = pool_fixture(&mut ctx).await;
+    init_pool(&mut ctx, &fixture, 1, 1_000_000, 1_000_000).await;
+    let program_id = cargo_swap::id();
+    let swap = [instruction::swap(&program_id, 1, &fixture.keys, &fixture.user, 1_000, true)];
+    let lp_program = token_program_id();
+    let deposit = [instruction::add_liquidity(&program_id, 1, &fixture.keys, &fixture.user, &lp_program, 1_000, 1_000)];
+    let withdraw = [instruction::remove_liquidity(&program_id, 1, &fixture.keys, &fixture.user, &lp_program, 1_000)];
+
+    // Only the guardian can pause, not even the admin
+    let ix = instruction::set_pause(&program_id, &payer, PAUSE_ALL);
+    assert_eq!(transaction_error(process(&mut ctx, &[ix], &[]).await), custom_error(SwapError::Unauthorized));
+
+    let ix = instruction::set_pause(&program_id, &guardian.pubkey(), PAUSE_SWAPS);
+    process(&mut ctx, &[ix], &[&guardian]).await.unwrap();
+    let error = transaction_error(process(&mut ctx, &swap, &[]).await);
+    assert_eq!(error, custom_error(SwapError::ProgramPaused));
+    process(&mut ctx, &deposit, &[]).await.unwrap();
+
+    let ix = instruction::set_pause(&program_id, &guardian.pubkey(), PAUSE_ALL);
+    process(&mut ctx, &[ix], &[&guardian]).await.unwrap();
+    for ix in [&deposit, &withdraw] {
+        let error = transaction_error(process(&mut ctx, ix, &[]).await);
+        assert_eq!(error, custom_error(SwapError::ProgramPaused));
+    }
+
+    // The admin can keep withdrawals open through any pause, once the change
+    // has waited out its delay
+    let (config, _) = instruction::find_config_address(&program_id);
+    let change = GovernanceChange::Guardian { guardian: guardian.pubkey(), withdrawals_open: true };
+    let ix = instruction::propose_change(&program_id, &payer, &payer, &config, change);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    advance_clock(&mut ctx, CHANGE_DELAY_SECONDS).await;
+    let ix = instruction::execute_change(&program_id, &payer, &payer, &config, 0);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    process(&mut ctx, &withdraw, &[]).await.unwrap();
+    let error = transaction_error(process(&mut ctx, &swap, &[]).await);
+    assert_eq!(error, custom_error(SwapError::ProgramPaused));
+
+    let ix = instruction::set_pause(&program_id, &guardian.pubkey(), 0);
+    process(&mut ctx, &[ix], &[&guardian]).await.unwrap();
+    process(&mut ctx, &swap, &[]).await.unwrap();
+}
+
+#[tokio::test]
+async fn price_band_limits_moves_within_a_slot() {
+    let mut ctx = start().await;
+    let payer = ctx.payer.pubkey();
+    initialize_config(&mut ctx, &payer, &payer).await;
+    let fixture = pool_fixture(&mut ctx).await;
+    init_pool(&mut ctx, &fixture, 1, 1_000_000, 1_000_000).await;
+    let program_id = cargo_swap::id();
+    let swap = |amount| [instruction::swap(&program_id, 1, &fixture.keys, &fixture.user, amount, true)];
+
+    let pool = fixture.keys.pool;
+    let band = GovernanceChange::PriceBand { max_price_move_bps: 100 };
+
+    let outsider = Keypair::new();
+    let ix = instruction::propose_change(&program_id, &outsider.pubkey(), &payer, &pool, band);
+    let error = transaction_error(process(&mut ctx, &[ix], &[&outsider]).await);
+    assert_eq!(error, custom_error(SwapError::Unauthorized));
+
+    // The band only applies once the timelock has passed
+    let ix = instruction::propose_change(&program_id, &payer, &payer, &pool, band);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    let execute = instruction::execute_change(&program_id, &payer, &payer, &pool, 0);
+    let error = transaction_error(process(&mut ctx, std::slice::from_ref(&execute), &[]).await);
+    assert_eq!(error, custom_error(SwapError::TimelockNotElapsed));
+    assert_eq!(get_pool(&mut ctx, &pool).await.max_price_move_bps, 0);
+    advance_clock(&mut ctx, CHANGE_DELAY_SECONDS).await;
+    process(&mut ctx, &[execute], &[]).await.unwrap();
+    assert_eq!(get_pool(&mut ctx, &pool).await.max_price_move_bps, 100);
+
+    // A single 1% band rejects a ~2% move outright
+    let error = transaction_error(process(&mut ctx, &swap(10_000), &[]).await);
+    assert_eq!(error, custom_error(SwapError::PriceBandExceeded));
+
+    // Smaller swaps add up against the price the slot started at
+    process(&mut ctx, &swap(2_000), &[]).await.unwrap();
+    process(&mut ctx, &swap(2_001), &[]).await.unwrap();
+    let error = transaction_error(process(&mut ctx, &swap(2_002), &[]).await);
+    assert_eq!(error, custom_error(SwapError::PriceBandExceeded));
+
+    let mut clock: Clock = ctx.banks_client.get_sysvar().await.unwrap();
+    clock.slot += 1;
+    ctx.set_sysvar(&clock);
+    process(&mut ctx, &swap(2_002), &[]).await.unwrap();
+}
@@ -0,0 +1,378 @@
+// Shared harness for the program-test suites: boots the AMM next to an SPL token
+// program deployed under the GorbChain token program id. Token-2022 ships with
+// program-test under its own id.
+
+#![allow(dead_code)]
+
+use cargo_swap::{
+    instruction::{self, PoolKeys, UserKeys},
+    Pool, PoolInfo, PoolRegistry, PoolType, GORBCHAIN_SPL_TOKEN_PROGRAM,
+};
+use solana_program::{clock::Clock, instruction::Instruction, program_pack::Pack, pubkey::Pubkey, system_instruction};
+use solana_program_test::{processor, BanksClientError, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use spl_token_2022::extension::{BaseStateWithExtensions, ExtensionType, StateWithExtensions};
+use spl_token_metadata_interface::state::TokenMetadata;
+use std::str::FromStr;
+
+pub const USER_FUNDS: u64 = 1_000_000_000_000;
+
+pub fn token_program_id() -> Pubkey {
+    Pubkey::from_str(GORBCHAIN_SPL_TOKEN_PROGRAM).unwrap()
+}
+
+pub async fn start() -> ProgramTestContext {
+    let mut program_test = ProgramTest::new(
+        "cargo_swap",
+        cargo_swap::id(),
+        processor!(cargo_swap::process_instruction),
+    );
+    program_test.add_program(
+        "gorbchain_spl_token",
+        token_program_id(),
+        processor!(spl_token::processor::Processor::process),
+    );
+    program_test.start_with_context().await
+}
+
+pub async fn process(
+    ctx: &mut ProgramTestContext,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+) -> Result<(), BanksClientError> {
+    let blockhash = ctx.get_new_latest_blockhash().await.unwrap();
+    let mut all_signers = vec![&ctx.payer];
+    all_signers.extend_from_slice(signers);
+    let transaction = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&ctx.payer.pubkey()),
+        &all_signers,
+        blockhash,
+    );
+    ctx.banks_client.process_transaction(transaction).await
+}
+
+// Moves the cluster's clock forward without producing blocks
+pub async fn advance_clock(ctx: &mut ProgramTestContext, seconds: i64) {
+    let mut clock: Clock = ctx.banks_client.get_sysvar().await.unwrap();
+    clock.unix_timestamp += seconds;
+    ctx.set_sysvar(&clock);
+}
+
+pub fn transaction_error(result: Result<(), BanksClientError>) -> TransactionError {
+    result.expect_err("transaction should fail").unwrap()
+}
+
+// spl_token's builders reject foreign program ids, so retarget after building;
+// the base instructions are encoded the same by both token programs
+fn token_ix(token_program: &Pubkey, mut ix: Instruction) -> Instruction {
+    ix.program_id = *token_program;
+    ix
+}
+
+// Returns the program that owns `account`, i.e. a mint's token program
+pub async fn owner_of(ctx: &mut ProgramTestContext, account: &Pubkey) -> Pubkey {
+    ctx.banks_client.get_account(*account).await.unwrap().unwrap().owner
+}
+
+pub async fn create_mint(ctx: &mut ProgramTestContext, authority: &Pubkey, decimals: u8) -> Pubkey {
+    create_mint_with_program(ctx, &token_program_id(), authority, decimals).await
+}
+
+pub async fn create_mint_with_program(
+    ctx: &mut ProgramTestContext,
+    token_program: &Pubkey,
+    authority: &Pubkey,
+    decimals: u8,
+) -> Pubkey {
+    let mint = Keypair::new();
+    let rent = ctx.banks_client.get_rent().await.unwrap();
+    let ixs = [
+        system_instruction::create_account(
+            &ctx.payer.pubkey(),
+            &mint.pubkey(),
+            rent.minimum_balance(spl_token::state::Mint::LEN),
+            spl_token::state::Mint::LEN as u64,
+            token_program,
+        ),
+        token_ix(
+            token_program,
+            spl_token::instruction::initialize_mint(&spl_token::id(), &mint.pubkey(), authority, None, decimals)
+                .unwrap(),
+        ),
+    ];
+    process(ctx, &ixs, &[&mint]).await.unwrap();
+    mint.pubkey()
+}
+
+// Token-2022 mint whose extensions are set up by `extension_ixs` (given the
+// mint address) before InitializeMint; `authority` also gets freeze authority
+pub async fn create_mint_2022(
+    ctx: &mut ProgramTestContext,
+    authority: &Pubkey,
+    decimals: u8,
+    extensions: &[ExtensionType],
+    extension_ixs: impl FnOnce(&Pubkey) -> Vec<Instruction>,
+) -> Pubkey {
+    let mint = Keypair::new();
+    let rent = ctx.banks_client.get_rent().await.unwrap();
+    let space = ExtensionType::try_calculate_account_len::<spl_token_2022::state::Mint>(extensions).unwrap();
+    let mut ixs = vec![system_instruction::create_account(
+        &ctx.payer.pubkey(),
+        &mint.pubkey(),
+        rent.minimum_balance(space),
+        space as u64,
+        &spl_token_2022::id(),
+    )];
+    ixs.extend(extension_ixs(&mint.pubkey()));
+    ixs.push(
+        spl_token_2022::instruction::initialize_mint(
+            &spl_token_2022::id(),
+            &mint.pubkey(),
+            authority,
+            Some(authority),
+            decimals,
+        )
+        .unwrap(),
+    );
+    process(ctx, &ixs, &[&mint]).await.unwrap();
+    mint.pubkey()
+}
+
+pub async fn create_token_account(ctx: &mut ProgramTestContext, mint: &Pubkey, owner: &Pubkey) -> Pubkey {
+    let mint_account = ctx.banks_client.get_account(*mint).await.unwrap().unwrap();
+    let token_program = mint_account.owner;
+    // Token-2022 accounts need room for the extensions their mint requires
+    let space = if token_program == spl_token_2022::id() {
+        let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&mint_account.data).unwrap();
+        let extensions = ExtensionType::get_required_init_account_extensions(&state.get_extension_types().unwrap());
+        ExtensionType::try_calculate_account_len::<spl_token_2022::state::Account>(&extensions).unwrap()
+    } else {
+        spl_token::state::Account::LEN
+    };
+    let account = Keypair::new();
+    let rent = ctx.banks_client.get_rent().await.unwrap();
+    let ixs = [
+        system_instruction::create_account(
+            &ctx.payer.pubkey(),
+            &account.pubkey(),
+            rent.minimum_balance(space),
+            space as u64,
+            &token_program,
+        ),
+        token_ix(
+            &token_program,
+            spl_token::instruction::initialize_account(&spl_token::id(), &account.pubkey(), mint, owner).unwrap(),
+        ),
+    ];
+    process(ctx, &ixs, &[&account]).await.unwrap();
+    account.pubkey()
+}
+
+pub async fn mint_to(ctx: &mut ProgramTestContext, mint: &Pubkey, account: &Pubkey, amount: u64) {
+    let token_program = owner_of(ctx, mint).await;
+    let ix = token_ix(
+        &token_program,
+        spl_token::instruction::mint_to(&spl_token::id(), mint, account, &ctx.payer.pubkey(), &[], amount).unwrap(),
+    );
+    process(ctx, &[ix], &[]).await.unwrap();
+}
+
+// Funds a token account, wrapping lamports for native mints
+pub async fn fund(ctx: &mut ProgramTestContext, mint: &Pubkey, account: &Pubkey, amount: u64) {
+    if !cargo_swap::is_native_mint(mint) {
+        return mint_to(ctx, mint, account, amount).await;
+    }
+    let token_program = owner_of(ctx, mint).await;
+    let ixs = [
+        system_instruction::transfer(&ctx.payer.pubkey(), account, amount),
+        token_ix(&token_program, spl_token::instruction::sync_native(&spl_token::id(), account).unwrap()),
+    ];
+    process(ctx, &ixs, &[]).await.unwrap();
+}
+
+// Installs SPL Token's wrapped-SOL mint under the GorbChain token program
+pub async fn install_native_mint(ctx: &mut ProgramTestContext) -> Pubkey {
+    let mint = spl_token::state::Mint { decimals: 9, is_initialized: true, ..Default::default() };
+    let mut data = vec![0; spl_token::state::Mint::LEN];
+    mint.pack_into_slice(&mut data);
+    let rent = ctx.banks_client.get_rent().await.unwrap();
+    let account = solana_sdk::account::Account {
+        lamports: rent.minimum_balance(data.len()),
+        data,
+        owner: token_program_id(),
+        executable: false,
+        rent_epoch: 0,
+    };
+    ctx.set_account(&spl_token::native_mint::id(), &account.into());
+    spl_token::native_mint::id()
+}
+
+pub async fn token_balance(ctx: &mut ProgramTestContext, account: &Pubkey) -> u64 {
+    let account = ctx.banks_client.get_account(*account).await.unwrap().unwrap();
+    spl_token::state::Account::unpack(&account.data[..spl_token::state::Account::LEN]).unwrap().amount
+}
+
+pub async fn get_pool(ctx: &mut ProgramTestContext, address: &Pubkey) -> Pool {
+    let account = ctx.banks_client.get_account(*address).await.unwrap().unwrap();
+    Pool::unpack(&account.data).unwrap()
+}
+
+pub async fn get_registry(ctx: &mut ProgramTestContext) -> PoolRegistry {
+    let (registry, _) = instruction::find_registry_address(&cargo_swap::id());
+    let account = ctx.banks_client.get_account(registry).await.unwrap().unwrap();
+    PoolRegistry::unpack(&account.data).unwrap()
+}
+
+pub async fn get_pool_info(ctx: &mut ProgramTestContext, pool_id: u64) -> PoolInfo {
+    let (pool_index, _) = instruction::find_pool_index_address(&cargo_swap::id(), pool_id);
+    let account = ctx.banks_client.get_account(pool_index).await.unwrap().unwrap();
+    PoolInfo::unpack(&account.data).unwrap()
+}
+
+// Runs an instruction through simulation and returns its return data
+pub async fn return_data(ctx: &mut ProgramTestContext, ix: Instruction) -> Vec<u8> {
+    let blockhash = ctx.get_new_latest_blockhash().await.unwrap();
+    let transaction = Transaction::new_signed_with_payer(&[ix], Some(&ctx.payer.pubkey()), &[&ctx.payer], blockhash);
+    let simulation = ctx.banks_client.simulate_transaction(transaction).await.unwrap();
+    simulation.result.unwrap().unwrap();
+    simulation.simulation_details.unwrap().return_data.map(|data| data.data).unwrap_or_default()
+}
+
+pub struct PoolFixture {
+    pub keys: PoolKeys,
+    pub user: UserKeys,
+}
+
+// Mints and funded user accounts for a pool registered with CreatePool, which
+// creates its vaults and LP mint; the pool account itself is created later by
+// (Legacy)InitPool
+pub async fn pool_fixture(ctx: &mut ProgramTestContext) -> PoolFixture {
+    pool_fixture_with_programs(ctx, &token_program_id(), &token_program_id()).await
+}
+
+// Same as `pool_fixture`, with each pool mint owned by the given token program
+pub async fn pool_fixture_with_programs(
+    ctx: &mut ProgramTestContext,
+    token_program_a: &Pubkey,
+    token_program_b: &Pubkey,
+) -> PoolFixture {
+    let payer = ctx.payer.pubkey();
+    let token_a = create_mint_with_program(ctx, token_program_a, &payer, 9).await;
+    let token_b = create_mint_with_program(ctx, token_program_b, &payer, 6).await;
+    pool_fixture_with_mints(ctx, token_a, token_b).await
+}
+
+// Same as `pool_fixture`, for mints the caller already created
+pub async fn pool_fixture_with_mints(ctx: &mut ProgramTestContext, token_a: Pubkey, token_b: Pubkey) -> PoolFixture {
+    pool_fixture_with_lp_program(ctx, token_a, token_b, &token_program_id()).await
+}
+
+// Same as `pool_fixture_with_mints`, with the LP mint owned by `lp_token_program`
+pub async fn pool_fixture_with_lp_program(
+    ctx: &mut ProgramTestContext,
+    token_a: Pubkey,
+    token_b: Pubkey,
+    lp_token_program: &Pubkey,
+) -> PoolFixture {
+    let payer = ctx.payer.pubkey();
+    let token_program_a = owner_of(ctx, &token_a).await;
+    let token_program_b = owner_of(ctx, &token_b).await;
+    let keys = PoolKeys::new(&cargo_swap::id(), &token_a, &token_b, &token_program_a, &token_program_b);
+    create_pool_with_lp_program(ctx, &keys, PoolType::Standard, lp_token_program).await;
+
+    let user_a = create_token_account(ctx, &token_a, &payer).await;
+    let user_b = create_token_account(ctx, &token_b, &payer).await;
+    let user_lp = create_token_account(ctx, &keys.lp_mint, &payer).await;
+    fund(ctx, &token_a, &user_a, USER_FUNDS).await;
+    fund(ctx, &token_b, &user_b, USER_FUNDS).await;
+
+    PoolFixture {
+        keys,
+        user: UserKeys {
+            user: payer,
+            token_a: user_a,
+            token_b: user_b,
+            lp: user_lp,
+        },
+    }
+}
+
+// Registers the pool under the registry's next ID, initializing the registry
+// on first use; returns the assigned ID
+pub async fn create_pool(ctx: &mut ProgramTestContext, keys: &PoolKeys, pool_type: PoolType) -> u64 {
+    create_pool_with_lp_program(ctx, keys, pool_type, &token_program_id()).await
+}
+
+pub async fn create_pool_with_lp_program(
+    ctx: &mut ProgramTestContext,
+    keys: &PoolKeys,
+    pool_type: PoolType,
+    lp_token_program: &Pubkey,
+) -> u64 {
+    let (registry, _) = instruction::find_registry_address(&cargo_swap::id());
+    if ctx.banks_client.get_account(registry).await.unwrap().is_none() {
+        initialize_registry(ctx).await;
+    }
+    let pool_id = get_registry(ctx).await.next_pool_id;
+    let payer = ctx.payer.pubkey();
+    let ix = instruction::create_pool(&cargo_swap::id(), &payer, keys, pool_type, lp_token_program, pool_id);
+    process(ctx, &[ix], &[]).await.unwrap();
+    pool_id
+}
+
+pub async fn init_pool(ctx: &mut ProgramTestContext, fixture: &PoolFixture, pool_id: u64, amount_a: u64, amount_b: u64) {
+    let lp_token_program = owner_of(ctx, &fixture.keys.lp_mint).await;
+    let ix = instruction::init_pool(
+        &cargo_swap::id(),
+        pool_id,
+        &fixture.keys,
+        &fixture.user,
+        &lp_token_program,
+        amount_a,
+        amount_b,
+    );
+    process(ctx, &[ix], &[]).await.unwrap();
+}
+
+pub async fn initialize_registry(ctx: &mut ProgramTestContext) {
+    let ix = instruction::initialize_registry(&cargo_swap::id(), &ctx.payer.pubkey(), &token_program_id());
+    process(ctx, &[ix], &[]).await.unwrap();
+}
+
+// Gives the payer the program's upgrade authority, which program-test
+// builtins don't have, then has it create the program config
+pub async fn initialize_config(ctx: &mut ProgramTestContext, admin: &Pubkey, guardian: &Pubkey) {
+    let payer = ctx.payer.pubkey();
+    set_upgrade_authority(ctx, &payer);
+    let ix = instruction::initialize_config(&cargo_swap::id(), &payer, admin, guardian);
+    process(ctx, &[ix], &[]).await.unwrap();
+}
+
+// Writes a ProgramData account for the program with `authority` as its
+// upgrade authority
+pub fn set_upgrade_authority(ctx: &mut ProgramTestContext, authority: &Pubkey) {
+    let (program_data, _) = instruction::find_program_data_address(&cargo_swap::id());
+    let mut data = 3u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&0u64.to_le_bytes());
+    data.push(1);
+    data.extend_from_slice(authority.as_ref());
+    let account = solana_sdk::account::Account {
+        lamports: 1_000_000_000,
+        data,
+        owner: solana_program::bpf_loader_upgradeable::id(),
+        executable: false,
+        rent_epoch: 0,
+    };
+    ctx.set_account(&program_data, &account.into());
+}
+
+// Token metadata stored in a Token-2022 mint
+pub async fn get_token_metadata(ctx: &mut ProgramTestContext, mint: &Pubkey) -> TokenMetadata {
+    let account = ctx.banks_client.get_account(*mint).await.unwrap().unwrap();
+    let mint = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&account.data).unwrap();
+    mint.get_variable_len_extension::<TokenMetadata>().unwrap()
+}
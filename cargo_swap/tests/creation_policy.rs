@@ -0,0 +1,147 @@
+mod common;
+
+use cargo_swap::{
+    error::SwapError,
+    instruction::{self, PoolKeys, TokenFee},
+    CreationPolicy, CreationRule, CreationRules, MintStatus, PoolType,
+};
+use common::*;
+use solana_program::{
+    instruction::{Instruction, InstructionError},
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+
+const FEE: u64 = 10_000_000;
+
+fn custom_error(error: SwapError) -> TransactionError {
+    TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+}
+
+// Program config with the payer as admin and an initialized registry
+async fn setup() -> ProgramTestContext {
+    let mut ctx = start().await;
+    let payer = ctx.payer.pubkey();
+    initialize_config(&mut ctx, &payer, &payer).await;
+    initialize_registry(&mut ctx).await;
+    ctx
+}
+
+async fn new_pool_keys(ctx: &mut ProgramTestContext) -> PoolKeys {
+    let payer = ctx.payer.pubkey();
+    let token_a = create_mint(ctx, &payer, 9).await;
+    let token_b = create_mint(ctx, &payer, 6).await;
+    PoolKeys::new(&cargo_swap::id(), &token_a, &token_b, &token_program_id(), &token_program_id())
+}
+
+async fn create_pool_ix(ctx: &mut ProgramTestContext, keys: &PoolKeys, pool_type: PoolType) -> Instruction {
+    let pool_id = get_registry(ctx).await.next_pool_id;
+    let payer = ctx.payer.pubkey();
+    instruction::create_pool(&cargo_swap::id(), &payer, keys, pool_type, &token_program_id(), pool_id)
+}
+
+async fn set_policy(ctx: &mut ProgramTestContext, fee_mint: &Pubkey, treasury: &Pubkey, rules: CreationRules) {
+    let payer = ctx.payer.pubkey();
+    let ix = instruction::set_creation_policy(&cargo_swap::id(), &payer, &payer, fee_mint, FEE, treasury, rules);
+    process(ctx, &[ix], &[]).await.unwrap();
+}
+
+#[tokio::test]
+async fn lamport_fee_goes_to_the_treasury() {
+    let mut ctx = setup().await;
+    let treasury = Keypair::new().pubkey();
+    set_policy(&mut ctx, &Pubkey::default(), &treasury, CreationRules::default()).await;
+    let keys = new_pool_keys(&mut ctx).await;
+
+    let ix = create_pool_ix(&mut ctx, &keys, PoolType::Standard).await;
+    let error = transaction_error(process(&mut ctx, std::slice::from_ref(&ix), &[]).await);
+    assert_eq!(error, TransactionError::InstructionError(0, InstructionError::NotEnoughAccountKeys));
+    let wrong = instruction::pay_creation_fee(ix.clone(), &ctx.payer.pubkey(), None);
+    let error = transaction_error(process(&mut ctx, &[wrong], &[]).await);
+    assert_eq!(error, TransactionError::InstructionError(0, InstructionError::InvalidArgument));
+
+    process(&mut ctx, &[instruction::pay_creation_fee(ix, &treasury, None)], &[]).await.unwrap();
+    assert_eq!(ctx.banks_client.get_balance(treasury).await.unwrap(), FEE);
+    assert_eq!(get_registry(&mut ctx).await.total_pools, 1);
+}
+
+#[tokio::test]
+async fn token_fee_goes_to_the_treasury_account() {
+    let mut ctx = setup().await;
+    let payer = ctx.payer.pubkey();
+    let fee_mint = create_mint(&mut ctx, &payer, 6).await;
+    let treasury = create_token_account(&mut ctx, &fee_mint, &Keypair::new().pubkey()).await;
+    let source = create_token_account(&mut ctx, &fee_mint, &payer).await;
+    fund(&mut ctx, &fee_mint, &source, FEE).await;
+    set_policy(&mut ctx, &fee_mint, &treasury, CreationRules::default()).await;
+
+    let keys = new_pool_keys(&mut ctx).await;
+    let fee = TokenFee { mint: fee_mint, source, token_program: token_program_id() };
+    let ix = create_pool_ix(&mut ctx, &keys, PoolType::Standard).await;
+    process(&mut ctx, &[instruction::pay_creation_fee(ix, &treasury, Some(&fee))], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut ctx, &treasury).await, FEE);
+    assert_eq!(token_balance(&mut ctx, &source).await, 0);
+
+    // The payer can't cover another pool
+    let keys = new_pool_keys(&mut ctx).await;
+    let ix = create_pool_ix(&mut ctx, &keys, PoolType::Standard).await;
+    let result = process(&mut ctx, &[instruction::pay_creation_fee(ix, &treasury, Some(&fee))], &[]).await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn mint_lists_and_type_rules_gate_creation() {
+    let mut ctx = setup().await;
+    let payer = ctx.payer.pubkey();
+    let program_id = cargo_swap::id();
+    let keys = new_pool_keys(&mut ctx).await;
+
+    // Only the admin lists mints, once the policy exists
+    let ix = instruction::set_mint_status(&program_id, &payer, &keys.token_a, MintStatus::Allowed);
+    let error = transaction_error(process(&mut ctx, &[ix], &[]).await);
+    assert_eq!(error, TransactionError::InstructionError(0, InstructionError::UninitializedAccount));
+
+    let rules = CreationRules {
+        standard: CreationRule::Open,
+        stable: CreationRule::AllowlistOnly,
+        concentrated: CreationRule::Disabled,
+    };
+    set_policy(&mut ctx, &Pubkey::default(), &payer, rules).await;
+    let outsider = Keypair::new();
+    let ix = instruction::set_mint_status(&program_id, &outsider.pubkey(), &keys.token_a, MintStatus::Allowed);
+    let error = transaction_error(process(&mut ctx, &[ix], &[&outsider]).await);
+    assert_eq!(error, custom_error(SwapError::Unauthorized));
+
+    let ix = instruction::set_mint_status(&program_id, &payer, &keys.token_a, MintStatus::Allowed);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    for (pool_type, expected) in [
+        (PoolType::Stable, SwapError::MintNotAllowed),
+        (PoolType::Concentrated, SwapError::PoolTypeDisabled),
+    ] {
+        let ix = instruction::pay_creation_fee(create_pool_ix(&mut ctx, &keys, pool_type).await, &payer, None);
+        let error = transaction_error(process(&mut ctx, &[ix], &[]).await);
+        assert_eq!(error, custom_error(expected));
+    }
+
+    // A denied mint gets no pool of any type
+    let ix = instruction::set_mint_status(&program_id, &payer, &keys.token_b, MintStatus::Denied);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    let ix = instruction::pay_creation_fee(create_pool_ix(&mut ctx, &keys, PoolType::Standard).await, &payer, None);
+    let error = transaction_error(process(&mut ctx, &[ix], &[]).await);
+    assert_eq!(error, custom_error(SwapError::MintNotAllowed));
+
+    let ix = instruction::set_mint_status(&program_id, &payer, &keys.token_b, MintStatus::Allowed);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    let ix = create_pool_ix(&mut ctx, &keys, PoolType::Stable).await;
+    process(&mut ctx, &[instruction::pay_creation_fee(ix, &payer, None)], &[]).await.unwrap();
+
+    let (policy, _) = instruction::find_creation_policy_address(&program_id);
+    let account = ctx.banks_client.get_account(policy).await.unwrap().unwrap();
+    let policy = CreationPolicy::unpack(&account.data).unwrap();
+    assert_eq!((policy.mint_count, policy.rules), (2, rules));
+}
@@ -0,0 +1,102 @@
+mod common;
+
+use cargo_swap::{error::SwapError, instruction, quote, DcaParams, DcaVault, PAUSE_WITHDRAWALS};
+use common::*;
+use solana_program::{instruction::InstructionError, program_pack::Pack, pubkey::Pubkey};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::{signature::Keypair, signer::Signer, transaction::TransactionError};
+
+const PARAMS: DcaParams = DcaParams { amount_per_period: 10_000, interval_seconds: 60, max_slippage_bps: 300 };
+
+fn custom_error(error: SwapError) -> TransactionError {
+    TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+}
+
+// A 1:1 pool with a vault buying B with 30k of A a minute at a time
+async fn setup() -> (ProgramTestContext, PoolFixture, Pubkey) {
+    let mut ctx = start().await;
+    let fixture = pool_fixture(&mut ctx).await;
+    init_pool(&mut ctx, &fixture, 1, 1_000_000, 1_000_000).await;
+    let program_id = cargo_swap::id();
+    let ix = instruction::create_dca(&program_id, &fixture.keys, &fixture.user, 1, 30_000, PARAMS, true);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    let (dca, _) = instruction::find_dca_address(&program_id, &fixture.keys.pool, &fixture.user.user, 1);
+    (ctx, fixture, dca)
+}
+
+async fn get_vault(ctx: &mut ProgramTestContext, address: &Pubkey) -> DcaVault {
+    let account = ctx.banks_client.get_account(*address).await.unwrap().unwrap();
+    DcaVault::unpack(&account.data).unwrap()
+}
+
+#[tokio::test]
+async fn crank_buys_once_per_period() {
+    let (mut ctx, fixture, dca) = setup().await;
+    let program_id = cargo_swap::id();
+    let execute = instruction::execute_dca(&program_id, &fixture.keys, &dca, &fixture.user.token_b, true);
+    let error = transaction_error(process(&mut ctx, std::slice::from_ref(&execute), &[]).await);
+    assert_eq!(error, custom_error(SwapError::NoIntervalsDue));
+
+    advance_clock(&mut ctx, 60).await;
+    let expected = quote::quote_swap_exact_in(&get_pool(&mut ctx, &fixture.keys.pool).await, 10_000, true).unwrap();
+    let balance_b = token_balance(&mut ctx, &fixture.user.token_b).await;
+    process(&mut ctx, std::slice::from_ref(&execute), &[]).await.unwrap();
+    assert_eq!(token_balance(&mut ctx, &fixture.user.token_b).await - balance_b, expected.amount_out);
+    let pool = get_pool(&mut ctx, &fixture.keys.pool).await;
+    assert_eq!((pool.reserve_a, pool.reserve_b), (expected.reserve_a_after, expected.reserve_b_after));
+    let vault = get_vault(&mut ctx, &dca).await;
+    assert_eq!(vault.periods_executed, 1);
+    assert_eq!(vault.next_execution, vault.observed_at + 60);
+    let (escrow, _) = instruction::find_order_escrow_address(&program_id, &dca);
+    assert_eq!(token_balance(&mut ctx, &escrow).await, 20_000);
+
+    // Once per period, however often the crank runs
+    let error = transaction_error(process(&mut ctx, &[execute], &[]).await);
+    assert_eq!(error, custom_error(SwapError::NoIntervalsDue));
+}
+
+#[tokio::test]
+async fn price_pushed_off_its_average_is_not_bought_into() {
+    let (mut ctx, fixture, dca) = setup().await;
+    let program_id = cargo_swap::id();
+    advance_clock(&mut ctx, 60).await;
+
+    // Dumping A right before the crank leaves B far dearer than its average
+    let ix = instruction::swap(&program_id, 1, &fixture.keys, &fixture.user, 200_000, true);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    let execute = instruction::execute_dca(&program_id, &fixture.keys, &dca, &fixture.user.token_b, true);
+    let error = transaction_error(process(&mut ctx, &[execute], &[]).await);
+    assert_eq!(error, custom_error(SwapError::SlippageExceeded));
+    assert_eq!(get_vault(&mut ctx, &dca).await.periods_executed, 0);
+
+    let outsider = Keypair::new();
+    let close = instruction::close_dca(&program_id, &fixture.keys, &fixture.user, 1, true);
+    let mut stolen = close.clone();
+    stolen.accounts[3].pubkey = outsider.pubkey();
+    let error = transaction_error(process(&mut ctx, &[stolen], &[&outsider]).await);
+    assert_eq!(error, custom_error(SwapError::Unauthorized));
+
+    let balance_a = token_balance(&mut ctx, &fixture.user.token_a).await;
+    process(&mut ctx, &[close], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut ctx, &fixture.user.token_a).await, balance_a + 30_000);
+    assert!(ctx.banks_client.get_account(dca).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn closing_is_a_withdrawal_for_the_pause() {
+    let (mut ctx, fixture, dca) = setup().await;
+    let program_id = cargo_swap::id();
+    let payer = ctx.payer.pubkey();
+    initialize_config(&mut ctx, &payer, &payer).await;
+    let close = instruction::close_dca(&program_id, &fixture.keys, &fixture.user, 1, true);
+
+    let ix = instruction::set_pause(&program_id, &payer, PAUSE_WITHDRAWALS);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    let error = transaction_error(process(&mut ctx, std::slice::from_ref(&close), &[]).await);
+    assert_eq!(error, custom_error(SwapError::ProgramPaused));
+
+    let ix = instruction::set_pause(&program_id, &payer, 0);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    process(&mut ctx, &[close], &[]).await.unwrap();
+    assert!(ctx.banks_client.get_account(dca).await.unwrap().is_none());
+}
@@ -0,0 +1,216 @@
+mod common;
+
+use cargo_swap::{
+    error::SwapError,
+    instruction::{self, RewardKeys, UserKeys},
+    FarmStake, RewardSchedule, PAUSE_DEPOSITS, PAUSE_WITHDRAWALS,
+};
+use common::*;
+use solana_program::{
+    clock::Clock, instruction::InstructionError, program_pack::Pack, pubkey::Pubkey, system_instruction,
+};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+
+fn custom_error(error: SwapError) -> TransactionError {
+    TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+}
+
+// A 1:1 pool whose farm the payer, as admin, created and added one reward
+// stream per entry of `emissions` to, each running for 1000 seconds from now
+async fn setup(emissions: &[u64]) -> (ProgramTestContext, PoolFixture, Vec<Pubkey>) {
+    let mut ctx = start().await;
+    let fixture = pool_fixture(&mut ctx).await;
+    init_pool(&mut ctx, &fixture, 1, 1_000_000, 1_000_000).await;
+    let program_id = cargo_swap::id();
+    let payer = ctx.payer.pubkey();
+    initialize_config(&mut ctx, &payer, &payer).await;
+    let ix = instruction::create_farm(&program_id, &payer, &payer, &fixture.keys, &token_program_id());
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+
+    let now = ctx.banks_client.get_sysvar::<Clock>().await.unwrap().unix_timestamp;
+    let mut mints = Vec::new();
+    for &emission_per_second in emissions {
+        let mint = create_mint(&mut ctx, &payer, 6).await;
+        let schedule = RewardSchedule { emission_per_second, start_time: now, end_time: now + 1000 };
+        let pool = fixture.keys.pool;
+        let ix = instruction::add_farm_reward(&program_id, &payer, &payer, &pool, &mint, &token_program_id(), schedule);
+        process(&mut ctx, &[ix], &[]).await.unwrap();
+        mints.push(mint);
+    }
+    (ctx, fixture, mints)
+}
+
+// Funds the farm's vault for `mint` with a plain token transfer's worth
+async fn fund_rewards(ctx: &mut ProgramTestContext, fixture: &PoolFixture, mint: &Pubkey, amount: u64) {
+    let program_id = cargo_swap::id();
+    let (farm, _) = instruction::find_farm_address(&program_id, &fixture.keys.pool);
+    let (vault, _) = instruction::find_farm_vault_address(&program_id, &farm, mint);
+    mint_to(ctx, mint, &vault, amount).await;
+}
+
+// Reward accounts for `owner`, one per mint
+async fn reward_keys(ctx: &mut ProgramTestContext, mints: &[Pubkey], owner: &Pubkey) -> Vec<RewardKeys> {
+    let mut keys = Vec::new();
+    for mint in mints {
+        let destination = create_token_account(ctx, mint, owner).await;
+        keys.push(RewardKeys { mint: *mint, token_program: token_program_id(), destination });
+    }
+    keys
+}
+
+async fn get_stake(ctx: &mut ProgramTestContext, fixture: &PoolFixture, owner: &Pubkey) -> FarmStake {
+    let program_id = cargo_swap::id();
+    let (farm, _) = instruction::find_farm_address(&program_id, &fixture.keys.pool);
+    let (stake, _) = instruction::find_farm_stake_address(&program_id, &farm, owner);
+    let account = ctx.banks_client.get_account(stake).await.unwrap().unwrap();
+    FarmStake::unpack(&account.data).unwrap()
+}
+
+#[tokio::test]
+async fn rewards_split_by_stake_and_time() {
+    let (mut ctx, fixture, mints) = setup(&[100, 10]).await;
+    let program_id = cargo_swap::id();
+    for mint in &mints {
+        fund_rewards(&mut ctx, &fixture, mint, 1_000_000).await;
+    }
+
+    // A second LP with its own position
+    let second = Keypair::new();
+    let ix = system_instruction::transfer(&ctx.payer.pubkey(), &second.pubkey(), 1_000_000_000);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    let user = UserKeys {
+        user: second.pubkey(),
+        token_a: create_token_account(&mut ctx, &fixture.keys.token_a, &second.pubkey()).await,
+        token_b: create_token_account(&mut ctx, &fixture.keys.token_b, &second.pubkey()).await,
+        lp: create_token_account(&mut ctx, &fixture.keys.lp_mint, &second.pubkey()).await,
+    };
+    fund(&mut ctx, &fixture.keys.token_a, &user.token_a, 10_000).await;
+    fund(&mut ctx, &fixture.keys.token_b, &user.token_b, 10_000).await;
+    let ix = instruction::add_liquidity(&program_id, 1, &fixture.keys, &user, &token_program_id(), 10_000, 10_000);
+    process(&mut ctx, &[ix], &[&second]).await.unwrap();
+
+    // The payer stakes alone for 100 seconds, then shares with three times its stake
+    let ix = instruction::stake_lp(&program_id, &fixture.keys, &fixture.user, &token_program_id(), 1_000);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    advance_clock(&mut ctx, 100).await;
+    let ix = instruction::stake_lp(&program_id, &fixture.keys, &user, &token_program_id(), 3_000);
+    process(&mut ctx, &[ix], &[&second]).await.unwrap();
+    assert_eq!(token_balance(&mut ctx, &user.lp).await, 7_000);
+    advance_clock(&mut ctx, 100).await;
+
+    let payer_rewards = reward_keys(&mut ctx, &mints, &fixture.user.user).await;
+    let ix = instruction::claim_farm_rewards(&program_id, &fixture.keys.pool, &fixture.user.user, &payer_rewards);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    let second_rewards = reward_keys(&mut ctx, &mints, &second.pubkey()).await;
+    let ix = instruction::claim_farm_rewards(&program_id, &fixture.keys.pool, &second.pubkey(), &second_rewards);
+    process(&mut ctx, &[ix], &[&second]).await.unwrap();
+
+    let mut claimed = Vec::new();
+    for keys in payer_rewards.iter().chain(&second_rewards) {
+        claimed.push(token_balance(&mut ctx, &keys.destination).await);
+    }
+    assert_eq!(claimed, [12_500, 1_250, 7_500, 750]);
+    assert_eq!(get_stake(&mut ctx, &fixture, &second.pubkey()).await.pending, [0; 4]);
+}
+
+#[tokio::test]
+async fn unstaking_and_short_vaults_keep_rewards_pending() {
+    let (mut ctx, fixture, mints) = setup(&[100]).await;
+    let program_id = cargo_swap::id();
+    fund_rewards(&mut ctx, &fixture, &mints[0], 6_000).await;
+    let lp_before = token_balance(&mut ctx, &fixture.user.lp).await;
+    let ix = instruction::stake_lp(&program_id, &fixture.keys, &fixture.user, &token_program_id(), 1_000);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    advance_clock(&mut ctx, 100).await;
+
+    let unstake = instruction::unstake_lp(&program_id, &fixture.keys, &fixture.user, &token_program_id(), 1_001);
+    let error = transaction_error(process(&mut ctx, &[unstake], &[]).await);
+    assert_eq!(error, TransactionError::InstructionError(0, InstructionError::InsufficientFunds));
+    let outsider = Keypair::new();
+    let mut stolen = instruction::unstake_lp(&program_id, &fixture.keys, &fixture.user, &token_program_id(), 1_000);
+    stolen.accounts[4].pubkey = outsider.pubkey();
+    let error = transaction_error(process(&mut ctx, &[stolen], &[&outsider]).await);
+    assert_eq!(error, custom_error(SwapError::Unauthorized));
+
+    // Nothing accrues once the stake is gone
+    let unstake = instruction::unstake_lp(&program_id, &fixture.keys, &fixture.user, &token_program_id(), 1_000);
+    process(&mut ctx, &[unstake], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut ctx, &fixture.user.lp).await, lp_before);
+    advance_clock(&mut ctx, 100).await;
+
+    // The vault only covers part of what was earned
+    let rewards = reward_keys(&mut ctx, &mints, &fixture.user.user).await;
+    let claim = instruction::claim_farm_rewards(&program_id, &fixture.keys.pool, &fixture.user.user, &rewards);
+    process(&mut ctx, std::slice::from_ref(&claim), &[]).await.unwrap();
+    assert_eq!(token_balance(&mut ctx, &rewards[0].destination).await, 6_000);
+    assert_eq!(get_stake(&mut ctx, &fixture, &fixture.user.user).await.pending[0], 4_000);
+
+    fund_rewards(&mut ctx, &fixture, &mints[0], 10_000).await;
+    process(&mut ctx, &[claim], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut ctx, &rewards[0].destination).await, 10_000);
+}
+
+#[tokio::test]
+async fn staking_and_leaving_follow_the_pause() {
+    let (mut ctx, fixture, mints) = setup(&[100]).await;
+    let program_id = cargo_swap::id();
+    let payer = ctx.payer.pubkey();
+    let stake = instruction::stake_lp(&program_id, &fixture.keys, &fixture.user, &token_program_id(), 1_000);
+    let unstake = instruction::unstake_lp(&program_id, &fixture.keys, &fixture.user, &token_program_id(), 1_000);
+    let rewards = reward_keys(&mut ctx, &mints, &fixture.user.user).await;
+    let claim = instruction::claim_farm_rewards(&program_id, &fixture.keys.pool, &fixture.user.user, &rewards);
+
+    let ix = instruction::set_pause(&program_id, &payer, PAUSE_DEPOSITS);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    let error = transaction_error(process(&mut ctx, std::slice::from_ref(&stake), &[]).await);
+    assert_eq!(error, custom_error(SwapError::ProgramPaused));
+
+    let ix = instruction::set_pause(&program_id, &payer, PAUSE_WITHDRAWALS);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    process(&mut ctx, &[stake], &[]).await.unwrap();
+    for ix in [&unstake, &claim] {
+        let error = transaction_error(process(&mut ctx, std::slice::from_ref(ix), &[]).await);
+        assert_eq!(error, custom_error(SwapError::ProgramPaused));
+    }
+
+    let ix = instruction::set_pause(&program_id, &payer, 0);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    process(&mut ctx, &[unstake, claim], &[]).await.unwrap();
+}
+
+#[tokio::test]
+async fn only_the_admin_manages_farms() {
+    let (mut ctx, fixture, mut mints) = setup(&[100]).await;
+    let program_id = cargo_swap::id();
+    let payer = ctx.payer.pubkey();
+    let outsider = Keypair::new();
+    let schedule = RewardSchedule { emission_per_second: 1, start_time: 0, end_time: i64::MAX };
+
+    let ix = instruction::set_farm_reward_schedule(&program_id, &outsider.pubkey(), &fixture.keys.pool, 0, schedule);
+    let error = transaction_error(process(&mut ctx, &[ix], &[&outsider]).await);
+    assert_eq!(error, custom_error(SwapError::Unauthorized));
+    let ix = instruction::create_farm(&program_id, &payer, &payer, &fixture.keys, &token_program_id());
+    let error = transaction_error(process(&mut ctx, &[ix], &[]).await);
+    assert_eq!(error, TransactionError::InstructionError(0, InstructionError::AccountAlreadyInitialized));
+
+    // A mint pays through one stream at most, and a farm has room for four
+    let pool = fixture.keys.pool;
+    let ix = instruction::add_farm_reward(&program_id, &payer, &payer, &pool, &mints[0], &token_program_id(), schedule);
+    let error = transaction_error(process(&mut ctx, &[ix], &[]).await);
+    assert_eq!(error, TransactionError::InstructionError(0, InstructionError::InvalidArgument));
+    for _ in 0..4 {
+        let mint = create_mint(&mut ctx, &payer, 6).await;
+        let ix = instruction::add_farm_reward(&program_id, &payer, &payer, &pool, &mint, &token_program_id(), schedule);
+        mints.push(mint);
+        let result = process(&mut ctx, &[ix], &[]).await;
+        if mints.len() <= 4 {
+            result.unwrap();
+        } else {
+            assert_eq!(transaction_error(result), custom_error(SwapError::FarmRewardsFull));
+        }
+    }
+}
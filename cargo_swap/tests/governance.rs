@@ -0,0 +1,204 @@
+mod common;
+
+use borsh::BorshSerialize;
+use cargo_swap::{
+    error::SwapError, instruction, quote, GovernanceChange, PendingChange, ProgramConfig,
+    TestProjectInstruction, CHANGE_DELAY_SECONDS,
+};
+use common::*;
+use solana_program::{clock::Clock, instruction::InstructionError, program_pack::Pack};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+
+fn custom_error(error: SwapError) -> TransactionError {
+    TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+}
+
+// A registered pool with the payer as admin
+async fn setup() -> (ProgramTestContext, PoolFixture) {
+    let mut ctx = start().await;
+    let payer = ctx.payer.pubkey();
+    initialize_config(&mut ctx, &payer, &payer).await;
+    let fixture = pool_fixture(&mut ctx).await;
+    init_pool(&mut ctx, &fixture, 1, 1_000_000, 1_000_000).await;
+    (ctx, fixture)
+}
+
+#[tokio::test]
+async fn fee_change_waits_for_the_delay() {
+    let (mut ctx, fixture) = setup().await;
+    let payer = ctx.payer.pubkey();
+    let program_id = cargo_swap::id();
+    let pool = fixture.keys.pool;
+    let change = GovernanceChange::FeeRate { fee_rate: 100 };
+
+    let ix = instruction::propose_change(&program_id, &payer, &payer, &pool, change);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    let (pending_address, _) = instruction::find_pending_change_address(&program_id, &pool);
+    let account = ctx.banks_client.get_account(pending_address).await.unwrap().unwrap();
+    let pending = PendingChange::unpack(&account.data).unwrap();
+    assert_eq!((pending.change(), pending.eta - pending.proposed_at), (change, CHANGE_DELAY_SECONDS));
+
+    let ix = instruction::execute_change(&program_id, &payer, &payer, &pool, 1);
+    let error = transaction_error(process(&mut ctx, &[ix], &[]).await);
+    assert_eq!(error, custom_error(SwapError::TimelockNotElapsed));
+    assert_eq!(get_pool(&mut ctx, &pool).await.fee_rate, 30);
+
+    advance_clock(&mut ctx, CHANGE_DELAY_SECONDS).await;
+    let ix = instruction::execute_change(&program_id, &payer, &payer, &pool, 1);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    assert_eq!(get_pool(&mut ctx, &pool).await.fee_rate, 100);
+    assert_eq!(get_pool_info(&mut ctx, 1).await.fee_rate, 100);
+    assert!(ctx.banks_client.get_account(pending_address).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn dynamic_fee_follows_volatility_within_bounds() {
+    let (mut ctx, fixture) = setup().await;
+    let payer = ctx.payer.pubkey();
+    let program_id = cargo_swap::id();
+    let pool = fixture.keys.pool;
+    let swap = |amount| [instruction::swap(&program_id, 1, &fixture.keys, &fixture.user, amount, true)];
+
+    let inverted = GovernanceChange::DynamicFee { min_fee_rate: 50, max_fee_rate: 10 };
+    let ix = instruction::propose_change(&program_id, &payer, &payer, &pool, inverted);
+    let error = transaction_error(process(&mut ctx, &[ix], &[]).await);
+    assert_eq!(error, custom_error(SwapError::InvalidFeeRate));
+
+    let change = GovernanceChange::DynamicFee { min_fee_rate: 10, max_fee_rate: 100 };
+    let ix = instruction::propose_change(&program_id, &payer, &payer, &pool, change);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    advance_clock(&mut ctx, CHANGE_DELAY_SECONDS).await;
+    let ix = instruction::execute_change(&program_id, &payer, &payer, &pool, 0);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    let state = get_pool(&mut ctx, &pool).await;
+    assert_eq!((state.dynamic_fee, state.min_fee_rate, state.max_fee_rate), (true, 10, 100));
+
+    // A calm pool charges the minimum, and a ~17% move saturates the fee
+    let now = ctx.banks_client.get_sysvar::<Clock>().await.unwrap().unix_timestamp;
+    assert_eq!(quote::dynamic_fee_rate(&state, now), 10);
+    process(&mut ctx, &swap(100_000), &[]).await.unwrap();
+    let state = get_pool(&mut ctx, &pool).await;
+    assert!(state.volatility_bps >= quote::VOLATILITY_SATURATION_BPS);
+    assert_eq!(quote::dynamic_fee_rate(&state, now), 100);
+
+    // Clients predict the fee the next swap pays
+    let before = token_balance(&mut ctx, &fixture.user.token_b).await;
+    let expected = quote::quote_swap_exact_in(&quote::with_current_fee(&state, now), 1_000, true).unwrap();
+    process(&mut ctx, &swap(1_000), &[]).await.unwrap();
+    assert_eq!(token_balance(&mut ctx, &fixture.user.token_b).await - before, expected.amount_out);
+
+    // Volatility decays back to the minimum fee
+    advance_clock(&mut ctx, quote::VOLATILITY_DECAY_SECONDS).await;
+    let state = get_pool(&mut ctx, &pool).await;
+    assert_eq!(quote::dynamic_fee_rate(&state, now + quote::VOLATILITY_DECAY_SECONDS), 10);
+
+    // A fixed fee turns dynamic fees off
+    let fee = GovernanceChange::FeeRate { fee_rate: 20 };
+    let ix = instruction::propose_change(&program_id, &payer, &payer, &pool, fee);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    advance_clock(&mut ctx, CHANGE_DELAY_SECONDS).await;
+    let ix = instruction::execute_change(&program_id, &payer, &payer, &pool, 1);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    let state = get_pool(&mut ctx, &pool).await;
+    assert_eq!((state.dynamic_fee, quote::dynamic_fee_rate(&state, now)), (false, 20));
+}
+
+#[tokio::test]
+async fn cancelled_change_can_not_be_executed() {
+    let (mut ctx, fixture) = setup().await;
+    let payer = ctx.payer.pubkey();
+    let program_id = cargo_swap::id();
+    let (config, _) = instruction::find_config_address(&program_id);
+    let guardian = Keypair::new().pubkey();
+    let change = GovernanceChange::Guardian { guardian, withdrawals_open: true };
+
+    let ix = instruction::propose_change(&program_id, &payer, &payer, &config, change);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+
+    // One change per target at a time
+    let other = GovernanceChange::Guardian { guardian: payer, withdrawals_open: false };
+    let ix = instruction::propose_change(&program_id, &payer, &payer, &config, other);
+    let error = transaction_error(process(&mut ctx, &[ix], &[]).await);
+    assert_eq!(error, TransactionError::InstructionError(0, InstructionError::AccountAlreadyInitialized));
+
+    let outsider = Keypair::new();
+    let ix = instruction::cancel_change(&program_id, &outsider.pubkey(), &payer, &config);
+    let error = transaction_error(process(&mut ctx, &[ix], &[&outsider]).await);
+    assert_eq!(error, custom_error(SwapError::Unauthorized));
+
+    let ix = instruction::cancel_change(&program_id, &payer, &payer, &config);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    let (pending_address, _) = instruction::find_pending_change_address(&program_id, &config);
+    assert!(ctx.banks_client.get_account(pending_address).await.unwrap().is_none());
+
+    advance_clock(&mut ctx, CHANGE_DELAY_SECONDS).await;
+    let ix = instruction::execute_change(&program_id, &payer, &payer, &config, 0);
+    let error = transaction_error(process(&mut ctx, &[ix], &[]).await);
+    assert_eq!(error, TransactionError::InstructionError(0, InstructionError::UninitializedAccount));
+    let account = ctx.banks_client.get_account(config).await.unwrap().unwrap();
+    assert_eq!(ProgramConfig::unpack(&account.data).unwrap().guardian, payer);
+
+    // The target is free for a new proposal
+    let fee = GovernanceChange::FeeRate { fee_rate: 5 };
+    let ix = instruction::propose_change(&program_id, &payer, &payer, &fixture.keys.pool, fee);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    let ix = instruction::propose_change(&program_id, &payer, &payer, &config, other);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+}
+
+#[tokio::test]
+async fn changes_are_checked_and_only_go_through_the_timelock() {
+    let (mut ctx, fixture) = setup().await;
+    let payer = ctx.payer.pubkey();
+    let program_id = cargo_swap::id();
+    let (config, _) = instruction::find_config_address(&program_id);
+
+    let fee = GovernanceChange::FeeRate { fee_rate: 10_000 };
+    let ix = instruction::propose_change(&program_id, &payer, &payer, &fixture.keys.pool, fee);
+    let error = transaction_error(process(&mut ctx, &[ix], &[]).await);
+    assert_eq!(error, custom_error(SwapError::InvalidFeeRate));
+
+    // Guardian changes target the program config, fee changes a pool
+    let change = GovernanceChange::Guardian { guardian: payer, withdrawals_open: true };
+    let ix = instruction::propose_change(&program_id, &payer, &payer, &fixture.keys.pool, change);
+    let error = transaction_error(process(&mut ctx, &[ix], &[]).await);
+    assert_eq!(error, TransactionError::InstructionError(0, InstructionError::InvalidArgument));
+    let fee = GovernanceChange::FeeRate { fee_rate: 5 };
+    let ix = instruction::propose_change(&program_id, &payer, &payer, &config, fee);
+    let error = transaction_error(process(&mut ctx, &[ix], &[]).await);
+    assert_eq!(error, TransactionError::InstructionError(0, InstructionError::InvalidAccountData));
+
+    // The instant forms propose the same change through the timelock
+    let pool = fixture.keys.pool;
+    for (data, target, change) in [
+        (
+            TestProjectInstruction::UpdatePoolFee { pool_id: 1, new_fee_rate: 100 },
+            pool,
+            GovernanceChange::FeeRate { fee_rate: 100 },
+        ),
+        (
+            TestProjectInstruction::SetPriceBand { max_price_move_bps: 300 },
+            pool,
+            GovernanceChange::PriceBand { max_price_move_bps: 300 },
+        ),
+        (
+            TestProjectInstruction::SetGuardian { guardian: payer, withdrawals_open: true },
+            config,
+            GovernanceChange::Guardian { guardian: payer, withdrawals_open: true },
+        ),
+    ] {
+        let mut ix = instruction::propose_change(&program_id, &payer, &payer, &target, change);
+        ix.data = data.try_to_vec().unwrap();
+        process(&mut ctx, &[ix], &[]).await.unwrap();
+        let (pending_address, _) = instruction::find_pending_change_address(&program_id, &target);
+        let account = ctx.banks_client.get_account(pending_address).await.unwrap().unwrap();
+        assert_eq!(PendingChange::unpack(&account.data).unwrap().change(), change);
+        let ix = instruction::cancel_change(&program_id, &payer, &payer, &target);
+        process(&mut ctx, &[ix], &[]).await.unwrap();
+    }
+    assert_eq!(get_pool(&mut ctx, &pool).await.max_price_move_bps, 0);
+}
@@ -0,0 +1,319 @@
+mod common;
+
+use borsh::BorshSerialize;
+use cargo_swap::{
+    error::SwapError, instruction::{self, PoolKeys}, quote, LegacyBorshPool,
+    LegacySinglePool, Pool, PoolType, ZeroCopy, POOL_VERSION,
+};
+use common::*;
+use solana_program::{instruction::InstructionError, program_pack::Pack, pubkey::Pubkey};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::{signature::Signer, transaction::TransactionError};
+
+const INITIAL: u64 = 1_000_000_000;
+
+async fn legacy_init(ctx: &mut ProgramTestContext, fixture: &PoolFixture) {
+    init_pool(ctx, fixture, 1, INITIAL, INITIAL).await;
+}
+
+#[tokio::test]
+async fn legacy_instructions_full_cycle() {
+    let mut ctx = start().await;
+    let fixture = pool_fixture(&mut ctx).await;
+    legacy_init(&mut ctx, &fixture).await;
+
+    let pool = get_pool(&mut ctx, &fixture.keys.pool).await;
+    assert_eq!(pool.pool_id, 1);
+    assert_eq!(pool.total_lp_supply, INITIAL);
+
+    let ix = instruction::legacy_add_liquidity(
+        &cargo_swap::id(),
+        &fixture.keys,
+        &fixture.user,
+        &token_program_id(),
+        INITIAL,
+        INITIAL,
+    );
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    let pool = get_pool(&mut ctx, &fixture.keys.pool).await;
+    assert_eq!(pool.reserve_a, 2 * INITIAL);
+    assert_eq!(pool.total_lp_supply, 2 * INITIAL);
+
+    for direction_a_to_b in [true, false] {
+        let before = get_pool(&mut ctx, &fixture.keys.pool).await;
+        let expected = quote::quote_swap_exact_in(&before, INITIAL / 10, direction_a_to_b).unwrap();
+        let ix = instruction::legacy_swap(
+            &cargo_swap::id(),
+            &fixture.keys,
+            &fixture.user,
+            INITIAL / 10,
+            direction_a_to_b,
+        );
+        process(&mut ctx, &[ix], &[]).await.unwrap();
+        let after = get_pool(&mut ctx, &fixture.keys.pool).await;
+        assert_eq!((after.reserve_a, after.reserve_b), (expected.reserve_a_after, expected.reserve_b_after));
+    }
+
+    let before = get_pool(&mut ctx, &fixture.keys.pool).await;
+    let expected = quote::quote_remove_liquidity(&before, INITIAL).unwrap();
+    let ix = instruction::legacy_remove_liquidity(
+        &cargo_swap::id(),
+        &fixture.keys,
+        &fixture.user,
+        &token_program_id(),
+        INITIAL,
+    );
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+
+    let after = get_pool(&mut ctx, &fixture.keys.pool).await;
+    assert_eq!(after.total_lp_supply, INITIAL);
+    assert_eq!(after.reserve_a, expected.reserve_a_after);
+    assert_eq!(token_balance(&mut ctx, &fixture.keys.vault_a).await, after.reserve_a);
+    assert_eq!(token_balance(&mut ctx, &fixture.keys.vault_b).await, after.reserve_b);
+}
+
+#[tokio::test]
+async fn legacy_and_multi_pool_instructions_share_pools() {
+    let mut ctx = start().await;
+    let fixture = pool_fixture(&mut ctx).await;
+    legacy_init(&mut ctx, &fixture).await;
+
+    let ix = instruction::swap(
+        &cargo_swap::id(),
+        0,
+        &fixture.keys,
+        &fixture.user,
+        INITIAL / 10,
+        true,
+    );
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+
+    let pool = get_pool(&mut ctx, &fixture.keys.pool).await;
+    assert_eq!(pool.reserve_a, INITIAL + INITIAL / 10);
+}
+
+#[tokio::test]
+async fn legacy_instructions_reject_wrong_pool_address() {
+    let mut ctx = start().await;
+    let fixture = pool_fixture(&mut ctx).await;
+    legacy_init(&mut ctx, &fixture).await;
+
+    let mut keys = fixture.keys;
+    std::mem::swap(&mut keys.token_a, &mut keys.token_b);
+
+    let ixs = [
+        instruction::legacy_add_liquidity(&cargo_swap::id(), &keys, &fixture.user, &token_program_id(), 10, 10),
+        instruction::legacy_remove_liquidity(&cargo_swap::id(), &keys, &fixture.user, &token_program_id(), 10),
+        instruction::legacy_swap(&cargo_swap::id(), &keys, &fixture.user, 10, true),
+    ];
+    for ix in ixs {
+        let err = transaction_error(process(&mut ctx, &[ix], &[]).await);
+        assert_eq!(err, TransactionError::InstructionError(0, InstructionError::InvalidSeeds));
+    }
+}
+
+#[tokio::test]
+async fn legacy_init_pool_rejects_registered_pools() {
+    let mut ctx = start().await;
+    let fixture = pool_fixture(&mut ctx).await;
+    let ix = instruction::legacy_init_pool(
+        &cargo_swap::id(),
+        &fixture.keys,
+        &fixture.user,
+        &token_program_id(),
+        INITIAL,
+        INITIAL,
+    );
+    let err = transaction_error(process(&mut ctx, &[ix], &[]).await);
+    assert_eq!(err, TransactionError::InstructionError(0, InstructionError::AccountAlreadyInitialized));
+    assert!(ctx.banks_client.get_account(fixture.keys.pool).await.unwrap().is_none());
+
+    // Without the pair index the legacy path has nothing to check against
+    let mut ix = instruction::legacy_init_pool(
+        &cargo_swap::id(),
+        &fixture.keys,
+        &fixture.user,
+        &token_program_id(),
+        INITIAL,
+        INITIAL,
+    );
+    ix.accounts.pop();
+    let err = transaction_error(process(&mut ctx, &[ix], &[]).await);
+    assert_eq!(err, TransactionError::InstructionError(0, InstructionError::NotEnoughAccountKeys));
+}
+
+// Replaces an account's data as an older program version would have left it,
+// funded for exactly that size
+async fn write_legacy_account(ctx: &mut ProgramTestContext, address: &Pubkey, data: Vec<u8>) {
+    let rent = ctx.banks_client.get_rent().await.unwrap();
+    let mut account = ctx
+        .banks_client
+        .get_account(*address)
+        .await
+        .unwrap()
+        .unwrap_or_else(|| solana_sdk::account::Account { owner: cargo_swap::id(), ..Default::default() });
+    account.lamports = rent.minimum_balance(data.len());
+    account.data = data;
+    ctx.set_account(address, &account.into());
+}
+
+// Moves a vault PDA's account to a fresh address, as the keypair vaults of
+// legacy pools were, and returns that address
+async fn move_to_keypair_account(ctx: &mut ProgramTestContext, vault: &Pubkey) -> Pubkey {
+    let account = ctx.banks_client.get_account(*vault).await.unwrap().unwrap();
+    let address = Pubkey::new_unique();
+    ctx.set_account(&address, &account.into());
+    ctx.set_account(vault, &solana_sdk::account::AccountSharedData::default());
+    address
+}
+
+fn custom(index: u8, error: SwapError) -> TransactionError {
+    TransactionError::InstructionError(index, InstructionError::Custom(error as u32))
+}
+
+#[tokio::test]
+async fn migrate_pool_converts_single_pool_layout() {
+    let mut ctx = start().await;
+    let fixture = pool_fixture(&mut ctx).await;
+    legacy_init(&mut ctx, &fixture).await;
+    let current = get_pool(&mut ctx, &fixture.keys.pool).await;
+
+    let legacy = LegacySinglePool {
+        token_a: current.token_a,
+        token_b: current.token_b,
+        bump: current.bump,
+        reserve_a: current.reserve_a,
+        reserve_b: current.reserve_b,
+        total_lp_supply: current.total_lp_supply,
+    };
+    let data = legacy.try_to_vec().unwrap();
+    assert_eq!(data.len(), LegacySinglePool::LEN);
+    write_legacy_account(&mut ctx, &fixture.keys.pool, data).await;
+
+    let swap = instruction::legacy_swap(
+        &cargo_swap::id(),
+        &fixture.keys,
+        &fixture.user,
+        INITIAL / 10,
+        true,
+    );
+    let err = transaction_error(process(&mut ctx, std::slice::from_ref(&swap), &[]).await);
+    assert_eq!(err, custom(0, SwapError::PoolMigrationRequired));
+
+    // Legacy vaults were keypair accounts, which handlers no longer accept
+    let legacy_vault_a = move_to_keypair_account(&mut ctx, &fixture.keys.vault_a).await;
+    let legacy_vault_b = move_to_keypair_account(&mut ctx, &fixture.keys.vault_b).await;
+    let payer = ctx.payer.pubkey();
+    let migrate = instruction::migrate_pool(&cargo_swap::id(), &fixture.keys, &legacy_vault_a, &legacy_vault_b, &payer);
+    process(&mut ctx, &[migrate], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut ctx, &fixture.keys.vault_a).await, current.reserve_a);
+    assert_eq!(token_balance(&mut ctx, &fixture.keys.vault_b).await, current.reserve_b);
+    for legacy_vault in [legacy_vault_a, legacy_vault_b] {
+        assert!(ctx.banks_client.get_account(legacy_vault).await.unwrap().is_none());
+    }
+
+    let account = ctx.banks_client.get_account(fixture.keys.pool).await.unwrap().unwrap();
+    let rent = ctx.banks_client.get_rent().await.unwrap();
+    assert_eq!(account.data.len(), Pool::LEN);
+    assert!(rent.is_exempt(account.lamports, Pool::LEN));
+
+    let migrated = Pool::unpack(&account.data).unwrap();
+    assert_eq!(migrated.header, Pool::new_header());
+    assert_eq!((migrated.pool_id, migrated.fee_rate, migrated.created_at), (0, 30, 0));
+    assert_eq!((migrated.reserve_a, migrated.reserve_b), (current.reserve_a, current.reserve_b));
+    assert_eq!(migrated.total_lp_supply, current.total_lp_supply);
+
+    process(&mut ctx, &[swap], &[]).await.unwrap();
+    let after = get_pool(&mut ctx, &fixture.keys.pool).await;
+    assert_eq!(after.reserve_a, INITIAL + INITIAL / 10);
+}
+
+#[tokio::test]
+async fn migrate_pool_converts_borsh_layout() {
+    let mut ctx = start().await;
+    let fixture = pool_fixture(&mut ctx).await;
+    legacy_init(&mut ctx, &fixture).await;
+    let current = get_pool(&mut ctx, &fixture.keys.pool).await;
+
+    let legacy = LegacyBorshPool {
+        pool_id: 3,
+        pool_type: PoolType::Stable,
+        token_a: current.token_a,
+        token_b: current.token_b,
+        bump: current.bump,
+        fee_rate: 5,
+        reserve_a: current.reserve_a,
+        reserve_b: current.reserve_b,
+        total_lp_supply: current.total_lp_supply,
+        created_at: 1_650_000_000,
+        is_active: true,
+    };
+    let data = legacy.try_to_vec().unwrap();
+    assert_eq!(data.len(), LegacyBorshPool::LEN);
+    write_legacy_account(&mut ctx, &fixture.keys.pool, data).await;
+
+    let (keys, payer) = (&fixture.keys, ctx.payer.pubkey());
+    let migrate = instruction::migrate_pool(&cargo_swap::id(), keys, &keys.vault_a, &keys.vault_b, &payer);
+    process(&mut ctx, &[migrate], &[]).await.unwrap();
+
+    let migrated = get_pool(&mut ctx, &fixture.keys.pool).await;
+    assert_eq!(migrated.header.version, POOL_VERSION);
+    assert_eq!((migrated.pool_id, migrated.pool_type, migrated.fee_rate), (3, PoolType::Stable, 5));
+    assert_eq!(migrated.created_at, 1_650_000_000);
+    assert_eq!(migrated.reserve_b, current.reserve_b);
+    assert_eq!((migrated.price_a_cumulative, migrated.price_b_cumulative), ([0; 16], [0; 16]));
+    assert!(migrated.price_cumulative_updated_at >= current.created_at);
+}
+
+#[tokio::test]
+async fn migrate_pool_rejects_current_and_foreign_accounts() {
+    let mut ctx = start().await;
+    let fixture = pool_fixture(&mut ctx).await;
+    legacy_init(&mut ctx, &fixture).await;
+    let current = get_pool(&mut ctx, &fixture.keys.pool).await;
+    let (keys, payer) = (fixture.keys, ctx.payer.pubkey());
+
+    let migrate = instruction::migrate_pool(&cargo_swap::id(), &keys, &keys.vault_a, &keys.vault_b, &payer);
+    let err = transaction_error(process(&mut ctx, &[migrate], &[]).await);
+    assert_eq!(err, TransactionError::InstructionError(0, InstructionError::AccountAlreadyInitialized));
+
+    // A legacy pool's bytes copied to an address that isn't its PDA
+    let legacy = LegacySinglePool {
+        token_a: current.token_a,
+        token_b: current.token_b,
+        bump: current.bump,
+        reserve_a: u64::MAX,
+        reserve_b: u64::MAX,
+        total_lp_supply: 1,
+    };
+    let impostor = Pubkey::new_unique();
+    write_legacy_account(&mut ctx, &impostor, legacy.try_to_vec().unwrap()).await;
+    let impostor_keys = PoolKeys { pool: impostor, ..keys };
+    let migrate = instruction::migrate_pool(&cargo_swap::id(), &impostor_keys, &keys.vault_a, &keys.vault_b, &payer);
+    let err = transaction_error(process(&mut ctx, &[migrate], &[]).await);
+    assert_eq!(err, TransactionError::InstructionError(0, InstructionError::InvalidSeeds));
+}
+
+#[tokio::test]
+async fn handlers_refuse_unknown_pool_versions() {
+    let mut ctx = start().await;
+    let fixture = pool_fixture(&mut ctx).await;
+    legacy_init(&mut ctx, &fixture).await;
+
+    let mut account = ctx.banks_client.get_account(fixture.keys.pool).await.unwrap().unwrap();
+    account.data[1] = POOL_VERSION + 1;
+    ctx.set_account(&fixture.keys.pool, &account.into());
+
+    let (keys, payer) = (&fixture.keys, ctx.payer.pubkey());
+    let ixs = [
+        instruction::legacy_swap(&cargo_swap::id(), &fixture.keys, &fixture.user, 10, true),
+        instruction::swap(&cargo_swap::id(), 0, &fixture.keys, &fixture.user, 10, true),
+        instruction::legacy_add_liquidity(&cargo_swap::id(), &fixture.keys, &fixture.user, &token_program_id(), 10, 10),
+        instruction::remove_liquidity(&cargo_swap::id(), 0, &fixture.keys, &fixture.user, &token_program_id(), 10),
+        instruction::migrate_pool(&cargo_swap::id(), keys, &keys.vault_a, &keys.vault_b, &payer),
+    ];
+    for ix in ixs {
+        let err = transaction_error(process(&mut ctx, &[ix], &[]).await);
+        assert_eq!(err, custom(0, SwapError::UnsupportedAccountVersion));
+    }
+}
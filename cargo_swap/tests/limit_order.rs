@@ -0,0 +1,168 @@
+mod common;
+
+use cargo_swap::{
+    error::SwapError,
+    instruction::{self, OrderKeys, UserKeys},
+    quote, LimitOrder, KEEPER_REWARD_LAMPORTS, PAUSE_WITHDRAWALS,
+};
+use common::*;
+use solana_program::{instruction::InstructionError, program_pack::Pack, pubkey::Pubkey, system_instruction};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+
+fn custom_error(error: SwapError) -> TransactionError {
+    TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+}
+
+// A 1:1 pool with the payer as the only LP and trader
+async fn setup() -> (ProgramTestContext, PoolFixture) {
+    let mut ctx = start().await;
+    let fixture = pool_fixture(&mut ctx).await;
+    init_pool(&mut ctx, &fixture, 1, 1_000_000, 1_000_000).await;
+    (ctx, fixture)
+}
+
+async fn place(ctx: &mut ProgramTestContext, fixture: &PoolFixture, order_id: u64, min_amount_out: u64) -> OrderKeys {
+    let program_id = cargo_swap::id();
+    let (keys, user) = (&fixture.keys, &fixture.user);
+    let ix = instruction::place_limit_order(&program_id, keys, user, order_id, 10_000, min_amount_out, true);
+    process(ctx, &[ix], &[]).await.unwrap();
+    OrderKeys {
+        order: instruction::find_limit_order_address(&program_id, &fixture.keys.pool, &fixture.user.user, order_id).0,
+        owner: fixture.user.user,
+        destination: fixture.user.token_b,
+        refund: fixture.user.token_a,
+    }
+}
+
+async fn exists(ctx: &mut ProgramTestContext, address: &Pubkey) -> bool {
+    ctx.banks_client.get_account(*address).await.unwrap().is_some()
+}
+
+#[tokio::test]
+async fn crank_fills_orders_the_pool_reaches() {
+    let (mut ctx, fixture) = setup().await;
+    let program_id = cargo_swap::id();
+    let keeper = Keypair::new();
+    let ix = system_instruction::transfer(&ctx.payer.pubkey(), &keeper.pubkey(), 1_000_000_000);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+
+    let near = place(&mut ctx, &fixture, 1, 10_000).await;
+    let far = place(&mut ctx, &fixture, 2, 20_000).await;
+    let (escrow, _) = instruction::find_order_escrow_address(&program_id, &near.order);
+    assert_eq!(token_balance(&mut ctx, &escrow).await, 10_000);
+    let account = ctx.banks_client.get_account(near.order).await.unwrap().unwrap();
+    let order = LimitOrder::unpack(&account.data).unwrap();
+    assert_eq!((order.amount_in, order.min_amount_out, order.destination), (10_000, 10_000, fixture.user.token_b));
+
+    // At 1:1 the fee and slippage keep both orders below their limits
+    let fill = instruction::fill_limit_orders(&program_id, &fixture.keys, &keeper.pubkey(), &[near, far]);
+    let error = transaction_error(process(&mut ctx, std::slice::from_ref(&fill), &[&keeper]).await);
+    assert_eq!(error, custom_error(SwapError::OrderNotFillable));
+
+    // Buying A lifts its price past the near order's limit only
+    let ix = instruction::swap(&program_id, 1, &fixture.keys, &fixture.user, 200_000, false);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    let pool = get_pool(&mut ctx, &fixture.keys.pool).await;
+    let expected = quote::quote_swap_exact_in(&pool, 10_000, true).unwrap();
+    let balance_b = token_balance(&mut ctx, &fixture.user.token_b).await;
+    let keeper_lamports = ctx.banks_client.get_balance(keeper.pubkey()).await.unwrap();
+
+    process(&mut ctx, &[fill], &[&keeper]).await.unwrap();
+    assert_eq!(token_balance(&mut ctx, &fixture.user.token_b).await - balance_b, expected.amount_out);
+    let pool = get_pool(&mut ctx, &fixture.keys.pool).await;
+    assert_eq!((pool.reserve_a, pool.reserve_b), (expected.reserve_a_after, expected.reserve_b_after));
+    assert_eq!(ctx.banks_client.get_balance(keeper.pubkey()).await.unwrap(), keeper_lamports + KEEPER_REWARD_LAMPORTS);
+    assert!(!exists(&mut ctx, &near.order).await && !exists(&mut ctx, &escrow).await);
+    assert!(exists(&mut ctx, &far.order).await);
+}
+
+#[tokio::test]
+async fn owner_cancels_and_gets_the_escrow_back() {
+    let (mut ctx, fixture) = setup().await;
+    let program_id = cargo_swap::id();
+    let balance_a = token_balance(&mut ctx, &fixture.user.token_a).await;
+
+    // Fills only pay out to the owner's own account
+    let outsider = Keypair::new();
+    let foreign = create_token_account(&mut ctx, &fixture.keys.token_b, &outsider.pubkey()).await;
+    let user = UserKeys { token_b: foreign, ..fixture.user };
+    let ix = instruction::place_limit_order(&program_id, &fixture.keys, &user, 1, 10_000, 10_000, true);
+    let error = transaction_error(process(&mut ctx, &[ix], &[]).await);
+    assert_eq!(error, TransactionError::InstructionError(0, InstructionError::InvalidArgument));
+
+    let keys = place(&mut ctx, &fixture, 1, 50_000).await;
+    let ix = instruction::place_limit_order(&program_id, &fixture.keys, &fixture.user, 1, 5_000, 5_000, true);
+    let error = transaction_error(process(&mut ctx, &[ix], &[]).await);
+    assert_eq!(error, TransactionError::InstructionError(0, InstructionError::AccountAlreadyInitialized));
+    assert_eq!(token_balance(&mut ctx, &fixture.user.token_a).await, balance_a - 10_000);
+
+    let cancel = instruction::cancel_limit_order(&program_id, &fixture.keys, &fixture.user, 1, true);
+    let mut stolen = cancel.clone();
+    stolen.accounts[3].pubkey = outsider.pubkey();
+    let error = transaction_error(process(&mut ctx, &[stolen], &[&outsider]).await);
+    assert_eq!(error, custom_error(SwapError::Unauthorized));
+
+    process(&mut ctx, std::slice::from_ref(&cancel), &[]).await.unwrap();
+    assert_eq!(token_balance(&mut ctx, &fixture.user.token_a).await, balance_a);
+    assert!(!exists(&mut ctx, &keys.order).await);
+
+    // A cancelled order is gone for the crank too
+    let fill = instruction::fill_limit_orders(&program_id, &fixture.keys, &fixture.user.user, &[keys]);
+    let error = transaction_error(process(&mut ctx, &[fill], &[]).await);
+    assert_eq!(error, custom_error(SwapError::OrderNotFillable));
+}
+
+#[tokio::test]
+async fn crank_refunds_escrow_dust_and_skips_broken_orders() {
+    let (mut ctx, fixture) = setup().await;
+    let program_id = cargo_swap::id();
+    let cancelled = place(&mut ctx, &fixture, 1, 1).await;
+    let ix = instruction::cancel_limit_order(&program_id, &fixture.keys, &fixture.user, 1, true);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+
+    // An order paying out to a closed account can't fill
+    let closed = create_token_account(&mut ctx, &fixture.keys.token_b, &fixture.user.user).await;
+    let user = UserKeys { token_b: closed, ..fixture.user };
+    let ix = instruction::place_limit_order(&program_id, &fixture.keys, &user, 2, 10_000, 1, true);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    let mut ix = spl_token::instruction::close_account(&spl_token::id(), &closed, &user.user, &user.user, &[]).unwrap();
+    ix.program_id = token_program_id();
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    let order = instruction::find_limit_order_address(&program_id, &fixture.keys.pool, &user.user, 2).0;
+    let unpayable = OrderKeys { order, owner: user.user, destination: closed, refund: user.token_a };
+
+    // Tokens sent to an escrow come back to the owner instead of blocking the close
+    let dusted = place(&mut ctx, &fixture, 3, 1).await;
+    let (escrow, _) = instruction::find_order_escrow_address(&program_id, &dusted.order);
+    fund(&mut ctx, &fixture.keys.token_a, &escrow, 7).await;
+    let balance_a = token_balance(&mut ctx, &fixture.user.token_a).await;
+
+    let fill = instruction::fill_limit_orders(&program_id, &fixture.keys, &user.user, &[cancelled, unpayable, dusted]);
+    process(&mut ctx, &[fill], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut ctx, &fixture.user.token_a).await, balance_a + 7);
+    assert!(!exists(&mut ctx, &dusted.order).await && !exists(&mut ctx, &escrow).await);
+    assert!(exists(&mut ctx, &unpayable.order).await);
+}
+
+#[tokio::test]
+async fn cancelling_is_a_withdrawal_for_the_pause() {
+    let (mut ctx, fixture) = setup().await;
+    let program_id = cargo_swap::id();
+    let payer = ctx.payer.pubkey();
+    initialize_config(&mut ctx, &payer, &payer).await;
+    place(&mut ctx, &fixture, 1, 50_000).await;
+    let cancel = instruction::cancel_limit_order(&program_id, &fixture.keys, &fixture.user, 1, true);
+
+    let ix = instruction::set_pause(&program_id, &payer, PAUSE_WITHDRAWALS);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    let error = transaction_error(process(&mut ctx, std::slice::from_ref(&cancel), &[]).await);
+    assert_eq!(error, custom_error(SwapError::ProgramPaused));
+
+    let ix = instruction::set_pause(&program_id, &payer, 0);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    process(&mut ctx, &[cancel], &[]).await.unwrap();
+}
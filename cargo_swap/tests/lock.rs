@@ -0,0 +1,236 @@
+mod common;
+
+use cargo_swap::{
+    error::SwapError,
+    instruction::{self, RewardKeys, UserKeys},
+    Farm, FarmStake, LiquidityLock, RewardSchedule, MAX_LOCK_SECONDS, PAUSE_DEPOSITS, PAUSE_WITHDRAWALS,
+};
+use common::*;
+use solana_program::{
+    clock::Clock, instruction::InstructionError, program_pack::Pack, pubkey::Pubkey, system_instruction,
+};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+
+fn custom_error(error: SwapError) -> TransactionError {
+    TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+}
+
+async fn now(ctx: &mut ProgramTestContext) -> i64 {
+    ctx.banks_client.get_sysvar::<Clock>().await.unwrap().unix_timestamp
+}
+
+async fn get_lock(ctx: &mut ProgramTestContext, fixture: &PoolFixture, owner: &Pubkey) -> Option<LiquidityLock> {
+    let (lock, _) = instruction::find_lp_lock_address(&cargo_swap::id(), &fixture.keys.pool, owner);
+    let account = ctx.banks_client.get_account(lock).await.unwrap()?;
+    Some(LiquidityLock::unpack(&account.data).unwrap())
+}
+
+// Another LP with 10_000 of each token deposited in the pool
+async fn second_lp(ctx: &mut ProgramTestContext, fixture: &PoolFixture) -> (Keypair, UserKeys) {
+    let second = Keypair::new();
+    let ix = system_instruction::transfer(&ctx.payer.pubkey(), &second.pubkey(), 1_000_000_000);
+    process(ctx, &[ix], &[]).await.unwrap();
+    let user = UserKeys {
+        user: second.pubkey(),
+        token_a: create_token_account(ctx, &fixture.keys.token_a, &second.pubkey()).await,
+        token_b: create_token_account(ctx, &fixture.keys.token_b, &second.pubkey()).await,
+        lp: create_token_account(ctx, &fixture.keys.lp_mint, &second.pubkey()).await,
+    };
+    fund(ctx, &fixture.keys.token_a, &user.token_a, 10_000).await;
+    fund(ctx, &fixture.keys.token_b, &user.token_b, 10_000).await;
+    let program_id = cargo_swap::id();
+    let ix = instruction::add_liquidity(&program_id, 1, &fixture.keys, &user, &token_program_id(), 10_000, 10_000);
+    process(ctx, &[ix], &[&second]).await.unwrap();
+    (second, user)
+}
+
+#[tokio::test]
+async fn locked_lp_stays_until_its_unlock_time() {
+    let mut ctx = start().await;
+    let fixture = pool_fixture(&mut ctx).await;
+    init_pool(&mut ctx, &fixture, 1, 1_000_000, 1_000_000).await;
+    let program_id = cargo_swap::id();
+    let lp_before = token_balance(&mut ctx, &fixture.user.lp).await;
+    let start_time = now(&mut ctx).await;
+    let (keys, user) = (&fixture.keys, &fixture.user);
+    let lock = |lp_amount, unlock_time| {
+        instruction::lock_liquidity(&program_id, keys, user, &token_program_id(), lp_amount, unlock_time)
+    };
+    let unlock = || instruction::unlock_liquidity(&program_id, keys, user, &token_program_id());
+
+    process(&mut ctx, &[lock(1_000, start_time + 100)], &[]).await.unwrap();
+    let (lock_address, _) = instruction::find_lp_lock_address(&program_id, &fixture.keys.pool, &fixture.user.user);
+    let (escrow, _) = instruction::find_order_escrow_address(&program_id, &lock_address);
+    assert_eq!(token_balance(&mut ctx, &escrow).await, 1_000);
+    assert_eq!(token_balance(&mut ctx, &fixture.user.lp).await, lp_before - 1_000);
+
+    // Locks only get longer, and stay shut until they open
+    let error = transaction_error(process(&mut ctx, &[lock(0, start_time + 50)], &[]).await);
+    assert_eq!(error, TransactionError::InstructionError(0, InstructionError::InvalidArgument));
+    let error = transaction_error(process(&mut ctx, &[unlock()], &[]).await);
+    assert_eq!(error, custom_error(SwapError::LiquidityLocked));
+    process(&mut ctx, &[lock(500, start_time + 200)], &[]).await.unwrap();
+    let state = get_lock(&mut ctx, &fixture, &fixture.user.user).await.unwrap();
+    assert_eq!((state.amount, state.unlock_time), (1_500, start_time + 200));
+    advance_clock(&mut ctx, 150).await;
+    let error = transaction_error(process(&mut ctx, &[unlock()], &[]).await);
+    assert_eq!(error, custom_error(SwapError::LiquidityLocked));
+
+    advance_clock(&mut ctx, 50).await;
+    let outsider = Keypair::new();
+    let mut stolen = unlock();
+    stolen.accounts[3].pubkey = outsider.pubkey();
+    let error = transaction_error(process(&mut ctx, &[stolen], &[&outsider]).await);
+    assert_eq!(error, custom_error(SwapError::Unauthorized));
+    process(&mut ctx, &[unlock()], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut ctx, &fixture.user.lp).await, lp_before);
+    assert!(get_lock(&mut ctx, &fixture, &fixture.user.user).await.is_none());
+    assert!(ctx.banks_client.get_account(escrow).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn locks_earn_boosted_farm_rewards() {
+    let mut ctx = start().await;
+    let fixture = pool_fixture(&mut ctx).await;
+    init_pool(&mut ctx, &fixture, 1, 1_000_000, 1_000_000).await;
+    let program_id = cargo_swap::id();
+    let payer = ctx.payer.pubkey();
+    initialize_config(&mut ctx, &payer, &payer).await;
+    let ix = instruction::create_farm(&program_id, &payer, &payer, &fixture.keys, &token_program_id());
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    let start_time = now(&mut ctx).await;
+    let mint = create_mint(&mut ctx, &payer, 6).await;
+    let schedule = RewardSchedule { emission_per_second: 90, start_time, end_time: start_time + 1000 };
+    let pool = fixture.keys.pool;
+    let ix = instruction::add_farm_reward(&program_id, &payer, &payer, &pool, &mint, &token_program_id(), schedule);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    let (farm, _) = instruction::find_farm_address(&program_id, &pool);
+    let (vault, _) = instruction::find_farm_vault_address(&program_id, &farm, &mint);
+    mint_to(&mut ctx, &mint, &vault, 1_000_000).await;
+
+    // A second LP stakes as much as the payer locks for the longest boost
+    let (second, user) = second_lp(&mut ctx, &fixture).await;
+
+    let unlock_time = start_time + MAX_LOCK_SECONDS;
+    let (keys, payer_keys) = (&fixture.keys, &fixture.user);
+    let ix = instruction::lock_liquidity(&program_id, keys, payer_keys, &token_program_id(), 1_000, unlock_time);
+    let lock = instruction::with_lock_farm(ix, &program_id, &pool, &payer);
+    process(&mut ctx, &[lock], &[]).await.unwrap();
+    let ix = instruction::stake_lp(&program_id, &fixture.keys, &user, &token_program_id(), 1_000);
+    process(&mut ctx, &[ix], &[&second]).await.unwrap();
+    advance_clock(&mut ctx, 100).await;
+
+    let mut claimed = Vec::new();
+    for (owner, signers) in [(&fixture.user.user, vec![]), (&second.pubkey(), vec![&second])] {
+        let destination = create_token_account(&mut ctx, &mint, owner).await;
+        let rewards = [RewardKeys { mint, token_program: token_program_id(), destination }];
+        let ix = instruction::claim_farm_rewards(&program_id, &pool, owner, &rewards);
+        process(&mut ctx, &[ix], &signers).await.unwrap();
+        claimed.push(token_balance(&mut ctx, &destination).await);
+    }
+    assert_eq!(claimed, [6_000, 3_000]);
+
+    // A lock in the farm must bring it along to leave
+    advance_clock(&mut ctx, MAX_LOCK_SECONDS).await;
+    let unlock = instruction::unlock_liquidity(&program_id, &fixture.keys, &fixture.user, &token_program_id());
+    let error = transaction_error(process(&mut ctx, std::slice::from_ref(&unlock), &[]).await);
+    assert_eq!(error, TransactionError::InstructionError(0, InstructionError::NotEnoughAccountKeys));
+    process(&mut ctx, &[instruction::with_lock_farm(unlock, &program_id, &pool, &payer)], &[]).await.unwrap();
+    let (stake, _) = instruction::find_farm_stake_address(&program_id, &farm, &payer);
+    let account = ctx.banks_client.get_account(stake).await.unwrap().unwrap();
+    assert_eq!(FarmStake::unpack(&account.data).unwrap().locked_weight, 0);
+    let account = ctx.banks_client.get_account(farm).await.unwrap().unwrap();
+    assert_eq!(Farm::unpack(&account.data).unwrap().total_weight, 1_000);
+}
+
+#[tokio::test]
+async fn expired_locks_drop_to_their_base_weight() {
+    let mut ctx = start().await;
+    let fixture = pool_fixture(&mut ctx).await;
+    init_pool(&mut ctx, &fixture, 1, 1_000_000, 1_000_000).await;
+    let program_id = cargo_swap::id();
+    let payer = ctx.payer.pubkey();
+    let pool = fixture.keys.pool;
+    initialize_config(&mut ctx, &payer, &payer).await;
+    let ix = instruction::create_farm(&program_id, &payer, &payer, &fixture.keys, &token_program_id());
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    let (second, user) = second_lp(&mut ctx, &fixture).await;
+
+    // The payer locks for the longest boost and the second LP stakes as much.
+    // Rewards only start once the lock has expired.
+    let unlock_time = now(&mut ctx).await + MAX_LOCK_SECONDS;
+    let (keys, payer_keys) = (&fixture.keys, &fixture.user);
+    let ix = instruction::lock_liquidity(&program_id, keys, payer_keys, &token_program_id(), 1_000, unlock_time);
+    process(&mut ctx, &[instruction::with_lock_farm(ix, &program_id, &pool, &payer)], &[]).await.unwrap();
+    let ix = instruction::stake_lp(&program_id, &fixture.keys, &user, &token_program_id(), 1_000);
+    process(&mut ctx, &[ix], &[&second]).await.unwrap();
+    let mint = create_mint(&mut ctx, &payer, 6).await;
+    let schedule = RewardSchedule { emission_per_second: 60, start_time: unlock_time, end_time: unlock_time + 1000 };
+    let ix = instruction::add_farm_reward(&program_id, &payer, &payer, &pool, &mint, &token_program_id(), schedule);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    let (farm, _) = instruction::find_farm_address(&program_id, &pool);
+    let (vault, _) = instruction::find_farm_vault_address(&program_id, &farm, &mint);
+    mint_to(&mut ctx, &mint, &vault, 1_000_000).await;
+
+    // Anyone can expire the lock, but only once it has opened and while it
+    // still has a boost
+    let expire = instruction::expire_liquidity_lock(&program_id, &pool, &payer);
+    let error = transaction_error(process(&mut ctx, std::slice::from_ref(&expire), &[]).await);
+    assert_eq!(error, custom_error(SwapError::LiquidityLocked));
+    let now = now(&mut ctx).await;
+    advance_clock(&mut ctx, unlock_time - now + 50).await;
+    assert!(expire.accounts.iter().all(|account| !account.is_signer));
+    process(&mut ctx, std::slice::from_ref(&expire), &[]).await.unwrap();
+    let state = get_lock(&mut ctx, &fixture, &payer).await.unwrap();
+    assert_eq!((state.amount, state.weight), (1_000, 1_000));
+    let account = ctx.banks_client.get_account(farm).await.unwrap().unwrap();
+    assert_eq!(Farm::unpack(&account.data).unwrap().total_weight, 2_000);
+    let error = transaction_error(process(&mut ctx, std::slice::from_ref(&expire), &[]).await);
+    assert_eq!(error, TransactionError::InstructionError(0, InstructionError::InvalidArgument));
+
+    // The boost stopped at the unlock time, so both earn alike from then on
+    advance_clock(&mut ctx, 100).await;
+    let mut claimed = Vec::new();
+    for (owner, signers) in [(&payer, vec![]), (&second.pubkey(), vec![&second])] {
+        let destination = create_token_account(&mut ctx, &mint, owner).await;
+        let rewards = [RewardKeys { mint, token_program: token_program_id(), destination }];
+        let ix = instruction::claim_farm_rewards(&program_id, &pool, owner, &rewards);
+        process(&mut ctx, &[ix], &signers).await.unwrap();
+        claimed.push(token_balance(&mut ctx, &destination).await);
+    }
+    assert_eq!(claimed, [4_500, 4_500]);
+}
+
+#[tokio::test]
+async fn locking_and_unlocking_follow_the_pause() {
+    let mut ctx = start().await;
+    let fixture = pool_fixture(&mut ctx).await;
+    init_pool(&mut ctx, &fixture, 1, 1_000_000, 1_000_000).await;
+    let program_id = cargo_swap::id();
+    let payer = ctx.payer.pubkey();
+    initialize_config(&mut ctx, &payer, &payer).await;
+    let unlock_time = now(&mut ctx).await + 100;
+    let (keys, user) = (&fixture.keys, &fixture.user);
+    let lock = instruction::lock_liquidity(&program_id, keys, user, &token_program_id(), 1_000, unlock_time);
+    let unlock = instruction::unlock_liquidity(&program_id, keys, user, &token_program_id());
+
+    let ix = instruction::set_pause(&program_id, &payer, PAUSE_DEPOSITS);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    let error = transaction_error(process(&mut ctx, std::slice::from_ref(&lock), &[]).await);
+    assert_eq!(error, custom_error(SwapError::ProgramPaused));
+
+    let ix = instruction::set_pause(&program_id, &payer, PAUSE_WITHDRAWALS);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    process(&mut ctx, &[lock], &[]).await.unwrap();
+    advance_clock(&mut ctx, 100).await;
+    let error = transaction_error(process(&mut ctx, std::slice::from_ref(&unlock), &[]).await);
+    assert_eq!(error, custom_error(SwapError::ProgramPaused));
+
+    let ix = instruction::set_pause(&program_id, &payer, 0);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    process(&mut ctx, &[unlock], &[]).await.unwrap();
+}
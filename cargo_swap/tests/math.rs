@@ -0,0 +1,487 @@
+// Property tests for the AMM math: the quote engine and the handlers built on it
+
+use borsh::BorshSerialize;
+use cargo_swap::{
+    instruction::{find_config_address, find_pool_address, find_vault_address},
+    quote::{self, FEE_DENOMINATOR},
+    FarmReward, IntegerSqrt, Pool, PoolType, TestProjectInstruction, ZeroCopy, GORBCHAIN_SPL_TOKEN_PROGRAM,
+    MAX_LOCK_BOOST_BPS, MAX_LOCK_SECONDS,
+};
+use proptest::prelude::*;
+use solana_program::{
+    account_info::AccountInfo, clock::Clock, entrypoint::SUCCESS, program_error::ProgramError,
+    program_option::COption, program_pack::Pack, program_stubs, pubkey::Pubkey,
+};
+use std::{str::FromStr, sync::Once};
+
+fn pool(reserve_a: u64, reserve_b: u64, total_lp_supply: u64, fee_rate: u16) -> Pool {
+    Pool {
+        header: Pool::new_header(),
+        _padding: [0; 1],
+        pool_id: 1,
+        pool_type: PoolType::Standard,
+        token_a: Pubkey::new_from_array([1; 32]),
+        token_b: Pubkey::new_from_array([2; 32]),
+        token_program_a: token_program_id(),
+        token_program_b: token_program_id(),
+        bump: 0,
+        fee_rate,
+        reserve_a,
+        reserve_b,
+        total_lp_supply,
+        created_at: 0,
+        is_active: true,
+        max_price_move_bps: 0,
+        dynamic_fee: false,
+        _padding2: [0; 1],
+        min_fee_rate: 0,
+        max_fee_rate: 0,
+        price_ref_slot: 0,
+        price_ref_reserve_a: 0,
+        price_ref_reserve_b: 0,
+        volatility_bps: 0,
+        volatility_updated_at: 0,
+        price_a_cumulative: [0; 16],
+        price_b_cumulative: [0; 16],
+        price_cumulative_updated_at: 0,
+    }
+}
+
+// Biases generation towards the boundaries where rounding and overflow live
+fn amount() -> impl Strategy<Value = u64> {
+    prop_oneof![
+        Just(0),
+        Just(1),
+        Just(u64::MAX),
+        1u64..1_000,
+        (u64::MAX - 1_000)..u64::MAX,
+        any::<u64>(),
+    ]
+}
+
+fn fee_rate() -> impl Strategy<Value = u16> {
+    prop_oneof![Just(0), Just(30), Just(FEE_DENOMINATOR as u16 - 1), 0..FEE_DENOMINATOR as u16]
+}
+
+fn k(reserve_a: u64, reserve_b: u64) -> u128 {
+    reserve_a as u128 * reserve_b as u128
+}
+
+fn assert_floor_sqrt(n: u128) {
+    let root = n.integer_sqrt();
+    assert!(root * root <= n, "sqrt({n}) = {root} is too large");
+    // (root + 1)^2 overflowing u128 means it is certainly above n
+    if let Some(next) = (root + 1).checked_mul(root + 1) {
+        assert!(next > n, "sqrt({n}) = {root} is too small");
+    }
+}
+
+#[test]
+fn integer_sqrt_edges() {
+    for n in [0, 1, 2, 3, 4, u64::MAX as u128, u128::MAX - 1, u128::MAX] {
+        assert_floor_sqrt(n);
+    }
+    assert_eq!(u128::MAX.integer_sqrt(), u64::MAX as u128);
+}
+
+proptest! {
+    #[test]
+    fn integer_sqrt_is_floor(n in any::<u128>()) {
+        assert_floor_sqrt(n);
+    }
+
+    #[test]
+    fn integer_sqrt_around_perfect_squares(root in any::<u64>()) {
+        let square = root as u128 * root as u128;
+        prop_assert_eq!(square.integer_sqrt(), root as u128);
+        assert_floor_sqrt(square.saturating_sub(1));
+        assert_floor_sqrt(square + 1);
+    }
+
+    #[test]
+    fn swap_never_decreases_k(
+        reserve_a in amount(),
+        reserve_b in amount(),
+        fee_rate in fee_rate(),
+        amount_in in amount(),
+        direction_a_to_b in any::<bool>(),
+    ) {
+        let pool = pool(reserve_a, reserve_b, 1, fee_rate);
+        if let Ok(q) = quote::quote_swap_exact_in(&pool, amount_in, direction_a_to_b) {
+            prop_assert!(k(q.reserve_a_after, q.reserve_b_after) >= k(reserve_a, reserve_b));
+            prop_assert!(q.fee <= amount_in);
+            prop_assert!(q.price_impact_bps as u64 <= FEE_DENOMINATOR);
+        }
+    }
+
+    #[test]
+    fn swap_sequence_never_decreases_k(
+        reserve_a in 1u64..=u64::MAX / 2,
+        reserve_b in 1u64..=u64::MAX / 2,
+        fee_rate in fee_rate(),
+        swaps in prop::collection::vec((amount(), any::<bool>()), 1..32),
+    ) {
+        let mut pool = pool(reserve_a, reserve_b, 1, fee_rate);
+        for (amount_in, direction_a_to_b) in swaps {
+            let Ok(q) = quote::quote_swap_exact_in(&pool, amount_in, direction_a_to_b) else {
+                continue;
+            };
+            prop_assert!(k(q.reserve_a_after, q.reserve_b_after) >= k(pool.reserve_a, pool.reserve_b));
+            pool.reserve_a = q.reserve_a_after;
+            pool.reserve_b = q.reserve_b_after;
+        }
+    }
+
+    #[test]
+    fn dynamic_fee_stays_within_bounds(
+        min_fee_rate in 0u16..FEE_DENOMINATOR as u16,
+        spread in any::<u16>(),
+        volatility_bps in any::<u64>(),
+        volatility_updated_at in any::<i64>(),
+        now in any::<i64>(),
+    ) {
+        let mut pool = pool(1, 1, 1, 30);
+        pool.dynamic_fee = true;
+        pool.min_fee_rate = min_fee_rate;
+        pool.max_fee_rate = min_fee_rate.saturating_add(spread).min(FEE_DENOMINATOR as u16 - 1);
+        pool.volatility_bps = volatility_bps;
+        pool.volatility_updated_at = volatility_updated_at;
+
+        let fee_rate = quote::dynamic_fee_rate(&pool, now);
+        prop_assert!((pool.min_fee_rate..=pool.max_fee_rate).contains(&fee_rate));
+        prop_assert!(quote::decayed_volatility(&pool, now) <= volatility_bps);
+        // Calm after a full decay period
+        let later = volatility_updated_at.saturating_add(quote::VOLATILITY_DECAY_SECONDS);
+        if later < i64::MAX {
+            prop_assert_eq!(quote::dynamic_fee_rate(&pool, later), min_fee_rate);
+        }
+    }
+
+    #[test]
+    fn swap_exact_out_delivers_at_least_requested(
+        reserve_a in amount(),
+        reserve_b in amount(),
+        fee_rate in fee_rate(),
+        amount_out in amount(),
+        direction_a_to_b in any::<bool>(),
+    ) {
+        let pool = pool(reserve_a, reserve_b, 1, fee_rate);
+        if let Ok(q) = quote::quote_swap_exact_out(&pool, amount_out, direction_a_to_b) {
+            prop_assert!(q.amount_out >= amount_out);
+            prop_assert!(k(q.reserve_a_after, q.reserve_b_after) >= k(reserve_a, reserve_b));
+        }
+    }
+
+    // Value per LP share, compared by cross-multiplying reserve / supply
+    #[test]
+    fn add_liquidity_never_dilutes_holders(
+        reserve_a in 1..=u64::MAX,
+        reserve_b in 1..=u64::MAX,
+        supply in 1..=u64::MAX,
+        amount_a in amount(),
+        amount_b in amount(),
+    ) {
+        let pool = pool(reserve_a, reserve_b, supply, 30);
+        if let Ok(q) = quote::quote_add_liquidity(&pool, amount_a, amount_b) {
+            prop_assert!(q.amount_a <= amount_a && q.amount_b <= amount_b);
+            prop_assert!(k(q.reserve_a_after, supply) >= k(reserve_a, q.lp_supply_after));
+            prop_assert!(k(q.reserve_b_after, supply) >= k(reserve_b, q.lp_supply_after));
+        }
+    }
+
+    #[test]
+    fn remove_liquidity_never_dilutes_holders(
+        reserve_a in amount(),
+        reserve_b in amount(),
+        supply in 1..=u64::MAX,
+        lp_amount in amount(),
+    ) {
+        let pool = pool(reserve_a, reserve_b, supply, 30);
+        match quote::quote_remove_liquidity(&pool, lp_amount) {
+            Ok(q) => {
+                prop_assert!(k(q.reserve_a_after, supply) >= k(reserve_a, q.lp_supply_after));
+                prop_assert!(k(q.reserve_b_after, supply) >= k(reserve_b, q.lp_supply_after));
+            }
+            Err(_) => prop_assert!(lp_amount > supply),
+        }
+    }
+
+    #[test]
+    fn add_then_remove_never_profits(
+        reserve_a in 1..=u64::MAX / 2,
+        reserve_b in 1..=u64::MAX / 2,
+        supply in 1..=u64::MAX / 2,
+        amount_a in amount(),
+        amount_b in amount(),
+    ) {
+        let mut pool = pool(reserve_a, reserve_b, supply, 30);
+        let Ok(added) = quote::quote_add_liquidity(&pool, amount_a, amount_b) else {
+            return Ok(());
+        };
+        pool.reserve_a = added.reserve_a_after;
+        pool.reserve_b = added.reserve_b_after;
+        pool.total_lp_supply = added.lp_supply_after;
+
+        let removed = quote::quote_remove_liquidity(&pool, added.lp_amount).unwrap();
+        prop_assert!(removed.amount_a <= added.amount_a);
+        prop_assert!(removed.amount_b <= added.amount_b);
+    }
+
+    // Prices compared as cross products of b / a; reserves stay small enough
+    // that scaling them by the bps denominator can't overflow
+    #[test]
+    fn price_band_never_admits_larger_moves(
+        reference in (1..=u32::MAX as u64, 1..=u32::MAX as u64),
+        after in (1..=u32::MAX as u64, 1..=u32::MAX as u64),
+        band in 1..=FEE_DENOMINATOR as u16,
+    ) {
+        let mut pool = pool(reference.0, reference.1, 1, 30);
+        pool.max_price_move_bps = band;
+        let price_after = after.1 as u128 * reference.0 as u128;
+        let price_before = after.0 as u128 * reference.1 as u128;
+        let within = price_after.abs_diff(price_before) * FEE_DENOMINATOR as u128 <= price_before * band as u128;
+        prop_assert_eq!(quote::check_price_band(&pool, reference, after.0, after.1).is_ok(), within);
+    }
+
+    // Accumulators wrap, so a steady pool's TWAP is its spot price from any
+    // starting value, and the slippage bound never exceeds the TWAP amount
+    #[test]
+    fn steady_pool_twap_is_spot_price(
+        reserve_a in 1u64..1 << 36,
+        reserve_b in 1u64..1 << 36,
+        cumulative in any::<u128>(),
+        seconds in 1i64..1 << 24,
+        amount_in in amount(),
+        slippage_bps in 0u16..=10_000,
+    ) {
+        let mut pool = pool(reserve_a, reserve_b, 1, 30);
+        pool.price_a_cumulative = cumulative.to_le_bytes();
+        pool.price_b_cumulative = cumulative.to_le_bytes();
+        let (earlier_a, earlier_b) = quote::price_cumulatives(&pool, 1_000);
+        let (later_a, later_b) = quote::price_cumulatives(&pool, 1_000 + seconds);
+
+        let (spot_a, spot_b) = quote::spot_prices_q64(reserve_a, reserve_b);
+        let twap_a = quote::twap_q64(earlier_a, later_a, seconds).unwrap();
+        prop_assert_eq!(twap_a, spot_a);
+        prop_assert_eq!(quote::twap_q64(earlier_b, later_b, seconds), Some(spot_b));
+        let bound = quote::min_amount_out_at(amount_in, twap_a, slippage_bps);
+        prop_assert!(bound <= quote::min_amount_out_at(amount_in, twap_a, 0));
+    }
+
+    // Emissions add up over split windows, and stakers sharing them never
+    // earn more than was emitted
+    #[test]
+    fn farm_never_pays_out_more_than_it_emits(
+        emission_per_second in 0u64..1 << 32,
+        start_time in 0i64..1 << 20,
+        from in 0i64..1 << 21,
+        split in 0i64..1 << 21,
+        to in 0i64..1 << 21,
+        stakes in prop::collection::vec(1u64..1 << 48, 1..8),
+    ) {
+        let reward = FarmReward {
+            emission_per_second,
+            start_time,
+            end_time: start_time + (1 << 20),
+            ..bytemuck::Zeroable::zeroed()
+        };
+        let split = split.clamp(from, to.max(from));
+        let emitted = quote::emitted_rewards(&reward, from, to);
+        prop_assert_eq!(
+            quote::emitted_rewards(&reward, from, split) + quote::emitted_rewards(&reward, split, to),
+            emitted
+        );
+
+        let total: u64 = stakes.iter().sum();
+        let per_share = quote::reward_per_share_q64(emitted, total);
+        let earned: u64 = stakes.iter().map(|&stake| quote::earned_rewards(stake, per_share)).sum();
+        prop_assert!(earned <= emitted);
+        prop_assert!(emitted - earned < stakes.len() as u64 + 1);
+    }
+
+    // A lock's farm weight grows with its length, from its amount up to the
+    // full boost at the cap
+    #[test]
+    fn lock_boost_grows_to_its_cap(amount in 0u64..1 << 63, seconds in -1i64..MAX_LOCK_SECONDS * 2) {
+        let weight = quote::locked_weight(amount, seconds);
+        prop_assert!(weight >= amount);
+        prop_assert!(weight <= quote::locked_weight(amount, seconds + 1));
+        let full = amount as u128 * (FEE_DENOMINATOR + MAX_LOCK_BOOST_BPS) as u128 / FEE_DENOMINATOR as u128;
+        prop_assert!(weight as u128 <= full);
+        prop_assert_eq!(quote::locked_weight(amount, MAX_LOCK_SECONDS) as u128, full);
+        prop_assert_eq!(quote::locked_weight(amount, 0), amount);
+    }
+}
+
+// In-memory accounts for calling the processor directly. Off-chain, CPIs are
+// no-ops, so only the pool account's state changes.
+struct TestAccount {
+    key: Pubkey,
+    lamports: u64,
+    data: Vec<u8>,
+    owner: Pubkey,
+    is_signer: bool,
+}
+
+impl TestAccount {
+    fn new(key: Pubkey) -> Self {
+        TestAccount { key, lamports: 0, data: vec![], owner: Pubkey::default(), is_signer: false }
+    }
+
+    fn token_state<T: Pack>(key: Pubkey, state: T) -> Self {
+        let mut data = vec![0; T::LEN];
+        state.pack_into_slice(&mut data);
+        TestAccount { key, lamports: 1, data, owner: token_program_id(), is_signer: false }
+    }
+}
+
+fn token_program_id() -> Pubkey {
+    Pubkey::from_str(GORBCHAIN_SPL_TOKEN_PROGRAM).unwrap()
+}
+
+// Handlers read the clock for the price accumulators; CPIs stay no-ops
+struct ClockStubs;
+
+impl program_stubs::SyscallStubs for ClockStubs {
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe { *(var_addr as *mut Clock) = Clock::default() };
+        SUCCESS
+    }
+}
+
+// Pool handlers take the program config first; an uncreated one pauses nothing
+fn run(accounts: &mut [TestAccount], instruction: TestProjectInstruction) -> Result<Pool, ProgramError> {
+    static STUBS: Once = Once::new();
+    STUBS.call_once(|| {
+        program_stubs::set_syscall_stubs(Box::new(ClockStubs));
+    });
+    let program_id = cargo_swap::id();
+    let mut config = TestAccount::new(find_config_address(&program_id).0);
+    let infos: Vec<AccountInfo> = std::iter::once(&mut config)
+        .chain(accounts.iter_mut())
+        .map(|a| AccountInfo::new(&a.key, a.is_signer, true, &mut a.lamports, &mut a.data, &a.owner, false, 0))
+        .collect();
+    let data = instruction.try_to_vec().unwrap();
+
+    cargo_swap::process_instruction(&program_id, &infos, &data)?;
+    Ok(Pool::unpack(&infos[1].data.borrow()).unwrap())
+}
+
+// Accounts in the order the pool handlers read them: pool, mints and vaults
+// first, then `rest` user accounts ending with the signer, followed by
+// `token_programs` copies of the token program
+fn pool_accounts(state: &Pool, rest: usize, token_programs: usize) -> Vec<TestAccount> {
+    let (address, _) = find_pool_address(&cargo_swap::id(), &state.token_a, &state.token_b);
+    let mut data = vec![0; Pool::LEN];
+    state.pack_into_slice(&mut data);
+    let vault = |mint: Pubkey| spl_token::state::Account {
+        mint,
+        owner: address,
+        state: spl_token::state::AccountState::Initialized,
+        ..Default::default()
+    };
+    let vault_address = |mint: Pubkey| find_vault_address(&cargo_swap::id(), &address, &mint).0;
+    let mint = spl_token::state::Mint { is_initialized: true, ..Default::default() };
+    let lp_mint = spl_token::state::Mint {
+        mint_authority: COption::Some(address),
+        is_initialized: true,
+        ..Default::default()
+    };
+
+    let mut accounts = vec![
+        TestAccount { key: address, lamports: 1, data, owner: cargo_swap::id(), is_signer: false },
+        TestAccount::token_state(state.token_a, mint),
+        TestAccount::token_state(state.token_b, mint),
+        TestAccount::token_state(vault_address(state.token_a), vault(state.token_a)),
+        TestAccount::token_state(vault_address(state.token_b), vault(state.token_b)),
+        TestAccount::token_state(Pubkey::new_unique(), lp_mint),
+    ];
+    accounts.extend((0..rest).map(|_| TestAccount::new(Pubkey::new_unique())));
+    let user = accounts.len() - 1;
+    accounts[user].is_signer = true;
+    accounts.extend((0..token_programs).map(|_| TestAccount::new(token_program_id())));
+    accounts
+}
+
+// Swaps charge the pool's own fee rate, not a fixed 0.3%
+#[test]
+fn swap_handler_charges_the_pool_fee_rate() {
+    for (fee_rate, amount_out) in [(1, 9_900), (30, 9_871), (100, 9_802)] {
+        let state = pool(1_000_000, 1_000_000, 1, fee_rate);
+        let mut accounts = pool_accounts(&state, 2, 2);
+        let swap = TestProjectInstruction::Swap { pool_id: 1, amount_in: 10_000, direction_a_to_b: true };
+        let after = run(&mut accounts, swap).unwrap();
+        assert_eq!((after.reserve_a, after.reserve_b), (1_010_000, 1_000_000 - amount_out));
+    }
+}
+
+proptest! {
+    #[test]
+    fn swap_handler_matches_quote_on_extreme_reserves(
+        reserve_a in amount(),
+        reserve_b in amount(),
+        fee_rate in any::<u16>(),
+        amount_in in amount(),
+        direction_a_to_b in any::<bool>(),
+    ) {
+        let state = pool(reserve_a, reserve_b, 1, fee_rate);
+        let expected = quote::quote_swap_exact_in(&state, amount_in, direction_a_to_b);
+        let mut accounts = pool_accounts(&state, 2, 2);
+
+        let result = run(&mut accounts, TestProjectInstruction::Swap { pool_id: 1, amount_in, direction_a_to_b });
+        match expected {
+            Ok(q) => {
+                let after = result.unwrap();
+                prop_assert_eq!((after.reserve_a, after.reserve_b), (q.reserve_a_after, q.reserve_b_after));
+            }
+            Err(e) => prop_assert_eq!(result.unwrap_err(), e),
+        }
+    }
+
+    #[test]
+    fn add_liquidity_handler_matches_quote_on_extreme_reserves(
+        reserve_a in amount(),
+        reserve_b in amount(),
+        supply in amount(),
+        amount_a in amount(),
+        amount_b in amount(),
+    ) {
+        let state = pool(reserve_a, reserve_b, supply, 30);
+        let expected = quote::quote_add_liquidity(&state, amount_a, amount_b);
+        let mut accounts = pool_accounts(&state, 4, 3);
+
+        let result = run(&mut accounts, TestProjectInstruction::AddLiquidity { pool_id: 1, amount_a, amount_b });
+        match expected {
+            Ok(q) => {
+                let after = result.unwrap();
+                prop_assert_eq!(after.reserve_a, q.reserve_a_after);
+                prop_assert_eq!(after.reserve_b, q.reserve_b_after);
+                prop_assert_eq!(after.total_lp_supply, q.lp_supply_after);
+            }
+            Err(e) => prop_assert_eq!(result.unwrap_err(), e),
+        }
+    }
+
+    #[test]
+    fn remove_liquidity_handler_matches_quote_on_extreme_reserves(
+        reserve_a in amount(),
+        reserve_b in amount(),
+        supply in amount(),
+        lp_amount in amount(),
+    ) {
+        let state = pool(reserve_a, reserve_b, supply, 30);
+        let expected = quote::quote_remove_liquidity(&state, lp_amount);
+        let mut accounts = pool_accounts(&state, 4, 3);
+
+        let result = run(&mut accounts, TestProjectInstruction::RemoveLiquidity { pool_id: 1, lp_amount });
+        match expected {
+            Ok(q) => {
+                let after = result.unwrap();
+                prop_assert_eq!(after.reserve_a, q.reserve_a_after);
+                prop_assert_eq!(after.reserve_b, q.reserve_b_after);
+                prop_assert_eq!(after.total_lp_supply, q.lp_supply_after);
+            }
+            Err(e) => prop_assert_eq!(result.unwrap_err(), e),
+        }
+    }
+}
@@ -0,0 +1,153 @@
+mod common;
+
+use cargo_swap::{error::SwapError, instruction, GovernanceChange, Multisig, ProgramConfig};
+use common::*;
+use solana_program::{instruction::InstructionError, program_pack::Pack, pubkey::Pubkey};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+
+fn custom_error(error: SwapError) -> TransactionError {
+    TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+}
+
+async fn get_multisig(ctx: &mut ProgramTestContext, address: &Pubkey) -> Multisig {
+    let account = ctx.banks_client.get_account(*address).await.unwrap().unwrap();
+    Multisig::unpack(&account.data).unwrap()
+}
+
+// A 2-of-3 multisig holding the admin role over a registered pool
+async fn setup() -> (ProgramTestContext, PoolFixture, Pubkey, Vec<Keypair>) {
+    let mut ctx = start().await;
+    let payer = ctx.payer.pubkey();
+    let program_id = cargo_swap::id();
+    initialize_config(&mut ctx, &payer, &payer).await;
+    let fixture = pool_fixture(&mut ctx).await;
+    init_pool(&mut ctx, &fixture, 1, 1_000_000, 1_000_000).await;
+
+    let members: Vec<Keypair> = (0..3).map(|_| Keypair::new()).collect();
+    let keys: Vec<Pubkey> = members.iter().map(|member| member.pubkey()).collect();
+    let create_key = Keypair::new().pubkey();
+    let ix = instruction::create_multisig(&program_id, &payer, &create_key, 2, &keys);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    let (multisig, _) = instruction::find_multisig_address(&program_id, &create_key);
+
+    let ix = instruction::set_admin(&program_id, &payer, &multisig);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    (ctx, fixture, multisig, members)
+}
+
+#[tokio::test]
+async fn multisig_members_are_validated() {
+    let mut ctx = start().await;
+    let payer = ctx.payer.pubkey();
+    let program_id = cargo_swap::id();
+    let member = Keypair::new().pubkey();
+    let other = Keypair::new().pubkey();
+
+    for (threshold, signers) in [
+        (0, vec![member, other]),
+        (3, vec![member, other]),
+        (2, vec![member, member]),
+        (1, vec![Pubkey::new_unique(); 11]),
+    ] {
+        let ix = instruction::create_multisig(&program_id, &payer, &Keypair::new().pubkey(), threshold, &signers);
+        let error = transaction_error(process(&mut ctx, &[ix], &[]).await);
+        assert_eq!(error, custom_error(SwapError::InvalidMultisig));
+    }
+
+    let create_key = Keypair::new().pubkey();
+    let ix = instruction::create_multisig(&program_id, &payer, &create_key, 2, &[member, other]);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    let (address, _) = instruction::find_multisig_address(&program_id, &create_key);
+    let multisig = get_multisig(&mut ctx, &address).await;
+    assert_eq!((multisig.members(), multisig.threshold), (&[member, other][..], 2));
+
+    let ix = instruction::create_multisig(&program_id, &payer, &create_key, 1, &[member]);
+    let error = transaction_error(process(&mut ctx, &[ix], &[]).await);
+    assert_eq!(error, TransactionError::InstructionError(0, InstructionError::AccountAlreadyInitialized));
+}
+
+#[tokio::test]
+async fn admin_instructions_need_the_threshold_of_members() {
+    let (mut ctx, fixture, multisig, members) = setup().await;
+    let payer = ctx.payer.pubkey();
+    let program_id = cargo_swap::id();
+    let pool = fixture.keys.pool;
+    let (config, _) = instruction::find_config_address(&program_id);
+    let account = ctx.banks_client.get_account(config).await.unwrap().unwrap();
+    assert_eq!(ProgramConfig::unpack(&account.data).unwrap().admin, multisig);
+
+    // The previous admin key lost the role
+    let change = GovernanceChange::PriceBand { max_price_move_bps: 100 };
+    let ix = instruction::propose_change(&program_id, &payer, &payer, &pool, change);
+    let error = transaction_error(process(&mut ctx, &[ix], &[]).await);
+    assert_eq!(error, custom_error(SwapError::Unauthorized));
+
+    // The same member twice counts once
+    let band = instruction::propose_change(&program_id, &multisig, &payer, &pool, change);
+    let signers = [members[0].pubkey(), members[0].pubkey()];
+    let ix = instruction::multisig_signers(band.clone(), &multisig, &signers);
+    let error = transaction_error(process(&mut ctx, &[ix], &[&members[0]]).await);
+    assert_eq!(error, TransactionError::InstructionError(0, InstructionError::MissingRequiredSignature));
+
+    // Signers outside the multisig don't count
+    let outsider = Keypair::new();
+    let ix = instruction::multisig_signers(band.clone(), &multisig, &[members[0].pubkey(), outsider.pubkey()]);
+    let error = transaction_error(process(&mut ctx, &[ix], &[&members[0], &outsider]).await);
+    assert_eq!(error, TransactionError::InstructionError(0, InstructionError::MissingRequiredSignature));
+
+    let ix = instruction::multisig_signers(band, &multisig, &[members[0].pubkey(), members[2].pubkey()]);
+    process(&mut ctx, &[ix], &[&members[0], &members[2]]).await.unwrap();
+    let (pending, _) = instruction::find_pending_change_address(&program_id, &pool);
+    assert!(ctx.banks_client.get_account(pending).await.unwrap().is_some());
+
+    // The multisig can hand the role back to a single key
+    let ix = instruction::set_admin(&program_id, &multisig, &payer);
+    let ix = instruction::multisig_signers(ix, &multisig, &[members[1].pubkey(), members[2].pubkey()]);
+    process(&mut ctx, &[ix], &[&members[1], &members[2]]).await.unwrap();
+    let ix = instruction::cancel_change(&program_id, &payer, &payer, &pool);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+}
+
+#[tokio::test]
+async fn membership_changes_keep_the_threshold_reachable() {
+    let (mut ctx, _, multisig, members) = setup().await;
+    let program_id = cargo_swap::id();
+    let approvers = [members[0].pubkey(), members[1].pubkey()];
+    let newcomer = Keypair::new();
+
+    let ix = instruction::add_multisig_signer(&program_id, &multisig, &approvers[..1], &newcomer.pubkey());
+    let error = transaction_error(process(&mut ctx, &[ix], &[&members[0]]).await);
+    assert_eq!(error, TransactionError::InstructionError(0, InstructionError::MissingRequiredSignature));
+
+    let ix = instruction::add_multisig_signer(&program_id, &multisig, &approvers, &newcomer.pubkey());
+    process(&mut ctx, &[ix], &[&members[0], &members[1]]).await.unwrap();
+    let ix = instruction::add_multisig_signer(&program_id, &multisig, &approvers, &newcomer.pubkey());
+    let error = transaction_error(process(&mut ctx, &[ix], &[&members[0], &members[1]]).await);
+    assert_eq!(error, custom_error(SwapError::InvalidMultisig));
+
+    let ix = instruction::set_multisig_threshold(&program_id, &multisig, &approvers, 4);
+    process(&mut ctx, &[ix], &[&members[0], &members[1]]).await.unwrap();
+    let state = get_multisig(&mut ctx, &multisig).await;
+    assert_eq!((state.members().len(), state.threshold), (4, 4));
+
+    // Removing a member would leave 4-of-3
+    let all: Vec<Pubkey> = members.iter().map(|member| member.pubkey()).chain([newcomer.pubkey()]).collect();
+    let signers = [&members[0], &members[1], &members[2], &newcomer];
+    let ix = instruction::remove_multisig_signer(&program_id, &multisig, &all, &members[2].pubkey());
+    let error = transaction_error(process(&mut ctx, &[ix], &signers).await);
+    assert_eq!(error, custom_error(SwapError::InvalidMultisig));
+    let ix = instruction::remove_multisig_signer(&program_id, &multisig, &all, &Keypair::new().pubkey());
+    let error = transaction_error(process(&mut ctx, &[ix], &signers).await);
+    assert_eq!(error, custom_error(SwapError::InvalidMultisig));
+
+    let ix = instruction::set_multisig_threshold(&program_id, &multisig, &all, 2);
+    process(&mut ctx, &[ix], &signers).await.unwrap();
+    let ix = instruction::remove_multisig_signer(&program_id, &multisig, &approvers, &members[2].pubkey());
+    process(&mut ctx, &[ix], &[&members[0], &members[1]]).await.unwrap();
+    let state = get_multisig(&mut ctx, &multisig).await;
+    assert_eq!(state.members(), &[members[0].pubkey(), members[1].pubkey(), newcomer.pubkey()][..]);
+}
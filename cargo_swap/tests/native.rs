@@ -0,0 +1,115 @@
+mod common;
+
+use cargo_swap::{error::SwapError, instruction, quote};
+use common::*;
+use solana_program::{instruction::InstructionError, pubkey::Pubkey};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::{signature::Signer, transaction::TransactionError};
+
+const INITIAL: u64 = 1_000_000_000;
+// program-test charges the default fee for the payer's signature
+const TX_FEE: u64 = 5_000;
+
+// Pool of wrapped SOL (side A) against an SPL token
+async fn native_pool(ctx: &mut ProgramTestContext) -> PoolFixture {
+    let native_mint = install_native_mint(ctx).await;
+    let payer = ctx.payer.pubkey();
+    let token_b = create_mint(ctx, &payer, 6).await;
+    let fixture = pool_fixture_with_mints(ctx, native_mint, token_b).await;
+    init_pool(ctx, &fixture, 1, INITIAL, INITIAL).await;
+    fixture
+}
+
+async fn lamports(ctx: &mut ProgramTestContext, address: &Pubkey) -> u64 {
+    ctx.banks_client.get_balance(*address).await.unwrap()
+}
+
+async fn assert_temporary_account_closed(ctx: &mut ProgramTestContext, fixture: &PoolFixture) {
+    let (native, _) = instruction::find_native_address(&cargo_swap::id(), &fixture.keys.pool, &fixture.user.user);
+    assert_eq!(ctx.banks_client.get_account(native).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn swap_native_wraps_input_and_unwraps_output() {
+    let mut ctx = start().await;
+    let fixture = native_pool(&mut ctx).await;
+    let user = fixture.user.user;
+
+    // SOL in: the user pays lamports, not wrapped SOL
+    let wsol_before = token_balance(&mut ctx, &fixture.user.token_a).await;
+    let lamports_before = lamports(&mut ctx, &user).await;
+    let b_before = token_balance(&mut ctx, &fixture.user.token_b).await;
+    let expected = quote::quote_swap_exact_in(&get_pool(&mut ctx, &fixture.keys.pool).await, INITIAL / 10, true).unwrap();
+    let ix = instruction::swap_native(&cargo_swap::id(), 1, &fixture.keys, &fixture.user, INITIAL / 10, true);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+
+    assert_eq!(lamports_before - lamports(&mut ctx, &user).await, INITIAL / 10 + TX_FEE);
+    assert_eq!(token_balance(&mut ctx, &fixture.user.token_b).await - b_before, expected.amount_out);
+    assert_eq!(token_balance(&mut ctx, &fixture.user.token_a).await, wsol_before);
+    assert_temporary_account_closed(&mut ctx, &fixture).await;
+
+    // SOL out: the output arrives as lamports
+    let lamports_before = lamports(&mut ctx, &user).await;
+    let expected = quote::quote_swap_exact_in(&get_pool(&mut ctx, &fixture.keys.pool).await, 1_000_000, false).unwrap();
+    let ix = instruction::swap_native(&cargo_swap::id(), 1, &fixture.keys, &fixture.user, 1_000_000, false);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+
+    assert_eq!(lamports(&mut ctx, &user).await - lamports_before, expected.amount_out - TX_FEE);
+    assert_eq!(token_balance(&mut ctx, &fixture.user.token_a).await, wsol_before);
+    assert_temporary_account_closed(&mut ctx, &fixture).await;
+
+    let pool = get_pool(&mut ctx, &fixture.keys.pool).await;
+    assert_eq!(token_balance(&mut ctx, &fixture.keys.vault_a).await, pool.reserve_a);
+}
+
+#[tokio::test]
+async fn liquidity_native_deposits_and_withdraws_lamports() {
+    let mut ctx = start().await;
+    let fixture = native_pool(&mut ctx).await;
+    let user = fixture.user.user;
+
+    // Excess SOL beyond the pool ratio is refunded when the account closes
+    let lamports_before = lamports(&mut ctx, &user).await;
+    let deposit = quote::quote_add_liquidity(&get_pool(&mut ctx, &fixture.keys.pool).await, 2 * INITIAL, INITIAL / 2).unwrap();
+    let ix = instruction::add_liquidity_native(
+        &cargo_swap::id(),
+        1,
+        &fixture.keys,
+        &fixture.user,
+        &token_program_id(),
+        2 * INITIAL,
+        INITIAL / 2,
+    );
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    assert_eq!(deposit.amount_a, INITIAL / 2);
+    assert_eq!(lamports_before - lamports(&mut ctx, &user).await, deposit.amount_a + TX_FEE);
+    assert_temporary_account_closed(&mut ctx, &fixture).await;
+
+    let lamports_before = lamports(&mut ctx, &user).await;
+    let withdrawal = quote::quote_remove_liquidity(&get_pool(&mut ctx, &fixture.keys.pool).await, INITIAL).unwrap();
+    let ix = instruction::remove_liquidity_native(
+        &cargo_swap::id(),
+        1,
+        &fixture.keys,
+        &fixture.user,
+        &token_program_id(),
+        INITIAL,
+    );
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    assert_eq!(lamports(&mut ctx, &user).await - lamports_before, withdrawal.amount_a - TX_FEE);
+    assert_temporary_account_closed(&mut ctx, &fixture).await;
+}
+
+#[tokio::test]
+async fn native_instructions_need_a_wrapped_sol_side() {
+    let mut ctx = start().await;
+    let fixture = pool_fixture(&mut ctx).await;
+    init_pool(&mut ctx, &fixture, 1, INITIAL, INITIAL).await;
+
+    let ix = instruction::swap_native(&cargo_swap::id(), 1, &fixture.keys, &fixture.user, 1_000, true);
+    let err = transaction_error(process(&mut ctx, &[ix], &[]).await);
+    assert_eq!(
+        err,
+        TransactionError::InstructionError(0, InstructionError::Custom(SwapError::NoNativeSide as u32))
+    );
+}
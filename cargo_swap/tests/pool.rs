@@ -0,0 +1,454 @@
+mod common;
+
+use cargo_swap::{error::SwapError, instruction, quote, PoolType};
+use common::*;
+use solana_program::{
+    instruction::{Instruction, InstructionError},
+    program_option::COption,
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+use solana_sdk::{signature::Signer, transaction::TransactionError};
+
+const INITIAL: u64 = 1_000_000_000;
+
+#[tokio::test]
+async fn init_pool_creates_pool_and_mints_lp() {
+    let mut ctx = start().await;
+    let fixture = pool_fixture(&mut ctx).await;
+    init_pool(&mut ctx, &fixture, 1, INITIAL, 4 * INITIAL).await;
+
+    let pool = get_pool(&mut ctx, &fixture.keys.pool).await;
+    assert_eq!(pool.pool_id, 1);
+    assert_eq!(pool.reserve_a, INITIAL);
+    assert_eq!(pool.reserve_b, 4 * INITIAL);
+    assert_eq!(pool.total_lp_supply, 2 * INITIAL);
+    assert_eq!(pool.fee_rate, 30);
+
+    assert_eq!(token_balance(&mut ctx, &fixture.keys.vault_a).await, INITIAL);
+    assert_eq!(token_balance(&mut ctx, &fixture.keys.vault_b).await, 4 * INITIAL);
+    assert_eq!(token_balance(&mut ctx, &fixture.user.lp).await, 2 * INITIAL);
+    assert_eq!(token_balance(&mut ctx, &fixture.user.token_a).await, USER_FUNDS - INITIAL);
+}
+
+#[tokio::test]
+async fn init_pool_takes_type_and_fee_from_registration() {
+    let mut ctx = start().await;
+    let payer = ctx.payer.pubkey();
+    pool_fixture(&mut ctx).await;
+    let token_a = create_mint(&mut ctx, &payer, 6).await;
+    let token_b = create_mint(&mut ctx, &payer, 6).await;
+    let program = token_program_id();
+    let keys = instruction::PoolKeys::new(&cargo_swap::id(), &token_a, &token_b, &program, &program);
+    assert_eq!(create_pool(&mut ctx, &keys, PoolType::Stable).await, 2);
+    let user = instruction::UserKeys {
+        user: payer,
+        token_a: create_token_account(&mut ctx, &token_a, &payer).await,
+        token_b: create_token_account(&mut ctx, &token_b, &payer).await,
+        lp: create_token_account(&mut ctx, &keys.lp_mint, &payer).await,
+    };
+    fund(&mut ctx, &token_a, &user.token_a, INITIAL).await;
+    fund(&mut ctx, &token_b, &user.token_b, INITIAL).await;
+    let init = |pool_id| instruction::init_pool(&cargo_swap::id(), pool_id, &keys, &user, &program, INITIAL, INITIAL);
+
+    // Another pool's registration doesn't fit
+    let err = transaction_error(process(&mut ctx, &[init(1)], &[]).await);
+    assert_eq!(err, TransactionError::InstructionError(0, InstructionError::InvalidArgument));
+    process(&mut ctx, &[init(2)], &[]).await.unwrap();
+
+    let pool = get_pool(&mut ctx, &keys.pool).await;
+    assert_eq!((pool.pool_id, pool.pool_type, pool.fee_rate), (2, PoolType::Stable, 1));
+}
+
+#[tokio::test]
+async fn create_pool_creates_pda_vaults_and_lp_mint() {
+    let mut ctx = start().await;
+    let fixture = pool_fixture_with_programs(&mut ctx, &token_program_id(), &spl_token_2022::id()).await;
+    let keys = fixture.keys;
+    let (vault_a, _) = instruction::find_vault_address(&cargo_swap::id(), &keys.pool, &keys.token_a);
+    let (lp_mint, _) = instruction::find_lp_mint_address(&cargo_swap::id(), &keys.pool);
+    assert_eq!((keys.vault_a, keys.lp_mint), (vault_a, lp_mint));
+
+    for (vault, mint, token_program) in [
+        (keys.vault_a, keys.token_a, keys.token_program_a),
+        (keys.vault_b, keys.token_b, keys.token_program_b),
+    ] {
+        let account = ctx.banks_client.get_account(vault).await.unwrap().unwrap();
+        assert_eq!(account.owner, token_program);
+        let state = spl_token::state::Account::unpack(&account.data[..spl_token::state::Account::LEN]).unwrap();
+        assert_eq!((state.mint, state.owner, state.amount), (mint, keys.pool, 0));
+    }
+
+    let account = ctx.banks_client.get_account(keys.lp_mint).await.unwrap().unwrap();
+    assert_eq!(account.owner, token_program_id());
+    let mint = spl_token::state::Mint::unpack(&account.data).unwrap();
+    assert_eq!(mint.mint_authority, COption::Some(keys.pool));
+    assert_eq!(mint.freeze_authority, COption::None);
+    assert_eq!((mint.decimals, mint.supply), (cargo_swap::LP_MINT_DECIMALS, 0));
+}
+
+#[tokio::test]
+async fn init_pool_rejects_vault_outside_pda() {
+    let mut ctx = start().await;
+    let mut fixture = pool_fixture(&mut ctx).await;
+    // A token account the pool owns, but not at the vault PDA
+    fixture.keys.vault_a = create_token_account(&mut ctx, &fixture.keys.token_a, &fixture.keys.pool).await;
+
+    let ix = instruction::init_pool(
+        &cargo_swap::id(),
+        1,
+        &fixture.keys,
+        &fixture.user,
+        &token_program_id(),
+        INITIAL,
+        INITIAL,
+    );
+    let err = transaction_error(process(&mut ctx, &[ix], &[]).await);
+    assert_eq!(
+        err,
+        TransactionError::InstructionError(0, InstructionError::Custom(SwapError::InvalidVault as u32))
+    );
+}
+
+#[tokio::test]
+async fn handlers_reject_vaults_outside_pda() {
+    let mut ctx = start().await;
+    let fixture = pool_fixture(&mut ctx).await;
+    init_pool(&mut ctx, &fixture, 1, INITIAL, INITIAL).await;
+    let mut keys = fixture.keys;
+    keys.vault_a = create_token_account(&mut ctx, &keys.token_a, &keys.pool).await;
+    fund(&mut ctx, &keys.token_a, &keys.vault_a, INITIAL).await;
+
+    let (user, program) = (&fixture.user, token_program_id());
+    let ixs = [
+        instruction::swap(&cargo_swap::id(), 1, &keys, user, INITIAL / 10, false),
+        instruction::add_liquidity(&cargo_swap::id(), 1, &keys, user, &program, INITIAL, INITIAL),
+        instruction::remove_liquidity(&cargo_swap::id(), 1, &keys, user, &program, INITIAL / 10),
+    ];
+    for ix in ixs {
+        let err = transaction_error(process(&mut ctx, &[ix], &[]).await);
+        assert_eq!(
+            err,
+            TransactionError::InstructionError(0, InstructionError::Custom(SwapError::InvalidVault as u32))
+        );
+    }
+}
+
+#[tokio::test]
+async fn init_pool_twice_fails() {
+    let mut ctx = start().await;
+    let fixture = pool_fixture(&mut ctx).await;
+    init_pool(&mut ctx, &fixture, 1, INITIAL, INITIAL).await;
+
+    let ix = instruction::init_pool(
+        &cargo_swap::id(),
+        1,
+        &fixture.keys,
+        &fixture.user,
+        &token_program_id(),
+        INITIAL,
+        INITIAL,
+    );
+    assert!(process(&mut ctx, &[ix], &[]).await.is_err());
+}
+
+#[tokio::test]
+async fn init_pool_rejects_wrong_pool_address() {
+    let mut ctx = start().await;
+    let mut fixture = pool_fixture(&mut ctx).await;
+    fixture.keys.pool = Pubkey::new_unique();
+
+    let ix = instruction::init_pool(
+        &cargo_swap::id(),
+        1,
+        &fixture.keys,
+        &fixture.user,
+        &token_program_id(),
+        INITIAL,
+        INITIAL,
+    );
+    let err = transaction_error(process(&mut ctx, &[ix], &[]).await);
+    assert_eq!(err, TransactionError::InstructionError(0, InstructionError::InvalidSeeds));
+}
+
+#[tokio::test]
+async fn add_liquidity_keeps_pool_ratio() {
+    let mut ctx = start().await;
+    let fixture = pool_fixture(&mut ctx).await;
+    init_pool(&mut ctx, &fixture, 1, INITIAL, 2 * INITIAL).await;
+
+    let before = get_pool(&mut ctx, &fixture.keys.pool).await;
+    let expected = quote::quote_add_liquidity(&before, INITIAL, 5 * INITIAL).unwrap();
+
+    let ix = instruction::add_liquidity(
+        &cargo_swap::id(),
+        1,
+        &fixture.keys,
+        &fixture.user,
+        &token_program_id(),
+        INITIAL,
+        5 * INITIAL,
+    );
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+
+    let after = get_pool(&mut ctx, &fixture.keys.pool).await;
+    assert_eq!(expected.amount_b, 2 * INITIAL);
+    assert_eq!(after.reserve_a, expected.reserve_a_after);
+    assert_eq!(after.reserve_b, expected.reserve_b_after);
+    assert_eq!(after.total_lp_supply, expected.lp_supply_after);
+    assert_eq!(token_balance(&mut ctx, &fixture.keys.vault_b).await, 4 * INITIAL);
+    assert_eq!(
+        token_balance(&mut ctx, &fixture.user.lp).await,
+        before.total_lp_supply + expected.lp_amount
+    );
+}
+
+#[tokio::test]
+async fn remove_liquidity_returns_proportional_share() {
+    let mut ctx = start().await;
+    let fixture = pool_fixture(&mut ctx).await;
+    init_pool(&mut ctx, &fixture, 1, INITIAL, 3 * INITIAL).await;
+
+    let before = get_pool(&mut ctx, &fixture.keys.pool).await;
+    let lp_amount = before.total_lp_supply / 4;
+    let expected = quote::quote_remove_liquidity(&before, lp_amount).unwrap();
+
+    let ix = instruction::remove_liquidity(
+        &cargo_swap::id(),
+        1,
+        &fixture.keys,
+        &fixture.user,
+        &token_program_id(),
+        lp_amount,
+    );
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+
+    let after = get_pool(&mut ctx, &fixture.keys.pool).await;
+    assert_eq!(after.reserve_a, expected.reserve_a_after);
+    assert_eq!(after.reserve_b, expected.reserve_b_after);
+    assert_eq!(after.total_lp_supply, before.total_lp_supply - lp_amount);
+    assert_eq!(token_balance(&mut ctx, &fixture.keys.vault_a).await, after.reserve_a);
+    assert_eq!(
+        token_balance(&mut ctx, &fixture.user.token_a).await,
+        USER_FUNDS - INITIAL + expected.amount_a
+    );
+}
+
+#[tokio::test]
+async fn remove_liquidity_rejects_more_than_supply() {
+    let mut ctx = start().await;
+    let fixture = pool_fixture(&mut ctx).await;
+    init_pool(&mut ctx, &fixture, 1, INITIAL, INITIAL).await;
+
+    let ix = instruction::remove_liquidity(
+        &cargo_swap::id(),
+        1,
+        &fixture.keys,
+        &fixture.user,
+        &token_program_id(),
+        INITIAL + 1,
+    );
+    let err = transaction_error(process(&mut ctx, &[ix], &[]).await);
+    assert_eq!(
+        err,
+        TransactionError::InstructionError(0, InstructionError::Custom(SwapError::InsufficientLiquidity as u32))
+    );
+}
+
+#[tokio::test]
+async fn swap_matches_quote_in_both_directions() {
+    let mut ctx = start().await;
+    let fixture = pool_fixture(&mut ctx).await;
+    init_pool(&mut ctx, &fixture, 1, 10 * INITIAL, 20 * INITIAL).await;
+
+    for (amount_in, direction_a_to_b) in [(INITIAL, true), (3 * INITIAL, false), (7, true)] {
+        let before = get_pool(&mut ctx, &fixture.keys.pool).await;
+        let expected = quote::quote_swap_exact_in(&before, amount_in, direction_a_to_b).unwrap();
+        let out_account = if direction_a_to_b { fixture.user.token_b } else { fixture.user.token_a };
+        let out_before = token_balance(&mut ctx, &out_account).await;
+
+        let ix = instruction::swap(
+            &cargo_swap::id(),
+            1,
+            &fixture.keys,
+            &fixture.user,
+            amount_in,
+            direction_a_to_b,
+        );
+        process(&mut ctx, &[ix], &[]).await.unwrap();
+
+        let after = get_pool(&mut ctx, &fixture.keys.pool).await;
+        assert_eq!(after.reserve_a, expected.reserve_a_after);
+        assert_eq!(after.reserve_b, expected.reserve_b_after);
+        assert_eq!(token_balance(&mut ctx, &out_account).await - out_before, expected.amount_out);
+        assert_eq!(token_balance(&mut ctx, &fixture.keys.vault_a).await, after.reserve_a);
+        assert_eq!(token_balance(&mut ctx, &fixture.keys.vault_b).await, after.reserve_b);
+    }
+}
+
+#[tokio::test]
+async fn swap_rejects_mismatched_mints() {
+    let mut ctx = start().await;
+    let fixture = pool_fixture(&mut ctx).await;
+    init_pool(&mut ctx, &fixture, 1, INITIAL, INITIAL).await;
+
+    let mut keys = fixture.keys;
+    std::mem::swap(&mut keys.token_a, &mut keys.token_b);
+    let ix = instruction::swap(&cargo_swap::id(), 1, &keys, &fixture.user, 1_000, true);
+    let err = transaction_error(process(&mut ctx, &[ix], &[]).await);
+    assert_eq!(err, TransactionError::InstructionError(0, InstructionError::InvalidSeeds));
+}
+
+#[tokio::test]
+async fn swap_fails_without_user_balance() {
+    let mut ctx = start().await;
+    let fixture = pool_fixture(&mut ctx).await;
+    init_pool(&mut ctx, &fixture, 1, INITIAL, INITIAL).await;
+
+    let ix = instruction::swap(
+        &cargo_swap::id(),
+        1,
+        &fixture.keys,
+        &fixture.user,
+        USER_FUNDS,
+        true,
+    );
+    assert!(process(&mut ctx, &[ix], &[]).await.is_err());
+
+    let pool = get_pool(&mut ctx, &fixture.keys.pool).await;
+    assert_eq!(pool.reserve_a, INITIAL);
+    assert_eq!(pool.reserve_b, INITIAL);
+}
+
+#[tokio::test]
+async fn pool_mixes_spl_token_and_token_2022_mints() {
+    let mut ctx = start().await;
+    let fixture = pool_fixture_with_programs(&mut ctx, &token_program_id(), &spl_token_2022::id()).await;
+    init_pool(&mut ctx, &fixture, 1, INITIAL, 2 * INITIAL).await;
+
+    let pool = get_pool(&mut ctx, &fixture.keys.pool).await;
+    assert_eq!(pool.token_program_a, token_program_id());
+    assert_eq!(pool.token_program_b, spl_token_2022::id());
+    assert_eq!(owner_of(&mut ctx, &fixture.keys.vault_b).await, spl_token_2022::id());
+
+    for direction_a_to_b in [true, false] {
+        let before = get_pool(&mut ctx, &fixture.keys.pool).await;
+        let expected = quote::quote_swap_exact_in(&before, INITIAL / 10, direction_a_to_b).unwrap();
+        let ix = instruction::swap(&cargo_swap::id(), 1, &fixture.keys, &fixture.user, INITIAL / 10, direction_a_to_b);
+        process(&mut ctx, &[ix], &[]).await.unwrap();
+
+        let after = get_pool(&mut ctx, &fixture.keys.pool).await;
+        assert_eq!((after.reserve_a, after.reserve_b), (expected.reserve_a_after, expected.reserve_b_after));
+    }
+
+    let ix = instruction::remove_liquidity(&cargo_swap::id(), 1, &fixture.keys, &fixture.user, &token_program_id(), INITIAL);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+
+    let pool = get_pool(&mut ctx, &fixture.keys.pool).await;
+    assert_eq!(token_balance(&mut ctx, &fixture.keys.vault_a).await, pool.reserve_a);
+    assert_eq!(token_balance(&mut ctx, &fixture.keys.vault_b).await, pool.reserve_b);
+}
+
+#[tokio::test]
+async fn swap_rejects_token_program_not_owning_mint() {
+    let mut ctx = start().await;
+    let fixture = pool_fixture_with_programs(&mut ctx, &token_program_id(), &spl_token_2022::id()).await;
+    init_pool(&mut ctx, &fixture, 1, INITIAL, INITIAL).await;
+
+    let mut keys = fixture.keys;
+    keys.token_program_b = token_program_id();
+    let ix = instruction::swap(&cargo_swap::id(), 1, &keys, &fixture.user, 1_000, true);
+    let err = transaction_error(process(&mut ctx, &[ix], &[]).await);
+    assert_eq!(err, TransactionError::InstructionError(0, InstructionError::IncorrectProgramId));
+}
+
+#[tokio::test]
+async fn swap_creates_missing_output_ata() {
+    let mut ctx = start().await;
+    // The upstream ATA program bundled with program-test only serves the
+    // upstream token programs
+    let fixture = pool_fixture_with_programs(&mut ctx, &token_program_id(), &spl_token_2022::id()).await;
+    init_pool(&mut ctx, &fixture, 1, INITIAL, INITIAL).await;
+
+    let ata_program = cargo_swap::SPL_ATA_PROGRAM_ID;
+    let mut user = fixture.user;
+    user.token_b =
+        instruction::find_associated_token_address(&ata_program, &user.user, &fixture.keys.token_b, &spl_token_2022::id());
+
+    // The second swap finds the account already there
+    let mut received = 0;
+    for _ in 0..2 {
+        let before = get_pool(&mut ctx, &fixture.keys.pool).await;
+        let expected = quote::quote_swap_exact_in(&before, 1_000, true).unwrap();
+        let ix = instruction::swap(&cargo_swap::id(), 1, &fixture.keys, &user, 1_000, true);
+        let ix = instruction::create_missing_accounts(ix, &user.user, &ata_program);
+        process(&mut ctx, &[ix], &[]).await.unwrap();
+        received += expected.amount_out;
+        assert_eq!(token_balance(&mut ctx, &user.token_b).await, received);
+    }
+    assert_eq!(owner_of(&mut ctx, &user.token_b).await, spl_token_2022::id());
+}
+
+#[tokio::test]
+async fn liquidity_creates_missing_atas() {
+    let mut ctx = start().await;
+    let payer = ctx.payer.pubkey();
+    let token_a = create_mint_with_program(&mut ctx, &spl_token_2022::id(), &payer, 9).await;
+    let token_b = create_mint_with_program(&mut ctx, &spl_token_2022::id(), &payer, 9).await;
+    let fixture = pool_fixture_with_lp_program(&mut ctx, token_a, token_b, &spl_token_2022::id()).await;
+    init_pool(&mut ctx, &fixture, 1, INITIAL, INITIAL).await;
+
+    let ata_program = cargo_swap::SPL_ATA_PROGRAM_ID;
+    let ata = |mint: &Pubkey| {
+        instruction::find_associated_token_address(&ata_program, &fixture.user.user, mint, &spl_token_2022::id())
+    };
+    let mut user = fixture.user;
+    user.lp = ata(&fixture.keys.lp_mint);
+    let ix =
+        instruction::add_liquidity(&cargo_swap::id(), 1, &fixture.keys, &user, &spl_token_2022::id(), INITIAL, INITIAL);
+    let ix = instruction::create_missing_accounts(ix, &user.user, &ata_program);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut ctx, &user.lp).await, INITIAL);
+
+    (user.token_a, user.token_b) = (ata(&fixture.keys.token_a), ata(&fixture.keys.token_b));
+    let ix = instruction::remove_liquidity(&cargo_swap::id(), 1, &fixture.keys, &user, &spl_token_2022::id(), INITIAL);
+    let ix = instruction::create_missing_accounts(ix, &user.user, &ata_program);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut ctx, &user.token_a).await, INITIAL);
+    assert_eq!(token_balance(&mut ctx, &user.token_b).await, INITIAL);
+}
+
+#[tokio::test]
+async fn swap_rejects_missing_output_outside_ata() {
+    let mut ctx = start().await;
+    let fixture = pool_fixture(&mut ctx).await;
+    init_pool(&mut ctx, &fixture, 1, INITIAL, INITIAL).await;
+
+    let mut user = fixture.user;
+    user.token_b = Pubkey::new_unique();
+    let ix = instruction::swap(&cargo_swap::id(), 1, &fixture.keys, &user, 1_000, true);
+    let ix = instruction::create_missing_accounts(ix, &user.user, &cargo_swap::SPL_ATA_PROGRAM_ID);
+    let err = transaction_error(process(&mut ctx, &[ix], &[]).await);
+    assert_eq!(err, TransactionError::InstructionError(0, InstructionError::InvalidSeeds));
+}
+
+#[tokio::test]
+async fn swap_on_uninitialized_pool_fails() {
+    let mut ctx = start().await;
+    let fixture = pool_fixture(&mut ctx).await;
+
+    let ix = instruction::swap(&cargo_swap::id(), 1, &fixture.keys, &fixture.user, 1_000, true);
+    assert!(process(&mut ctx, &[ix], &[]).await.is_err());
+}
+
+#[tokio::test]
+async fn invalid_instruction_data_is_rejected() {
+    let mut ctx = start().await;
+    let ix = Instruction {
+        program_id: cargo_swap::id(),
+        accounts: vec![],
+        data: vec![255, 1, 2, 3],
+    };
+    let err = transaction_error(process(&mut ctx, &[ix], &[]).await);
+    assert_eq!(err, TransactionError::InstructionError(0, InstructionError::InvalidInstructionData));
+}
@@ -0,0 +1,279 @@
+mod common;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use cargo_swap::{
+    error::SwapError,
+    instruction::{self, find_pair_index_address, find_pool_address, PoolKeys},
+    PairIndex, PoolInfo, PoolType, TestProjectInstruction, MAX_LIST_POOLS,
+};
+use common::*;
+use solana_program::{instruction::InstructionError, program_pack::Pack, pubkey::Pubkey};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+
+async fn new_mint(ctx: &mut ProgramTestContext) -> Pubkey {
+    let payer = ctx.payer.pubkey();
+    create_mint(ctx, &payer, 9).await
+}
+
+fn pool_keys(token_a: &Pubkey, token_b: &Pubkey) -> PoolKeys {
+    PoolKeys::new(&cargo_swap::id(), token_a, token_b, &token_program_id(), &token_program_id())
+}
+
+// Registers a pool for two fresh mints
+async fn create_new_pool(ctx: &mut ProgramTestContext) -> u64 {
+    let (a, b) = (new_mint(ctx).await, new_mint(ctx).await);
+    create_pool(ctx, &pool_keys(&a, &b), PoolType::Standard).await
+}
+
+#[tokio::test]
+async fn initialize_registry_creates_empty_registry() {
+    let mut ctx = start().await;
+    initialize_registry(&mut ctx).await;
+
+    let registry = get_registry(&mut ctx).await;
+    assert_eq!(registry.next_pool_id, 1);
+    assert_eq!(registry.total_pools, 0);
+}
+
+#[tokio::test]
+async fn initialize_registry_rejects_wrong_bump() {
+    let mut ctx = start().await;
+    let mut ix = instruction::initialize_registry(&cargo_swap::id(), &ctx.payer.pubkey(), &token_program_id());
+    let (_, bump) = instruction::find_registry_address(&cargo_swap::id());
+    ix.data = TestProjectInstruction::InitializeRegistry { bump: bump.wrapping_sub(1) }
+        .try_to_vec()
+        .unwrap();
+
+    let err = transaction_error(process(&mut ctx, &[ix], &[]).await);
+    assert_eq!(err, TransactionError::InstructionError(0, InstructionError::InvalidSeeds));
+}
+
+#[tokio::test]
+async fn initialize_registry_twice_fails() {
+    let mut ctx = start().await;
+    initialize_registry(&mut ctx).await;
+
+    let ix = instruction::initialize_registry(&cargo_swap::id(), &ctx.payer.pubkey(), &token_program_id());
+    assert!(process(&mut ctx, &[ix], &[]).await.is_err());
+}
+
+#[tokio::test]
+async fn create_pool_writes_pool_and_pair_indexes() {
+    let mut ctx = start().await;
+    initialize_registry(&mut ctx).await;
+
+    let (a, b, c) = (new_mint(&mut ctx).await, new_mint(&mut ctx).await, new_mint(&mut ctx).await);
+    assert_eq!(create_pool(&mut ctx, &pool_keys(&a, &b), PoolType::Standard).await, 1);
+    assert_eq!(create_pool(&mut ctx, &pool_keys(&b, &c), PoolType::Stable).await, 2);
+
+    let registry = get_registry(&mut ctx).await;
+    assert_eq!(registry.total_pools, 2);
+    assert_eq!(registry.next_pool_id, 3);
+
+    let first = get_pool_info(&mut ctx, 1).await;
+    assert_eq!(first.pool_address, find_pool_address(&cargo_swap::id(), &a, &b).0);
+    assert_eq!(first.fee_rate, 30);
+    assert!(first.is_active);
+
+    let second = get_pool_info(&mut ctx, 2).await;
+    assert_eq!((second.token_a, second.token_b), (b, c));
+    assert_eq!(second.pool_type, PoolType::Stable);
+    assert_eq!(second.fee_rate, 1);
+
+    let (pair_index, _) = find_pair_index_address(&cargo_swap::id(), &b, &c);
+    let account = ctx.banks_client.get_account(pair_index).await.unwrap().unwrap();
+    assert_eq!(PairIndex::unpack(&account.data).unwrap().pool_id, 2);
+}
+
+#[tokio::test]
+async fn create_pool_rejects_duplicate_pair() {
+    let mut ctx = start().await;
+    initialize_registry(&mut ctx).await;
+
+    let keys = pool_keys(&new_mint(&mut ctx).await, &new_mint(&mut ctx).await);
+    create_pool(&mut ctx, &keys, PoolType::Standard).await;
+
+    let payer = ctx.payer.pubkey();
+    let ix = instruction::create_pool(&cargo_swap::id(), &payer, &keys, PoolType::Stable, &token_program_id(), 2);
+    let err = transaction_error(process(&mut ctx, &[ix], &[]).await);
+    assert_eq!(
+        err,
+        TransactionError::InstructionError(0, InstructionError::AccountAlreadyInitialized)
+    );
+}
+
+#[tokio::test]
+async fn create_pool_rejects_stale_pool_id() {
+    let mut ctx = start().await;
+    initialize_registry(&mut ctx).await;
+    create_new_pool(&mut ctx).await;
+
+    let payer = ctx.payer.pubkey();
+    let keys = pool_keys(&new_mint(&mut ctx).await, &new_mint(&mut ctx).await);
+    let ix = instruction::create_pool(&cargo_swap::id(), &payer, &keys, PoolType::Standard, &token_program_id(), 1);
+    let err = transaction_error(process(&mut ctx, &[ix], &[]).await);
+    assert_eq!(err, TransactionError::InstructionError(0, InstructionError::InvalidSeeds));
+}
+
+#[tokio::test]
+async fn create_pool_requires_registry() {
+    let mut ctx = start().await;
+
+    let payer = ctx.payer.pubkey();
+    let ix = instruction::create_pool(
+        &cargo_swap::id(),
+        &payer,
+        &pool_keys(&Pubkey::new_unique(), &Pubkey::new_unique()),
+        PoolType::Standard,
+        &token_program_id(),
+        1,
+    );
+    let err = transaction_error(process(&mut ctx, &[ix], &[]).await);
+    assert_eq!(err, TransactionError::InstructionError(0, InstructionError::InvalidAccountData));
+}
+
+#[tokio::test]
+async fn registry_queries_return_pool_infos() {
+    let mut ctx = start().await;
+    initialize_registry(&mut ctx).await;
+    for _ in 0..3 {
+        create_new_pool(&mut ctx).await;
+    }
+
+    let payer = ctx.payer.pubkey();
+    let ix = instruction::get_pool_info(&cargo_swap::id(), &payer, &token_program_id(), 2);
+    let info = PoolInfo::try_from_slice(&return_data(&mut ctx, ix).await).unwrap();
+    assert_eq!(info.pool_id, 2);
+
+    let ix = instruction::list_pools(&cargo_swap::id(), &payer, &token_program_id(), &[3, 1]);
+    let page = Vec::<PoolInfo>::try_from_slice(&return_data(&mut ctx, ix).await).unwrap();
+    assert_eq!(page.iter().map(|p| p.pool_id).collect::<Vec<_>>(), vec![3, 1]);
+}
+
+#[tokio::test]
+async fn list_pools_rejects_oversized_page() {
+    let mut ctx = start().await;
+    initialize_registry(&mut ctx).await;
+
+    let payer = ctx.payer.pubkey();
+    let pool_ids: Vec<u64> = (1..=MAX_LIST_POOLS as u64 + 1).collect();
+    let ix = instruction::list_pools(&cargo_swap::id(), &payer, &token_program_id(), &pool_ids);
+    let err = transaction_error(process(&mut ctx, &[ix], &[]).await);
+    assert_eq!(err, TransactionError::InstructionError(0, InstructionError::InvalidArgument));
+}
+
+#[tokio::test]
+async fn registry_updates_touch_only_the_pool_index() {
+    let mut ctx = start().await;
+    initialize_registry(&mut ctx).await;
+    create_new_pool(&mut ctx).await;
+    create_new_pool(&mut ctx).await;
+    let payer = ctx.payer.pubkey();
+    initialize_config(&mut ctx, &payer, &payer).await;
+
+    // Only the admin deactivates pools
+    let outsider = Keypair::new();
+    let pool = get_pool_info(&mut ctx, 2).await.pool_address;
+    let ix = instruction::deactivate_pool(&cargo_swap::id(), &outsider.pubkey(), &token_program_id(), &pool, 2);
+    let err = transaction_error(process(&mut ctx, &[ix], &[&outsider]).await);
+    assert_eq!(err, TransactionError::InstructionError(0, InstructionError::Custom(SwapError::Unauthorized as u32)));
+    assert!(get_pool_info(&mut ctx, 2).await.is_active);
+
+    let ix = instruction::deactivate_pool(&cargo_swap::id(), &payer, &token_program_id(), &pool, 2);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+
+    let first = get_pool_info(&mut ctx, 1).await;
+    assert_eq!(first.fee_rate, 30);
+    assert!(first.is_active);
+    let second = get_pool_info(&mut ctx, 2).await;
+    assert_eq!(second.fee_rate, 30);
+    assert!(!second.is_active);
+}
+
+#[tokio::test]
+async fn registry_instructions_reject_unknown_pool_id() {
+    let mut ctx = start().await;
+    initialize_registry(&mut ctx).await;
+    let payer = ctx.payer.pubkey();
+    initialize_config(&mut ctx, &payer, &payer).await;
+    for ix in [
+        instruction::get_pool_info(&cargo_swap::id(), &payer, &token_program_id(), 7),
+        instruction::deactivate_pool(&cargo_swap::id(), &payer, &token_program_id(), &Pubkey::default(), 7),
+    ] {
+        let err = transaction_error(process(&mut ctx, &[ix], &[]).await);
+        assert_eq!(err, TransactionError::InstructionError(0, InstructionError::InvalidAccountData));
+    }
+}
+
+#[tokio::test]
+async fn registry_instructions_reject_mismatched_index() {
+    let mut ctx = start().await;
+    initialize_registry(&mut ctx).await;
+    create_new_pool(&mut ctx).await;
+    create_new_pool(&mut ctx).await;
+
+    let payer = ctx.payer.pubkey();
+    initialize_config(&mut ctx, &payer, &payer).await;
+
+    // Pool 2's index account passed for pool 1
+    let pool = get_pool_info(&mut ctx, 2).await.pool_address;
+    let mut ix = instruction::deactivate_pool(&cargo_swap::id(), &payer, &token_program_id(), &pool, 2);
+    ix.data = TestProjectInstruction::DeactivatePool { pool_id: 1 }.try_to_vec().unwrap();
+    let err = transaction_error(process(&mut ctx, &[ix], &[]).await);
+    assert_eq!(err, TransactionError::InstructionError(0, InstructionError::InvalidSeeds));
+
+    // Pool 1's account passed with pool 2's index
+    let other = get_pool_info(&mut ctx, 1).await.pool_address;
+    let ix = instruction::deactivate_pool(&cargo_swap::id(), &payer, &token_program_id(), &other, 2);
+    let err = transaction_error(process(&mut ctx, &[ix], &[]).await);
+    assert_eq!(err, TransactionError::InstructionError(0, InstructionError::InvalidArgument));
+}
+
+#[tokio::test]
+async fn deactivated_pool_refuses_trades_but_pays_out_lps() {
+    let mut ctx = start().await;
+    let fixture = pool_fixture(&mut ctx).await;
+    init_pool(&mut ctx, &fixture, 1, USER_FUNDS / 10, USER_FUNDS / 10).await;
+    let payer = ctx.payer.pubkey();
+    initialize_config(&mut ctx, &payer, &payer).await;
+
+    let (keys, user, program) = (&fixture.keys, &fixture.user, token_program_id());
+    let ix = instruction::deactivate_pool(&cargo_swap::id(), &payer, &program, &keys.pool, 1);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    assert!(!get_pool(&mut ctx, &keys.pool).await.is_active);
+
+    let inactive = TransactionError::InstructionError(0, InstructionError::Custom(SwapError::PoolInactive as u32));
+    for ix in [
+        instruction::swap(&cargo_swap::id(), 1, keys, user, 1_000, true),
+        instruction::add_liquidity(&cargo_swap::id(), 1, keys, user, &program, 1_000, 1_000),
+    ] {
+        assert_eq!(transaction_error(process(&mut ctx, &[ix], &[]).await), inactive);
+    }
+
+    // LPs can still withdraw
+    let lp = token_balance(&mut ctx, &user.lp).await;
+    let ix = instruction::remove_liquidity(&cargo_swap::id(), 1, keys, user, &program, lp);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut ctx, &user.lp).await, 0);
+}
+
+#[tokio::test]
+async fn deactivated_registration_cannot_be_initialized() {
+    let mut ctx = start().await;
+    let fixture = pool_fixture(&mut ctx).await;
+    let payer = ctx.payer.pubkey();
+    initialize_config(&mut ctx, &payer, &payer).await;
+
+    // No pool account exists yet; only the index entry changes
+    let ix = instruction::deactivate_pool(&cargo_swap::id(), &payer, &token_program_id(), &fixture.keys.pool, 1);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+
+    let (keys, user) = (&fixture.keys, &fixture.user);
+    let ix = instruction::init_pool(&cargo_swap::id(), 1, keys, user, &token_program_id(), 1_000, 1_000);
+    let err = transaction_error(process(&mut ctx, &[ix], &[]).await);
+    assert_eq!(err, TransactionError::InstructionError(0, InstructionError::Custom(SwapError::PoolInactive as u32)));
+}
@@ -0,0 +1,235 @@
+use borsh::BorshSerialize;
+use cargo_swap::{
+    error::SwapError, AccountType, CreationPolicy, CreationRule, CreationRules, DcaVault, Farm, FarmStake,
+    GovernanceChange, LimitOrder, LiquidityLock, LongTermOrder, MintStatus, PairIndex, PendingChange, Pool, PoolInfo,
+    PoolRegistry, PoolType, ZeroCopy, MAX_LISTED_MINTS, PAIR_INDEX_VERSION, POOL_INFO_VERSION,
+};
+use solana_program::{program_error::ProgramError, program_pack::Pack, pubkey::Pubkey};
+use std::mem::offset_of;
+
+fn pool() -> Pool {
+    Pool {
+        header: Pool::new_header(),
+        bump: 254,
+        pool_type: PoolType::Stable,
+        is_active: true,
+        _padding: [0; 1],
+        fee_rate: 1,
+        pool_id: 7,
+        token_a: Pubkey::new_unique(),
+        token_b: Pubkey::new_unique(),
+        token_program_a: spl_token::id(),
+        token_program_b: spl_token_2022::id(),
+        reserve_a: 1_000,
+        reserve_b: 2_000,
+        total_lp_supply: 1_414,
+        created_at: 1_700_000_000,
+        max_price_move_bps: 0,
+        dynamic_fee: false,
+        _padding2: [0; 1],
+        min_fee_rate: 0,
+        max_fee_rate: 0,
+        price_ref_slot: 0,
+        price_ref_reserve_a: 0,
+        price_ref_reserve_b: 0,
+        volatility_bps: 0,
+        volatility_updated_at: 0,
+        price_a_cumulative: [0; 16],
+        price_b_cumulative: [0; 16],
+        price_cumulative_updated_at: 0,
+    }
+}
+
+// u64-aligned buffer, like account data handed to the program
+fn account_data(len: usize) -> Vec<u64> {
+    vec![0; len.div_ceil(8)]
+}
+
+#[test]
+fn pool_offsets_are_stable() {
+    // Clients filter and decode on these offsets
+    assert_eq!(offset_of!(Pool, header), 0);
+    assert_eq!(offset_of!(Pool, fee_rate), 6);
+    assert_eq!(offset_of!(Pool, pool_id), 8);
+    assert_eq!(offset_of!(Pool, token_a), 16);
+    assert_eq!(offset_of!(Pool, token_b), 48);
+    assert_eq!(offset_of!(Pool, token_program_a), 80);
+    assert_eq!(offset_of!(Pool, token_program_b), 112);
+    assert_eq!(offset_of!(Pool, reserve_a), 144);
+    assert_eq!(offset_of!(Pool, created_at), 168);
+    assert_eq!(offset_of!(Pool, max_price_move_bps), 176);
+    assert_eq!(offset_of!(Pool, min_fee_rate), 180);
+    assert_eq!(offset_of!(Pool, price_ref_slot), 184);
+    assert_eq!(offset_of!(Pool, volatility_bps), 208);
+    assert_eq!(offset_of!(Pool, price_a_cumulative), 224);
+    assert_eq!(offset_of!(Pool, price_cumulative_updated_at), 256);
+    assert_eq!(offset_of!(PoolRegistry, next_pool_id), 8);
+    assert_eq!(offset_of!(CreationPolicy, fee_amount), 8);
+    assert_eq!(offset_of!(CreationPolicy, treasury), 56);
+    assert_eq!(offset_of!(CreationPolicy, mints), 88);
+    assert_eq!(offset_of!(LimitOrder, pool), 16);
+    assert_eq!(offset_of!(LimitOrder, owner), 48);
+    assert_eq!(offset_of!(LongTermOrder, pool), 16);
+    assert_eq!(offset_of!(LongTermOrder, owner), 48);
+    assert_eq!(offset_of!(DcaVault, pool), 16);
+    assert_eq!(offset_of!(DcaVault, owner), 48);
+    assert_eq!(offset_of!(Farm, pool), 8);
+    assert_eq!(offset_of!(Farm, rewards), 120);
+    assert_eq!(offset_of!(FarmStake, farm), 8);
+    assert_eq!(offset_of!(FarmStake, owner), 40);
+    assert_eq!(offset_of!(LiquidityLock, pool), 16);
+    assert_eq!(offset_of!(LiquidityLock, owner), 48);
+    assert_eq!(offset_of!(PendingChange, target), 8);
+    assert_eq!(offset_of!(PendingChange, eta), 80);
+    assert_eq!(offset_of!(PendingChange, max_price_move_bps), 92);
+}
+
+#[test]
+fn pool_round_trips_in_place() {
+    let mut words = account_data(Pool::LEN);
+    let data: &mut [u8] = bytemuck::cast_slice_mut(&mut words);
+    let original = pool();
+    Pool::pack(original, data).unwrap();
+    assert_eq!(data[0], AccountType::Pool as u8);
+
+    Pool::load_mut(data).unwrap().reserve_a = 5_000;
+    let loaded = Pool::unpack(data).unwrap();
+    assert_eq!(loaded.reserve_a, 5_000);
+    assert_eq!((loaded.token_a, loaded.token_b), (original.token_a, original.token_b));
+    assert_eq!(loaded.token_program_b, spl_token_2022::id());
+    assert_eq!(loaded.pool_type, PoolType::Stable);
+}
+
+#[test]
+fn load_rejects_wrong_discriminator() {
+    let mut words = account_data(Pool::LEN);
+    let data: &mut [u8] = bytemuck::cast_slice_mut(&mut words);
+    Pool::pack(pool(), data).unwrap();
+
+    data[0] = AccountType::PoolRegistry as u8;
+    assert_eq!(Pool::load(data).unwrap_err(), ProgramError::InvalidAccountData);
+
+    // A zeroed account is uninitialized, not a pool
+    data.fill(0);
+    assert_eq!(Pool::load(data).unwrap_err(), ProgramError::InvalidAccountData);
+}
+
+#[test]
+fn load_rejects_invalid_bit_patterns() {
+    let mut words = account_data(Pool::LEN);
+    let data: &mut [u8] = bytemuck::cast_slice_mut(&mut words);
+    Pool::pack(pool(), data).unwrap();
+
+    let mut bad_type = data.to_vec();
+    bad_type[offset_of!(Pool, pool_type)] = 9;
+    assert_eq!(Pool::unpack(&bad_type).unwrap_err(), ProgramError::InvalidAccountData);
+
+    data[offset_of!(Pool, is_active)] = 2;
+    assert_eq!(Pool::load(data).unwrap_err(), ProgramError::InvalidAccountData);
+}
+
+#[test]
+fn load_rejects_short_data() {
+    let mut words = account_data(Pool::LEN);
+    let data: &mut [u8] = bytemuck::cast_slice_mut(&mut words);
+    Pool::pack(pool(), data).unwrap();
+    assert!(Pool::load(&data[..Pool::LEN - 1]).is_err());
+    assert!(PoolRegistry::load(&data[..PoolRegistry::LEN - 1]).is_err());
+}
+
+#[test]
+fn mint_list_tracks_statuses_through_unlisting() {
+    let mut policy = CreationPolicy {
+        header: CreationPolicy::new_header(),
+        bump: 255,
+        rules: CreationRules { stable: CreationRule::AllowlistOnly, ..CreationRules::default() },
+        mint_count: 0,
+        _padding: [0; 1],
+        fee_amount: 0,
+        allowed: 0,
+        fee_mint: Pubkey::default(),
+        treasury: Pubkey::default(),
+        mints: [Pubkey::default(); MAX_LISTED_MINTS],
+    };
+    let [usdc, usdt, scam, other] = [(); 4].map(|_| Pubkey::new_unique());
+    policy.set_mint_status(&usdc, MintStatus::Allowed).unwrap();
+    policy.set_mint_status(&scam, MintStatus::Denied).unwrap();
+    policy.set_mint_status(&usdt, MintStatus::Allowed).unwrap();
+    policy.set_mint_status(&other, MintStatus::Unlisted).unwrap();
+    assert_eq!(policy.mint_count, 3);
+
+    let denied = Err(SwapError::MintNotAllowed.into());
+    assert_eq!(policy.check_pool(PoolType::Stable, &usdc, &usdt), Ok(()));
+    assert_eq!(policy.check_pool(PoolType::Stable, &usdc, &other), denied);
+    assert_eq!(policy.check_pool(PoolType::Standard, &usdc, &other), Ok(()));
+    assert_eq!(policy.check_pool(PoolType::Standard, &scam, &other), denied);
+
+    // The last entry, usdt, moves into the unlisted slot and stays allowed
+    policy.set_mint_status(&usdc, MintStatus::Unlisted).unwrap();
+    assert_eq!(policy.mint_count, 2);
+    assert_eq!(
+        [&usdc, &usdt, &scam].map(|mint| policy.mint_status(mint)),
+        [MintStatus::Unlisted, MintStatus::Allowed, MintStatus::Denied]
+    );
+    policy.set_mint_status(&scam, MintStatus::Allowed).unwrap();
+    assert_eq!(policy.check_pool(PoolType::Stable, &scam, &usdt), Ok(()));
+
+    for _ in 2..MAX_LISTED_MINTS {
+        policy.set_mint_status(&Pubkey::new_unique(), MintStatus::Denied).unwrap();
+    }
+    let full = Err(SwapError::MintListFull.into());
+    assert_eq!(policy.set_mint_status(&other, MintStatus::Allowed), full);
+    // Relisting needs no new slot
+    assert_eq!(policy.set_mint_status(&scam, MintStatus::Denied), Ok(()));
+}
+
+#[test]
+fn registry_entries_pack_to_their_borsh_layout() {
+    let info = PoolInfo {
+        version: POOL_INFO_VERSION,
+        pool_id: 7,
+        pool_address: Pubkey::new_unique(),
+        token_a: Pubkey::new_unique(),
+        token_b: Pubkey::new_unique(),
+        pool_type: PoolType::Stable,
+        fee_rate: 1,
+        is_active: true,
+        created_at: -5,
+        tvl: u64::MAX,
+    };
+    let mut data = vec![0; PoolInfo::LEN];
+    PoolInfo::pack(info.clone(), &mut data).unwrap();
+    assert_eq!(data, info.try_to_vec().unwrap());
+    assert_eq!(PoolInfo::unpack(&data).unwrap().pool_address, info.pool_address);
+
+    let index = PairIndex { version: PAIR_INDEX_VERSION, pool_id: 7 };
+    let mut data = vec![0; PairIndex::LEN];
+    PairIndex::pack(index.clone(), &mut data).unwrap();
+    assert_eq!(data, index.try_to_vec().unwrap());
+    assert_eq!(PairIndex::unpack(&data).unwrap().pool_id, 7);
+}
+
+#[test]
+fn pending_changes_keep_each_kind_in_its_own_fields() {
+    let target = Pubkey::new_unique();
+    for change in [
+        GovernanceChange::FeeRate { fee_rate: 50 },
+        GovernanceChange::DynamicFee { min_fee_rate: 10, max_fee_rate: 90 },
+        GovernanceChange::Guardian { guardian: Pubkey::new_unique(), withdrawals_open: true },
+        GovernanceChange::PriceBand { max_price_move_bps: 500 },
+    ] {
+        let mut words = account_data(PendingChange::LEN);
+        let data: &mut [u8] = bytemuck::cast_slice_mut(&mut words);
+        PendingChange::pack(PendingChange::new(1, target, change, 0), data).unwrap();
+        let pending = PendingChange::unpack(data).unwrap();
+        assert_eq!(pending.change(), change);
+        // No value field is shared between kinds
+        let fee_fields = [pending.fee_rate, pending.min_fee_rate, pending.max_fee_rate, pending.max_price_move_bps];
+        let set = fee_fields.iter().filter(|&&value| value != 0).count();
+        assert_eq!(set, match change {
+            GovernanceChange::DynamicFee { .. } => 2,
+            GovernanceChange::Guardian { .. } => 0,
+            _ => 1,
+        });
+    }
+}
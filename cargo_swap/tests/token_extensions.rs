@@ -0,0 +1,160 @@
+mod common;
+
+use cargo_swap::{error::SwapError, instruction, quote, PoolType};
+use common::*;
+use solana_program::{instruction::InstructionError, pubkey::Pubkey};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::{
+    signature::Signer,
+    transaction::TransactionError,
+};
+use spl_token_2022::{
+    extension::{confidential_transfer, default_account_state, transfer_fee, transfer_hook, ExtensionType},
+    state::AccountState,
+};
+
+const INITIAL: u64 = 1_000_000_000;
+const FEE_BPS: u16 = 100;
+
+fn fee(amount: u64) -> u64 {
+    (amount * FEE_BPS as u64).div_ceil(10_000)
+}
+
+async fn transfer_fee_mint(ctx: &mut ProgramTestContext) -> Pubkey {
+    let payer = ctx.payer.pubkey();
+    create_mint_2022(ctx, &payer, 9, &[ExtensionType::TransferFeeConfig], |mint| {
+        vec![transfer_fee::instruction::initialize_transfer_fee_config(
+            &spl_token_2022::id(),
+            mint,
+            None,
+            None,
+            FEE_BPS,
+            u64::MAX,
+        )
+        .unwrap()]
+    })
+    .await
+}
+
+async fn assert_reserves_match_vaults(ctx: &mut ProgramTestContext, fixture: &PoolFixture) {
+    let pool = get_pool(ctx, &fixture.keys.pool).await;
+    assert_eq!(token_balance(ctx, &fixture.keys.vault_a).await, pool.reserve_a);
+    assert_eq!(token_balance(ctx, &fixture.keys.vault_b).await, pool.reserve_b);
+}
+
+#[tokio::test]
+async fn transfer_fee_mint_reserves_track_received_amounts() {
+    let mut ctx = start().await;
+    let token_a = transfer_fee_mint(&mut ctx).await;
+    let payer = ctx.payer.pubkey();
+    let token_b = create_mint(&mut ctx, &payer, 6).await;
+    let fixture = pool_fixture_with_mints(&mut ctx, token_a, token_b).await;
+
+    init_pool(&mut ctx, &fixture, 1, INITIAL, INITIAL).await;
+    let pool = get_pool(&mut ctx, &fixture.keys.pool).await;
+    assert_eq!((pool.reserve_a, pool.reserve_b), (INITIAL - fee(INITIAL), INITIAL));
+    assert_reserves_match_vaults(&mut ctx, &fixture).await;
+
+    // Input leg pays the fee: the swap is priced on what the vault receives
+    let amount_in = INITIAL / 10;
+    let before = get_pool(&mut ctx, &fixture.keys.pool).await;
+    let expected = quote::quote_swap_exact_in(&before, amount_in - fee(amount_in), true).unwrap();
+    let ix = instruction::swap(&cargo_swap::id(), 1, &fixture.keys, &fixture.user, amount_in, true);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    let after = get_pool(&mut ctx, &fixture.keys.pool).await;
+    assert_eq!((after.reserve_a, after.reserve_b), (expected.reserve_a_after, expected.reserve_b_after));
+    assert_reserves_match_vaults(&mut ctx, &fixture).await;
+
+    // Output leg: the vault sends the full amount, the user receives less
+    let user_a_before = token_balance(&mut ctx, &fixture.user.token_a).await;
+    let expected = quote::quote_swap_exact_in(&after, amount_in, false).unwrap();
+    let ix = instruction::swap(&cargo_swap::id(), 1, &fixture.keys, &fixture.user, amount_in, false);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    let received = token_balance(&mut ctx, &fixture.user.token_a).await - user_a_before;
+    assert_eq!(received, expected.amount_out - fee(expected.amount_out));
+    assert_reserves_match_vaults(&mut ctx, &fixture).await;
+
+    let before = get_pool(&mut ctx, &fixture.keys.pool).await;
+    let deposit = quote::quote_add_liquidity(&before, INITIAL, INITIAL).unwrap();
+    let lp_before = token_balance(&mut ctx, &fixture.user.lp).await;
+    let ix = instruction::add_liquidity(
+        &cargo_swap::id(),
+        1,
+        &fixture.keys,
+        &fixture.user,
+        &token_program_id(),
+        INITIAL,
+        INITIAL,
+    );
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    let minted = token_balance(&mut ctx, &fixture.user.lp).await - lp_before;
+    let expected_lp = quote::lp_for_deposit(&before, deposit.amount_a - fee(deposit.amount_a), deposit.amount_b).unwrap();
+    assert_eq!(minted, expected_lp);
+    assert!(minted < deposit.lp_amount);
+    assert_reserves_match_vaults(&mut ctx, &fixture).await;
+}
+
+#[tokio::test]
+async fn create_pool_rejects_unsupported_mint_extensions() {
+    let mut ctx = start().await;
+    initialize_registry(&mut ctx).await;
+    let payer = ctx.payer.pubkey();
+    let token_a = create_mint(&mut ctx, &payer, 9).await;
+
+    let non_transferable = create_mint_2022(&mut ctx, &payer, 9, &[ExtensionType::NonTransferable], |mint| {
+        vec![spl_token_2022::instruction::initialize_non_transferable_mint(&spl_token_2022::id(), mint).unwrap()]
+    })
+    .await;
+    let permanent_delegate = create_mint_2022(&mut ctx, &payer, 9, &[ExtensionType::PermanentDelegate], |mint| {
+        vec![spl_token_2022::instruction::initialize_permanent_delegate(&spl_token_2022::id(), mint, &payer).unwrap()]
+    })
+    .await;
+    let default_frozen = create_mint_2022(&mut ctx, &payer, 9, &[ExtensionType::DefaultAccountState], |mint| {
+        vec![default_account_state::instruction::initialize_default_account_state(
+            &spl_token_2022::id(),
+            mint,
+            &AccountState::Frozen,
+        )
+        .unwrap()]
+    })
+    .await;
+
+    // Transfers pass no extra accounts for a hook, so every one would fail
+    let transfer_hook = create_mint_2022(&mut ctx, &payer, 9, &[ExtensionType::TransferHook], |mint| {
+        vec![transfer_hook::instruction::initialize(&spl_token_2022::id(), mint, None, Some(Pubkey::new_unique()))
+            .unwrap()]
+    })
+    .await;
+    let confidential = create_mint_2022(&mut ctx, &payer, 9, &[ExtensionType::ConfidentialTransferMint], |mint| {
+        vec![confidential_transfer::instruction::initialize_mint(&spl_token_2022::id(), mint, None, true, None)
+            .unwrap()]
+    })
+    .await;
+
+    for token_b in [non_transferable, permanent_delegate, default_frozen, transfer_hook, confidential] {
+        let keys = instruction::PoolKeys::new(
+            &cargo_swap::id(),
+            &token_a,
+            &token_b,
+            &token_program_id(),
+            &spl_token_2022::id(),
+        );
+        let pool_id = get_registry(&mut ctx).await.next_pool_id;
+        let ix = instruction::create_pool(&cargo_swap::id(), &payer, &keys, PoolType::Standard, &token_program_id(), pool_id);
+        let err = transaction_error(process(&mut ctx, &[ix], &[]).await);
+        assert_eq!(
+            err,
+            TransactionError::InstructionError(0, InstructionError::Custom(SwapError::UnsupportedMintExtension as u32))
+        );
+    }
+
+    // Extensions that leave transfers alone are fine
+    let close_authority = create_mint_2022(&mut ctx, &payer, 9, &[ExtensionType::MintCloseAuthority], |mint| {
+        vec![spl_token_2022::instruction::initialize_mint_close_authority(&spl_token_2022::id(), mint, Some(&payer))
+            .unwrap()]
+    })
+    .await;
+    let fixture = pool_fixture_with_mints(&mut ctx, token_a, close_authority).await;
+    init_pool(&mut ctx, &fixture, 1, INITIAL, INITIAL).await;
+    assert_reserves_match_vaults(&mut ctx, &fixture).await;
+}
@@ -0,0 +1,165 @@
+mod common;
+
+use cargo_swap::{
+    error::SwapError,
+    instruction,
+    quote, LongTermOrder, OrderSchedule, Pool, PAUSE_WITHDRAWALS,
+};
+use common::*;
+use solana_program::{instruction::InstructionError, program_pack::Pack, pubkey::Pubkey};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::{signature::Keypair, signer::Signer, transaction::TransactionError};
+
+const SCHEDULE: OrderSchedule = OrderSchedule { intervals: 3, interval_seconds: 60, max_slippage_bps: 300 };
+
+fn custom_error(error: SwapError) -> TransactionError {
+    TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+}
+
+// A 1:1 pool with a 30k order selling A over three minutes
+async fn setup() -> (ProgramTestContext, PoolFixture, Pubkey) {
+    let mut ctx = start().await;
+    let fixture = pool_fixture(&mut ctx).await;
+    init_pool(&mut ctx, &fixture, 1, 1_000_000, 1_000_000).await;
+    let program_id = cargo_swap::id();
+    let ix = instruction::place_long_term_order(&program_id, &fixture.keys, &fixture.user, 1, 30_000, SCHEDULE, true);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    let (order, _) = instruction::find_long_term_order_address(&program_id, &fixture.keys.pool, &fixture.user.user, 1);
+    (ctx, fixture, order)
+}
+
+async fn get_order(ctx: &mut ProgramTestContext, address: &Pubkey) -> LongTermOrder {
+    let account = ctx.banks_client.get_account(*address).await.unwrap().unwrap();
+    LongTermOrder::unpack(&account.data).unwrap()
+}
+
+#[tokio::test]
+async fn crank_sells_each_interval_as_it_comes_due() {
+    let (mut ctx, fixture, order) = setup().await;
+    let program_id = cargo_swap::id();
+    let execute = instruction::execute_long_term_orders(&program_id, &fixture.keys.pool, &[order]);
+    let error = transaction_error(process(&mut ctx, std::slice::from_ref(&execute), &[]).await);
+    assert_eq!(error, custom_error(SwapError::NoIntervalsDue));
+
+    // The first slice trades on the curve while the rest waits in the vault
+    advance_clock(&mut ctx, 60).await;
+    let first = quote::quote_swap_exact_in(&get_pool(&mut ctx, &fixture.keys.pool).await, 10_000, true).unwrap();
+    process(&mut ctx, std::slice::from_ref(&execute), &[]).await.unwrap();
+    let pool = get_pool(&mut ctx, &fixture.keys.pool).await;
+    assert_eq!((pool.reserve_a, pool.reserve_b), (first.reserve_a_after, first.reserve_b_after));
+    let state = get_order(&mut ctx, &order).await;
+    assert_eq!((state.sold, state.proceeds, state.intervals_executed), (10_000, first.amount_out, 1));
+    assert_eq!(token_balance(&mut ctx, &fixture.keys.vault_a).await, pool.reserve_a + 20_000);
+
+    // A late crank sells one interval per run, and a run needs time since
+    // the last for its average price
+    advance_clock(&mut ctx, 300).await;
+    let second = quote::quote_swap_exact_in(&pool, 10_000, true).unwrap();
+    let after_second = Pool { reserve_a: second.reserve_a_after, reserve_b: second.reserve_b_after, ..pool };
+    let third = quote::quote_swap_exact_in(&after_second, 10_000, true).unwrap();
+    process(&mut ctx, std::slice::from_ref(&execute), &[]).await.unwrap();
+    assert_eq!(get_order(&mut ctx, &order).await.intervals_executed, 2);
+    let retry = instruction::execute_long_term_orders(&program_id, &fixture.keys.pool, &[order, order]);
+    let error = transaction_error(process(&mut ctx, &[retry], &[]).await);
+    assert_eq!(error, custom_error(SwapError::NoIntervalsDue));
+    advance_clock(&mut ctx, 1).await;
+    process(&mut ctx, std::slice::from_ref(&execute), &[]).await.unwrap();
+    let state = get_order(&mut ctx, &order).await;
+    assert_eq!((state.sold, state.intervals_executed), (30_000, 3));
+    assert_eq!(state.proceeds, first.amount_out + second.amount_out + third.amount_out);
+    let error = transaction_error(process(&mut ctx, &[execute], &[]).await);
+    assert_eq!(error, custom_error(SwapError::NoIntervalsDue));
+
+    let balance_b = token_balance(&mut ctx, &fixture.user.token_b).await;
+    let ix = instruction::withdraw_long_term_proceeds(&program_id, &fixture.keys, &fixture.user, 1, true);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut ctx, &fixture.user.token_b).await - balance_b, state.proceeds);
+    assert_eq!(get_order(&mut ctx, &order).await.proceeds, 0);
+    let pool = get_pool(&mut ctx, &fixture.keys.pool).await;
+    assert_eq!(token_balance(&mut ctx, &fixture.keys.vault_b).await, pool.reserve_b);
+}
+
+#[tokio::test]
+async fn crank_refuses_intervals_far_below_the_twap() {
+    let (mut ctx, fixture, order) = setup().await;
+    let program_id = cargo_swap::id();
+    advance_clock(&mut ctx, 60).await;
+
+    // Dumping A just before the run would sell the slice cheap
+    let dump = instruction::swap(&program_id, 1, &fixture.keys, &fixture.user, 100_000, true);
+    let execute = instruction::execute_long_term_orders(&program_id, &fixture.keys.pool, &[order]);
+    let error = transaction_error(process(&mut ctx, &[dump, execute.clone()], &[]).await);
+    let slippage = InstructionError::Custom(SwapError::SlippageExceeded as u32);
+    assert_eq!(error, TransactionError::InstructionError(1, slippage));
+    assert_eq!(get_order(&mut ctx, &order).await.intervals_executed, 0);
+
+    process(&mut ctx, &[execute], &[]).await.unwrap();
+    assert_eq!(get_order(&mut ctx, &order).await.intervals_executed, 1);
+}
+
+#[tokio::test]
+async fn owner_cancels_mid_way() {
+    let (mut ctx, fixture, order) = setup().await;
+    let program_id = cargo_swap::id();
+    advance_clock(&mut ctx, 60).await;
+    let ix = instruction::execute_long_term_orders(&program_id, &fixture.keys.pool, &[order]);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    let proceeds = get_order(&mut ctx, &order).await.proceeds;
+    let balance_a = token_balance(&mut ctx, &fixture.user.token_a).await;
+    let balance_b = token_balance(&mut ctx, &fixture.user.token_b).await;
+
+    // Only the owner can stop the order
+    let outsider = Keypair::new();
+    let mut stolen = instruction::cancel_long_term_order(&program_id, &fixture.keys, &fixture.user, 1);
+    stolen.accounts[3].pubkey = outsider.pubkey();
+    let error = transaction_error(process(&mut ctx, &[stolen], &[&outsider]).await);
+    assert_eq!(error, custom_error(SwapError::Unauthorized));
+
+    let ix = instruction::cancel_long_term_order(&program_id, &fixture.keys, &fixture.user, 1);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut ctx, &fixture.user.token_a).await, balance_a + 20_000);
+    assert_eq!(token_balance(&mut ctx, &fixture.user.token_b).await, balance_b + proceeds);
+    assert!(ctx.banks_client.get_account(order).await.unwrap().is_none());
+    let pool = get_pool(&mut ctx, &fixture.keys.pool).await;
+    assert_eq!(token_balance(&mut ctx, &fixture.keys.vault_a).await, pool.reserve_a);
+}
+
+#[tokio::test]
+async fn withdrawing_and_cancelling_are_withdrawals_for_the_pause() {
+    let (mut ctx, fixture, _) = setup().await;
+    let program_id = cargo_swap::id();
+    let payer = ctx.payer.pubkey();
+    initialize_config(&mut ctx, &payer, &payer).await;
+    let withdraw = instruction::withdraw_long_term_proceeds(&program_id, &fixture.keys, &fixture.user, 1, true);
+    let cancel = instruction::cancel_long_term_order(&program_id, &fixture.keys, &fixture.user, 1);
+
+    let ix = instruction::set_pause(&program_id, &payer, PAUSE_WITHDRAWALS);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    for ix in [&withdraw, &cancel] {
+        let error = transaction_error(process(&mut ctx, std::slice::from_ref(ix), &[]).await);
+        assert_eq!(error, custom_error(SwapError::ProgramPaused));
+    }
+
+    let ix = instruction::set_pause(&program_id, &payer, 0);
+    process(&mut ctx, &[ix], &[]).await.unwrap();
+    process(&mut ctx, &[cancel], &[]).await.unwrap();
+}
+
+#[tokio::test]
+async fn schedule_must_sell_something_every_interval() {
+    let mut ctx = start().await;
+    let fixture = pool_fixture(&mut ctx).await;
+    init_pool(&mut ctx, &fixture, 1, 1_000_000, 1_000_000).await;
+    let (program_id, keys, user) = (cargo_swap::id(), &fixture.keys, &fixture.user);
+
+    for (amount_in, schedule) in [
+        (2, SCHEDULE),
+        (30_000, OrderSchedule { intervals: 0, ..SCHEDULE }),
+        (30_000, OrderSchedule { interval_seconds: 0, ..SCHEDULE }),
+        (30_000, OrderSchedule { max_slippage_bps: 10_001, ..SCHEDULE }),
+    ] {
+        let ix = instruction::place_long_term_order(&program_id, keys, user, 1, amount_in, schedule, true);
+        let error = transaction_error(process(&mut ctx, &[ix], &[]).await);
+        assert_eq!(error, TransactionError::InstructionError(0, InstructionError::InvalidArgument));
+    }
+}